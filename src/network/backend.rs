@@ -0,0 +1,51 @@
+use bitcoin::{Script, Txid};
+
+use crate::util::error::S5Error;
+
+/// An unspent output paying to a script.
+/// Liquid outputs are usually confidential, in which case `value` is 0 and the
+/// real value has to be recovered by unblinding the raw transaction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Utxo {
+    pub txid: Txid,
+    pub vout: u32,
+    pub value: u64,
+    /// 0 while unconfirmed
+    pub height: u32,
+}
+
+/// A transaction that paid to or spent from a script.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryItem {
+    pub txid: Txid,
+    /// 0 or less while unconfirmed (Electrum uses -1 for unconfirmed parents)
+    pub height: i32,
+}
+
+/// Chain access required by the swap transaction builders.
+///
+/// Scripts are always passed as `script_pubkey`s. Transactions cross this boundary
+/// as raw consensus bytes so that the same backend can serve Bitcoin and Liquid.
+/// Liquid txids are sha256d hashes just like Bitcoin txids and are passed as `bitcoin::Txid`.
+pub trait ChainBackend {
+    fn list_unspent(&self, script_pubkey: &Script) -> Result<Vec<Utxo>, S5Error>;
+    fn get_history(&self, script_pubkey: &Script) -> Result<Vec<HistoryItem>, S5Error>;
+    fn get_tx(&self, txid: &Txid) -> Result<Vec<u8>, S5Error>;
+    fn broadcast(&self, raw_tx: &[u8]) -> Result<Txid, S5Error>;
+    fn tip_height(&self) -> Result<u32, S5Error>;
+    /// Fee rate in sat/vbyte to confirm within `target_blocks`.
+    fn get_fee_estimate(&self, target_blocks: usize) -> Result<f64, S5Error>;
+}
+
+/// Async counterpart of ChainBackend, for callers running inside an async runtime.
+#[cfg(feature = "async")]
+#[async_trait::async_trait]
+pub trait AsyncChainBackend: Send + Sync {
+    async fn list_unspent(&self, script_pubkey: &Script) -> Result<Vec<Utxo>, S5Error>;
+    async fn get_history(&self, script_pubkey: &Script) -> Result<Vec<HistoryItem>, S5Error>;
+    async fn get_tx(&self, txid: &Txid) -> Result<Vec<u8>, S5Error>;
+    async fn broadcast(&self, raw_tx: &[u8]) -> Result<Txid, S5Error>;
+    async fn tip_height(&self) -> Result<u32, S5Error>;
+    /// Fee rate in sat/vbyte to confirm within `target_blocks`.
+    async fn get_fee_estimate(&self, target_blocks: usize) -> Result<f64, S5Error>;
+}
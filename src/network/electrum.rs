@@ -1,4 +1,12 @@
-use crate::util::error::S5Error;
+use bitcoin::{Script, Txid};
+use electrum_client::ElectrumApi;
+
+use crate::util::error::{ErrorKind, S5Error};
+
+use super::backend::{ChainBackend, HistoryItem, Utxo};
+use super::esplora::EsploraClient;
+use super::rpc::RpcClient;
+use super::spv::SpvElectrumClient;
 
 // TODO: policy asset should only be set for ElementsRegtest, fail otherwise
 pub const _LIQUID_POLICY_ASSET_STR: &str =
@@ -20,10 +28,33 @@ pub enum BitcoinNetwork {
     ElementsRegtest,
 }
 
-#[derive(Debug, Clone)]
+/// Where chain data is fetched from. Besides Electrum servers this also covers Esplora REST APIs
+/// and the JSON-RPC interface of bitcoind/elementsd.
+#[derive(Clone)]
 pub enum ElectrumUrl {
     Tls(String, bool), // the bool value indicates if the domain name should be validated
     Plaintext(String),
+    Esplora(String), // base url of the api, e.g. https://blockstream.info/testnet/api
+    Rpc(String, String, String), // url, rpc user, rpc password
+}
+
+// written by hand so that the rpc password does not end up in logs
+impl std::fmt::Debug for ElectrumUrl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ElectrumUrl::Tls(url, validate) => {
+                f.debug_tuple("Tls").field(url).field(validate).finish()
+            }
+            ElectrumUrl::Plaintext(url) => f.debug_tuple("Plaintext").field(url).finish(),
+            ElectrumUrl::Esplora(url) => f.debug_tuple("Esplora").field(url).finish(),
+            ElectrumUrl::Rpc(url, user, _) => f
+                .debug_tuple("Rpc")
+                .field(url)
+                .field(user)
+                .field(&"<redacted>")
+                .finish(),
+        }
+    }
 }
 
 impl ElectrumUrl {
@@ -35,10 +66,27 @@ impl ElectrumUrl {
                 (format!("ssl://{}", url), builder.validate_domain(*validate))
             }
             ElectrumUrl::Plaintext(url) => (format!("tcp://{}", url), builder),
+            ElectrumUrl::Esplora(_) | ElectrumUrl::Rpc(..) => {
+                return Err(S5Error::new(
+                    ErrorKind::Network,
+                    "Only electrum urls have an electrum client. Use build_backend.",
+                ))
+            }
         };
         // Ok(_builder.build())
         Ok(electrum_client::Client::from_config(&url, builder.build()).unwrap())
     }
+
+    /// Builds whichever ChainBackend this url points to.
+    pub fn build_backend(&self) -> Result<Box<dyn ChainBackend>, S5Error> {
+        match self {
+            ElectrumUrl::Esplora(url) => Ok(Box::new(EsploraClient::new(url))),
+            ElectrumUrl::Rpc(url, user, password) => {
+                Ok(Box::new(RpcClient::new(url, user, password)))
+            }
+            _ => Ok(Box::new(self.build_client()?)),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -96,6 +144,40 @@ impl NetworkConfig {
         }
     }
 
+    /// spv verification relies on electrum merkle proofs, so with spv_enabled `build_backend`
+    /// returns an error for esplora urls rather than silently trusting the api.
+    pub fn new_esplora(
+        network: BitcoinNetwork,
+        esplora_url: &str,
+        spv_enabled: bool,
+        policy_asset: Option<&str>,
+    ) -> Self {
+        let mut config = NetworkConfig::new(
+            network,
+            esplora_url,
+            false,
+            false,
+            spv_enabled,
+            policy_asset,
+        );
+        config.electrum_url = ElectrumUrl::Esplora(esplora_url.into());
+        config
+    }
+
+    /// Uses our own bitcoind/elementsd, which is trusted, so spv is never enabled.
+    pub fn new_rpc(
+        network: BitcoinNetwork,
+        rpc_url: &str,
+        rpc_user: &str,
+        rpc_password: &str,
+        policy_asset: Option<&str>,
+    ) -> Self {
+        let mut config = NetworkConfig::new(network, rpc_url, false, false, false, policy_asset);
+        config.electrum_url =
+            ElectrumUrl::Rpc(rpc_url.into(), rpc_user.into(), rpc_password.into());
+        config
+    }
+
     pub fn network(&self) -> BitcoinNetwork {
         self.network
     }
@@ -103,13 +185,92 @@ impl NetworkConfig {
     pub fn electrum_url(&self) -> ElectrumUrl {
         self.electrum_url.clone()
     }
+
+    /// With spv_enabled, confirmations reported by the electrum server are verified
+    /// against merkle proofs and block headers. See SpvElectrumClient, which is only
+    /// available on Bitcoin mainnet.
+    pub fn build_backend(&self) -> Result<Box<dyn ChainBackend>, S5Error> {
+        if !self.spv_enabled {
+            return self.electrum_url.build_backend();
+        }
+        match self.electrum_url {
+            ElectrumUrl::Tls(..) | ElectrumUrl::Plaintext(_) => Ok(Box::new(
+                SpvElectrumClient::new(self.electrum_url.build_client()?, self.network)?,
+            )),
+            _ => Err(S5Error::new(
+                ErrorKind::Network,
+                "SPV verification requires an electrum url",
+            )),
+        }
+    }
+}
+
+impl ChainBackend for electrum_client::Client {
+    fn list_unspent(&self, script_pubkey: &Script) -> Result<Vec<Utxo>, S5Error> {
+        match self.script_list_unspent(script_pubkey) {
+            Ok(result) => Ok(result
+                .iter()
+                .map(|utxo| Utxo {
+                    txid: utxo.tx_hash,
+                    vout: utxo.tx_pos as u32,
+                    value: utxo.value,
+                    height: utxo.height as u32,
+                })
+                .collect()),
+            Err(e) => Err(S5Error::new(ErrorKind::Network, &e.to_string())),
+        }
+    }
+    fn get_history(&self, script_pubkey: &Script) -> Result<Vec<HistoryItem>, S5Error> {
+        match self.script_get_history(script_pubkey) {
+            Ok(result) => Ok(result
+                .iter()
+                .map(|item| HistoryItem {
+                    txid: item.tx_hash,
+                    height: item.height,
+                })
+                .collect()),
+            Err(e) => Err(S5Error::new(ErrorKind::Network, &e.to_string())),
+        }
+    }
+    fn get_tx(&self, txid: &Txid) -> Result<Vec<u8>, S5Error> {
+        match self.transaction_get_raw(txid) {
+            Ok(result) => Ok(result),
+            Err(e) => Err(S5Error::new(ErrorKind::Network, &e.to_string())),
+        }
+    }
+    fn broadcast(&self, raw_tx: &[u8]) -> Result<Txid, S5Error> {
+        match self.transaction_broadcast_raw(raw_tx) {
+            Ok(txid) => Ok(txid),
+            Err(e) => Err(S5Error::new(ErrorKind::Network, &e.to_string())),
+        }
+    }
+    fn tip_height(&self) -> Result<u32, S5Error> {
+        // raw, since liquid headers do not parse as bitcoin headers
+        match self.block_headers_subscribe_raw() {
+            Ok(result) => Ok(result.height as u32),
+            Err(e) => Err(S5Error::new(ErrorKind::Network, &e.to_string())),
+        }
+    }
+    fn get_fee_estimate(&self, target_blocks: usize) -> Result<f64, S5Error> {
+        let btc_per_kvb = match ElectrumApi::estimate_fee(self, target_blocks) {
+            Ok(result) => result,
+            Err(e) => return Err(S5Error::new(ErrorKind::Network, &e.to_string())),
+        };
+        if btc_per_kvb < 0.0 {
+            return Err(S5Error::new(
+                ErrorKind::Network,
+                "Server could not estimate fees",
+            ));
+        }
+        // BTC/kvB -> sat/vB
+        Ok(btc_per_kvb * 100_000.0)
+    }
 }
 
 #[cfg(test)]
 mod tests {
 
     use super::*;
-    use electrum_client::ElectrumApi;
 
     #[test]
     fn test_electrum_default_clients() {
@@ -121,4 +282,33 @@ mod tests {
         let electrum_client = network_config.electrum_url.build_client().unwrap();
         assert!(electrum_client.ping().is_ok());
     }
+
+    #[test]
+    fn test_spv_requires_electrum() {
+        let mut network_config = NetworkConfig::new_rpc(
+            BitcoinNetwork::BitcoinTestnet,
+            "http://127.0.0.1:18332",
+            "user",
+            "pass",
+            None,
+        );
+        assert!(network_config.build_backend().is_ok());
+        network_config.spv_enabled = true;
+        assert!(network_config.build_backend().is_err());
+    }
+
+    #[test]
+    fn test_rpc_password_not_in_debug() {
+        let network_config = NetworkConfig::new_rpc(
+            BitcoinNetwork::BitcoinTestnet,
+            "http://127.0.0.1:18332",
+            "user",
+            "hunter2",
+            None,
+        );
+        let debug = format!("{:?}", network_config);
+        assert!(debug.contains("http://127.0.0.1:18332"));
+        assert!(debug.contains("user"));
+        assert!(!debug.contains("hunter2"));
+    }
 }
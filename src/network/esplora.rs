@@ -0,0 +1,545 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::{Script, Txid};
+use reqwest::blocking::{Client, Response};
+use serde::Deserialize;
+
+use crate::util::error::{ErrorKind, S5Error};
+
+#[cfg(feature = "async")]
+use super::backend::AsyncChainBackend;
+use super::backend::{ChainBackend, HistoryItem, Utxo};
+
+pub const DEFAULT_ESPLORA_TESTNET_URL: &str = "https://blockstream.info/testnet/api";
+pub const DEFAULT_LIQUID_ESPLORA_TESTNET_URL: &str = "https://blockstream.info/liquidtestnet/api";
+
+pub const DEFAULT_ESPLORA_MAINNET_URL: &str = "https://blockstream.info/api";
+
+/// Chain access over an Esplora REST API (Blockstream, mempool.space, electrs).
+/// The same client serves Bitcoin and Liquid instances.
+pub struct EsploraClient {
+    base_url: String,
+    client: Client,
+}
+
+#[derive(Deserialize, Debug)]
+struct EsploraStatus {
+    confirmed: bool,
+    block_height: Option<u32>,
+}
+
+impl EsploraStatus {
+    fn height(&self) -> u32 {
+        match (self.confirmed, self.block_height) {
+            (true, Some(height)) => height,
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct EsploraUtxo {
+    txid: String,
+    vout: u32,
+    /// missing for confidential liquid outputs, which carry a `valuecommitment` instead
+    #[serde(default)]
+    value: u64,
+    status: EsploraStatus,
+}
+
+#[derive(Deserialize, Debug)]
+struct EsploraTx {
+    txid: String,
+    status: EsploraStatus,
+}
+
+impl EsploraClient {
+    pub fn new(base_url: &str) -> Self {
+        EsploraClient {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client: Client::new(),
+        }
+    }
+
+    fn check_status(res: Response) -> Result<Response, S5Error> {
+        if res.status().is_success() {
+            Ok(res)
+        } else {
+            let status = res.status();
+            let body = res.text().unwrap_or_default();
+            Err(status_error(status, &body))
+        }
+    }
+
+    fn get(&self, path: &str) -> Result<Response, S5Error> {
+        let url = format!("{}{}", self.base_url, path);
+        match self.client.get(&url).send() {
+            Ok(res) => Self::check_status(res),
+            Err(e) => Err(S5Error::new(ErrorKind::Network, &e.to_string())),
+        }
+    }
+
+    fn get_text(&self, path: &str) -> Result<String, S5Error> {
+        match self.get(path)?.text() {
+            Ok(text) => Ok(text),
+            Err(e) => Err(S5Error::new(ErrorKind::Network, &e.to_string())),
+        }
+    }
+}
+
+fn status_error(status: reqwest::StatusCode, body: &str) -> S5Error {
+    S5Error::new(
+        ErrorKind::Network,
+        &format!("Esplora returned {}: {}", status, body),
+    )
+}
+
+/// Esplora indexes scripts by the plain (not byte reversed) sha256 of the script_pubkey.
+fn script_hash(script_pubkey: &Script) -> String {
+    hex::encode(sha256::Hash::hash(script_pubkey.as_bytes()).as_byte_array())
+}
+
+fn parse_json<T: for<'de> Deserialize<'de>>(body: &str) -> Result<T, S5Error> {
+    match serde_json::from_str(body) {
+        Ok(result) => Ok(result),
+        Err(e) => Err(S5Error::new(ErrorKind::Network, &e.to_string())),
+    }
+}
+
+fn parse_txid(txid: &str) -> Result<Txid, S5Error> {
+    match Txid::from_str(txid.trim()) {
+        Ok(result) => Ok(result),
+        Err(e) => Err(S5Error::new(ErrorKind::Network, &e.to_string())),
+    }
+}
+
+fn parse_utxos(body: &str) -> Result<Vec<Utxo>, S5Error> {
+    let utxos: Vec<EsploraUtxo> = parse_json(body)?;
+    utxos
+        .iter()
+        .map(|utxo| {
+            Ok(Utxo {
+                txid: parse_txid(&utxo.txid)?,
+                vout: utxo.vout,
+                value: utxo.value,
+                height: utxo.status.height(),
+            })
+        })
+        .collect()
+}
+
+fn parse_history(body: &str) -> Result<Vec<HistoryItem>, S5Error> {
+    let txs: Vec<EsploraTx> = parse_json(body)?;
+    txs.iter()
+        .map(|tx| {
+            Ok(HistoryItem {
+                txid: parse_txid(&tx.txid)?,
+                height: tx.status.height() as i32,
+            })
+        })
+        .collect()
+}
+
+fn parse_height(body: &str) -> Result<u32, S5Error> {
+    match body.trim().parse::<u32>() {
+        Ok(result) => Ok(result),
+        Err(e) => Err(S5Error::new(ErrorKind::Network, &e.to_string())),
+    }
+}
+
+fn select_fee_estimate(body: &str, target_blocks: usize) -> Result<f64, S5Error> {
+    // keys are confirmation targets, values are sat/vB
+    let estimates: HashMap<String, f64> = parse_json(body)?;
+    let mut estimates: Vec<(usize, f64)> = estimates
+        .iter()
+        .filter_map(|(target, rate)| target.parse::<usize>().ok().map(|t| (t, *rate)))
+        .collect();
+    estimates.sort_by_key(|(target, _)| *target);
+    // the closest target that still confirms in time; fall back to the fastest one
+    let estimate = estimates
+        .iter()
+        .rev()
+        .find(|(target, _)| *target <= target_blocks)
+        .or(estimates.first());
+    match estimate {
+        Some((_, rate)) => Ok(*rate),
+        None => Err(S5Error::new(
+            ErrorKind::Network,
+            "Server could not estimate fees",
+        )),
+    }
+}
+
+impl ChainBackend for EsploraClient {
+    fn list_unspent(&self, script_pubkey: &Script) -> Result<Vec<Utxo>, S5Error> {
+        parse_utxos(&self.get_text(&format!("/scripthash/{}/utxo", script_hash(script_pubkey)))?)
+    }
+    fn get_history(&self, script_pubkey: &Script) -> Result<Vec<HistoryItem>, S5Error> {
+        parse_history(&self.get_text(&format!("/scripthash/{}/txs", script_hash(script_pubkey)))?)
+    }
+    fn get_tx(&self, txid: &Txid) -> Result<Vec<u8>, S5Error> {
+        let res = self.get(&format!("/tx/{}/raw", txid))?;
+        match res.bytes() {
+            Ok(bytes) => Ok(bytes.to_vec()),
+            Err(e) => Err(S5Error::new(ErrorKind::Network, &e.to_string())),
+        }
+    }
+    fn broadcast(&self, raw_tx: &[u8]) -> Result<Txid, S5Error> {
+        let url = format!("{}/tx", self.base_url);
+        let res = match self.client.post(&url).body(hex::encode(raw_tx)).send() {
+            Ok(res) => Self::check_status(res)?,
+            Err(e) => return Err(S5Error::new(ErrorKind::Network, &e.to_string())),
+        };
+        match res.text() {
+            Ok(txid) => parse_txid(&txid),
+            Err(e) => Err(S5Error::new(ErrorKind::Network, &e.to_string())),
+        }
+    }
+    fn tip_height(&self) -> Result<u32, S5Error> {
+        parse_height(&self.get_text("/blocks/tip/height")?)
+    }
+    fn get_fee_estimate(&self, target_blocks: usize) -> Result<f64, S5Error> {
+        select_fee_estimate(&self.get_text("/fee-estimates")?, target_blocks)
+    }
+}
+
+/// EsploraClient on the non-blocking reqwest client, for use inside async runtimes.
+#[cfg(feature = "async")]
+pub struct AsyncEsploraClient {
+    base_url: String,
+    client: reqwest::Client,
+}
+
+#[cfg(feature = "async")]
+impl AsyncEsploraClient {
+    pub fn new(base_url: &str) -> Self {
+        AsyncEsploraClient {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    async fn check_status(res: reqwest::Response) -> Result<reqwest::Response, S5Error> {
+        if res.status().is_success() {
+            Ok(res)
+        } else {
+            let status = res.status();
+            let body = res.text().await.unwrap_or_default();
+            Err(status_error(status, &body))
+        }
+    }
+
+    async fn get(&self, path: &str) -> Result<reqwest::Response, S5Error> {
+        let url = format!("{}{}", self.base_url, path);
+        match self.client.get(&url).send().await {
+            Ok(res) => Self::check_status(res).await,
+            Err(e) => Err(S5Error::new(ErrorKind::Network, &e.to_string())),
+        }
+    }
+
+    async fn get_text(&self, path: &str) -> Result<String, S5Error> {
+        match self.get(path).await?.text().await {
+            Ok(text) => Ok(text),
+            Err(e) => Err(S5Error::new(ErrorKind::Network, &e.to_string())),
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+#[async_trait::async_trait]
+impl AsyncChainBackend for AsyncEsploraClient {
+    async fn list_unspent(&self, script_pubkey: &Script) -> Result<Vec<Utxo>, S5Error> {
+        let path = format!("/scripthash/{}/utxo", script_hash(script_pubkey));
+        parse_utxos(&self.get_text(&path).await?)
+    }
+    async fn get_history(&self, script_pubkey: &Script) -> Result<Vec<HistoryItem>, S5Error> {
+        let path = format!("/scripthash/{}/txs", script_hash(script_pubkey));
+        parse_history(&self.get_text(&path).await?)
+    }
+    async fn get_tx(&self, txid: &Txid) -> Result<Vec<u8>, S5Error> {
+        let res = self.get(&format!("/tx/{}/raw", txid)).await?;
+        match res.bytes().await {
+            Ok(bytes) => Ok(bytes.to_vec()),
+            Err(e) => Err(S5Error::new(ErrorKind::Network, &e.to_string())),
+        }
+    }
+    async fn broadcast(&self, raw_tx: &[u8]) -> Result<Txid, S5Error> {
+        let url = format!("{}/tx", self.base_url);
+        let res = match self
+            .client
+            .post(&url)
+            .body(hex::encode(raw_tx))
+            .send()
+            .await
+        {
+            Ok(res) => Self::check_status(res).await?,
+            Err(e) => return Err(S5Error::new(ErrorKind::Network, &e.to_string())),
+        };
+        match res.text().await {
+            Ok(txid) => parse_txid(&txid),
+            Err(e) => Err(S5Error::new(ErrorKind::Network, &e.to_string())),
+        }
+    }
+    async fn tip_height(&self) -> Result<u32, S5Error> {
+        parse_height(&self.get_text("/blocks/tip/height").await?)
+    }
+    async fn get_fee_estimate(&self, target_blocks: usize) -> Result<f64, S5Error> {
+        select_fee_estimate(&self.get_text("/fee-estimates").await?, target_blocks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use bitcoin::ScriptBuf;
+
+    use super::*;
+    use crate::network::electrum::{BitcoinNetwork, NetworkConfig};
+    use crate::network::mock::mock_http_server;
+
+    type Route = (&'static str, String, u16, Vec<u8>);
+    type Requests = Arc<Mutex<Vec<(String, String, Vec<u8>)>>>;
+
+    /// Serves canned responses for (method, path) and records every request it receives.
+    fn mock_server(routes: Vec<Route>) -> (String, Requests) {
+        let requests: Requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        let url = mock_http_server(move |method, path, body| {
+            recorded
+                .lock()
+                .unwrap()
+                .push((method.to_string(), path.to_string(), body.to_vec()));
+            routes
+                .iter()
+                .find(|route| route.0 == method && route.1 == path)
+                .map(|route| (route.2, route.3.clone()))
+                .unwrap_or((404, b"Not Found".to_vec()))
+        });
+        (url, requests)
+    }
+
+    const TXID_A: &str = "9be9e9ea8d0bd2a3b9c6a9ea5b93a77ce3f3e2e0a6b0bbde0de0e5d34adf1c2a";
+    const TXID_B: &str = "3f2a7ae1e1c1e1ca7d3bd0f1d8e6f1d2a90b5f7e4ee27bb3a1c8cf2bd0bfcc4f";
+
+    fn test_script() -> ScriptBuf {
+        ScriptBuf::from_hex("0020e4f7e5d1f7a2b4a6c6e1b9b8ae1b8e1c1a6c2e0fb6cfb9f6e0e9d5d3b1a8c7e6")
+            .unwrap()
+    }
+
+    #[test]
+    fn test_script_hash() {
+        // sha256 of the empty script, not reversed
+        assert_eq!(
+            script_hash(&ScriptBuf::new()),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn test_esplora_scripthash_queries() {
+        let script = test_script();
+        let hash = script_hash(&script);
+        let utxos = format!(
+            r#"[
+                {{"txid":"{}","vout":1,"value":100000,"status":{{"confirmed":true,"block_height":2540001,"block_hash":"00","block_time":1700000000}}}},
+                {{"txid":"{}","vout":0,"valuecommitment":"08aabb","status":{{"confirmed":false}}}}
+            ]"#,
+            TXID_A, TXID_B
+        );
+        let txs = format!(
+            r#"[
+                {{"txid":"{}","status":{{"confirmed":false}}}},
+                {{"txid":"{}","status":{{"confirmed":true,"block_height":2540001}}}}
+            ]"#,
+            TXID_B, TXID_A
+        );
+        let (url, requests) = mock_server(vec![
+            (
+                "GET",
+                format!("/scripthash/{}/utxo", hash),
+                200,
+                utxos.into_bytes(),
+            ),
+            (
+                "GET",
+                format!("/scripthash/{}/txs", hash),
+                200,
+                txs.into_bytes(),
+            ),
+        ]);
+        let esplora = EsploraClient::new(&(url + "/"));
+
+        let utxos = esplora.list_unspent(&script).unwrap();
+        assert_eq!(
+            utxos,
+            vec![
+                Utxo {
+                    txid: Txid::from_str(TXID_A).unwrap(),
+                    vout: 1,
+                    value: 100000,
+                    height: 2540001,
+                },
+                Utxo {
+                    txid: Txid::from_str(TXID_B).unwrap(),
+                    vout: 0,
+                    value: 0,
+                    height: 0,
+                },
+            ]
+        );
+
+        let history = esplora.get_history(&script).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].txid, Txid::from_str(TXID_B).unwrap());
+        assert_eq!(history[0].height, 0);
+        assert_eq!(history[1].height, 2540001);
+
+        assert_eq!(requests.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_esplora_tx_broadcast_and_tip() {
+        let raw_tx = vec![0x02, 0x00, 0x00, 0x00, 0xff, 0x00];
+        let (url, requests) = mock_server(vec![
+            ("GET", format!("/tx/{}/raw", TXID_A), 200, raw_tx.clone()),
+            ("POST", "/tx".to_string(), 200, TXID_B.as_bytes().to_vec()),
+            (
+                "GET",
+                "/blocks/tip/height".to_string(),
+                200,
+                b"2540123".to_vec(),
+            ),
+        ]);
+        let esplora = EsploraClient::new(&url);
+
+        assert_eq!(
+            esplora.get_tx(&Txid::from_str(TXID_A).unwrap()).unwrap(),
+            raw_tx
+        );
+        assert_eq!(
+            esplora.broadcast(&raw_tx).unwrap(),
+            Txid::from_str(TXID_B).unwrap()
+        );
+        assert_eq!(esplora.tip_height().unwrap(), 2540123);
+
+        let requests = requests.lock().unwrap();
+        let (method, path, body) = &requests[1];
+        assert_eq!(method, "POST");
+        assert_eq!(path, "/tx");
+        assert_eq!(body, hex::encode(&raw_tx).as_bytes());
+    }
+
+    #[test]
+    fn test_esplora_fee_estimates() {
+        let estimates = r#"{"1":20.5,"2":20.5,"3":15.0,"6":10.0,"144":1.0}"#;
+        let (url, _) = mock_server(vec![(
+            "GET",
+            "/fee-estimates".to_string(),
+            200,
+            estimates.as_bytes().to_vec(),
+        )]);
+        let esplora = EsploraClient::new(&url);
+        assert_eq!(esplora.get_fee_estimate(1).unwrap(), 20.5);
+        assert_eq!(esplora.get_fee_estimate(5).unwrap(), 15.0);
+        assert_eq!(esplora.get_fee_estimate(6).unwrap(), 10.0);
+        assert_eq!(esplora.get_fee_estimate(1008).unwrap(), 1.0);
+        // below the fastest target, pay the fastest rate
+        assert_eq!(esplora.get_fee_estimate(0).unwrap(), 20.5);
+
+        let (url, _) = mock_server(vec![(
+            "GET",
+            "/fee-estimates".to_string(),
+            200,
+            b"{}".to_vec(),
+        )]);
+        assert!(EsploraClient::new(&url).get_fee_estimate(1).is_err());
+    }
+
+    #[test]
+    fn test_esplora_errors() {
+        let (url, _) = mock_server(vec![(
+            "POST",
+            "/tx".to_string(),
+            400,
+            b"sendrawtransaction RPC error: bad-txns-inputs-missingorspent".to_vec(),
+        )]);
+        let esplora = EsploraClient::new(&url);
+        let error = esplora.broadcast(&[0x00]).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::Network.to_string());
+        assert!(error.message.contains("bad-txns-inputs-missingorspent"));
+        // unknown path
+        assert!(esplora.tip_height().is_err());
+    }
+
+    #[test]
+    fn test_network_config_esplora_backend() {
+        let (url, _) = mock_server(vec![(
+            "GET",
+            "/blocks/tip/height".to_string(),
+            200,
+            b"812000".to_vec(),
+        )]);
+        let network_config =
+            NetworkConfig::new_esplora(BitcoinNetwork::BitcoinTestnet, &url, false, None);
+        assert!(network_config.electrum_url().build_client().is_err());
+        let backend = network_config.build_backend().unwrap();
+        assert_eq!(backend.tip_height().unwrap(), 812000);
+        // esplora has no merkle proofs to verify against
+        let network_config = NetworkConfig::new_esplora(BitcoinNetwork::Bitcoin, &url, true, None);
+        assert!(network_config.build_backend().is_err());
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn test_async_esplora() {
+        let script = test_script();
+        let hash = script_hash(&script);
+        let utxos = format!(
+            r#"[{{"txid":"{}","vout":1,"value":100000,"status":{{"confirmed":true,"block_height":2540001}}}}]"#,
+            TXID_A
+        );
+        let raw_tx = vec![0x02, 0x00, 0x00, 0x00, 0xff, 0x00];
+        let (url, requests) = mock_server(vec![
+            (
+                "GET",
+                format!("/scripthash/{}/utxo", hash),
+                200,
+                utxos.into_bytes(),
+            ),
+            ("GET", format!("/tx/{}/raw", TXID_A), 200, raw_tx.clone()),
+            ("POST", "/tx".to_string(), 200, TXID_B.as_bytes().to_vec()),
+            (
+                "GET",
+                "/blocks/tip/height".to_string(),
+                200,
+                b"2540123".to_vec(),
+            ),
+            (
+                "GET",
+                "/fee-estimates".to_string(),
+                200,
+                br#"{"1":20.5,"6":10.0}"#.to_vec(),
+            ),
+        ]);
+        let esplora = AsyncEsploraClient::new(&url);
+
+        let utxos = esplora.list_unspent(&script).await.unwrap();
+        assert_eq!(utxos[0].value, 100000);
+        assert_eq!(utxos[0].height, 2540001);
+        let txid = Txid::from_str(TXID_A).unwrap();
+        assert_eq!(esplora.get_tx(&txid).await.unwrap(), raw_tx);
+        assert_eq!(
+            esplora.broadcast(&raw_tx).await.unwrap(),
+            Txid::from_str(TXID_B).unwrap()
+        );
+        assert_eq!(esplora.tip_height().await.unwrap(), 2540123);
+        assert_eq!(esplora.get_fee_estimate(3).await.unwrap(), 20.5);
+        assert!(esplora.get_history(&script).await.is_err());
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests[2].2, hex::encode(&raw_tx).as_bytes());
+    }
+}
@@ -0,0 +1,187 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use bitcoin::{
+    consensus::deserialize,
+    hashes::{sha256d, Hash},
+    Script, ScriptBuf, Transaction, Txid,
+};
+
+use crate::util::error::{ErrorKind, S5Error};
+
+#[cfg(feature = "async")]
+use super::backend::AsyncChainBackend;
+use super::backend::{ChainBackend, HistoryItem, Utxo};
+
+/// In-memory ChainBackend, used to exercise claim and refund flows offline.
+/// With the `async` feature it is also an AsyncChainBackend.
+#[derive(Debug, Default)]
+pub struct MockChainBackend {
+    utxos: HashMap<ScriptBuf, Vec<Utxo>>,
+    history: HashMap<ScriptBuf, Vec<HistoryItem>>,
+    txs: HashMap<Txid, Vec<u8>>,
+    tip_height: u32,
+    fee_rate: f64,
+    broadcasted: Mutex<Vec<Vec<u8>>>,
+}
+
+impl MockChainBackend {
+    pub fn new(tip_height: u32, fee_rate: f64) -> Self {
+        MockChainBackend {
+            tip_height,
+            fee_rate,
+            ..Default::default()
+        }
+    }
+    /// Registers a funding transaction paying `script_pubkey`.
+    /// Every output in `outputs` (vout, value) is added as unspent.
+    pub fn add_funding(
+        &mut self,
+        script_pubkey: &Script,
+        txid: Txid,
+        raw_tx: Vec<u8>,
+        outputs: &[(u32, u64)],
+        height: u32,
+    ) {
+        let utxos = self.utxos.entry(script_pubkey.to_owned()).or_default();
+        for (vout, value) in outputs {
+            utxos.push(Utxo {
+                txid,
+                vout: *vout,
+                value: *value,
+                height,
+            });
+        }
+        self.history
+            .entry(script_pubkey.to_owned())
+            .or_default()
+            .push(HistoryItem {
+                txid,
+                height: height as i32,
+            });
+        self.txs.insert(txid, raw_tx);
+    }
+    /// Registers a transaction touching `script_pubkey`, e.g. a spend of a swap lockup.
+    pub fn add_history(&mut self, script_pubkey: &Script, txid: Txid, height: i32) {
+        self.history
+            .entry(script_pubkey.to_owned())
+            .or_default()
+            .push(HistoryItem { txid, height });
+    }
+    pub fn set_tip_height(&mut self, tip_height: u32) {
+        self.tip_height = tip_height;
+    }
+    /// Raw transactions passed to `broadcast`, oldest first.
+    pub fn broadcasted(&self) -> Vec<Vec<u8>> {
+        self.broadcasted.lock().unwrap().clone()
+    }
+}
+
+impl ChainBackend for MockChainBackend {
+    fn list_unspent(&self, script_pubkey: &Script) -> Result<Vec<Utxo>, S5Error> {
+        Ok(self.utxos.get(script_pubkey).cloned().unwrap_or_default())
+    }
+    fn get_history(&self, script_pubkey: &Script) -> Result<Vec<HistoryItem>, S5Error> {
+        Ok(self.history.get(script_pubkey).cloned().unwrap_or_default())
+    }
+    fn get_tx(&self, txid: &Txid) -> Result<Vec<u8>, S5Error> {
+        match self.txs.get(txid) {
+            Some(raw_tx) => Ok(raw_tx.clone()),
+            None => Err(S5Error::new(
+                ErrorKind::Network,
+                &format!("Transaction {} not found", txid),
+            )),
+        }
+    }
+    fn broadcast(&self, raw_tx: &[u8]) -> Result<Txid, S5Error> {
+        self.broadcasted.lock().unwrap().push(raw_tx.to_vec());
+        // Liquid transactions do not parse as bitcoin transactions; any unique id will do.
+        match deserialize::<Transaction>(raw_tx) {
+            Ok(tx) => Ok(tx.txid()),
+            Err(_) => Ok(Txid::from_raw_hash(sha256d::Hash::hash(raw_tx))),
+        }
+    }
+    fn tip_height(&self) -> Result<u32, S5Error> {
+        Ok(self.tip_height)
+    }
+    fn get_fee_estimate(&self, _target_blocks: usize) -> Result<f64, S5Error> {
+        Ok(self.fee_rate)
+    }
+}
+
+#[cfg(feature = "async")]
+#[async_trait::async_trait]
+impl AsyncChainBackend for MockChainBackend {
+    async fn list_unspent(&self, script_pubkey: &Script) -> Result<Vec<Utxo>, S5Error> {
+        ChainBackend::list_unspent(self, script_pubkey)
+    }
+    async fn get_history(&self, script_pubkey: &Script) -> Result<Vec<HistoryItem>, S5Error> {
+        ChainBackend::get_history(self, script_pubkey)
+    }
+    async fn get_tx(&self, txid: &Txid) -> Result<Vec<u8>, S5Error> {
+        ChainBackend::get_tx(self, txid)
+    }
+    async fn broadcast(&self, raw_tx: &[u8]) -> Result<Txid, S5Error> {
+        ChainBackend::broadcast(self, raw_tx)
+    }
+    async fn tip_height(&self) -> Result<u32, S5Error> {
+        ChainBackend::tip_height(self)
+    }
+    async fn get_fee_estimate(&self, target_blocks: usize) -> Result<f64, S5Error> {
+        ChainBackend::get_fee_estimate(self, target_blocks)
+    }
+}
+
+/// Minimal HTTP/1.1 server for testing the http based backends.
+/// Every request is answered with `handler(method, path, body)` -> (status, body)
+/// and the connection is closed. Returns the base url of the server.
+#[cfg(test)]
+pub(crate) fn mock_http_server<F>(handler: F) -> String
+where
+    F: Fn(&str, &str, &[u8]) -> (u16, Vec<u8>) + Send + 'static,
+{
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => break,
+            };
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut content_length = 0;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header.trim().is_empty() {
+                    break;
+                }
+                let header = header.to_ascii_lowercase();
+                if let Some(length) = header.strip_prefix("content-length:") {
+                    content_length = length.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+
+            let mut parts = request_line.split_whitespace();
+            let method = parts.next().unwrap_or_default();
+            let path = parts.next().unwrap_or_default();
+            let (status, response) = handler(method, path, &body);
+
+            write!(
+                stream,
+                "HTTP/1.1 {} MOCK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                status,
+                response.len()
+            )
+            .unwrap();
+            stream.write_all(&response).unwrap();
+        }
+    });
+    url
+}
@@ -1 +1,7 @@
-pub mod electrum;
\ No newline at end of file
+pub mod backend;
+pub mod electrum;
+pub mod esplora;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
+pub mod rpc;
+pub mod spv;
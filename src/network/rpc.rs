@@ -0,0 +1,390 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use bitcoin::{Amount, Script, Txid};
+use reqwest::blocking::Client;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::util::error::{ErrorKind, S5Error};
+
+use super::backend::{ChainBackend, HistoryItem, Utxo};
+
+/// Chain access over the JSON-RPC interface of bitcoind or elementsd.
+///
+/// Scripts are looked up with `scantxoutset`, so no wallet or index is required. This only
+/// sees confirmed, unspent outputs: unconfirmed lockups are not found until they are mined.
+/// Spending transactions are never visible to a scan, so `get_history` returns an error instead
+/// of a history that silently leaves them out.
+/// Transactions found by a scan are fetched with their block hash so `txindex` is not needed.
+pub struct RpcClient {
+    url: String,
+    user: String,
+    password: String,
+    client: Client,
+    known_heights: RefCell<HashMap<Txid, u32>>,
+}
+
+#[derive(Deserialize, Debug)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct RpcResponse {
+    result: Option<Value>,
+    error: Option<RpcError>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ScanUnspent {
+    txid: String,
+    vout: u32,
+    /// missing for confidential elements outputs, which carry an `amountcommitment` instead
+    amount: Option<f64>,
+    height: u32,
+}
+
+#[derive(Deserialize, Debug)]
+struct ScanTxOutSetResult {
+    success: bool,
+    unspents: Vec<ScanUnspent>,
+}
+
+#[derive(Deserialize, Debug)]
+struct SmartFeeEstimate {
+    /// BTC/kvB
+    feerate: Option<f64>,
+    errors: Option<Vec<String>>,
+}
+
+fn parse_txid(txid: &str) -> Result<Txid, S5Error> {
+    match Txid::from_str(txid) {
+        Ok(result) => Ok(result),
+        Err(e) => Err(S5Error::new(ErrorKind::Network, &e.to_string())),
+    }
+}
+
+impl RpcClient {
+    pub fn new(url: &str, user: &str, password: &str) -> Self {
+        RpcClient {
+            url: url.to_string(),
+            user: user.to_string(),
+            password: password.to_string(),
+            client: Client::new(),
+            known_heights: RefCell::new(HashMap::new()),
+        }
+    }
+
+    fn call<T: DeserializeOwned>(&self, method: &str, params: Value) -> Result<T, S5Error> {
+        let request = json!({
+            "jsonrpc": "1.0",
+            "id": "boltz-client",
+            "method": method,
+            "params": params,
+        });
+        let res = match self
+            .client
+            .post(&self.url)
+            .basic_auth(&self.user, Some(&self.password))
+            .json(&request)
+            .send()
+        {
+            Ok(res) => res,
+            Err(e) => return Err(S5Error::new(ErrorKind::Network, &e.to_string())),
+        };
+        // errors come back as a json body with a non 200 status
+        let status = res.status();
+        let body = match res.text() {
+            Ok(body) => body,
+            Err(e) => return Err(S5Error::new(ErrorKind::Network, &e.to_string())),
+        };
+        let response: RpcResponse = match serde_json::from_str(&body) {
+            Ok(result) => result,
+            Err(_) => {
+                return Err(S5Error::new(
+                    ErrorKind::Network,
+                    &format!("RPC returned {}: {}", status, body),
+                ))
+            }
+        };
+        if let Some(error) = response.error {
+            return Err(S5Error::new(
+                ErrorKind::Network,
+                &format!("RPC error {} on {}: {}", error.code, method, error.message),
+            ));
+        }
+        match serde_json::from_value(response.result.unwrap_or(Value::Null)) {
+            Ok(result) => Ok(result),
+            Err(e) => Err(S5Error::new(ErrorKind::Network, &e.to_string())),
+        }
+    }
+
+    fn scan(&self, script_pubkey: &Script) -> Result<Vec<ScanUnspent>, S5Error> {
+        let descriptor = format!("raw({})", hex::encode(script_pubkey.as_bytes()));
+        let result: ScanTxOutSetResult =
+            self.call("scantxoutset", json!(["start", [{ "desc": descriptor }]]))?;
+        if !result.success {
+            return Err(S5Error::new(
+                ErrorKind::Network,
+                "scantxoutset did not complete",
+            ));
+        }
+        let mut known_heights = self.known_heights.borrow_mut();
+        for unspent in result.unspents.iter() {
+            known_heights.insert(parse_txid(&unspent.txid)?, unspent.height);
+        }
+        Ok(result.unspents)
+    }
+}
+
+impl ChainBackend for RpcClient {
+    fn list_unspent(&self, script_pubkey: &Script) -> Result<Vec<Utxo>, S5Error> {
+        self.scan(script_pubkey)?
+            .iter()
+            .map(|unspent| {
+                let value = match unspent.amount {
+                    Some(amount) => match Amount::from_btc(amount) {
+                        Ok(amount) => amount.to_sat(),
+                        Err(e) => return Err(S5Error::new(ErrorKind::Network, &e.to_string())),
+                    },
+                    None => 0,
+                };
+                Ok(Utxo {
+                    txid: parse_txid(&unspent.txid)?,
+                    vout: unspent.vout,
+                    value,
+                    height: unspent.height,
+                })
+            })
+            .collect()
+    }
+    fn get_history(&self, _script_pubkey: &Script) -> Result<Vec<HistoryItem>, S5Error> {
+        Err(S5Error::new(
+            ErrorKind::Network,
+            "Script history is not available over RPC: scantxoutset only finds unspent outputs",
+        ))
+    }
+    fn get_tx(&self, txid: &Txid) -> Result<Vec<u8>, S5Error> {
+        let known_height = self.known_heights.borrow().get(txid).copied();
+        let params = match known_height {
+            Some(height) => {
+                let block_hash: String = self.call("getblockhash", json!([height]))?;
+                json!([txid.to_string(), false, block_hash])
+            }
+            // mempool, or any transaction when the node runs with txindex
+            None => json!([txid.to_string(), false]),
+        };
+        let raw_tx: String = self.call("getrawtransaction", params)?;
+        match hex::decode(raw_tx) {
+            Ok(result) => Ok(result),
+            Err(e) => Err(S5Error::new(ErrorKind::Network, &e.to_string())),
+        }
+    }
+    fn broadcast(&self, raw_tx: &[u8]) -> Result<Txid, S5Error> {
+        let txid: String = self.call("sendrawtransaction", json!([hex::encode(raw_tx)]))?;
+        parse_txid(&txid)
+    }
+    fn tip_height(&self) -> Result<u32, S5Error> {
+        self.call("getblockcount", json!([]))
+    }
+    fn get_fee_estimate(&self, target_blocks: usize) -> Result<f64, S5Error> {
+        let estimate: SmartFeeEstimate = self.call("estimatesmartfee", json!([target_blocks]))?;
+        match estimate.feerate {
+            // BTC/kvB -> sat/vB
+            Some(btc_per_kvb) => Ok(btc_per_kvb * 100_000.0),
+            None => Err(S5Error::new(
+                ErrorKind::Network,
+                &format!(
+                    "Server could not estimate fees: {}",
+                    estimate.errors.unwrap_or_default().join(", ")
+                ),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use bitcoin::ScriptBuf;
+
+    use super::*;
+    use crate::network::electrum::{BitcoinNetwork, NetworkConfig};
+    use crate::network::mock::mock_http_server;
+
+    const TXID_A: &str = "9be9e9ea8d0bd2a3b9c6a9ea5b93a77ce3f3e2e0a6b0bbde0de0e5d34adf1c2a";
+    const TXID_B: &str = "3f2a7ae1e1c1e1ca7d3bd0f1d8e6f1d2a90b5f7e4ee27bb3a1c8cf2bd0bfcc4f";
+    const BLOCK_HASH: &str = "000000000000000a1c7b4a1e07a8e7e1d8c0c3cf5a1b0a4c2f6d1e0f9a8b7c6d";
+
+    type Calls = Arc<Mutex<Vec<(String, Value)>>>;
+
+    /// JSON-RPC server answering from `responses` (method -> response body) and recording calls.
+    fn mock_rpc_server(responses: Vec<(&'static str, Value)>) -> (String, Calls) {
+        let calls: Calls = Arc::new(Mutex::new(Vec::new()));
+        let recorded = calls.clone();
+        let url = mock_http_server(move |_, _, body| {
+            let request: Value = serde_json::from_slice(body).unwrap();
+            let method = request["method"].as_str().unwrap().to_string();
+            recorded
+                .lock()
+                .unwrap()
+                .push((method.clone(), request["params"].clone()));
+            match responses.iter().find(|response| response.0 == method) {
+                Some((_, response)) => {
+                    let status = if response["error"].is_null() {
+                        200
+                    } else {
+                        500
+                    };
+                    (status, response.to_string().into_bytes())
+                }
+                None => (404, b"".to_vec()),
+            }
+        });
+        (url, calls)
+    }
+
+    fn ok(result: Value) -> Value {
+        json!({ "result": result, "error": null, "id": "boltz-client" })
+    }
+
+    #[test]
+    fn test_rpc_scantxoutset() {
+        let script = ScriptBuf::from_hex("a914b8b1a2a5bd6b4f0e8d0cb83a4c1b61e64ac4c1c487").unwrap();
+        let (url, calls) = mock_rpc_server(vec![(
+            "scantxoutset",
+            ok(json!({
+                "success": true,
+                "txouts": 9000,
+                "height": 2540100,
+                "unspents": [
+                    { "txid": TXID_A, "vout": 0, "scriptPubKey": "a914", "desc": "raw", "amount": 0.00100000, "height": 2540001 },
+                    { "txid": TXID_A, "vout": 2, "scriptPubKey": "a914", "desc": "raw", "amount": 0.0000546, "height": 2540001 },
+                    { "txid": TXID_B, "vout": 1, "scriptPubKey": "a914", "desc": "raw", "amountcommitment": "08aa", "height": 2540050 }
+                ],
+                "total_amount": 0.0010546
+            })),
+        )]);
+        let rpc = RpcClient::new(&url, "user", "pass");
+
+        let utxos = rpc.list_unspent(&script).unwrap();
+        assert_eq!(utxos.len(), 3);
+        assert_eq!(utxos[0].txid, Txid::from_str(TXID_A).unwrap());
+        assert_eq!(utxos[0].value, 100000);
+        assert_eq!(utxos[1].vout, 2);
+        assert_eq!(utxos[1].value, 5460);
+        assert_eq!(utxos[2].value, 0);
+        assert_eq!(utxos[2].height, 2540050);
+
+        // spends are invisible to scantxoutset, so there is no history to report
+        assert!(rpc.get_history(&script).is_err());
+
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls[0].0, "scantxoutset");
+        assert_eq!(
+            calls[0].1,
+            json!(["start", [{ "desc": "raw(a914b8b1a2a5bd6b4f0e8d0cb83a4c1b61e64ac4c1c487)" }]])
+        );
+    }
+
+    #[test]
+    fn test_rpc_get_tx() {
+        let (url, calls) = mock_rpc_server(vec![
+            (
+                "scantxoutset",
+                ok(json!({
+                    "success": true,
+                    "unspents": [{ "txid": TXID_A, "vout": 0, "amount": 0.001, "height": 2540001 }]
+                })),
+            ),
+            ("getblockhash", ok(json!(BLOCK_HASH))),
+            ("getrawtransaction", ok(json!("0200000000ff"))),
+        ]);
+        let rpc = RpcClient::new(&url, "user", "pass");
+
+        // unknown transactions are looked up in the mempool / txindex
+        let raw_tx = rpc.get_tx(&Txid::from_str(TXID_B).unwrap()).unwrap();
+        assert_eq!(raw_tx, hex::decode("0200000000ff").unwrap());
+
+        // scanned transactions are looked up in their block
+        rpc.list_unspent(&ScriptBuf::new()).unwrap();
+        rpc.get_tx(&Txid::from_str(TXID_A).unwrap()).unwrap();
+
+        let calls = calls.lock().unwrap();
+        assert_eq!(
+            calls[0],
+            ("getrawtransaction".to_string(), json!([TXID_B, false]))
+        );
+        assert_eq!(calls[2], ("getblockhash".to_string(), json!([2540001])));
+        assert_eq!(
+            calls[3],
+            (
+                "getrawtransaction".to_string(),
+                json!([TXID_A, false, BLOCK_HASH])
+            )
+        );
+    }
+
+    #[test]
+    fn test_rpc_broadcast_tip_and_fees() {
+        let (url, calls) = mock_rpc_server(vec![
+            ("sendrawtransaction", ok(json!(TXID_B))),
+            ("getblockcount", ok(json!(2540123))),
+            (
+                "estimatesmartfee",
+                ok(json!({ "feerate": 0.00012, "blocks": 2 })),
+            ),
+        ]);
+        let rpc = RpcClient::new(&url, "user", "pass");
+
+        assert_eq!(
+            rpc.broadcast(&[0x02, 0x00]).unwrap(),
+            Txid::from_str(TXID_B).unwrap()
+        );
+        assert_eq!(rpc.tip_height().unwrap(), 2540123);
+        assert!((rpc.get_fee_estimate(2).unwrap() - 12.0).abs() < 1e-9);
+
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls[0].1, json!(["0200"]));
+        assert_eq!(calls[2].1, json!([2]));
+
+        let (url, _) = mock_rpc_server(vec![(
+            "estimatesmartfee",
+            ok(json!({ "errors": ["Insufficient data or no feerate found"], "blocks": 0 })),
+        )]);
+        let error = RpcClient::new(&url, "user", "pass")
+            .get_fee_estimate(2)
+            .unwrap_err();
+        assert!(error.message.contains("Insufficient data"));
+    }
+
+    #[test]
+    fn test_rpc_errors() {
+        let (url, _) = mock_rpc_server(vec![(
+            "sendrawtransaction",
+            json!({ "result": null, "error": { "code": -25, "message": "bad-txns-inputs-missingorspent" }, "id": "boltz-client" }),
+        )]);
+        let rpc = RpcClient::new(&url, "user", "pass");
+        let error = rpc.broadcast(&[0x00]).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::Network.to_string());
+        assert!(error.message.contains("-25"));
+        assert!(error.message.contains("bad-txns-inputs-missingorspent"));
+        // method not found: empty 404 body
+        assert!(rpc.tip_height().is_err());
+    }
+
+    #[test]
+    fn test_network_config_rpc_backend() {
+        let (url, _) = mock_rpc_server(vec![("getblockcount", ok(json!(812000)))]);
+        let network_config =
+            NetworkConfig::new_rpc(BitcoinNetwork::BitcoinTestnet, &url, "user", "pass", None);
+        assert!(network_config.electrum_url().build_client().is_err());
+        let backend = network_config.build_backend().unwrap();
+        assert_eq!(backend.tip_height().unwrap(), 812000);
+    }
+}
@@ -0,0 +1,554 @@
+use std::cell::RefCell;
+use std::str::FromStr;
+
+use bitcoin::block::Header;
+use bitcoin::consensus::deserialize;
+use bitcoin::hashes::{sha256d, Hash, HashEngine};
+use bitcoin::pow::{CompactTarget, Target, Work};
+use bitcoin::{BlockHash, Script, Transaction, Txid};
+use electrum_client::ElectrumApi;
+
+use crate::util::error::{ErrorKind, S5Error};
+
+use super::backend::{ChainBackend, HistoryItem, Utxo};
+use super::electrum::BitcoinNetwork;
+
+/// electrum servers return at most this many headers per request
+const MAX_HEADERS_PER_REQUEST: u32 = 2016;
+/// blocks per difficulty period
+const RETARGET_INTERVAL: u32 = 2016;
+/// two weeks, in seconds
+const TARGET_TIMESPAN: i64 = 14 * 24 * 60 * 60;
+/// known headers fetched again on every sync, so that shallow reorgs replace them
+const REORG_DEPTH: usize = 6;
+
+// the 2024 halving block
+const MAINNET_CHECKPOINT_HEIGHT: u32 = 840_000;
+const MAINNET_CHECKPOINT_HASH: &str =
+    "0000000000000000000320283a032748cef8227873ff4872689bf23f1cda83a5";
+// 26 difficulty periods at the checkpoint's difficulty (bits 0x17034219), about a year of blocks
+const MAINNET_MIN_WORK: &str = "00000000000000000000000000000000000000003ed770a3e9e5a68b954a5c40";
+
+/// A block the verified header chain has to pass through.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Checkpoint {
+    pub height: u32,
+    pub hash: BlockHash,
+    /// Least total work of the headers from the start of the checkpoint's difficulty period up
+    /// to the tip, so that a server cannot present a chain that stops shortly after the checkpoint.
+    pub min_work: Work,
+}
+
+impl Checkpoint {
+    /// Only Bitcoin mainnet has a checkpoint. Test networks allow minimum difficulty blocks, so
+    /// their headers prove next to nothing, and Liquid blocks are signed by the federation rather
+    /// than mined.
+    pub fn for_network(network: BitcoinNetwork) -> Option<Checkpoint> {
+        match network {
+            BitcoinNetwork::Bitcoin => {
+                let mut min_work = [0u8; 32];
+                min_work.copy_from_slice(
+                    &hex::decode(MAINNET_MIN_WORK).expect("valid min work constant"),
+                );
+                Some(Checkpoint {
+                    height: MAINNET_CHECKPOINT_HEIGHT,
+                    hash: BlockHash::from_str(MAINNET_CHECKPOINT_HASH)
+                        .expect("valid checkpoint hash constant"),
+                    min_work: Work::from_be_bytes(min_work),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Height of the first block of the checkpoint's difficulty period, where header
+    /// verification starts.
+    pub fn period_start(&self) -> u32 {
+        self.height - self.height % RETARGET_INTERVAL
+    }
+}
+
+/// An Electrum ChainBackend that does not take the server's word for confirmations.
+///
+/// Every transaction the server reports as confirmed is checked with a merkle proof
+/// (`blockchain.transaction.get_merkle`) against the header at its height, and raw transactions
+/// must hash to the txid they were requested by.
+/// The headers from the start of the checkpoint's difficulty period up to the tip must pass
+/// through the checkpoint, follow the difficulty retargets, carry valid proof of work and add up
+/// to the checkpoint's minimum work. See `verify_header_chain`.
+/// Unconfirmed outputs cannot be proven and are left out of `list_unspent`.
+///
+/// Only available on Bitcoin mainnet, see `Checkpoint::for_network`.
+pub struct SpvElectrumClient {
+    client: electrum_client::Client,
+    checkpoint: Checkpoint,
+    /// verified headers, starting at `checkpoint.period_start()`
+    headers: RefCell<Vec<Header>>,
+}
+
+impl SpvElectrumClient {
+    pub fn new(client: electrum_client::Client, network: BitcoinNetwork) -> Result<Self, S5Error> {
+        match Checkpoint::for_network(network) {
+            Some(checkpoint) => Ok(SpvElectrumClient {
+                client,
+                checkpoint,
+                headers: RefCell::new(Vec::new()),
+            }),
+            None => Err(S5Error::new(
+                ErrorKind::Network,
+                &format!("SPV verification is not available on {:?}", network),
+            )),
+        }
+    }
+
+    /// Headers from `start_height` up to and including `tip_height`.
+    fn bitcoin_headers(&self, start_height: u32, tip_height: u32) -> Result<Vec<Header>, S5Error> {
+        let mut headers: Vec<Header> = Vec::new();
+        let mut height = start_height;
+        while height <= tip_height {
+            let count = std::cmp::min(MAX_HEADERS_PER_REQUEST, tip_height - height + 1);
+            let result = match self.client.block_headers(height as usize, count as usize) {
+                Ok(result) => result,
+                Err(e) => return Err(S5Error::new(ErrorKind::Network, &e.to_string())),
+            };
+            if result.headers.is_empty() {
+                return Err(S5Error::new(
+                    ErrorKind::Network,
+                    &format!("Server did not return the header at height {}", height),
+                ));
+            }
+            height += result.headers.len() as u32;
+            headers.extend(result.headers);
+        }
+        Ok(headers)
+    }
+
+    /// Extends the verified headers up to the server's tip and returns the tip height.
+    fn sync_headers(&self) -> Result<u32, S5Error> {
+        let tip_height = self.tip_height()?;
+        if tip_height < self.checkpoint.height {
+            return Err(S5Error::new(
+                ErrorKind::Network,
+                "SPV: Server chain ends before the checkpoint",
+            ));
+        }
+        let start = self.checkpoint.period_start();
+        let mut headers = self.headers.borrow_mut();
+        let keep = headers.len().saturating_sub(REORG_DEPTH);
+        let mut updated = headers[..keep].to_vec();
+        updated.extend(self.bitcoin_headers(start + keep as u32, tip_height)?);
+        let updated = match verify_header_chain(&self.checkpoint, &updated) {
+            Ok(()) => updated,
+            // a reorg deeper than REORG_DEPTH; start over from the checkpoint
+            Err(_) if keep > 0 => {
+                let updated = self.bitcoin_headers(start, tip_height)?;
+                verify_header_chain(&self.checkpoint, &updated)?;
+                updated
+            }
+            Err(e) => return Err(e),
+        };
+        *headers = updated;
+        Ok(tip_height)
+    }
+
+    /// Checks that `txid` is included in the block at `height`.
+    fn verify_confirmation(&self, txid: &Txid, height: u32) -> Result<(), S5Error> {
+        let proof = match self.client.transaction_get_merkle(txid, height as usize) {
+            Ok(result) => result,
+            Err(e) => return Err(S5Error::new(ErrorKind::Network, &e.to_string())),
+        };
+        if proof.block_height != height as usize {
+            return Err(S5Error::new(
+                ErrorKind::Network,
+                "SPV: Merkle proof is for a different block",
+            ));
+        }
+        let merkle_root = merkle_root_from_proof(txid, proof.pos, &proof.merkle);
+
+        let tip_height = self.sync_headers()?;
+        if height > tip_height {
+            return Err(S5Error::new(
+                ErrorKind::Network,
+                "SPV: Confirmation is above the tip",
+            ));
+        }
+        let start = self.checkpoint.period_start();
+        if height < start {
+            return Err(S5Error::new(
+                ErrorKind::Network,
+                "SPV: Confirmation is older than the checkpoint",
+            ));
+        }
+        let headers = self.headers.borrow();
+        let header = match headers.get((height - start) as usize) {
+            Some(header) => header,
+            None => {
+                return Err(S5Error::new(
+                    ErrorKind::Network,
+                    &format!("SPV: No verified header at height {}", height),
+                ))
+            }
+        };
+
+        if header.merkle_root.to_byte_array() != merkle_root.to_byte_array() {
+            return Err(S5Error::new(
+                ErrorKind::Network,
+                &format!("SPV: Transaction {} is not in block {}", txid, height),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks that output `vout` of `raw_tx` pays `value` to `script_pubkey`.
+    fn verify_output(
+        &self,
+        raw_tx: &[u8],
+        vout: u32,
+        script_pubkey: &Script,
+        value: u64,
+    ) -> Result<(), S5Error> {
+        let tx: Transaction = match deserialize(raw_tx) {
+            Ok(result) => result,
+            Err(e) => return Err(S5Error::new(ErrorKind::Network, &e.to_string())),
+        };
+        let matches = match tx.output.get(vout as usize) {
+            Some(output) => {
+                output.script_pubkey.as_script() == script_pubkey && output.value == value
+            }
+            None => false,
+        };
+        if matches {
+            Ok(())
+        } else {
+            Err(S5Error::new(
+                ErrorKind::Network,
+                "SPV: Server reported an output the transaction does not have",
+            ))
+        }
+    }
+}
+
+/// Folds an electrum merkle branch into the merkle root of the block containing `txid`.
+/// Branch hashes are in electrum's (reversed, display) byte order.
+pub fn merkle_root_from_proof(txid: &Txid, pos: usize, merkle: &[[u8; 32]]) -> sha256d::Hash {
+    let mut index = pos;
+    let mut current = txid.to_raw_hash();
+    for branch in merkle.iter() {
+        let mut branch = *branch;
+        branch.reverse();
+        let mut engine = sha256d::Hash::engine();
+        if index & 1 == 0 {
+            engine.input(current.as_byte_array());
+            engine.input(&branch);
+        } else {
+            engine.input(&branch);
+            engine.input(current.as_byte_array());
+        }
+        current = sha256d::Hash::from_engine(engine);
+        index /= 2;
+    }
+    current
+}
+
+/// The bits of the first header of a difficulty period, given the bits of the previous period
+/// and the timestamps of its first and last blocks.
+pub fn retarget_bits(bits: CompactTarget, first_time: u32, last_time: u32) -> CompactTarget {
+    let timespan = (last_time as i64 - first_time as i64)
+        .clamp(TARGET_TIMESPAN / 4, TARGET_TIMESPAN * 4) as u64;
+    let target = Target::from_be_bytes(mul_div(
+        Target::from_compact(bits).to_be_bytes(),
+        timespan,
+        TARGET_TIMESPAN as u64,
+    ));
+    std::cmp::min(target, Target::MAX).to_compact_lossy()
+}
+
+/// `value * mul / div` for a 256 bit big endian `value` below 2^224, with `mul` and `div`
+/// below 2^23, which covers every target and clamped timespan.
+fn mul_div(value: [u8; 32], mul: u64, div: u64) -> [u8; 32] {
+    let mut limbs = [0u64; 8];
+    for (i, limb) in limbs.iter_mut().enumerate() {
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(&value[i * 4..i * 4 + 4]);
+        *limb = u32::from_be_bytes(bytes) as u64;
+    }
+    let mut carry = 0;
+    for limb in limbs.iter_mut().rev() {
+        let product = *limb * mul + carry;
+        *limb = product & 0xffff_ffff;
+        carry = product >> 32;
+    }
+    let mut remainder = 0;
+    for limb in limbs.iter_mut() {
+        let current = (remainder << 32) | *limb;
+        *limb = current / div;
+        remainder = current % div;
+    }
+    let mut result = [0u8; 32];
+    for (i, limb) in limbs.iter().enumerate() {
+        result[i * 4..i * 4 + 4].copy_from_slice(&(*limb as u32).to_be_bytes());
+    }
+    result
+}
+
+/// Checks `headers`, which start at `checkpoint.period_start()`: they link together, pass
+/// through the checkpoint, carry the bits dictated by the difficulty retargets with valid proof
+/// of work, and add up to at least `checkpoint.min_work`.
+pub fn verify_header_chain(checkpoint: &Checkpoint, headers: &[Header]) -> Result<(), S5Error> {
+    let start = checkpoint.period_start();
+    if headers.len() as u32 <= checkpoint.height - start {
+        return Err(S5Error::new(
+            ErrorKind::Network,
+            "SPV: Headers end before the checkpoint",
+        ));
+    }
+    let mut work = Work::from_be_bytes([0; 32]);
+    for (i, header) in headers.iter().enumerate() {
+        let height = start + i as u32;
+        if i > 0 {
+            let previous = &headers[i - 1];
+            if header.prev_blockhash != previous.block_hash() {
+                return Err(S5Error::new(
+                    ErrorKind::Network,
+                    "SPV: Headers do not form a chain",
+                ));
+            }
+            // headers start at a period boundary, so every later boundary has a full period
+            // before it
+            let period_offset = height % RETARGET_INTERVAL;
+            let expected_bits = if period_offset == 0 {
+                let first = &headers[i - RETARGET_INTERVAL as usize];
+                retarget_bits(previous.bits, first.time, previous.time)
+            } else {
+                previous.bits
+            };
+            if header.bits != expected_bits {
+                return Err(S5Error::new(
+                    ErrorKind::Network,
+                    &format!("SPV: Header at height {} has the wrong difficulty", height),
+                ));
+            }
+        }
+        let target = header.target();
+        if target > Target::MAX {
+            return Err(S5Error::new(
+                ErrorKind::Network,
+                "SPV: Header target is above the proof of work limit",
+            ));
+        }
+        if header.validate_pow(target).is_err() {
+            return Err(S5Error::new(
+                ErrorKind::Network,
+                "SPV: Header has invalid proof of work",
+            ));
+        }
+        if height == checkpoint.height && header.block_hash() != checkpoint.hash {
+            return Err(S5Error::new(
+                ErrorKind::Network,
+                "SPV: Headers do not pass through the checkpoint",
+            ));
+        }
+        work = work + header.work();
+    }
+    if work < checkpoint.min_work {
+        return Err(S5Error::new(
+            ErrorKind::Network,
+            "SPV: Headers carry less work than the checkpoint requires",
+        ));
+    }
+    Ok(())
+}
+
+impl ChainBackend for SpvElectrumClient {
+    fn list_unspent(&self, script_pubkey: &Script) -> Result<Vec<Utxo>, S5Error> {
+        let utxos: Vec<Utxo> = ChainBackend::list_unspent(&self.client, script_pubkey)?
+            .into_iter()
+            .filter(|utxo| utxo.height > 0)
+            .collect();
+        for utxo in utxos.iter() {
+            let raw_tx = self.get_tx(&utxo.txid)?;
+            self.verify_output(&raw_tx, utxo.vout, script_pubkey, utxo.value)?;
+            self.verify_confirmation(&utxo.txid, utxo.height)?;
+        }
+        Ok(utxos)
+    }
+    fn get_history(&self, script_pubkey: &Script) -> Result<Vec<HistoryItem>, S5Error> {
+        let history = ChainBackend::get_history(&self.client, script_pubkey)?;
+        for item in history.iter().filter(|item| item.height > 0) {
+            self.verify_confirmation(&item.txid, item.height as u32)?;
+        }
+        Ok(history)
+    }
+    fn get_tx(&self, txid: &Txid) -> Result<Vec<u8>, S5Error> {
+        let raw_tx = ChainBackend::get_tx(&self.client, txid)?;
+        let tx: Transaction = match deserialize(&raw_tx) {
+            Ok(result) => result,
+            Err(e) => return Err(S5Error::new(ErrorKind::Network, &e.to_string())),
+        };
+        if tx.txid() != *txid {
+            return Err(S5Error::new(
+                ErrorKind::Network,
+                &format!("SPV: Server returned a different transaction for {}", txid),
+            ));
+        }
+        Ok(raw_tx)
+    }
+    fn broadcast(&self, raw_tx: &[u8]) -> Result<Txid, S5Error> {
+        ChainBackend::broadcast(&self.client, raw_tx)
+    }
+    fn tip_height(&self) -> Result<u32, S5Error> {
+        ChainBackend::tip_height(&self.client)
+    }
+    fn get_fee_estimate(&self, target_blocks: usize) -> Result<f64, S5Error> {
+        ChainBackend::get_fee_estimate(&self.client, target_blocks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bitcoin::merkle_tree::calculate_root;
+
+    use super::*;
+
+    // mainnet blocks 0, 1 and 2
+    const HEADERS: [&str; 3] = [
+        "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c",
+        "010000006fe28c0ab6f1b372c1a6a246ae63f74f931e8365e15a089c68d6190000000000982051fd1e4ba744bbbe680e1fee14677ba1a3c3540bf7b1cdb606e857233e0e61bc6649ffff001d01e36299",
+        "010000004860eb18bf1b1620e37e9490fc8a427514416fd75159ab86688e9a8300000000d5fdcc541e25de1c7a5addedf24858b8bb665c9f36ef744ee42c316022c90f9bb0bc6649ffff001d08d2bd61",
+    ];
+
+    fn headers() -> Vec<Header> {
+        HEADERS
+            .iter()
+            .map(|header| deserialize(&hex::decode(header).unwrap()).unwrap())
+            .collect()
+    }
+
+    /// checkpoint at mainnet block 2, requiring `blocks` blocks worth of genesis difficulty work
+    fn checkpoint(blocks: u64) -> Checkpoint {
+        let headers = headers();
+        let mut min_work = Work::from_be_bytes([0; 32]);
+        for _ in 0..blocks {
+            min_work = min_work + headers[0].work();
+        }
+        Checkpoint {
+            height: 2,
+            hash: headers[2].block_hash(),
+            min_work,
+        }
+    }
+
+    #[test]
+    fn test_verify_header_chain() {
+        let headers = headers();
+        assert!(verify_header_chain(&checkpoint(3), &headers).is_ok());
+        // not enough work
+        assert!(verify_header_chain(&checkpoint(4), &headers).is_err());
+        // ends before the checkpoint
+        assert!(verify_header_chain(&checkpoint(2), &headers[..2]).is_err());
+        // a different chain through the checkpoint height
+        let mut other = checkpoint(3);
+        other.hash = headers[1].block_hash();
+        assert!(verify_header_chain(&other, &headers).is_err());
+        // gap
+        let mut checkpoint_1 = checkpoint(2);
+        checkpoint_1.height = 1;
+        checkpoint_1.hash = headers[2].block_hash();
+        assert!(verify_header_chain(&checkpoint_1, &[headers[0], headers[2]]).is_err());
+        // tampered header no longer meets its target
+        let mut tampered = headers.clone();
+        tampered[2].time += 1;
+        assert!(verify_header_chain(&checkpoint(3), &tampered).is_err());
+        // difficulty changes outside of a retarget
+        let mut easy = headers.clone();
+        easy[2].bits = CompactTarget::from_consensus(0x207fffff);
+        assert!(verify_header_chain(&checkpoint(3), &easy).is_err());
+        // a trivially easy target, even at the start
+        let mut easy = headers[0];
+        easy.bits = CompactTarget::from_consensus(0x207fffff);
+        let mut checkpoint_0 = checkpoint(1);
+        checkpoint_0.height = 0;
+        checkpoint_0.hash = easy.block_hash();
+        assert!(verify_header_chain(&checkpoint_0, &[easy]).is_err());
+    }
+
+    #[test]
+    fn test_retarget_bits() {
+        let genesis_bits = CompactTarget::from_consensus(0x1d00ffff);
+        // first mainnet retarget, at block 32256: blocks 30240 to 32255 took 1022578 seconds
+        assert_eq!(
+            retarget_bits(genesis_bits, 1261130161, 1262152739).to_consensus(),
+            0x1d00d86a
+        );
+        // on schedule
+        assert_eq!(retarget_bits(genesis_bits, 0, 1_209_600), genesis_bits);
+        // never easier than the proof of work limit
+        assert_eq!(retarget_bits(genesis_bits, 0, 10_000_000), genesis_bits);
+        // at most 4x harder
+        let bits = CompactTarget::from_consensus(0x1c00ffff);
+        assert_eq!(retarget_bits(bits, 0, 1), retarget_bits(bits, 0, 302_400));
+        assert_eq!(retarget_bits(bits, 0, 1).to_consensus(), 0x1b3fffc0);
+        // at most 4x easier
+        let bits = CompactTarget::from_consensus(0x1b00ffff);
+        assert_eq!(
+            retarget_bits(bits, 0, 10_000_000).to_consensus(),
+            0x1b03fffc
+        );
+    }
+
+    #[test]
+    fn test_checkpoints() {
+        let checkpoint = Checkpoint::for_network(BitcoinNetwork::Bitcoin).unwrap();
+        assert_eq!(checkpoint.period_start(), 838_656);
+        assert!(Checkpoint::for_network(BitcoinNetwork::BitcoinTestnet).is_none());
+        assert!(Checkpoint::for_network(BitcoinNetwork::Liquid).is_none());
+        assert!(Checkpoint::for_network(BitcoinNetwork::LiquidTestnet).is_none());
+        assert!(Checkpoint::for_network(BitcoinNetwork::ElementsRegtest).is_none());
+    }
+
+    #[test]
+    fn test_merkle_root_from_proof() {
+        // a single transaction block: the root is the txid
+        let headers = headers();
+        let coinbase =
+            Txid::from_str("0e3e2357e806b6cdb1f70b54c3a3a17b6714ee1f0e68bebb44a74b1efd512098")
+                .unwrap();
+        let root = merkle_root_from_proof(&coinbase, 0, &[]);
+        assert_eq!(root.to_byte_array(), headers[1].merkle_root.to_byte_array());
+
+        let txids: Vec<Txid> = (0u8..5)
+            .map(|i| Txid::from_raw_hash(sha256d::Hash::hash(&[i])))
+            .collect();
+        let expected = calculate_root(txids.iter().cloned()).unwrap();
+
+        // electrum branch for txids[2]: sibling txids[3], then hash(txids[0], txids[1]),
+        // then the right subtree (txids[4] paired with itself, twice), all byte reversed
+        let pair = |a: &[u8], b: &[u8]| {
+            let mut engine = sha256d::Hash::engine();
+            engine.input(a);
+            engine.input(b);
+            sha256d::Hash::from_engine(engine)
+        };
+        let left = pair(txids[0].as_byte_array(), txids[1].as_byte_array());
+        let right = pair(txids[4].as_byte_array(), txids[4].as_byte_array());
+        let right = pair(right.as_byte_array(), right.as_byte_array());
+        let reversed = |hash: &[u8; 32]| {
+            let mut hash = *hash;
+            hash.reverse();
+            hash
+        };
+        let branch = [
+            reversed(txids[3].as_byte_array()),
+            reversed(left.as_byte_array()),
+            reversed(right.as_byte_array()),
+        ];
+        let root = merkle_root_from_proof(&txids[2], 2, &branch);
+        assert_eq!(root.to_byte_array(), expected.to_byte_array());
+
+        // wrong position
+        let root = merkle_root_from_proof(&txids[2], 3, &branch);
+        assert_ne!(root.to_byte_array(), expected.to_byte_array());
+    }
+}
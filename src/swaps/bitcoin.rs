@@ -1,32 +1,51 @@
 use std::str::FromStr;
 
-use bitcoin::secp256k1::{KeyPair, Message, Secp256k1};
+use bitcoin::psbt::Psbt;
+use bitcoin::secp256k1::{KeyPair, Message, Secp256k1, XOnlyPublicKey};
+use bitcoin::sighash::{Prevouts, TapSighashType};
+use bitcoin::taproot::{ControlBlock, LeafVersion, TapLeafHash, TaprootBuilder, TaprootSpendInfo};
 use bitcoin::{
-    blockdata::script::{Builder, Instruction, Script, ScriptBuf},
-    opcodes::{all::*, OP_0},
-    Address, OutPoint, PublicKey,
+    blockdata::script::{Builder, Instruction, PushBytes, Script, ScriptBuf},
+    opcodes::all::*,
+    Address, Amount, OutPoint, PublicKey,
 };
-use bitcoin::{sighash::SighashCache, Network, Sequence, Transaction, TxIn, TxOut, Witness};
-use electrum_client::ElectrumApi;
+use bitcoin::{sighash::SighashCache, Network, Sequence, Transaction, TxIn, TxOut, Txid, Witness};
 
+#[cfg(feature = "async")]
+use crate::network::backend::AsyncChainBackend;
 use crate::{
-    network::electrum::{BitcoinNetwork, NetworkConfig},
-    swaps::boltz::SwapTxKind,
+    network::{
+        backend::{ChainBackend, HistoryItem, Utxo},
+        electrum::BitcoinNetwork,
+    },
+    swaps::{
+        boltz::{ChainSwapSide, Fee, SwapScriptVersion, SwapTxKind, UtxoPolicy, UtxoReport},
+        boltzv2::{BoltzApiClientV2, SwapTree},
+        cooperative::{fall_back, CooperativeSigner, CooperativeSpend},
+    },
     util::{
         error::{ErrorKind, S5Error},
+        musig,
         preimage::Preimage,
+        script::{cltv_satisfied, legacy_template, match_template, ScriptElement},
     },
 };
 
-use bitcoin::{blockdata::locktime::absolute::LockTime, hashes::hash160::Hash};
+use bitcoin::{
+    blockdata::locktime::absolute::LockTime, consensus::serialize, hashes::hash160::Hash,
+    hashes::Hash as _,
+};
+use serde::{Deserialize, Serialize};
 
 use super::boltz::SwapType;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct BtcSwapScript {
     network: BitcoinNetwork,
-    electrum_url: String,
     swap_type: SwapType,
+    version: SwapScriptVersion,
+    // only set for chain swaps
+    side: Option<ChainSwapSide>,
     pub hashlock: String,
     pub reciever_pubkey: String,
     pub timelock: u32,
@@ -36,7 +55,6 @@ pub struct BtcSwapScript {
 impl BtcSwapScript {
     pub fn new(
         network: BitcoinNetwork,
-        electrum_url: String,
         swap_type: SwapType,
         hashlock: String,
         reciever_pubkey: String,
@@ -45,152 +63,130 @@ impl BtcSwapScript {
     ) -> Self {
         BtcSwapScript {
             network,
-            electrum_url,
             swap_type,
+            version: SwapScriptVersion::Legacy,
+            side: None,
+            hashlock,
+            reciever_pubkey,
+            timelock,
+            sender_pubkey,
+        }
+    }
+    /// A taproot swap: the lockup is a P2TR output whose internal key is the MuSig2 aggregate of
+    /// both pubkeys, committing to a claim leaf (hashlock + reciever) and a refund leaf (timelock + sender).
+    pub fn new_taproot(
+        network: BitcoinNetwork,
+        swap_type: SwapType,
+        hashlock: String,
+        reciever_pubkey: String,
+        timelock: u32,
+        sender_pubkey: String,
+    ) -> Self {
+        BtcSwapScript {
+            network,
+            swap_type,
+            version: SwapScriptVersion::Taproot,
+            side: None,
+            hashlock,
+            reciever_pubkey,
+            timelock,
+            sender_pubkey,
+        }
+    }
+    /// One of the two lockup scripts of a chain swap. The `Lockup` side is the script we fund on
+    /// the source chain (Boltz claims it); the `Claim` side is the script Boltz funds on the
+    /// destination chain (we claim it).
+    pub fn new_chain(
+        network: BitcoinNetwork,
+        side: ChainSwapSide,
+        hashlock: String,
+        reciever_pubkey: String,
+        timelock: u32,
+        sender_pubkey: String,
+    ) -> Self {
+        BtcSwapScript {
+            network,
+            swap_type: SwapType::Chain,
+            version: SwapScriptVersion::Taproot,
+            side: Some(side),
             hashlock,
             reciever_pubkey,
             timelock,
             sender_pubkey,
         }
     }
+    pub fn version(&self) -> SwapScriptVersion {
+        self.version
+    }
+    pub fn side(&self) -> Option<ChainSwapSide> {
+        self.side
+    }
     pub fn submarine_from_str(
         network: BitcoinNetwork,
-        electrum_url: String,
         redeem_script_str: &str,
     ) -> Result<Self, S5Error> {
-        let script_bytes = match hex::decode(redeem_script_str) {
-            Ok(result) => result.to_owned(),
-            Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
-        };
-        let script = Script::from_bytes(&script_bytes);
-
-        let instructions = script.instructions();
-        let mut last_op = OP_0;
-        let mut hashlock = None;
-        let mut reciever_pubkey = None;
-        let mut timelock = None;
-        let mut sender_pubkey = None;
-
-        for instruction in instructions {
-            match instruction {
-                Ok(Instruction::Op(opcode)) => {
-                    last_op = opcode;
-                }
-
-                Ok(Instruction::PushBytes(bytes)) => {
-                    if last_op == OP_HASH160 {
-                        hashlock = Some(hex::encode(bytes.as_bytes()));
-                    }
-                    if last_op == OP_IF {
-                        reciever_pubkey = Some(hex::encode(bytes.as_bytes()));
-                    }
-                    if last_op == OP_ELSE {
-                        timelock = Some(bytes_to_u32_little_endian(&bytes.as_bytes()));
-                    }
-                    if last_op == OP_DROP {
-                        sender_pubkey = Some(hex::encode(bytes.as_bytes()));
-                    }
-                }
-                Err(e) => println!("Error: {:?}", e),
-            }
-        }
-
-        if hashlock.is_some()
-            && sender_pubkey.is_some()
-            && timelock.is_some()
-            && sender_pubkey.is_some()
-        {
-            Ok(BtcSwapScript {
-                network: network,
-                electrum_url: electrum_url,
-                swap_type: SwapType::Submarine,
-                hashlock: hashlock.unwrap(),
-                reciever_pubkey: reciever_pubkey.unwrap(),
-                timelock: timelock.unwrap(),
-                sender_pubkey: sender_pubkey.unwrap(),
-            })
-        } else {
-            Err(S5Error::new(
-                ErrorKind::Input,
-                &format!(
-                    "Could not extract all elements: {:?} {:?} {:?} {:?}",
-                    hashlock, reciever_pubkey, timelock, sender_pubkey
-                ),
-            ))
-        }
+        Self::from_template(network, SwapType::Submarine, redeem_script_str)
     }
 
     pub fn reverse_from_str(
         network: BitcoinNetwork,
-        electrum_url: String,
+        redeem_script_str: &str,
+    ) -> Result<Self, S5Error> {
+        Self::from_template(network, SwapType::ReverseSubmarine, redeem_script_str)
+    }
+
+    /// Only accepts a redeem script byte-identical to the one we would build from its fields.
+    fn from_template(
+        network: BitcoinNetwork,
+        swap_type: SwapType,
         redeem_script_str: &str,
     ) -> Result<Self, S5Error> {
         let script_bytes = match hex::decode(redeem_script_str) {
-            Ok(result) => result.to_owned(),
+            Ok(result) => result,
             Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
         };
-        let script = Script::from_bytes(&script_bytes);
-
-        let instructions = script.instructions();
-        let mut last_op = OP_0;
-        let mut hashlock = None;
-        let mut reciever_pubkey = None;
-        let mut timelock = None;
-        let mut sender_pubkey = None;
-
-        for instruction in instructions {
-            match instruction {
-                Ok(Instruction::Op(opcode)) => {
-                    last_op = opcode;
-                    // println!("{:?}", opcode)
-                }
-
+        let elements: Vec<Result<ScriptElement, String>> = Script::from_bytes(&script_bytes)
+            .instructions_minimal()
+            .map(|instruction| match instruction {
+                Ok(Instruction::Op(opcode)) => Ok(ScriptElement::Op(opcode.to_u8())),
                 Ok(Instruction::PushBytes(bytes)) => {
-                    if last_op == OP_HASH160 {
-                        hashlock = Some(hex::encode(bytes.as_bytes()));
-                    }
-                    if last_op == OP_EQUALVERIFY {
-                        reciever_pubkey = Some(hex::encode(bytes.as_bytes()));
-                    }
-                    if last_op == OP_DROP {
-                        if bytes.len() == 3 as usize {
-                            timelock = Some(bytes_to_u32_little_endian(&bytes.as_bytes()));
-                        } else {
-                            sender_pubkey = Some(hex::encode(bytes.as_bytes()));
-                        }
-                    }
-                    // println!("{:?}: LENGTH: {}", bytes, bytes.len() )
+                    Ok(ScriptElement::Push(bytes.as_bytes().to_vec()))
                 }
-                Err(e) => println!("Error: {:?}", e),
-            }
-        }
-
-        if hashlock.is_some()
-            && sender_pubkey.is_some()
-            && timelock.is_some()
-            && sender_pubkey.is_some()
-        {
-            Ok(BtcSwapScript {
-                network: network,
-                electrum_url: electrum_url,
-                swap_type: SwapType::ReverseSubmarine,
-                hashlock: hashlock.unwrap(),
-                reciever_pubkey: reciever_pubkey.unwrap(),
-                timelock: timelock.unwrap(),
-                sender_pubkey: sender_pubkey.unwrap(),
+                Err(e) => Err(e.to_string()),
             })
-        } else {
-            Err(S5Error::new(
-                ErrorKind::Input,
-                &format!(
-                    "Could not extract all script elements. Check your redeem script and swap_type."
-                ),
-            ))
+            .collect();
+        let fields = match_template(legacy_template(&swap_type)?, &elements)?;
+        let swap_script = BtcSwapScript {
+            network,
+            swap_type,
+            version: SwapScriptVersion::Legacy,
+            side: None,
+            hashlock: fields.hashlock,
+            reciever_pubkey: fields.reciever_pubkey,
+            timelock: fields.timelock,
+            sender_pubkey: fields.sender_pubkey,
+        };
+        if swap_script.to_script()?.as_bytes() != script_bytes.as_slice() {
+            return Err(S5Error::new(
+                ErrorKind::Script,
+                "Redeem script is not byte-identical to the script rebuilt from its fields.",
+            ));
         }
+        Ok(swap_script)
     }
 
-    fn to_script(&self) -> Result<ScriptBuf, S5Error> {
+    pub fn to_script(&self) -> Result<ScriptBuf, S5Error> {
+        if self.version == SwapScriptVersion::Taproot {
+            return Err(S5Error::new(
+                ErrorKind::Script,
+                "Taproot swaps have no single redeem script. Use claim_leaf/refund_leaf.",
+            ));
+        }
         match self.swap_type {
+            SwapType::Chain => Err(S5Error::new(
+                ErrorKind::Script,
+                "Chain swaps only use taproot swap trees.",
+            )),
             SwapType::Submarine => {
                 /*
                     HASH160 <hash of the preimage>
@@ -253,10 +249,19 @@ impl BtcSwapScript {
                     OP_ENDIF
                     OP_CHECKSIG
                 */
-                let reciever_pubkey = PublicKey::from_str(&self.reciever_pubkey).unwrap();
-                let sender_pubkey = PublicKey::from_str(&self.sender_pubkey).unwrap();
+                let reciever_pubkey = match PublicKey::from_str(&self.reciever_pubkey) {
+                    Ok(result) => result,
+                    Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+                };
+                let sender_pubkey = match PublicKey::from_str(&self.sender_pubkey) {
+                    Ok(result) => result,
+                    Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+                };
                 let locktime = LockTime::from_consensus(self.timelock);
-                let hashvalue = Hash::from_str(&self.hashlock).unwrap();
+                let hashvalue = match Hash::from_str(&self.hashlock) {
+                    Ok(result) => result,
+                    Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+                };
                 let hashbytes: [u8; 20] = *hashvalue.as_ref();
 
                 let script = Builder::new()
@@ -283,37 +288,205 @@ impl BtcSwapScript {
         }
     }
 
+    /// Claim leaf of the taproot swap tree.
+    /// Submarine: HASH160 <hash> EQUALVERIFY <reciever> CHECKSIG
+    /// Reverse: SIZE 32 EQUALVERIFY HASH160 <hash> EQUALVERIFY <reciever> CHECKSIG
+    pub fn claim_leaf(&self) -> Result<ScriptBuf, S5Error> {
+        let reciever_pubkey = x_only_pubkey(&self.reciever_pubkey)?;
+        let hashvalue = match Hash::from_str(&self.hashlock) {
+            Ok(result) => result,
+            Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+        };
+        let hashbytes: [u8; 20] = *hashvalue.as_ref();
+
+        let builder = match self.swap_type {
+            SwapType::Submarine => Builder::new(),
+            SwapType::ReverseSubmarine | SwapType::Chain => Builder::new()
+                .push_opcode(OP_SIZE)
+                .push_slice([32])
+                .push_opcode(OP_EQUALVERIFY),
+        };
+        Ok(builder
+            .push_opcode(OP_HASH160)
+            .push_slice(hashbytes)
+            .push_opcode(OP_EQUALVERIFY)
+            .push_x_only_key(&reciever_pubkey)
+            .push_opcode(OP_CHECKSIG)
+            .into_script())
+    }
+
+    /// Refund leaf of the taproot swap tree: <sender> CHECKSIGVERIFY <timeout block height> CLTV
+    pub fn refund_leaf(&self) -> Result<ScriptBuf, S5Error> {
+        let sender_pubkey = x_only_pubkey(&self.sender_pubkey)?;
+        Ok(Builder::new()
+            .push_x_only_key(&sender_pubkey)
+            .push_opcode(OP_CHECKSIGVERIFY)
+            .push_lock_time(LockTime::from_consensus(self.timelock))
+            .push_opcode(OP_CLTV)
+            .into_script())
+    }
+
+    /// MuSig2 signers in Boltz's order: Boltz's key first, then ours.
+    /// Boltz is the reciever of a submarine swap or a chain swap lockup, and the sender otherwise.
+    pub fn musig_pubkeys(&self) -> Result<[bitcoin::secp256k1::PublicKey; 2], S5Error> {
+        let reciever_pubkey = match PublicKey::from_str(&self.reciever_pubkey) {
+            Ok(result) => result.inner,
+            Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+        };
+        let sender_pubkey = match PublicKey::from_str(&self.sender_pubkey) {
+            Ok(result) => result.inner,
+            Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+        };
+        let boltz_is_reciever = match self.swap_type {
+            SwapType::Submarine => true,
+            SwapType::ReverseSubmarine => false,
+            SwapType::Chain => self.side == Some(ChainSwapSide::Lockup),
+        };
+        if boltz_is_reciever {
+            Ok([reciever_pubkey, sender_pubkey])
+        } else {
+            Ok([sender_pubkey, reciever_pubkey])
+        }
+    }
+
+    pub fn taproot_spend_info(&self) -> Result<TaprootSpendInfo, S5Error> {
+        let internal_key = musig::key_agg(&self.musig_pubkeys()?)?;
+        let (claim_leaf, refund_leaf) = (self.claim_leaf()?, self.refund_leaf()?);
+        let builder = match TaprootBuilder::new()
+            .add_leaf(1, claim_leaf)
+            .and_then(|builder| builder.add_leaf(1, refund_leaf))
+        {
+            Ok(result) => result,
+            Err(e) => return Err(S5Error::new(ErrorKind::Script, &e.to_string())),
+        };
+        match builder.finalize(&Secp256k1::new(), internal_key.x_only_public_key().0) {
+            Ok(result) => Ok(result),
+            Err(_) => Err(S5Error::new(
+                ErrorKind::Script,
+                "Could not finalize taproot swap tree.",
+            )),
+        }
+    }
+
+    /// Checks that the swap tree returned by Boltz commits to exactly our claim and refund leaves,
+    /// and that the lockup address Boltz returned with it is the one derived from those leaves.
+    pub fn validate_swap_tree(
+        &self,
+        swap_tree: &SwapTree,
+        lockup_address: &str,
+    ) -> Result<(), S5Error> {
+        let leaf_version = LeafVersion::TapScript.to_consensus();
+        let leaves = [
+            ("claim", &swap_tree.claim_leaf, self.claim_leaf()?),
+            ("refund", &swap_tree.refund_leaf, self.refund_leaf()?),
+        ];
+        for (name, leaf, expected) in leaves {
+            if leaf.version != leaf_version {
+                return Err(S5Error::new(
+                    ErrorKind::Script,
+                    &format!(
+                        "Unexpected {} leaf version. Expected {}, Found {}",
+                        name, leaf_version, leaf.version
+                    ),
+                ));
+            }
+            if leaf.output != expected.to_hex_string() {
+                return Err(S5Error::new(
+                    ErrorKind::Script,
+                    &format!(
+                        "Boltz {} leaf does not match. Expected {}, Found {}",
+                        name,
+                        expected.to_hex_string(),
+                        leaf.output
+                    ),
+                ));
+            }
+        }
+        let expected = self.to_address()?.to_string();
+        if expected != lockup_address {
+            return Err(S5Error::new(
+                ErrorKind::Script,
+                &format!(
+                    "Boltz lockup address does not match. Expected {}, Found {}",
+                    expected, lockup_address
+                ),
+            ));
+        }
+        Ok(())
+    }
+
     pub fn to_address(&self) -> Result<Address, S5Error> {
-        let script = self.to_script()?;
         let network = match self.network {
             BitcoinNetwork::Bitcoin => Network::Bitcoin,
             _ => Network::Testnet,
         };
+        if self.version == SwapScriptVersion::Taproot {
+            let spend_info = self.taproot_spend_info()?;
+            return Ok(Address::p2tr_tweaked(spend_info.output_key(), network));
+        }
+        let script = self.to_script()?;
         match self.swap_type {
             SwapType::Submarine => Ok(Address::p2shwsh(&script, network)),
-            SwapType::ReverseSubmarine => Ok(Address::p2wsh(&script, network)),
+            SwapType::ReverseSubmarine | SwapType::Chain => Ok(Address::p2wsh(&script, network)),
         }
     }
-    pub fn get_balance(&self) -> Result<(u64, i64), S5Error> {
-        let electrum_client =
-            NetworkConfig::new(self.network, &self.electrum_url, true, true, false, None)
-                .electrum_url
-                .build_client()?;
+    /// Returns (confirmed, unconfirmed) balance of the lockup address.
+    pub fn get_balance(&self, backend: &dyn ChainBackend) -> Result<(u64, i64), S5Error> {
+        let utxos = backend.list_unspent(&self.to_address()?.script_pubkey())?;
+        Ok(balance_of(&utxos))
+    }
 
-        let script_balance = match electrum_client.script_get_balance(&self.to_script().unwrap()) {
-            Ok(result) => result,
-            Err(e) => return Err(S5Error::new(ErrorKind::Script, &e.to_string())),
-        };
-        Ok((script_balance.confirmed, script_balance.unconfirmed))
+    #[cfg(feature = "async")]
+    pub async fn get_balance_async(
+        &self,
+        backend: &dyn AsyncChainBackend,
+    ) -> Result<(u64, i64), S5Error> {
+        let script_pubkey = self.to_address()?.script_pubkey();
+        let utxos = backend.list_unspent(&script_pubkey).await?;
+        Ok(balance_of(&utxos))
+    }
+    /// Every output at the lockup script, compared to `expected_value`.
+    pub fn utxo_report(
+        &self,
+        backend: &dyn ChainBackend,
+        expected_value: u64,
+    ) -> Result<UtxoReport, S5Error> {
+        Ok(UtxoReport {
+            expected_value: Some(expected_value),
+            utxos: backend.list_unspent(&self.to_address()?.script_pubkey())?,
+            skipped: vec![],
+        })
+    }
+}
+
+/// (confirmed, unconfirmed) value of `utxos`
+fn balance_of(utxos: &[Utxo]) -> (u64, i64) {
+    let confirmed = utxos
+        .iter()
+        .filter(|utxo| utxo.height > 0)
+        .map(|utxo| utxo.value)
+        .sum();
+    let unconfirmed = utxos
+        .iter()
+        .filter(|utxo| utxo.height == 0)
+        .map(|utxo| utxo.value as i64)
+        .sum();
+    (confirmed, unconfirmed)
+}
+
+/// A BIP340 signature with an optional sighash type byte, as found in taproot witnesses.
+fn parse_schnorr_sig(bytes: &[u8]) -> Result<bitcoin::taproot::Signature, S5Error> {
+    match bitcoin::taproot::Signature::from_slice(bytes) {
+        Ok(result) => Ok(result),
+        Err(e) => Err(S5Error::new(ErrorKind::Transaction, &e.to_string())),
     }
 }
 
-fn bytes_to_u32_little_endian(bytes: &[u8]) -> u32 {
-    let mut result = 0u32;
-    for (i, &byte) in bytes.iter().enumerate() {
-        result |= (byte as u32) << (8 * i);
+fn x_only_pubkey(pubkey: &str) -> Result<XOnlyPublicKey, S5Error> {
+    match PublicKey::from_str(pubkey) {
+        Ok(result) => Ok(result.inner.x_only_public_key().0),
+        Err(e) => Err(S5Error::new(ErrorKind::Input, &e.to_string())),
     }
-    result
 }
 
 // fn find_difference(s1: &str, s2: &str) -> Vec<(usize, char, char)> {
@@ -323,14 +496,38 @@ fn bytes_to_u32_little_endian(bytes: &[u8]) -> u32 {
 //         .map(|((i, char1), char2)| (i, char1, char2))
 //         .collect()
 // }
+// DER signature of at most 72 bytes, plus the sighash type
+const MAX_ECDSA_SIG_SIZE: usize = 73;
+const SCHNORR_SIG_SIZE: usize = 64;
+const PREIMAGE_SIZE: usize = 32;
+/// Minimum fee rate (sat/vB) a replacement has to add on top of the replaced fee (BIP125).
+const INCREMENTAL_RELAY_FEE: u64 = 1;
+
+/// A signed spend of the swap utxo, and the absolute fee it pays.
+/// Serializable, so that attempts can be persisted across restarts; see `set_attempts`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpendAttempt {
+    pub txid: Txid,
+    pub fee: u64,
+}
+
+/// The output a CPFP child should spend, and the fee it has to pay.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CpfpTarget {
+    pub outpoint: OutPoint,
+    pub value: u64,
+    pub child_fee: u64,
+}
+
 pub struct BtcSwapTx {
     kind: SwapTxKind,
     swap_script: BtcSwapScript,
     output_address: Address,
-    absolute_fees: u32,
-    network: Network,
-    utxo: Option<OutPoint>,
-    utxo_value: Option<u64>, // there should only ever be one outpoint in a swap
+    fee: Fee,
+    utxos: Vec<Utxo>,
+    utxo_policy: UtxoPolicy,
+    rbf: bool,
+    attempts: Vec<SpendAttempt>,
 }
 
 impl BtcSwapTx {
@@ -356,10 +553,11 @@ impl BtcSwapTx {
             kind: SwapTxKind::Claim,
             swap_script,
             output_address: address.assume_checked(),
-            absolute_fees,
-            network: network,
-            utxo: None,
-            utxo_value: None,
+            fee: Fee::Absolute(absolute_fees as u64),
+            utxos: vec![],
+            utxo_policy: UtxoPolicy::Exact,
+            rbf: false,
+            attempts: vec![],
         })
     }
     /// BTC Swap refund Tx is constructed only for a failed submarine swap
@@ -384,193 +582,1105 @@ impl BtcSwapTx {
             kind: SwapTxKind::Refund,
             swap_script: swap_script,
             output_address: address.assume_checked(),
-            absolute_fees,
-            network: network,
-            utxo: None,
-            utxo_value: None,
+            fee: Fee::Absolute(absolute_fees as u64),
+            utxos: vec![],
+            utxo_policy: UtxoPolicy::Exact,
+            rbf: false,
+            attempts: vec![],
         })
     }
+    /// Replaces the fee given on construction, e.g. with a fee rate from `Fee::from_backend`.
+    pub fn set_fee(&mut self, fee: Fee) {
+        self.fee = fee;
+    }
+    /// Which lockup outputs `drain` spends. Defaults to `UtxoPolicy::Exact`.
+    pub fn set_utxo_policy(&mut self, utxo_policy: UtxoPolicy) {
+        self.utxo_policy = utxo_policy;
+    }
+    /// The lockup outputs spent by the last `drain`.
+    pub fn utxos(&self) -> &[Utxo] {
+        &self.utxos
+    }
+    /// Signal replaceability (BIP125) on every spend, so that it can later be `bump`ed.
+    pub fn set_rbf(&mut self, rbf: bool) {
+        self.rbf = rbf;
+    }
+    /// Every spend signed by `drain`, `drain_cooperative` or `bump`, oldest first.
+    pub fn attempts(&self) -> &[SpendAttempt] {
+        &self.attempts
+    }
+    /// Restores `attempts` saved before a restart, so that `confirmed_attempt` recognizes them and
+    /// a `bump` has to beat the last one.
+    pub fn set_attempts(&mut self, attempts: Vec<SpendAttempt>) {
+        self.attempts = attempts;
+    }
+    /// Claims are final unless RBF is signaled. Script-path refunds need a non-final sequence,
+    /// so that the timelock is enforced.
+    fn sequence(&self, cooperative: bool) -> Sequence {
+        if self.rbf {
+            return Sequence::ENABLE_RBF_NO_LOCKTIME;
+        }
+        match self.kind {
+            SwapTxKind::Claim => Sequence::MAX,
+            SwapTxKind::Refund if cooperative => Sequence::MAX,
+            SwapTxKind::Refund => Sequence::ENABLE_LOCKTIME_NO_RBF,
+        }
+    }
+    /// Weight of the signed transaction. Signatures are counted at their largest size, so a fee
+    /// rate is never undershot. `cooperative` selects the key-path spend of a taproot lockup.
+    pub fn weight(&self, cooperative: bool) -> Result<u64, S5Error> {
+        let mut script_sig = ScriptBuf::new();
+        let witness: Vec<Vec<u8>> = match self.swap_script.version {
+            SwapScriptVersion::Taproot if cooperative => vec![vec![0; SCHNORR_SIG_SIZE]],
+            SwapScriptVersion::Taproot => {
+                let (leaf, preimage) = match self.kind {
+                    SwapTxKind::Claim => (self.swap_script.claim_leaf()?, Some(PREIMAGE_SIZE)),
+                    SwapTxKind::Refund => (self.swap_script.refund_leaf()?, None),
+                };
+                let mut witness = vec![vec![0; SCHNORR_SIG_SIZE]];
+                if let Some(size) = preimage {
+                    witness.push(vec![0; size]);
+                }
+                witness.push(leaf.to_bytes());
+                witness.push(self.control_block(&leaf)?.serialize());
+                witness
+            }
+            SwapScriptVersion::Legacy => {
+                let redeem_script = self.swap_script.to_script()?;
+                let preimage = match self.kind {
+                    SwapTxKind::Claim => vec![0; PREIMAGE_SIZE],
+                    SwapTxKind::Refund => {
+                        if let Some(result) = self.lockup_script_sig(&redeem_script) {
+                            script_sig = result;
+                        }
+                        vec![]
+                    }
+                };
+                vec![
+                    vec![0; MAX_ECDSA_SIG_SIZE],
+                    preimage,
+                    redeem_script.to_bytes(),
+                ]
+            }
+        };
+        // only the sizes matter
+        let input = TxIn {
+            previous_output: OutPoint::null(),
+            script_sig,
+            sequence: Sequence::MAX,
+            witness: Witness::from_slice(&witness),
+        };
+        let tx = Transaction {
+            version: 2,
+            lock_time: LockTime::ZERO,
+            input: vec![input; self.utxos.len().max(1)],
+            output: vec![TxOut {
+                script_pubkey: self.output_address.script_pubkey(),
+                value: 0,
+            }],
+        };
+        Ok(tx.weight().to_wu())
+    }
+    /// The absolute fee this transaction pays, given its weight.
+    pub fn fee(&self, cooperative: bool) -> Result<u64, S5Error> {
+        Ok(self.fee.absolute(self.weight(cooperative)?))
+    }
     pub fn drain(
         &mut self,
+        backend: &dyn ChainBackend,
+        keys: KeyPair,
+        preimage: Preimage,
+        expected_utxo_value: u64,
+    ) -> Result<Transaction, S5Error> {
+        self.fetch_utxo(backend, expected_utxo_value)?;
+        let signed_tx = self.sign(keys, preimage)?;
+        self.record(&signed_tx);
+        Ok(signed_tx)
+    }
+
+    #[cfg(feature = "async")]
+    pub async fn drain_async(
+        &mut self,
+        backend: &dyn AsyncChainBackend,
         keys: KeyPair,
         preimage: Preimage,
         expected_utxo_value: u64,
     ) -> Result<Transaction, S5Error> {
-        self.fetch_utxo(expected_utxo_value)?;
+        // submarine lockups are p2shwsh, reverse lockups are p2wsh
+        let script_pubkey = self.swap_script.to_address()?.script_pubkey();
+        let utxos = backend.list_unspent(&script_pubkey).await?;
+        self.set_utxo(utxos, expected_utxo_value)?;
+        let signed_tx = self.sign(keys, preimage)?;
+        self.record(&signed_tx);
+        Ok(signed_tx)
+    }
+
+    fn sign(&self, keys: KeyPair, preimage: Preimage) -> Result<Transaction, S5Error> {
         if !self.has_utxo() {
             return Err(S5Error::new(ErrorKind::Transaction, "No Utxos Found."));
         }
-        // FETCH UTXOS HERE
-
-        match self.kind {
-            SwapTxKind::Claim => self.sign_claim_tx(keys, preimage),
-            SwapTxKind::Refund => {
-                self.sign_refund_tx(keys);
-                Err(S5Error::new(
-                    ErrorKind::Transaction,
-                    "Refund transaction signing not supported yet",
-                ))
+        match (&self.kind, self.swap_script.version) {
+            (SwapTxKind::Claim, SwapScriptVersion::Legacy) => self.sign_claim_tx(keys, preimage),
+            (SwapTxKind::Refund, SwapScriptVersion::Legacy) => self.sign_refund_tx(keys),
+            (SwapTxKind::Claim, SwapScriptVersion::Taproot) => {
+                let preimage = match preimage.bytes {
+                    Some(result) => result,
+                    None => return Err(S5Error::new(ErrorKind::Input, "Claim requires preimage.")),
+                };
+                self.sign_taproot_script_path(
+                    keys,
+                    self.swap_script.claim_leaf()?,
+                    Some(preimage.to_vec()),
+                    LockTime::ZERO,
+                    self.sequence(false),
+                )
             }
+            (SwapTxKind::Refund, SwapScriptVersion::Taproot) => self.sign_taproot_script_path(
+                keys,
+                self.swap_script.refund_leaf()?,
+                None,
+                self.refund_lock_time()?,
+                self.sequence(false),
+            ),
         }
-        // let sweep_psbt = Psbt::from_unsigned_tx(sweep_tx);
     }
-    fn fetch_utxo(&mut self, expected_value: u64) -> Result<(), S5Error> {
-        let network = match &self.network {
-            Network::Bitcoin => BitcoinNetwork::Bitcoin,
-            _ => BitcoinNetwork::BitcoinTestnet,
+    /// Like drain, but returns the unsigned script-path spend as a PSBT instead of signing it,
+    /// for keys held by an external signer. Pass the signed PSBT to `finalize_psbt`.
+    pub fn psbt(
+        &mut self,
+        backend: &dyn ChainBackend,
+        expected_utxo_value: u64,
+    ) -> Result<Psbt, S5Error> {
+        self.fetch_utxo(backend, expected_utxo_value)?;
+        self.to_psbt()
+    }
+
+    #[cfg(feature = "async")]
+    pub async fn psbt_async(
+        &mut self,
+        backend: &dyn AsyncChainBackend,
+        expected_utxo_value: u64,
+    ) -> Result<Psbt, S5Error> {
+        let script_pubkey = self.swap_script.to_address()?.script_pubkey();
+        let utxos = backend.list_unspent(&script_pubkey).await?;
+        self.set_utxo(utxos, expected_utxo_value)?;
+        self.to_psbt()
+    }
+
+    /// The unsigned script-path spend of the current utxos. Every input carries `witness_utxo`.
+    /// Legacy inputs add `witness_script`, and `redeem_script` for nested segwit lockups.
+    /// Taproot inputs add the leaf to sign in `tap_scripts`.
+    pub fn to_psbt(&self) -> Result<Psbt, S5Error> {
+        if !self.has_utxo() {
+            return Err(S5Error::new(ErrorKind::Transaction, "No Utxos Found."));
+        }
+        let mut psbt = match Psbt::from_unsigned_tx(self.unsigned_tx()?) {
+            Ok(result) => result,
+            Err(e) => return Err(S5Error::new(ErrorKind::Transaction, &e.to_string())),
         };
-        let electrum_client = NetworkConfig::new(
-            network,
-            &self.swap_script.electrum_url,
-            true,
-            true,
-            false,
-            None,
-        )
-        .electrum_url
-        .build_client()?;
+        let script_pubkey = self.swap_script.to_address()?.script_pubkey();
+        for (input, utxo) in psbt.inputs.iter_mut().zip(&self.utxos) {
+            input.witness_utxo = Some(TxOut {
+                script_pubkey: script_pubkey.clone(),
+                value: utxo.value,
+            });
+            match self.swap_script.version {
+                SwapScriptVersion::Legacy => {
+                    let redeem_script = self.swap_script.to_script()?;
+                    if self.lockup_script_sig(&redeem_script).is_some() {
+                        input.redeem_script = Some(redeem_script.to_v0_p2wsh());
+                    }
+                    input.witness_script = Some(redeem_script);
+                    input.sighash_type = Some(bitcoin::sighash::EcdsaSighashType::All.into());
+                }
+                SwapScriptVersion::Taproot => {
+                    let spend_info = self.swap_script.taproot_spend_info()?;
+                    let leaf = self.leaf()?;
+                    input
+                        .tap_scripts
+                        .insert(self.control_block(&leaf)?, (leaf, LeafVersion::TapScript));
+                    input.tap_internal_key = Some(spend_info.internal_key());
+                    input.tap_merkle_root = spend_info.merkle_root();
+                }
+            }
+        }
+        Ok(psbt)
+    }
 
-        let utxos = electrum_client
-            .script_list_unspent(&self.swap_script.to_script()?.to_v0_p2wsh())
-            .unwrap();
-        if utxos.len() == 0 {
+    /// Builds the witnesses of a PSBT from `to_psbt` once it is signed: every input needs a
+    /// signature of the receiver key for claims, or of the sender key for refunds.
+    /// Signatures are checked first. `preimage` is required for claims and rejected for refunds.
+    pub fn finalize_psbt(
+        &mut self,
+        mut psbt: Psbt,
+        preimage: Option<&Preimage>,
+    ) -> Result<Transaction, S5Error> {
+        let outpoints: Vec<OutPoint> = self
+            .utxos
+            .iter()
+            .map(|utxo| OutPoint::new(utxo.txid, utxo.vout))
+            .collect();
+        let spent: Vec<OutPoint> = psbt
+            .unsigned_tx
+            .input
+            .iter()
+            .map(|input| input.previous_output)
+            .collect();
+        if !self.has_utxo() || spent != outpoints {
             return Err(S5Error::new(
-                ErrorKind::Transaction,
-                &format!("0 utxos found for this script",),
+                ErrorKind::Input,
+                "PSBT does not spend the swap utxos.",
             ));
-        } else {
-            let outpoint_0 = OutPoint::new(utxos[0].tx_hash, utxos[0].tx_pos as u32);
-            let utxo_value = utxos[0].value;
-            if utxo_value == expected_value {
-                self.utxo = Some(outpoint_0);
-                self.utxo_value = Some(utxo_value);
-                Ok(())
-            } else {
+        }
+        let (pubkey, preimage) = match (&self.kind, preimage) {
+            (SwapTxKind::Claim, Some(preimage)) => match preimage.bytes {
+                Some(_) if preimage.hash160.to_string() != self.swap_script.hashlock => {
+                    return Err(S5Error::new(
+                        ErrorKind::Input,
+                        "Preimage does not match the hashlock.",
+                    ))
+                }
+                Some(result) => (&self.swap_script.reciever_pubkey, Some(result.to_vec())),
+                None => return Err(S5Error::new(ErrorKind::Input, "Claim requires preimage.")),
+            },
+            (SwapTxKind::Claim, None) => {
+                return Err(S5Error::new(ErrorKind::Input, "Claim requires preimage."))
+            }
+            (SwapTxKind::Refund, None) => (&self.swap_script.sender_pubkey, None),
+            (SwapTxKind::Refund, Some(_)) => {
                 return Err(S5Error::new(
                     ErrorKind::Input,
-                    &format!(
-                        "Expected value does not match utxo value. Expected {}, Found {}",
-                        expected_value, utxo_value
-                    ),
-                ));
+                    "Refunds spend the timeout branch and take no preimage.",
+                ))
             }
-        }
-    }
-    fn has_utxo(&self) -> bool {
-        self.utxo.is_some() && self.utxo_value.is_some()
-    }
-
-    fn sign_claim_tx(&self, keys: KeyPair, preimage: Preimage) -> Result<Transaction, S5Error> {
-        let sequence = Sequence::from_consensus(0xFFFFFFFF);
-
-        let unsigned_input: TxIn = TxIn {
-            sequence: sequence,
-            previous_output: self.utxo.unwrap(),
-            script_sig: Script::empty().into(), // always empty because segwit
-            witness: Witness::new(),
         };
-
-        let output_amount = self.utxo_value.unwrap() - self.absolute_fees as u64;
-        let output: TxOut = TxOut {
-            script_pubkey: self.output_address.payload.script_pubkey(),
-            value: output_amount,
+        let pubkey = match PublicKey::from_str(pubkey) {
+            Ok(result) => result,
+            Err(e) => return Err(S5Error::new(ErrorKind::Key, &e.to_string())),
         };
 
-        let unsigned_tx = Transaction {
-            version: 1,
-            lock_time: LockTime::from_consensus(self.swap_script.timelock),
-            input: vec![unsigned_input],
-            output: vec![output.clone()],
+        let secp = Secp256k1::verification_only();
+        let script_sig = match self.swap_script.version {
+            SwapScriptVersion::Legacy => self.lockup_script_sig(&self.swap_script.to_script()?),
+            SwapScriptVersion::Taproot => None,
         };
-        // SIGN TRANSACTION
-        let hash_type = bitcoin::sighash::EcdsaSighashType::All;
-        let secp = Secp256k1::new();
-        let sighash = match SighashCache::new(unsigned_tx.clone()).segwit_signature_hash(
-            0,
-            &self.swap_script.to_script()?,
-            self.utxo_value.unwrap(),
-            hash_type,
-        ) {
-            Ok(result) => result,
-            Err(e) => return Err(S5Error::new(ErrorKind::Transaction, &e.to_string())),
+        let script_pubkey = self.swap_script.to_address()?.script_pubkey();
+        let prevouts: Vec<TxOut> = self
+            .utxos
+            .iter()
+            .map(|utxo| TxOut {
+                script_pubkey: script_pubkey.clone(),
+                value: utxo.value,
+            })
+            .collect();
+        let mut sighash_cache = SighashCache::new(&psbt.unsigned_tx);
+        let mut witnesses = vec![];
+        for (index, utxo) in self.utxos.iter().enumerate() {
+            let input = &psbt.inputs[index];
+            let mut witness = Witness::new();
+            match self.swap_script.version {
+                SwapScriptVersion::Legacy => {
+                    let redeem_script = self.swap_script.to_script()?;
+                    let signature = match input.partial_sigs.get(&pubkey) {
+                        Some(result) => *result,
+                        None => {
+                            return Err(S5Error::new(
+                                ErrorKind::Transaction,
+                                &format!("Input {} is not signed by {}", index, pubkey),
+                            ))
+                        }
+                    };
+                    let sighash = match sighash_cache.segwit_signature_hash(
+                        index,
+                        &redeem_script,
+                        utxo.value,
+                        signature.hash_ty,
+                    ) {
+                        Ok(result) => result,
+                        Err(e) => return Err(S5Error::new(ErrorKind::Transaction, &e.to_string())),
+                    };
+                    let message = match Message::from_slice(&sighash[..]) {
+                        Ok(result) => result,
+                        Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+                    };
+                    if secp
+                        .verify_ecdsa(&message, &signature.sig, &pubkey.inner)
+                        .is_err()
+                    {
+                        return Err(S5Error::new(
+                            ErrorKind::Transaction,
+                            &format!("Invalid signature for input {}", index),
+                        ));
+                    }
+                    // an empty preimage fails the HASH160 check and selects the ELSE branch
+                    witness.push(signature.to_vec());
+                    witness.push(preimage.clone().unwrap_or_default());
+                    witness.push(redeem_script.as_bytes());
+                }
+                SwapScriptVersion::Taproot => {
+                    let leaf = self.leaf()?;
+                    let leaf_hash = TapLeafHash::from_script(&leaf, LeafVersion::TapScript);
+                    let x_only = pubkey.inner.x_only_public_key().0;
+                    let signature = match input.tap_script_sigs.get(&(x_only, leaf_hash)) {
+                        Some(result) => *result,
+                        None => {
+                            return Err(S5Error::new(
+                                ErrorKind::Transaction,
+                                &format!("Input {} is not signed by {}", index, x_only),
+                            ))
+                        }
+                    };
+                    let sighash = match sighash_cache.taproot_script_spend_signature_hash(
+                        index,
+                        &Prevouts::All(&prevouts),
+                        leaf_hash,
+                        signature.hash_ty,
+                    ) {
+                        Ok(result) => result,
+                        Err(e) => return Err(S5Error::new(ErrorKind::Transaction, &e.to_string())),
+                    };
+                    let message = match Message::from_slice(&sighash[..]) {
+                        Ok(result) => result,
+                        Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+                    };
+                    if secp
+                        .verify_schnorr(&signature.sig, &message, &x_only)
+                        .is_err()
+                    {
+                        return Err(S5Error::new(
+                            ErrorKind::Transaction,
+                            &format!("Invalid signature for input {}", index),
+                        ));
+                    }
+                    witness.push(signature.to_vec());
+                    if let Some(preimage) = &preimage {
+                        witness.push(preimage);
+                    }
+                    witness.push(leaf.as_bytes());
+                    witness.push(self.control_block(&leaf)?.serialize());
+                }
+            }
+            witnesses.push(witness);
+        }
+
+        // BIP174 finalizer: only the final fields remain
+        for (input, witness) in psbt.inputs.iter_mut().zip(witnesses) {
+            let witness_utxo = input.witness_utxo.take();
+            *input = bitcoin::psbt::Input::default();
+            input.witness_utxo = witness_utxo;
+            input.final_script_sig = script_sig.clone();
+            input.final_script_witness = Some(witness);
+        }
+        let signed_tx = psbt.extract_tx();
+        self.record(&signed_tx);
+        Ok(signed_tx)
+    }
+    /// The leaf a script-path spend of a taproot lockup uses.
+    fn leaf(&self) -> Result<ScriptBuf, S5Error> {
+        match self.kind {
+            SwapTxKind::Claim => self.swap_script.claim_leaf(),
+            SwapTxKind::Refund => self.swap_script.refund_leaf(),
+        }
+    }
+    /// Like drain, but first tries a cooperative key-path spend with Boltz.
+    /// If Boltz does not cooperate the script path is signed instead and the reason is returned
+    /// with it. A refund only falls back once the chain tip has reached the timelock.
+    pub fn drain_cooperative(
+        &mut self,
+        backend: &dyn ChainBackend,
+        keys: KeyPair,
+        preimage: Preimage,
+        expected_utxo_value: u64,
+        boltz: &BoltzApiClientV2,
+        swap_id: &str,
+    ) -> Result<CooperativeSpend<Transaction>, S5Error> {
+        self.fetch_utxo(backend, expected_utxo_value)?;
+        let spend = match self.sign_cooperative(keys, preimage.clone(), boltz, swap_id) {
+            Ok(tx) => CooperativeSpend {
+                tx,
+                cooperative_error: None,
+            },
+            Err(e) => fall_back(
+                &self.kind,
+                self.swap_script.timelock,
+                backend.tip_height()?,
+                e,
+                || self.sign(keys, preimage),
+            )?,
         };
+        self.record(&spend.tx);
+        Ok(spend)
+    }
+    /// Signs a key-path spend of a taproot lockup together with Boltz (MuSig2).
+    /// Legacy lockups have no key path and are signed on the script path.
+    /// Errors if Boltz does not cooperate or sends an invalid partial signature; `drain_cooperative`
+    /// falls back to the script path instead.
+    pub fn sign_cooperative(
+        &self,
+        keys: KeyPair,
+        preimage: Preimage,
+        boltz: &BoltzApiClientV2,
+        swap_id: &str,
+    ) -> Result<Transaction, S5Error> {
+        if !self.has_utxo() {
+            return Err(S5Error::new(ErrorKind::Transaction, "No Utxos Found."));
+        }
+        if self.swap_script.version != SwapScriptVersion::Taproot {
+            return self.sign(keys, preimage);
+        }
+        self.sign_taproot_key_path(keys, &preimage, boltz, swap_id)
+    }
+    /// Witness: <aggregate schnorr signature>
+    fn sign_taproot_key_path(
+        &self,
+        keys: KeyPair,
+        preimage: &Preimage,
+        boltz: &BoltzApiClientV2,
+        swap_id: &str,
+    ) -> Result<Transaction, S5Error> {
+        let (mut tx, prevouts) =
+            self.taproot_unsigned_tx(LockTime::ZERO, self.sequence(true), self.fee(true)?)?;
+        let tweak = self
+            .swap_script
+            .taproot_spend_info()?
+            .tap_tweak()
+            .to_scalar();
 
-        let sighash_message = match Message::from_slice(&sighash[..]) {
+        let mut sighash_cache = SighashCache::new(&tx);
+        let mut sighashes = vec![];
+        for index in 0..prevouts.len() {
+            match sighash_cache.taproot_key_spend_signature_hash(
+                index,
+                &Prevouts::All(&prevouts),
+                TapSighashType::Default,
+            ) {
+                Ok(result) => sighashes.push(result.to_byte_array()),
+                Err(e) => return Err(S5Error::new(ErrorKind::Transaction, &e.to_string())),
+            };
+        }
+        let signer = CooperativeSigner {
+            boltz,
+            swap_id,
+            swap_type: self.swap_script.swap_type.clone(),
+            kind: self.kind.clone(),
+        };
+        let signatures = signer.sign_inputs(
+            &keys,
+            preimage,
+            &self.swap_script.musig_pubkeys()?,
+            tweak,
+            &hex::encode(serialize(&tx)),
+            &sighashes,
+        )?;
+
+        for (input, signature) in tx.input.iter_mut().zip(signatures) {
+            input.witness.push(signature.as_ref());
+        }
+        Ok(tx)
+    }
+    fn fetch_utxo(
+        &mut self,
+        backend: &dyn ChainBackend,
+        expected_value: u64,
+    ) -> Result<(), S5Error> {
+        // submarine lockups are p2shwsh, reverse lockups are p2wsh
+        let utxos = backend.list_unspent(&self.swap_script.to_address()?.script_pubkey())?;
+        self.set_utxo(utxos, expected_value)
+    }
+    fn set_utxo(&mut self, utxos: Vec<Utxo>, expected_value: u64) -> Result<(), S5Error> {
+        let report = UtxoReport {
+            expected_value: Some(expected_value),
+            utxos,
+            skipped: vec![],
+        };
+        self.utxos = report.select(self.utxo_policy)?;
+        Ok(())
+    }
+    fn has_utxo(&self) -> bool {
+        !self.utxos.is_empty()
+    }
+    fn utxo_total(&self) -> u64 {
+        self.utxos.iter().map(|utxo| utxo.value).sum()
+    }
+    /// One input per utxo, in the order of `utxos`.
+    fn inputs(&self, script_sig: ScriptBuf, sequence: Sequence) -> Vec<TxIn> {
+        self.utxos
+            .iter()
+            .map(|utxo| TxIn {
+                previous_output: OutPoint::new(utxo.txid, utxo.vout),
+                script_sig: script_sig.clone(),
+                sequence,
+                witness: Witness::new(),
+            })
+            .collect()
+    }
+    /// What is left of the utxos after paying `fee`.
+    fn output_value(&self, fee: u64) -> Result<u64, S5Error> {
+        let utxo_value = self.utxo_total();
+        if utxo_value <= fee {
+            return Err(S5Error::new(
+                ErrorKind::Transaction,
+                &format!("Fees exceed utxo value. Fees {}, Utxo {}", fee, utxo_value),
+            ));
+        }
+        Ok(utxo_value - fee)
+    }
+    /// The fee `signed_tx` pays out of the utxos.
+    fn paid_fee(&self, signed_tx: &Transaction) -> u64 {
+        let output_value: u64 = signed_tx.output.iter().map(|output| output.value).sum();
+        self.utxo_total().saturating_sub(output_value)
+    }
+    fn record(&mut self, signed_tx: &Transaction) {
+        self.attempts.push(SpendAttempt {
+            txid: signed_tx.txid(),
+            fee: self.paid_fee(signed_tx),
+        });
+    }
+    /// Re-signs the spend at a higher `fee`, replacing the last attempt (BIP125).
+    /// Requires `set_rbf(true)` before the first spend. The replacement is always a script-path
+    /// spend and has to pay the replaced fee plus the incremental relay fee for its own size.
+    pub fn bump(
+        &mut self,
+        keys: KeyPair,
+        preimage: Preimage,
+        fee: Fee,
+    ) -> Result<Transaction, S5Error> {
+        if !self.rbf {
+            return Err(S5Error::new(
+                ErrorKind::Transaction,
+                "Replacement is not signaled. Call set_rbf before the first spend.",
+            ));
+        }
+        let previous = match self.attempts.last() {
+            Some(result) => result.clone(),
+            None => return Err(S5Error::new(ErrorKind::Transaction, "No spend to replace.")),
+        };
+        let previous_fee = self.fee;
+        self.fee = fee;
+        let signed_tx = match self.sign(keys, preimage) {
             Ok(result) => result,
-            Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+            Err(e) => {
+                self.fee = previous_fee;
+                return Err(e);
+            }
         };
-        let signature = secp.sign_ecdsa(&sighash_message, &keys.secret_key());
+        let paid = self.paid_fee(&signed_tx);
+        let min_fee = previous.fee + signed_tx.vsize() as u64 * INCREMENTAL_RELAY_FEE;
+        if paid < min_fee {
+            self.fee = previous_fee;
+            return Err(S5Error::new(
+                ErrorKind::Transaction,
+                &format!(
+                    "Replacement fee too low. Fees {}, Required {}",
+                    paid, min_fee
+                ),
+            ));
+        }
+        self.record(&signed_tx);
+        Ok(signed_tx)
+    }
+    /// The attempt that confirmed, if any. At most one of them can, as they all spend the utxo.
+    /// Spends are looked up in the history of the lockup address, so this needs a backend with an
+    /// address index (Electrum, Esplora). `RpcClient` has none and its error is returned.
+    pub fn confirmed_attempt(
+        &self,
+        backend: &dyn ChainBackend,
+    ) -> Result<Option<SpendAttempt>, S5Error> {
+        let history = backend.get_history(&self.swap_script.to_address()?.script_pubkey())?;
+        Ok(self.find_confirmed(&history))
+    }
 
-        // https://github.com/bitcoin/bips/blob/master/bip-0141.mediawiki
-        let mut witness = Witness::new();
-        witness.push_bitcoin_signature(&signature.serialize_der(), hash_type);
-        witness.push(preimage.bytes.unwrap());
-        witness.push(self.swap_script.to_script().unwrap().as_bytes());
-
-        // https://github.com/bitcoin-teleport/teleport-transactions/blob/master/src/wallet_sync.rs#L255
-        // println!("{:?}", witness);
-        // BUILD SIGNED TX w/ WITNESS
-        let signed_txin = TxIn {
-            previous_output: self.utxo.unwrap(),
-            script_sig: Script::empty().into(),
-            sequence: sequence,
-            witness: witness,
+    #[cfg(feature = "async")]
+    pub async fn confirmed_attempt_async(
+        &self,
+        backend: &dyn AsyncChainBackend,
+    ) -> Result<Option<SpendAttempt>, S5Error> {
+        let script_pubkey = self.swap_script.to_address()?.script_pubkey();
+        let history = backend.get_history(&script_pubkey).await?;
+        Ok(self.find_confirmed(&history))
+    }
+    fn find_confirmed(&self, history: &[HistoryItem]) -> Option<SpendAttempt> {
+        history
+            .iter()
+            .filter(|item| item.height > 0)
+            .find_map(|item| {
+                self.attempts
+                    .iter()
+                    .find(|attempt| attempt.txid == item.txid)
+                    .cloned()
+            })
+    }
+    /// Child-pays-for-parent: the output of `parent` a child transaction should spend, and the
+    /// fee that child of `child_weight` has to pay for both to confirm at `package_rate` sat/vB.
+    pub fn cpfp(
+        &self,
+        parent: &Transaction,
+        child_weight: u64,
+        package_rate: f64,
+    ) -> Result<CpfpTarget, S5Error> {
+        if !self.has_utxo() {
+            return Err(S5Error::new(ErrorKind::Transaction, "No Utxos Found."));
+        }
+        let outpoints: Vec<OutPoint> = self
+            .utxos
+            .iter()
+            .map(|utxo| OutPoint::new(utxo.txid, utxo.vout))
+            .collect();
+        let spent: Vec<OutPoint> = parent
+            .input
+            .iter()
+            .map(|input| input.previous_output)
+            .collect();
+        if spent != outpoints {
+            return Err(S5Error::new(
+                ErrorKind::Input,
+                "Parent does not spend the swap utxos.",
+            ));
+        }
+        let script_pubkey = self.output_address.script_pubkey();
+        let vout = match parent
+            .output
+            .iter()
+            .position(|output| output.script_pubkey == script_pubkey)
+        {
+            Some(result) => result,
+            None => {
+                return Err(S5Error::new(
+                    ErrorKind::Input,
+                    "Parent does not pay the output address.",
+                ))
+            }
         };
+        let value = parent.output[vout].value;
+        let rate = Fee::Relative(package_rate);
+        let package_fee = rate.absolute(parent.weight().to_wu() + child_weight);
+        let child_fee = package_fee
+            .saturating_sub(self.paid_fee(parent))
+            .max(rate.absolute(child_weight));
+        if child_fee >= value {
+            return Err(S5Error::new(
+                ErrorKind::Transaction,
+                &format!(
+                    "Fees exceed output value. Fees {}, Output {}",
+                    child_fee, value
+                ),
+            ));
+        }
+        Ok(CpfpTarget {
+            outpoint: OutPoint::new(parent.txid(), vout as u32),
+            value,
+            child_fee,
+        })
+    }
 
-        let signed_tx = Transaction {
-            version: 1,
-            lock_time: LockTime::from_consensus(self.swap_script.timelock),
-            input: vec![signed_txin],
-            output: vec![output.clone()],
+    fn sign_claim_tx(&self, keys: KeyPair, preimage: Preimage) -> Result<Transaction, S5Error> {
+        let preimage = match preimage.bytes {
+            Some(result) => result,
+            None => return Err(S5Error::new(ErrorKind::Input, "Claim requires preimage.")),
+        };
+        self.sign_segwit_v0(
+            keys,
+            self.unsigned_tx()?,
+            &self.swap_script.to_script()?,
+            preimage.to_vec(),
+        )
+    }
+    /// Spends the timeout branch of a submarine swap script.
+    /// Only valid once the chain has reached `BtcSwapScript::timelock`.
+    fn sign_refund_tx(&self, keys: KeyPair) -> Result<Transaction, S5Error> {
+        let redeem_script = self.swap_script.to_script()?;
+        let mut unsigned_tx = self.unsigned_tx()?;
+        // nested segwit: scriptSig pushes the p2wsh witness program
+        if let Some(script_sig) = self.lockup_script_sig(&redeem_script) {
+            for input in unsigned_tx.input.iter_mut() {
+                input.script_sig = script_sig.clone();
+            }
+        }
+        // an empty preimage fails the HASH160 check and selects the ELSE branch
+        self.sign_segwit_v0(keys, unsigned_tx, &redeem_script, Vec::new())
+    }
+    /// The script-path spend without witness, as signed by `sign` and exported by `to_psbt`.
+    /// scriptSigs are left empty; segwit sighashes do not commit to them.
+    fn unsigned_tx(&self) -> Result<Transaction, S5Error> {
+        let lock_time = match self.kind {
+            // the claim branch has no timelock; with RBF a non-final sequence would enforce one
+            SwapTxKind::Claim => LockTime::ZERO,
+            SwapTxKind::Refund => self.refund_lock_time()?,
         };
-        // signed_tx.size();
-        // calculate absolute fee as size * fee_rate
-        // then calcualte real output_amount as output_value - absolute_fees
+        // non-final for refunds, so that nLockTime is enforced
+        let sequence = self.sequence(false);
+        let fee = self.fee(false)?;
+        match self.swap_script.version {
+            SwapScriptVersion::Legacy => Ok(Transaction {
+                version: 1,
+                lock_time,
+                input: self.inputs(ScriptBuf::new(), sequence),
+                output: vec![self.output(fee)?],
+            }),
+            SwapScriptVersion::Taproot => Ok(self.taproot_unsigned_tx(lock_time, sequence, fee)?.0),
+        }
+    }
+    fn refund_lock_time(&self) -> Result<LockTime, S5Error> {
+        match LockTime::from_height(self.swap_script.timelock) {
+            Ok(result) => Ok(result),
+            Err(e) => Err(S5Error::new(ErrorKind::Script, &e.to_string())),
+        }
+    }
+    /// Signs every input of a segwit v0 spend of `redeem_script`.
+    /// Witness: <signature> <preimage> <redeem script>
+    fn sign_segwit_v0(
+        &self,
+        keys: KeyPair,
+        mut tx: Transaction,
+        redeem_script: &Script,
+        preimage: Vec<u8>,
+    ) -> Result<Transaction, S5Error> {
+        let hash_type = bitcoin::sighash::EcdsaSighashType::All;
+        let secp = Secp256k1::new();
+        let mut sighash_cache = SighashCache::new(&tx);
+        let mut witnesses = vec![];
+        for (index, utxo) in self.utxos.iter().enumerate() {
+            let sighash = match sighash_cache.segwit_signature_hash(
+                index,
+                redeem_script,
+                utxo.value,
+                hash_type,
+            ) {
+                Ok(result) => result,
+                Err(e) => return Err(S5Error::new(ErrorKind::Transaction, &e.to_string())),
+            };
+            let sighash_message = match Message::from_slice(&sighash[..]) {
+                Ok(result) => result,
+                Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+            };
+            let signature = secp.sign_ecdsa(&sighash_message, &keys.secret_key());
 
-        Ok(signed_tx)
+            // https://github.com/bitcoin/bips/blob/master/bip-0141.mediawiki
+            let mut witness = Witness::new();
+            witness.push_bitcoin_signature(&signature.serialize_der(), hash_type);
+            witness.push(&preimage);
+            witness.push(redeem_script.as_bytes());
+            witnesses.push(witness);
+        }
+
+        for (input, witness) in tx.input.iter_mut().zip(witnesses) {
+            input.witness = witness;
+        }
+        Ok(tx)
     }
-    fn sign_refund_tx(&self, _keys: KeyPair) -> () {
-        // submarine
-        ()
+    /// Everything but `fee` goes to `output_address`.
+    fn output(&self, fee: u64) -> Result<TxOut, S5Error> {
+        Ok(TxOut {
+            script_pubkey: self.output_address.payload.script_pubkey(),
+            value: self.output_value(fee)?,
+        })
     }
-    pub fn broadcast(&mut self, signed_tx: Transaction) -> Result<String, S5Error> {
-        let network = match &self.network {
-            Network::Bitcoin => BitcoinNetwork::Bitcoin,
-            _ => BitcoinNetwork::BitcoinTestnet,
+    /// The taproot spend without witness, and the lockup outputs it spends.
+    /// Script-path and key-path spends share it.
+    fn taproot_unsigned_tx(
+        &self,
+        lock_time: LockTime,
+        sequence: Sequence,
+        fee: u64,
+    ) -> Result<(Transaction, Vec<TxOut>), S5Error> {
+        let tx = Transaction {
+            version: 2,
+            lock_time,
+            input: self.inputs(ScriptBuf::new(), sequence),
+            output: vec![self.output(fee)?],
         };
-        let electrum_client = NetworkConfig::new(
-            network,
-            &self.swap_script.electrum_url,
-            true,
-            true,
-            false,
-            None,
-        )
-        .electrum_url
-        .build_client()?;
+        let script_pubkey = self.swap_script.to_address()?.script_pubkey();
+        let prevouts = self
+            .utxos
+            .iter()
+            .map(|utxo| TxOut {
+                script_pubkey: script_pubkey.clone(),
+                value: utxo.value,
+            })
+            .collect();
+        Ok((tx, prevouts))
+    }
+    /// Spends `leaf` of the taproot swap tree with every input.
+    /// Witness: <schnorr signature> [<preimage>] <leaf script> <control block>
+    fn sign_taproot_script_path(
+        &self,
+        keys: KeyPair,
+        leaf: ScriptBuf,
+        preimage: Option<Vec<u8>>,
+        lock_time: LockTime,
+        sequence: Sequence,
+    ) -> Result<Transaction, S5Error> {
+        let (mut tx, prevouts) = self.taproot_unsigned_tx(lock_time, sequence, self.fee(false)?)?;
+        let control_block = self.control_block(&leaf)?;
+        let secp = Secp256k1::new();
+        let mut sighash_cache = SighashCache::new(&tx);
+        let mut witnesses = vec![];
+        for index in 0..prevouts.len() {
+            let sighash = match sighash_cache.taproot_script_spend_signature_hash(
+                index,
+                &Prevouts::All(&prevouts),
+                TapLeafHash::from_script(&leaf, LeafVersion::TapScript),
+                TapSighashType::Default,
+            ) {
+                Ok(result) => result,
+                Err(e) => return Err(S5Error::new(ErrorKind::Transaction, &e.to_string())),
+            };
+            let sighash_message = match Message::from_slice(&sighash[..]) {
+                Ok(result) => result,
+                Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+            };
+            let signature = secp.sign_schnorr(&sighash_message, &keys);
 
-        match electrum_client.transaction_broadcast(&signed_tx) {
-            Ok(txid) => Ok(txid.to_string()),
-            Err(e) => Err(S5Error::new(ErrorKind::Network, &e.to_string())),
+            let mut witness = Witness::new();
+            witness.push(signature.as_ref());
+            if let Some(preimage) = &preimage {
+                witness.push(preimage);
+            }
+            witness.push(leaf.as_bytes());
+            witness.push(control_block.serialize());
+            witnesses.push(witness);
+        }
+
+        for (input, witness) in tx.input.iter_mut().zip(witnesses) {
+            input.witness = witness;
+        }
+        Ok(tx)
+    }
+    fn control_block(&self, leaf: &ScriptBuf) -> Result<ControlBlock, S5Error> {
+        let spend_info = self.swap_script.taproot_spend_info()?;
+        match spend_info.control_block(&(leaf.clone(), LeafVersion::TapScript)) {
+            Some(result) => Ok(result),
+            None => Err(S5Error::new(
+                ErrorKind::Script,
+                "Leaf is not part of the swap tree.",
+            )),
+        }
+    }
+    /// The scriptSig required to spend the lockup. Only submarine (p2shwsh) lockups need one.
+    fn lockup_script_sig(&self, redeem_script: &ScriptBuf) -> Option<ScriptBuf> {
+        match self.swap_script.swap_type {
+            SwapType::Submarine => {
+                let witness_program = redeem_script.to_v0_p2wsh();
+                let push: &PushBytes = witness_program.as_bytes().try_into().ok()?;
+                Some(Builder::new().push_slice(push).into_script())
+            }
+            SwapType::ReverseSubmarine | SwapType::Chain => None,
+        }
+    }
+    /// Checks that `signed_tx` spends swap utxos and that every input satisfies its lockup
+    /// script, so that a broken spend fails with the reason instead of being rejected by the node.
+    /// Segwit v0 lockups run through libbitcoinconsensus. It predates taproot and accepts any
+    /// witness for a v1 output, so taproot inputs are checked against the swap tree instead.
+    /// Whether the chain has reached a refund's timelock is left to the node.
+    pub fn verify(&self, signed_tx: &Transaction) -> Result<(), S5Error> {
+        let script_pubkey = self.swap_script.to_address()?.script_pubkey();
+        let mut prevouts = vec![];
+        for (index, input) in signed_tx.input.iter().enumerate() {
+            match self
+                .utxos
+                .iter()
+                .find(|utxo| OutPoint::new(utxo.txid, utxo.vout) == input.previous_output)
+            {
+                Some(utxo) => prevouts.push(TxOut {
+                    script_pubkey: script_pubkey.clone(),
+                    value: utxo.value,
+                }),
+                None => {
+                    return Err(S5Error::new(
+                        ErrorKind::Transaction,
+                        &format!("Input {} does not spend a swap utxo", index),
+                    ))
+                }
+            }
+        }
+        let serialized = serialize(signed_tx);
+        for index in 0..signed_tx.input.len() {
+            let result = match self.swap_script.version {
+                SwapScriptVersion::Legacy => {
+                    match script_pubkey.verify(
+                        index,
+                        Amount::from_sat(prevouts[index].value),
+                        &serialized,
+                    ) {
+                        Ok(()) => Ok(()),
+                        Err(e) => Err(S5Error::new(ErrorKind::Transaction, &e.to_string())),
+                    }
+                }
+                SwapScriptVersion::Taproot => {
+                    self.verify_taproot_input(signed_tx, index, &prevouts)
+                }
+            };
+            if let Err(e) = result {
+                return Err(S5Error::new(
+                    ErrorKind::Transaction,
+                    &format!(
+                        "Input {} does not satisfy the lockup script: {}",
+                        index, e.message
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// A key-path spend needs a signature of the tweaked output key. A script-path spend has to
+    /// reveal one of the two leaves with its control block, and satisfy it: the receiver's
+    /// signature and the preimage for the claim leaf, the sender's signature and a locktime of
+    /// at least the timelock for the refund leaf.
+    fn verify_taproot_input(
+        &self,
+        tx: &Transaction,
+        index: usize,
+        prevouts: &[TxOut],
+    ) -> Result<(), S5Error> {
+        let witness: Vec<&[u8]> = tx.input[index].witness.iter().collect();
+        let mut sighash_cache = SighashCache::new(tx);
+        let (signature, pubkey, sighash) = match witness.as_slice() {
+            [] => return Err(S5Error::new(ErrorKind::Transaction, "Empty witness.")),
+            [signature] => {
+                let output_key = match XOnlyPublicKey::from_slice(
+                    &prevouts[index].script_pubkey.as_bytes()[2..],
+                ) {
+                    Ok(result) => result,
+                    Err(e) => return Err(S5Error::new(ErrorKind::Script, &e.to_string())),
+                };
+                let signature = parse_schnorr_sig(signature)?;
+                let sighash = match sighash_cache.taproot_key_spend_signature_hash(
+                    index,
+                    &Prevouts::All(prevouts),
+                    signature.hash_ty,
+                ) {
+                    Ok(result) => result,
+                    Err(e) => return Err(S5Error::new(ErrorKind::Transaction, &e.to_string())),
+                };
+                (signature, output_key, sighash)
+            }
+            [args @ .., leaf, control_block] => {
+                let leaf = ScriptBuf::from_bytes(leaf.to_vec());
+                let is_claim = leaf == self.swap_script.claim_leaf()?;
+                if !is_claim && leaf != self.swap_script.refund_leaf()? {
+                    return Err(S5Error::new(
+                        ErrorKind::Script,
+                        "Leaf is not part of the swap tree.",
+                    ));
+                }
+                if *control_block != self.control_block(&leaf)?.serialize().as_slice() {
+                    return Err(S5Error::new(
+                        ErrorKind::Script,
+                        "Control block does not commit to the leaf.",
+                    ));
+                }
+                let pubkey = match (is_claim, args) {
+                    (true, [_, preimage]) => {
+                        if Hash::hash(preimage).to_string() != self.swap_script.hashlock {
+                            return Err(S5Error::new(
+                                ErrorKind::Script,
+                                "Preimage does not match the hashlock.",
+                            ));
+                        }
+                        if self.swap_script.swap_type != SwapType::Submarine && preimage.len() != 32
+                        {
+                            return Err(S5Error::new(
+                                ErrorKind::Script,
+                                "Preimage is not 32 bytes.",
+                            ));
+                        }
+                        x_only_pubkey(&self.swap_script.reciever_pubkey)?
+                    }
+                    (false, [_]) => {
+                        if !cltv_satisfied(
+                            tx.lock_time.to_consensus_u32(),
+                            tx.input[index].sequence.to_consensus_u32(),
+                            self.swap_script.timelock,
+                        ) {
+                            return Err(S5Error::new(
+                                ErrorKind::Script,
+                                &format!(
+                                    "Locktime {} does not reach the timelock {}, or the input is final.",
+                                    tx.lock_time, self.swap_script.timelock
+                                ),
+                            ));
+                        }
+                        x_only_pubkey(&self.swap_script.sender_pubkey)?
+                    }
+                    _ => {
+                        return Err(S5Error::new(
+                            ErrorKind::Script,
+                            &format!("Unexpected number of witness elements: {}", args.len()),
+                        ))
+                    }
+                };
+                let signature = parse_schnorr_sig(args[0])?;
+                let sighash = match sighash_cache.taproot_script_spend_signature_hash(
+                    index,
+                    &Prevouts::All(prevouts),
+                    TapLeafHash::from_script(&leaf, LeafVersion::TapScript),
+                    signature.hash_ty,
+                ) {
+                    Ok(result) => result,
+                    Err(e) => return Err(S5Error::new(ErrorKind::Transaction, &e.to_string())),
+                };
+                (signature, pubkey, sighash)
+            }
+        };
+        let message = match Message::from_slice(&sighash[..]) {
+            Ok(result) => result,
+            Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+        };
+        let secp = Secp256k1::verification_only();
+        match secp.verify_schnorr(&signature.sig, &message, &pubkey) {
+            Ok(()) => Ok(()),
+            Err(_) => Err(S5Error::new(ErrorKind::Transaction, "Invalid signature.")),
         }
     }
+
+    /// Verifies `signed_tx` before handing it to the backend, see `verify`.
+    pub fn broadcast(
+        &self,
+        backend: &dyn ChainBackend,
+        signed_tx: Transaction,
+    ) -> Result<String, S5Error> {
+        self.verify(&signed_tx)?;
+        let txid = backend.broadcast(&serialize(&signed_tx))?;
+        Ok(txid.to_string())
+    }
+
+    #[cfg(feature = "async")]
+    pub async fn broadcast_async(
+        &self,
+        backend: &dyn AsyncChainBackend,
+        signed_tx: Transaction,
+    ) -> Result<String, S5Error> {
+        self.verify(&signed_tx)?;
+        let txid = backend.broadcast(&serialize(&signed_tx)).await?;
+        Ok(txid.to_string())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::network::electrum::{NetworkConfig, DEFAULT_TESTNET_NODE};
+    use crate::network::{electrum::NetworkConfig, mock::MockChainBackend};
+    use crate::swaps::boltzv2::PartialSigRequest;
     use bitcoin::opcodes::all::{OP_EQUAL, OP_HASH160};
     use bitcoin::script::Builder;
     use bitcoin::secp256k1::hashes::{hash160, Hash};
     use bitcoin::{
         absolute::LockTime, Address, Network, OutPoint, Script, Sequence, Transaction, TxIn, TxOut,
-        Witness,
+        Txid, Witness,
     };
     use electrum_client::ElectrumApi;
     use std::io;
     use std::io::Write;
     use std::str::FromStr;
+    fn lockup_utxo(outpoint: OutPoint, value: u64) -> Utxo {
+        Utxo {
+            txid: outpoint.txid,
+            vout: outpoint.vout,
+            value,
+            height: 0,
+        }
+    }
     pub fn pause_and_wait(msg: &str) {
         let stdin = io::stdin();
         let mut stdout = io::stdout();
@@ -642,23 +1752,20 @@ mod tests {
             output: vec![output.clone()],
         };
 
+        let verify_result = tx.verify(|outpoint| {
+            if *outpoint == outpoint_0 {
+                Some(TxOut {
+                    script_pubkey: script.to_v0_p2wsh(),
+                    value: utxo_value,
+                })
+            } else {
+                None
+            }
+        });
+        assert!(verify_result.is_ok());
+
         let txid = electrum_client.transaction_broadcast(&tx).unwrap();
         println!("{}", txid);
-
-        // let mut utxo_map = HashMap::new();
-        // utxo_map.insert(outpoint_0, output);
-
-        // let verify_result = tx.verify(|outpoint| {
-        //     utxo_map.get(outpoint).cloned()
-        // });
-
-        // match verify_result {
-        //     Ok(_) => println!("Transaction verified successfully!"),
-        //     Err(e) =>{
-        //         println!("Verification failed: {:?}", e.to_string())
-        //     }
-        // }
-        // assert!(verify_result.is_ok());
         // let unsigned_tx = Transaction{
         //     version : 1,
         //     lock_time: LockTime::from_consensus(script_elements.timelock),
@@ -680,7 +1787,6 @@ mod tests {
         .unwrap();
         let decoded = BtcSwapScript::submarine_from_str(
             BitcoinNetwork::BitcoinTestnet,
-            DEFAULT_TESTNET_NODE.to_owned(),
             &redeem_script_str.clone(),
         )
         .unwrap();
@@ -690,15 +1796,27 @@ mod tests {
 
         let encoded = BtcSwapScript {
             network: BitcoinNetwork::BitcoinTestnet,
-            electrum_url: DEFAULT_TESTNET_NODE.to_owned(),
             swap_type: SwapType::Submarine,
-            hashlock: decoded.hashlock,
-            reciever_pubkey: decoded.reciever_pubkey,
-            sender_pubkey: decoded.sender_pubkey,
+            version: SwapScriptVersion::Legacy,
+            side: None,
+            hashlock: decoded.hashlock.clone(),
+            reciever_pubkey: decoded.reciever_pubkey.clone(),
+            sender_pubkey: decoded.sender_pubkey.clone(),
             timelock: decoded.timelock,
         }
         .to_script()
         .unwrap();
+
+        for swap_type in [SwapType::Submarine, SwapType::ReverseSubmarine] {
+            let mut invalid = decoded.clone();
+            invalid.swap_type = swap_type.clone();
+            invalid.reciever_pubkey = "02".to_string();
+            assert_eq!(invalid.to_script().unwrap_err().kind, "Input");
+            let mut invalid = decoded.clone();
+            invalid.swap_type = swap_type.clone();
+            invalid.hashlock = "00".to_string();
+            assert_eq!(invalid.to_script().unwrap_err().kind, "Input");
+        }
         let script_hash = encoded.script_hash();
         let sh_str = hex::encode(script_hash.to_raw_hash().to_string());
         println!("ENCODED SCRIPT HASH: {}", sh_str);
@@ -707,4 +1825,1127 @@ mod tests {
         println!("ADDRESS FROM ENCODED: {:?}", address.to_string());
         assert!(address.to_string() == expected_address);
     }
+
+    #[test]
+    fn test_redeem_script_template() {
+        let redeem_script_str = "a91461be1fecdb989e10275a19f893836066230ab208876321039f3dece2229c2e957e43df168bd078bcdad7e66d1690a27c8b0277d7832ced216703e0c926b17521023946267e8f3eeeea651b0ea865b52d1f9d1c12e851b0f98a3303c15a26cf235d68ac";
+        assert!(BtcSwapScript::submarine_from_str(
+            BitcoinNetwork::BitcoinTestnet,
+            redeem_script_str
+        )
+        .is_ok());
+
+        let error =
+            BtcSwapScript::reverse_from_str(BitcoinNetwork::BitcoinTestnet, redeem_script_str)
+                .unwrap_err();
+        assert_eq!(
+            error.message,
+            "Redeem script element 0 should be OP_SIZE: found OP_HASH160"
+        );
+
+        // same hashlock, pushed with OP_PUSHDATA1
+        let non_minimal = redeem_script_str.replacen("a914", "a94c14", 1);
+        let error = BtcSwapScript::submarine_from_str(BitcoinNetwork::BitcoinTestnet, &non_minimal)
+            .unwrap_err();
+        assert!(error
+            .message
+            .starts_with("Redeem script element 1 should be 20 byte hashlock"));
+
+        let trailing = format!("{}75", redeem_script_str);
+        assert!(
+            BtcSwapScript::submarine_from_str(BitcoinNetwork::BitcoinTestnet, &trailing).is_err()
+        );
+    }
+
+    #[test]
+    fn test_sign_refund_tx() {
+        let secp = Secp256k1::new();
+        let redeem_script_str = "a91461be1fecdb989e10275a19f893836066230ab208876321039f3dece2229c2e957e43df168bd078bcdad7e66d1690a27c8b0277d7832ced216703e0c926b17521023946267e8f3eeeea651b0ea865b52d1f9d1c12e851b0f98a3303c15a26cf235d68ac";
+        const RETURN_ADDRESS: &str = "tb1qw2c3lxufxqe2x9s4rdzh65tpf4d7fssjgh8nv6";
+        let utxo_value = 50_000;
+        let absolute_fees = 300;
+        let sender_key_pair = KeyPair::from_seckey_str(
+            &secp,
+            "d5f984d2ab332345dbf7ddff9f47852125721b2025329e6981c4130671e237d0",
+        )
+        .unwrap();
+        let swap_script =
+            BtcSwapScript::submarine_from_str(BitcoinNetwork::BitcoinTestnet, redeem_script_str)
+                .unwrap();
+        let redeem_script = swap_script.to_script().unwrap();
+        let lockup_address = swap_script.to_address().unwrap();
+        let timelock = swap_script.timelock;
+
+        let mut refund_tx =
+            BtcSwapTx::new_refund(swap_script, RETURN_ADDRESS.to_string(), absolute_fees).unwrap();
+        let outpoint = OutPoint::from_str(
+            "10c31b81b93a69a635ab337bb48adeefe662d40d3e5d50b319a1fed17485104a:1",
+        )
+        .unwrap();
+        refund_tx.utxos = vec![lockup_utxo(outpoint, utxo_value)];
+
+        let signed_tx = refund_tx.sign_refund_tx(sender_key_pair).unwrap();
+
+        assert_eq!(signed_tx.lock_time, LockTime::from_consensus(timelock));
+        assert_eq!(signed_tx.input.len(), 1);
+        assert_eq!(signed_tx.output.len(), 1);
+        assert_eq!(signed_tx.output[0].value, utxo_value - absolute_fees as u64);
+        let input = &signed_tx.input[0];
+        assert_eq!(input.previous_output, outpoint);
+        assert!(input.sequence.enables_absolute_lock_time());
+
+        // scriptSig must push the p2wsh program that hashes to the p2sh lockup
+        let pushes: Vec<_> = input
+            .script_sig
+            .instructions()
+            .map(|instruction| instruction.unwrap())
+            .collect();
+        assert_eq!(pushes.len(), 1);
+        let witness_program = match pushes[0] {
+            Instruction::PushBytes(bytes) => ScriptBuf::from(bytes.as_bytes().to_vec()),
+            Instruction::Op(_) => panic!("scriptSig must only contain a push"),
+        };
+        assert_eq!(witness_program, redeem_script.to_v0_p2wsh());
+        assert_eq!(
+            ScriptBuf::new_p2sh(&witness_program.script_hash()),
+            lockup_address.script_pubkey()
+        );
+
+        // witness: <signature> <empty preimage> <redeem script>
+        let witness: Vec<&[u8]> = input.witness.iter().collect();
+        assert_eq!(witness.len(), 3);
+        assert!(witness[1].is_empty());
+        assert_eq!(witness[2], redeem_script.as_bytes());
+
+        let signature = bitcoin::ecdsa::Signature::from_slice(witness[0]).unwrap();
+        assert_eq!(signature.hash_ty, bitcoin::sighash::EcdsaSighashType::All);
+        let sighash = SighashCache::new(&signed_tx)
+            .segwit_signature_hash(0, &redeem_script, utxo_value, signature.hash_ty)
+            .unwrap();
+        let message = Message::from_slice(&sighash[..]).unwrap();
+        assert!(secp
+            .verify_ecdsa(&message, &signature.sig, &sender_key_pair.public_key())
+            .is_ok());
+    }
+
+    #[test]
+    fn test_drain_with_mock_backend() {
+        let secp = Secp256k1::new();
+        const RETURN_ADDRESS: &str = "tb1qw2c3lxufxqe2x9s4rdzh65tpf4d7fssjgh8nv6";
+        let out_amount = 50_000;
+        let absolute_fees = 300;
+        let keypair = KeyPair::from_seckey_str(
+            &secp,
+            "5f9f8cb71d8193cb031b1a8b9b1ec08057a130dd8ac9f69cea2e3d8e6675f3a1",
+        )
+        .unwrap();
+        let preimage =
+            Preimage::from_str("898396fe53c58375cf8a5a8cfead2a285dc4b5b84cd149800914fc60c9f3a70b")
+                .unwrap();
+        let redeem_script = "8201208763a9143b2b7485171679c84f6540a8b907c2c830e9a60b88210223a99c57bfbc2a4bfc9353d49d6fd7312afaec8e8eefb82273d26c34c54589866775030bce26b1752103778dc69769e3cbdd9091d05a5e027ebc1919675d0725d2c1f2259f821a3e6a2668ac";
+        let swap_script =
+            BtcSwapScript::reverse_from_str(BitcoinNetwork::BitcoinTestnet, redeem_script).unwrap();
+        assert_eq!(
+            swap_script.reciever_pubkey,
+            keypair.public_key().to_string()
+        );
+
+        let lockup_script_pubkey = swap_script.to_address().unwrap().script_pubkey();
+        let funding_txid =
+            Txid::from_str("10c31b81b93a69a635ab337bb48adeefe662d40d3e5d50b319a1fed17485104a")
+                .unwrap();
+        let mut backend = MockChainBackend::new(swap_script.timelock - 10, 1.0);
+        backend.add_funding(
+            &lockup_script_pubkey,
+            funding_txid,
+            vec![],
+            &[(1, out_amount)],
+            swap_script.timelock - 11,
+        );
+        assert_eq!(swap_script.get_balance(&backend).unwrap(), (out_amount, 0));
+
+        let mut claim_tx =
+            BtcSwapTx::new_claim(swap_script, RETURN_ADDRESS.to_string(), absolute_fees).unwrap();
+        assert!(claim_tx
+            .drain(&backend, keypair, preimage.clone(), out_amount + 1)
+            .is_err());
+        let signed_tx = claim_tx
+            .drain(&backend, keypair, preimage.clone(), out_amount)
+            .unwrap();
+        assert_eq!(
+            signed_tx.input[0].previous_output,
+            OutPoint::new(funding_txid, 1)
+        );
+        assert_eq!(signed_tx.output[0].value, out_amount - absolute_fees as u64);
+        let witness: Vec<&[u8]> = signed_tx.input[0].witness.iter().collect();
+        assert_eq!(witness.len(), 3);
+        assert_eq!(witness[1], preimage.bytes.unwrap());
+
+        let txid = claim_tx.broadcast(&backend, signed_tx.clone()).unwrap();
+        assert_eq!(txid, signed_tx.txid().to_string());
+        assert_eq!(backend.broadcasted(), vec![serialize(&signed_tx)]);
+    }
+
+    #[test]
+    fn test_multiple_utxos() {
+        let secp = Secp256k1::new();
+        const RETURN_ADDRESS: &str = "tb1qw2c3lxufxqe2x9s4rdzh65tpf4d7fssjgh8nv6";
+        let expected_value = 50_000;
+        let absolute_fees = 300;
+        let keypair = KeyPair::from_seckey_str(
+            &secp,
+            "5f9f8cb71d8193cb031b1a8b9b1ec08057a130dd8ac9f69cea2e3d8e6675f3a1",
+        )
+        .unwrap();
+        let preimage =
+            Preimage::from_str("898396fe53c58375cf8a5a8cfead2a285dc4b5b84cd149800914fc60c9f3a70b")
+                .unwrap();
+        let redeem_script = "8201208763a9143b2b7485171679c84f6540a8b907c2c830e9a60b88210223a99c57bfbc2a4bfc9353d49d6fd7312afaec8e8eefb82273d26c34c54589866775030bce26b1752103778dc69769e3cbdd9091d05a5e027ebc1919675d0725d2c1f2259f821a3e6a2668ac";
+        let swap_script =
+            BtcSwapScript::reverse_from_str(BitcoinNetwork::BitcoinTestnet, redeem_script).unwrap();
+        let lockup_script_pubkey = swap_script.to_address().unwrap().script_pubkey();
+        let first_txid =
+            Txid::from_str("10c31b81b93a69a635ab337bb48adeefe662d40d3e5d50b319a1fed17485104a")
+                .unwrap();
+        let second_txid =
+            Txid::from_str("8e2a3e7e3e3fb18a2e6f0c2d4ab4a4c2a1a6c1c4f5f5d7c2b7b3f1f0e0d0c0b0")
+                .unwrap();
+
+        // paid short, then topped up
+        let mut backend = MockChainBackend::new(swap_script.timelock - 10, 1.0);
+        backend.add_funding(
+            &lockup_script_pubkey,
+            first_txid,
+            vec![],
+            &[(0, 20_000)],
+            swap_script.timelock - 11,
+        );
+        let report = swap_script.utxo_report(&backend, expected_value).unwrap();
+        assert_eq!(report.difference(), Some(-30_000));
+        let mut claim_tx = BtcSwapTx::new_claim(
+            swap_script.clone(),
+            RETURN_ADDRESS.to_string(),
+            absolute_fees,
+        )
+        .unwrap();
+        claim_tx.set_utxo_policy(UtxoPolicy::AtLeast);
+        let error = claim_tx
+            .drain(&backend, keypair, preimage.clone(), expected_value)
+            .unwrap_err();
+        assert!(error.message.contains("Underpaid by 30000"));
+
+        backend.add_funding(
+            &lockup_script_pubkey,
+            second_txid,
+            vec![],
+            &[(1, 40_000)],
+            0,
+        );
+        let report = swap_script.utxo_report(&backend, expected_value).unwrap();
+        assert_eq!(report.total(), 60_000);
+        assert_eq!(report.difference(), Some(10_000));
+        // no single output of the expected value
+        let mut exact_tx = BtcSwapTx::new_claim(
+            swap_script.clone(),
+            RETURN_ADDRESS.to_string(),
+            absolute_fees,
+        )
+        .unwrap();
+        assert!(exact_tx
+            .drain(&backend, keypair, preimage.clone(), expected_value)
+            .is_err());
+
+        let signed_tx = claim_tx
+            .drain(&backend, keypair, preimage.clone(), expected_value)
+            .unwrap();
+        assert_eq!(claim_tx.utxos(), report.utxos.as_slice());
+        assert_eq!(signed_tx.input.len(), 2);
+        assert_eq!(signed_tx.output.len(), 1);
+        assert_eq!(signed_tx.output[0].value, 60_000 - absolute_fees as u64);
+        assert_eq!(claim_tx.attempts()[0].fee, absolute_fees as u64);
+
+        let redeem_script = claim_tx.swap_script.to_script().unwrap();
+        for (index, utxo) in report.utxos.iter().enumerate() {
+            let input = &signed_tx.input[index];
+            assert_eq!(input.previous_output, OutPoint::new(utxo.txid, utxo.vout));
+            let witness: Vec<&[u8]> = input.witness.iter().collect();
+            assert_eq!(witness[1], preimage.bytes.unwrap());
+            let signature = bitcoin::ecdsa::Signature::from_slice(witness[0]).unwrap();
+            let sighash = SighashCache::new(&signed_tx)
+                .segwit_signature_hash(index, &redeem_script, utxo.value, signature.hash_ty)
+                .unwrap();
+            let message = Message::from_slice(&sighash[..]).unwrap();
+            assert!(secp
+                .verify_ecdsa(&message, &signature.sig, &keypair.public_key())
+                .is_ok());
+        }
+
+        // the weight grows with every input
+        let mut single_tx =
+            BtcSwapTx::new_claim(swap_script, RETURN_ADDRESS.to_string(), 0).unwrap();
+        single_tx.utxos = vec![report.utxos[0].clone()];
+        let single_weight = single_tx.weight(false).unwrap();
+        assert!(claim_tx.weight(false).unwrap() > single_weight);
+        assert!(signed_tx.weight().to_wu() <= claim_tx.weight(false).unwrap());
+
+        // every input of a taproot spend signs all prevouts
+        let (swap_script, _, sender) = taproot_swap_script(SwapType::Submarine);
+        let mut refund_tx = BtcSwapTx::new_refund(
+            swap_script.clone(),
+            RETURN_ADDRESS.to_string(),
+            absolute_fees,
+        )
+        .unwrap();
+        refund_tx.utxos = report.utxos.clone();
+        let signed_tx = refund_tx.sign(sender, Preimage::new()).unwrap();
+        assert_eq!(signed_tx.input.len(), 2);
+        let (_, prevouts) = refund_tx
+            .taproot_unsigned_tx(LockTime::ZERO, Sequence::MAX, 0)
+            .unwrap();
+        let leaf = swap_script.refund_leaf().unwrap();
+        for (index, input) in signed_tx.input.iter().enumerate() {
+            let witness: Vec<&[u8]> = input.witness.iter().collect();
+            let signature = bitcoin::secp256k1::schnorr::Signature::from_slice(witness[0]).unwrap();
+            let sighash = SighashCache::new(&signed_tx)
+                .taproot_script_spend_signature_hash(
+                    index,
+                    &Prevouts::All(&prevouts),
+                    TapLeafHash::from_script(&leaf, LeafVersion::TapScript),
+                    TapSighashType::Default,
+                )
+                .unwrap();
+            let message = Message::from_slice(&sighash[..]).unwrap();
+            assert!(secp
+                .verify_schnorr(&signature, &message, &sender.x_only_public_key().0)
+                .is_ok());
+        }
+        assert_eq!(signed_tx.weight().to_wu(), refund_tx.weight(false).unwrap());
+    }
+
+    #[test]
+    fn test_psbt() {
+        let secp = Secp256k1::new();
+        const RETURN_ADDRESS: &str = "tb1qw2c3lxufxqe2x9s4rdzh65tpf4d7fssjgh8nv6";
+        let outpoint = OutPoint::from_str(
+            "10c31b81b93a69a635ab337bb48adeefe662d40d3e5d50b319a1fed17485104a:1",
+        )
+        .unwrap();
+        let utxo_value = 50_000;
+
+        // legacy submarine refund: nested segwit, signed by the sender
+        let redeem_script_str = "a91461be1fecdb989e10275a19f893836066230ab208876321039f3dece2229c2e957e43df168bd078bcdad7e66d1690a27c8b0277d7832ced216703e0c926b17521023946267e8f3eeeea651b0ea865b52d1f9d1c12e851b0f98a3303c15a26cf235d68ac";
+        let sender_key_pair = KeyPair::from_seckey_str(
+            &secp,
+            "d5f984d2ab332345dbf7ddff9f47852125721b2025329e6981c4130671e237d0",
+        )
+        .unwrap();
+        let swap_script =
+            BtcSwapScript::submarine_from_str(BitcoinNetwork::BitcoinTestnet, redeem_script_str)
+                .unwrap();
+        let redeem_script = swap_script.to_script().unwrap();
+        let mut refund_tx =
+            BtcSwapTx::new_refund(swap_script.clone(), RETURN_ADDRESS.to_string(), 300).unwrap();
+        assert!(refund_tx.to_psbt().is_err());
+        refund_tx.utxos = vec![lockup_utxo(outpoint, utxo_value)];
+
+        let psbt = refund_tx.to_psbt().unwrap();
+        let input = &psbt.inputs[0];
+        assert_eq!(input.witness_script, Some(redeem_script.clone()));
+        assert_eq!(input.redeem_script, Some(redeem_script.to_v0_p2wsh()));
+        assert_eq!(
+            input.witness_utxo,
+            Some(TxOut {
+                script_pubkey: swap_script.to_address().unwrap().script_pubkey(),
+                value: utxo_value,
+            })
+        );
+        assert!(psbt.unsigned_tx.input[0].script_sig.is_empty());
+        assert!(refund_tx.finalize_psbt(psbt.clone(), None).is_err());
+
+        // what an external signer adds
+        let sign_ecdsa = |psbt: &mut Psbt, keys: &KeyPair| {
+            let hash_type = bitcoin::sighash::EcdsaSighashType::All;
+            let sighash = SighashCache::new(&psbt.unsigned_tx)
+                .segwit_signature_hash(0, &redeem_script, utxo_value, hash_type)
+                .unwrap();
+            let message = Message::from_slice(&sighash[..]).unwrap();
+            let signature = bitcoin::ecdsa::Signature {
+                sig: secp.sign_ecdsa(&message, &keys.secret_key()),
+                hash_ty: hash_type,
+            };
+            psbt.inputs[0]
+                .partial_sigs
+                .insert(PublicKey::new(keys.public_key()), signature);
+        };
+        let mut wrong_psbt = psbt.clone();
+        sign_ecdsa(
+            &mut wrong_psbt,
+            &KeyPair::new(&secp, &mut bitcoin::secp256k1::rand::thread_rng()),
+        );
+        assert!(refund_tx.finalize_psbt(wrong_psbt, None).is_err());
+        let mut signed_psbt = psbt.clone();
+        sign_ecdsa(&mut signed_psbt, &sender_key_pair);
+        let mut forged_psbt = signed_psbt.clone();
+        forged_psbt.unsigned_tx.output[0].value -= 1;
+        assert!(refund_tx.finalize_psbt(forged_psbt, None).is_err());
+        // a refund takes the timeout branch, a preimage means the caller mixed up the swap
+        let error = refund_tx
+            .finalize_psbt(signed_psbt.clone(), Some(&Preimage::new()))
+            .unwrap_err();
+        assert_eq!(error.kind, "Input");
+
+        let finalized_tx = refund_tx.finalize_psbt(signed_psbt, None).unwrap();
+        // ECDSA signatures are deterministic, so signing in place gives the same transaction
+        assert_eq!(
+            finalized_tx,
+            refund_tx.sign_refund_tx(sender_key_pair).unwrap()
+        );
+        assert_eq!(refund_tx.attempts()[0].txid, finalized_tx.txid());
+
+        // taproot claim: the leaf is signed by the receiver
+        let (swap_script, reciever, _) = taproot_swap_script(SwapType::ReverseSubmarine);
+        let preimage =
+            Preimage::from_str("898396fe53c58375cf8a5a8cfead2a285dc4b5b84cd149800914fc60c9f3a70b")
+                .unwrap();
+        let mut claim_tx =
+            BtcSwapTx::new_claim(swap_script.clone(), RETURN_ADDRESS.to_string(), 300).unwrap();
+        claim_tx.utxos = vec![lockup_utxo(outpoint, utxo_value)];
+        let mut psbt = claim_tx.to_psbt().unwrap();
+        let leaf = swap_script.claim_leaf().unwrap();
+        let spend_info = swap_script.taproot_spend_info().unwrap();
+        assert_eq!(
+            psbt.inputs[0].tap_scripts.values().next(),
+            Some(&(leaf.clone(), LeafVersion::TapScript))
+        );
+        assert_eq!(
+            psbt.inputs[0].tap_internal_key,
+            Some(spend_info.internal_key())
+        );
+
+        let prevouts = vec![psbt.inputs[0].witness_utxo.clone().unwrap()];
+        let leaf_hash = TapLeafHash::from_script(&leaf, LeafVersion::TapScript);
+        let sighash = SighashCache::new(&psbt.unsigned_tx)
+            .taproot_script_spend_signature_hash(
+                0,
+                &Prevouts::All(&prevouts),
+                leaf_hash,
+                TapSighashType::Default,
+            )
+            .unwrap();
+        let message = Message::from_slice(&sighash[..]).unwrap();
+        psbt.inputs[0].tap_script_sigs.insert(
+            (reciever.x_only_public_key().0, leaf_hash),
+            bitcoin::taproot::Signature {
+                sig: secp.sign_schnorr(&message, &reciever),
+                hash_ty: TapSighashType::Default,
+            },
+        );
+        assert!(claim_tx.finalize_psbt(psbt.clone(), None).is_err());
+        assert!(claim_tx
+            .finalize_psbt(psbt.clone(), Some(&Preimage::new()))
+            .is_err());
+        let finalized_tx = claim_tx.finalize_psbt(psbt, Some(&preimage)).unwrap();
+        let witness: Vec<&[u8]> = finalized_tx.input[0].witness.iter().collect();
+        assert_eq!(witness.len(), 4);
+        assert_eq!(witness[1], preimage.bytes.unwrap());
+        assert_eq!(witness[2], leaf.as_bytes());
+        assert_eq!(
+            finalized_tx.weight().to_wu(),
+            claim_tx.weight(false).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_rbf_bump() {
+        let secp = Secp256k1::new();
+        const RETURN_ADDRESS: &str = "tb1qw2c3lxufxqe2x9s4rdzh65tpf4d7fssjgh8nv6";
+        let out_amount = 50_000;
+        let keypair = KeyPair::from_seckey_str(
+            &secp,
+            "5f9f8cb71d8193cb031b1a8b9b1ec08057a130dd8ac9f69cea2e3d8e6675f3a1",
+        )
+        .unwrap();
+        let preimage =
+            Preimage::from_str("898396fe53c58375cf8a5a8cfead2a285dc4b5b84cd149800914fc60c9f3a70b")
+                .unwrap();
+        let redeem_script = "8201208763a9143b2b7485171679c84f6540a8b907c2c830e9a60b88210223a99c57bfbc2a4bfc9353d49d6fd7312afaec8e8eefb82273d26c34c54589866775030bce26b1752103778dc69769e3cbdd9091d05a5e027ebc1919675d0725d2c1f2259f821a3e6a2668ac";
+        let swap_script =
+            BtcSwapScript::reverse_from_str(BitcoinNetwork::BitcoinTestnet, redeem_script).unwrap();
+        let lockup_script_pubkey = swap_script.to_address().unwrap().script_pubkey();
+        let funding_txid =
+            Txid::from_str("10c31b81b93a69a635ab337bb48adeefe662d40d3e5d50b319a1fed17485104a")
+                .unwrap();
+        let mut backend = MockChainBackend::new(swap_script.timelock - 10, 1.0);
+        backend.add_funding(
+            &lockup_script_pubkey,
+            funding_txid,
+            vec![],
+            &[(1, out_amount)],
+            swap_script.timelock - 11,
+        );
+
+        // without RBF the claim is final and cannot be bumped
+        let mut claim_tx =
+            BtcSwapTx::new_claim(swap_script.clone(), RETURN_ADDRESS.to_string(), 300).unwrap();
+        let signed_tx = claim_tx
+            .drain(&backend, keypair, preimage.clone(), out_amount)
+            .unwrap();
+        assert_eq!(signed_tx.input[0].sequence, Sequence::MAX);
+        assert!(claim_tx
+            .bump(keypair, preimage.clone(), Fee::Relative(5.0))
+            .is_err());
+
+        let mut claim_tx =
+            BtcSwapTx::new_claim(swap_script.clone(), RETURN_ADDRESS.to_string(), 300).unwrap();
+        claim_tx.set_rbf(true);
+        assert!(claim_tx
+            .bump(keypair, preimage.clone(), Fee::Relative(5.0))
+            .is_err());
+        let first_tx = claim_tx
+            .drain(&backend, keypair, preimage.clone(), out_amount)
+            .unwrap();
+        assert_eq!(first_tx.input[0].sequence, Sequence::ENABLE_RBF_NO_LOCKTIME);
+        assert_eq!(first_tx.lock_time, LockTime::ZERO);
+        assert_eq!(
+            claim_tx.attempts(),
+            &[SpendAttempt {
+                txid: first_tx.txid(),
+                fee: 300
+            }]
+        );
+
+        // a replacement has to pay for its own relay on top of the replaced fee
+        assert!(claim_tx
+            .bump(keypair, preimage.clone(), Fee::Absolute(301))
+            .is_err());
+        assert_eq!(claim_tx.fee(false).unwrap(), 300);
+        assert_eq!(claim_tx.attempts().len(), 1);
+        let bumped_tx = claim_tx
+            .bump(keypair, preimage.clone(), Fee::Relative(5.0))
+            .unwrap();
+        let bumped_fee = out_amount - bumped_tx.output[0].value;
+        assert!(bumped_fee >= 300 + bumped_tx.vsize() as u64);
+        assert_eq!(
+            bumped_tx.input[0].previous_output,
+            first_tx.input[0].previous_output
+        );
+        assert_eq!(claim_tx.attempts().len(), 2);
+        assert_eq!(claim_tx.attempts()[1].fee, bumped_fee);
+
+        assert_eq!(claim_tx.confirmed_attempt(&backend).unwrap(), None);
+        backend.add_history(&lockup_script_pubkey, first_tx.txid(), 0);
+        assert_eq!(claim_tx.confirmed_attempt(&backend).unwrap(), None);
+        backend.add_history(&lockup_script_pubkey, bumped_tx.txid(), 100);
+        assert_eq!(
+            claim_tx.confirmed_attempt(&backend).unwrap(),
+            Some(claim_tx.attempts()[1].clone())
+        );
+
+        // attempts survive a restart
+        let saved = serde_json::to_string(claim_tx.attempts()).unwrap();
+        let mut restored_tx =
+            BtcSwapTx::new_claim(swap_script.clone(), RETURN_ADDRESS.to_string(), 300).unwrap();
+        assert_eq!(restored_tx.confirmed_attempt(&backend).unwrap(), None);
+        restored_tx.set_attempts(serde_json::from_str(&saved).unwrap());
+        assert_eq!(restored_tx.attempts(), claim_tx.attempts());
+        assert_eq!(
+            restored_tx.confirmed_attempt(&backend).unwrap(),
+            Some(claim_tx.attempts()[1].clone())
+        );
+
+        // the child brings the package up to the target rate
+        let child_weight = 438;
+        let target = claim_tx.cpfp(&first_tx, child_weight, 10.0).unwrap();
+        assert_eq!(target.outpoint, OutPoint::new(first_tx.txid(), 0));
+        assert_eq!(target.value, out_amount - 300);
+        let package_vsize = (first_tx.weight().to_wu() + child_weight).div_ceil(4);
+        assert_eq!(target.child_fee, 10 * package_vsize - 300);
+        // but never pays less than the target rate for itself
+        let target = claim_tx.cpfp(&bumped_tx, child_weight, 1.0).unwrap();
+        assert_eq!(target.child_fee, child_weight.div_ceil(4));
+
+        let mut unrelated_tx = first_tx.clone();
+        unrelated_tx.input[0].previous_output = OutPoint::new(funding_txid, 0);
+        assert!(claim_tx.cpfp(&unrelated_tx, child_weight, 10.0).is_err());
+        assert!(claim_tx.cpfp(&first_tx, child_weight, 1_000.0).is_err());
+    }
+
+    fn taproot_swap_script(swap_type: SwapType) -> (BtcSwapScript, KeyPair, KeyPair) {
+        let secp = Secp256k1::new();
+        let reciever = KeyPair::from_seckey_str(
+            &secp,
+            "5f9f8cb71d8193cb031b1a8b9b1ec08057a130dd8ac9f69cea2e3d8e6675f3a1",
+        )
+        .unwrap();
+        let sender = KeyPair::from_seckey_str(
+            &secp,
+            "d5f984d2ab332345dbf7ddff9f47852125721b2025329e6981c4130671e237d0",
+        )
+        .unwrap();
+        let preimage =
+            Preimage::from_str("898396fe53c58375cf8a5a8cfead2a285dc4b5b84cd149800914fc60c9f3a70b")
+                .unwrap();
+        let swap_script = BtcSwapScript::new_taproot(
+            BitcoinNetwork::BitcoinTestnet,
+            swap_type,
+            preimage.hash160.to_string(),
+            reciever.public_key().to_string(),
+            2_542_048,
+            sender.public_key().to_string(),
+        );
+        (swap_script, reciever, sender)
+    }
+
+    #[test]
+    fn test_taproot_swap_tree() {
+        let secp = Secp256k1::new();
+        let (swap_script, reciever, sender) = taproot_swap_script(SwapType::ReverseSubmarine);
+        let claim_leaf = swap_script.claim_leaf().unwrap();
+        let refund_leaf = swap_script.refund_leaf().unwrap();
+        assert_eq!(
+            claim_leaf.to_hex_string(),
+            format!(
+                "82012088a914{}8820{}ac",
+                swap_script.hashlock,
+                hex::encode(reciever.x_only_public_key().0.serialize())
+            )
+        );
+        assert_eq!(
+            refund_leaf.to_hex_string(),
+            format!(
+                "20{}ad03e0c926b1",
+                hex::encode(sender.x_only_public_key().0.serialize())
+            )
+        );
+        let submarine = taproot_swap_script(SwapType::Submarine).0;
+        assert!(submarine
+            .claim_leaf()
+            .unwrap()
+            .to_hex_string()
+            .starts_with("a914"));
+
+        // boltz's key comes first in the musig key aggregation
+        assert_eq!(
+            swap_script.musig_pubkeys().unwrap(),
+            [sender.public_key(), reciever.public_key()]
+        );
+        assert_eq!(
+            submarine.musig_pubkeys().unwrap(),
+            [reciever.public_key(), sender.public_key()]
+        );
+
+        let spend_info = swap_script.taproot_spend_info().unwrap();
+        let address = swap_script.to_address().unwrap();
+        assert!(address.to_string().starts_with("tb1p"));
+        assert_eq!(
+            address.script_pubkey(),
+            ScriptBuf::new_v1_p2tr_tweaked(spend_info.output_key())
+        );
+        for leaf in [&claim_leaf, &refund_leaf] {
+            let control_block = spend_info
+                .control_block(&(leaf.clone(), LeafVersion::TapScript))
+                .unwrap();
+            assert!(control_block.verify_taproot_commitment(
+                &secp,
+                spend_info.output_key().to_inner(),
+                leaf
+            ));
+        }
+        assert!(swap_script.to_script().is_err());
+
+        let mut swap_tree: SwapTree = serde_json::from_value(serde_json::json!({
+            "claimLeaf": {"version": 192, "output": claim_leaf.to_hex_string()},
+            "refundLeaf": {"version": 192, "output": refund_leaf.to_hex_string()},
+        }))
+        .unwrap();
+        let lockup_address = swap_script.to_address().unwrap().to_string();
+        assert!(swap_script
+            .validate_swap_tree(&swap_tree, &lockup_address)
+            .is_ok());
+        // the right leaves, but Boltz asks us to pay somewhere else
+        let other_address = submarine.to_address().unwrap().to_string();
+        assert!(swap_script
+            .validate_swap_tree(&swap_tree, &other_address)
+            .is_err());
+        swap_tree.refund_leaf.version = 0xc4;
+        assert!(swap_script
+            .validate_swap_tree(&swap_tree, &lockup_address)
+            .is_err());
+        swap_tree.refund_leaf.version = 0xc0;
+        swap_tree.claim_leaf.output = submarine.claim_leaf().unwrap().to_hex_string();
+        assert!(swap_script
+            .validate_swap_tree(&swap_tree, &lockup_address)
+            .is_err());
+    }
+
+    #[test]
+    fn test_sign_taproot_script_path() {
+        let secp = Secp256k1::new();
+        const RETURN_ADDRESS: &str = "tb1qw2c3lxufxqe2x9s4rdzh65tpf4d7fssjgh8nv6";
+        let utxo_value = 50_000;
+        let absolute_fees = 300;
+        let outpoint = OutPoint::from_str(
+            "10c31b81b93a69a635ab337bb48adeefe662d40d3e5d50b319a1fed17485104a:1",
+        )
+        .unwrap();
+        let preimage =
+            Preimage::from_str("898396fe53c58375cf8a5a8cfead2a285dc4b5b84cd149800914fc60c9f3a70b")
+                .unwrap();
+
+        for kind in [SwapTxKind::Claim, SwapTxKind::Refund] {
+            let (swap_script, reciever, sender) = taproot_swap_script(SwapType::ReverseSubmarine);
+            let (leaf, keys) = match kind {
+                SwapTxKind::Claim => (swap_script.claim_leaf().unwrap(), reciever),
+                SwapTxKind::Refund => (swap_script.refund_leaf().unwrap(), sender),
+            };
+            let prevout = TxOut {
+                script_pubkey: swap_script.to_address().unwrap().script_pubkey(),
+                value: utxo_value,
+            };
+            let timelock = swap_script.timelock;
+            let mut swap_tx = match kind {
+                SwapTxKind::Claim => {
+                    BtcSwapTx::new_claim(swap_script, RETURN_ADDRESS.to_string(), absolute_fees)
+                }
+                SwapTxKind::Refund => {
+                    BtcSwapTx::new_refund(swap_script, RETURN_ADDRESS.to_string(), absolute_fees)
+                }
+            }
+            .unwrap();
+            swap_tx.utxos = vec![lockup_utxo(outpoint, utxo_value)];
+
+            let signed_tx = swap_tx.sign(keys, preimage.clone()).unwrap();
+            assert_eq!(signed_tx.output[0].value, utxo_value - absolute_fees as u64);
+            let input = &signed_tx.input[0];
+            assert_eq!(input.previous_output, outpoint);
+            assert!(input.script_sig.is_empty());
+
+            let witness: Vec<&[u8]> = input.witness.iter().collect();
+            match kind {
+                SwapTxKind::Claim => {
+                    assert_eq!(signed_tx.lock_time, LockTime::ZERO);
+                    assert_eq!(witness.len(), 4);
+                    assert_eq!(witness[1], preimage.bytes.unwrap());
+                }
+                SwapTxKind::Refund => {
+                    assert_eq!(signed_tx.lock_time, LockTime::from_consensus(timelock));
+                    assert!(input.sequence.enables_absolute_lock_time());
+                    assert_eq!(witness.len(), 3);
+                }
+            }
+            assert_eq!(witness[witness.len() - 2], leaf.as_bytes());
+
+            let signature = bitcoin::secp256k1::schnorr::Signature::from_slice(witness[0]).unwrap();
+            let sighash = SighashCache::new(&signed_tx)
+                .taproot_script_spend_signature_hash(
+                    0,
+                    &Prevouts::All(&[prevout]),
+                    TapLeafHash::from_script(&leaf, LeafVersion::TapScript),
+                    TapSighashType::Default,
+                )
+                .unwrap();
+            let message = Message::from_slice(&sighash[..]).unwrap();
+            assert!(secp
+                .verify_schnorr(&signature, &message, &keys.x_only_public_key().0)
+                .is_ok());
+        }
+    }
+
+    #[test]
+    fn test_verify() {
+        const RETURN_ADDRESS: &str = "tb1qw2c3lxufxqe2x9s4rdzh65tpf4d7fssjgh8nv6";
+        let utxo_value = 50_000;
+        let outpoint = OutPoint::from_str(
+            "10c31b81b93a69a635ab337bb48adeefe662d40d3e5d50b319a1fed17485104a:1",
+        )
+        .unwrap();
+        let preimage =
+            Preimage::from_str("898396fe53c58375cf8a5a8cfead2a285dc4b5b84cd149800914fc60c9f3a70b")
+                .unwrap();
+
+        for kind in [SwapTxKind::Claim, SwapTxKind::Refund] {
+            let (swap_script, reciever, sender) = taproot_swap_script(SwapType::ReverseSubmarine);
+            let (keys, other_keys) = match kind {
+                SwapTxKind::Claim => (reciever, sender),
+                SwapTxKind::Refund => (sender, reciever),
+            };
+            let mut swap_tx = match kind {
+                SwapTxKind::Claim => {
+                    BtcSwapTx::new_claim(swap_script.clone(), RETURN_ADDRESS.to_string(), 300)
+                }
+                SwapTxKind::Refund => {
+                    BtcSwapTx::new_refund(swap_script.clone(), RETURN_ADDRESS.to_string(), 300)
+                }
+            }
+            .unwrap();
+            swap_tx.utxos = vec![lockup_utxo(outpoint, utxo_value)];
+            let signed_tx = swap_tx.sign(keys, preimage.clone()).unwrap();
+            assert!(swap_tx.verify(&signed_tx).is_ok());
+
+            let wrong_signer = swap_tx.sign(other_keys, preimage.clone()).unwrap();
+            let error = swap_tx.verify(&wrong_signer).unwrap_err();
+            assert!(error
+                .message
+                .contains("Input 0 does not satisfy the lockup script"));
+            assert!(error.message.contains("Invalid signature"));
+
+            let mut tampered = signed_tx.clone();
+            match kind {
+                SwapTxKind::Claim => {
+                    let mut witness: Vec<Vec<u8>> = tampered.input[0]
+                        .witness
+                        .iter()
+                        .map(|e| e.to_vec())
+                        .collect();
+                    witness[1] = vec![0; 32];
+                    tampered.input[0].witness = Witness::from_slice(&witness);
+                    let error = swap_tx.verify(&tampered).unwrap_err();
+                    assert!(error
+                        .message
+                        .contains("Preimage does not match the hashlock"));
+                }
+                SwapTxKind::Refund => {
+                    tampered.lock_time = LockTime::from_consensus(swap_script.timelock - 1);
+                    let error = swap_tx.verify(&tampered).unwrap_err();
+                    assert!(error.message.contains("does not reach the timelock"));
+                }
+            }
+
+            // the key-path signature has to be of the tweaked output key
+            let mut key_path = signed_tx.clone();
+            let signature = signed_tx.input[0].witness.iter().next().unwrap().to_vec();
+            key_path.input[0].witness = Witness::from_slice(&[signature]);
+            assert!(swap_tx.verify(&key_path).is_err());
+
+            let mut foreign = signed_tx.clone();
+            foreign.input[0].previous_output.vout = 0;
+            let error = swap_tx.verify(&foreign).unwrap_err();
+            assert!(error.message.contains("does not spend a swap utxo"));
+
+            let backend = MockChainBackend::new(swap_script.timelock, 1.0);
+            assert!(swap_tx.broadcast(&backend, wrong_signer).is_err());
+            assert!(backend.broadcasted().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_verify_legacy() {
+        let secp = Secp256k1::new();
+        let redeem_script_str = "a91461be1fecdb989e10275a19f893836066230ab208876321039f3dece2229c2e957e43df168bd078bcdad7e66d1690a27c8b0277d7832ced216703e0c926b17521023946267e8f3eeeea651b0ea865b52d1f9d1c12e851b0f98a3303c15a26cf235d68ac";
+        let sender_key_pair = KeyPair::from_seckey_str(
+            &secp,
+            "d5f984d2ab332345dbf7ddff9f47852125721b2025329e6981c4130671e237d0",
+        )
+        .unwrap();
+        let other_key_pair = KeyPair::from_seckey_str(
+            &secp,
+            "aecbc2bddfcd3fa6953d257a9f369dc20cdc66f2605c73efb4c91b90703506b6",
+        )
+        .unwrap();
+        let swap_script =
+            BtcSwapScript::submarine_from_str(BitcoinNetwork::BitcoinTestnet, redeem_script_str)
+                .unwrap();
+        let mut refund_tx = BtcSwapTx::new_refund(
+            swap_script,
+            "tb1qw2c3lxufxqe2x9s4rdzh65tpf4d7fssjgh8nv6".to_string(),
+            300,
+        )
+        .unwrap();
+        let outpoint = OutPoint::from_str(
+            "10c31b81b93a69a635ab337bb48adeefe662d40d3e5d50b319a1fed17485104a:1",
+        )
+        .unwrap();
+        refund_tx.utxos = vec![lockup_utxo(outpoint, 50_000)];
+
+        let signed_tx = refund_tx.sign_refund_tx(sender_key_pair).unwrap();
+        assert!(refund_tx.verify(&signed_tx).is_ok());
+
+        // libbitcoinconsensus rejects a signature of the wrong key and a missing scriptSig
+        let wrong_signer = refund_tx.sign_refund_tx(other_key_pair).unwrap();
+        let error = refund_tx.verify(&wrong_signer).unwrap_err();
+        assert!(error
+            .message
+            .contains("Input 0 does not satisfy the lockup script"));
+        let mut no_script_sig = signed_tx.clone();
+        no_script_sig.input[0].script_sig = ScriptBuf::new();
+        assert!(refund_tx.verify(&no_script_sig).is_err());
+    }
+
+    #[test]
+    fn test_fee_rate() {
+        const RETURN_ADDRESS: &str = "tb1qw2c3lxufxqe2x9s4rdzh65tpf4d7fssjgh8nv6";
+        let utxo_value = 50_000;
+        let outpoint = OutPoint::from_str(
+            "10c31b81b93a69a635ab337bb48adeefe662d40d3e5d50b319a1fed17485104a:1",
+        )
+        .unwrap();
+        let preimage =
+            Preimage::from_str("898396fe53c58375cf8a5a8cfead2a285dc4b5b84cd149800914fc60c9f3a70b")
+                .unwrap();
+        let backend = MockChainBackend::new(0, 2.5);
+        let fee = Fee::from_backend(&backend, 2).unwrap();
+
+        // schnorr signatures have a fixed size, so taproot weights are exact
+        for kind in [SwapTxKind::Claim, SwapTxKind::Refund] {
+            let (swap_script, reciever, sender) = taproot_swap_script(SwapType::ReverseSubmarine);
+            let mut swap_tx = match kind {
+                SwapTxKind::Claim => {
+                    BtcSwapTx::new_claim(swap_script, RETURN_ADDRESS.to_string(), 0)
+                }
+                SwapTxKind::Refund => {
+                    BtcSwapTx::new_refund(swap_script, RETURN_ADDRESS.to_string(), 0)
+                }
+            }
+            .unwrap();
+            swap_tx.set_fee(fee);
+            swap_tx.utxos = vec![lockup_utxo(outpoint, utxo_value)];
+            let keys = match kind {
+                SwapTxKind::Claim => reciever,
+                SwapTxKind::Refund => sender,
+            };
+
+            let signed_tx = swap_tx.sign(keys, preimage.clone()).unwrap();
+            let weight = signed_tx.weight().to_wu();
+            assert_eq!(swap_tx.weight(false).unwrap(), weight);
+            assert_eq!(
+                signed_tx.output[0].value,
+                utxo_value - Fee::Relative(2.5).absolute(weight)
+            );
+            assert!(swap_tx.weight(true).unwrap() < weight);
+        }
+
+        // ECDSA signatures are counted at their largest size
+        let keypair = KeyPair::from_seckey_str(
+            &Secp256k1::new(),
+            "5f9f8cb71d8193cb031b1a8b9b1ec08057a130dd8ac9f69cea2e3d8e6675f3a1",
+        )
+        .unwrap();
+        let redeem_script = "8201208763a9143b2b7485171679c84f6540a8b907c2c830e9a60b88210223a99c57bfbc2a4bfc9353d49d6fd7312afaec8e8eefb82273d26c34c54589866775030bce26b1752103778dc69769e3cbdd9091d05a5e027ebc1919675d0725d2c1f2259f821a3e6a2668ac";
+        let swap_script =
+            BtcSwapScript::reverse_from_str(BitcoinNetwork::BitcoinTestnet, redeem_script).unwrap();
+        let mut claim_tx =
+            BtcSwapTx::new_claim(swap_script, RETURN_ADDRESS.to_string(), 0).unwrap();
+        claim_tx.set_fee(fee);
+        claim_tx.utxos = vec![lockup_utxo(outpoint, utxo_value)];
+
+        let signed_tx = claim_tx.sign(keypair, preimage.clone()).unwrap();
+        let weight = claim_tx.weight(false).unwrap();
+        let signed_weight = signed_tx.weight().to_wu();
+        assert!(weight >= signed_weight && weight <= signed_weight + 2);
+        let fee_paid = utxo_value - signed_tx.output[0].value;
+        assert_eq!(fee_paid, claim_tx.fee(false).unwrap());
+        assert!(fee_paid as f64 >= 2.5 * signed_weight.div_ceil(4) as f64);
+
+        claim_tx.set_fee(Fee::Relative(1_000.0));
+        assert!(claim_tx.sign(keypair, preimage).is_err());
+    }
+
+    /// A Boltz stand-in that co-signs key-path spends of `prevout` with `boltz_keys`.
+    /// Returns an invalid partial signature when `honest` is false.
+    fn mock_cooperative_boltz(
+        swap_script: &BtcSwapScript,
+        boltz_keys: KeyPair,
+        prevout: TxOut,
+        honest: bool,
+    ) -> String {
+        let musig_pubkeys = swap_script.musig_pubkeys().unwrap();
+        let tweak = swap_script
+            .taproot_spend_info()
+            .unwrap()
+            .tap_tweak()
+            .to_scalar();
+        crate::network::mock::mock_http_server(move |_, _, body| {
+            let request: PartialSigRequest = serde_json::from_slice(body).unwrap();
+            let tx: Transaction =
+                bitcoin::consensus::deserialize(&hex::decode(&request.transaction).unwrap())
+                    .unwrap();
+            let sighash = SighashCache::new(&tx)
+                .taproot_key_spend_signature_hash(
+                    0,
+                    &Prevouts::All(std::slice::from_ref(&prevout)),
+                    TapSighashType::Default,
+                )
+                .unwrap();
+            let mut cache = musig::KeyAggCache::new(&musig_pubkeys).unwrap();
+            cache.x_only_tweak_add(tweak).unwrap();
+            let their_nonce =
+                musig::PubNonce::from_slice(&hex::decode(&request.pub_nonce).unwrap()).unwrap();
+            let (secnonce, pubnonce) = musig::nonce_gen();
+            let session = musig::Session::new(
+                &cache,
+                musig::nonce_agg(&[pubnonce, their_nonce]),
+                &sighash.to_byte_array(),
+            )
+            .unwrap();
+            let mut partial_sig = musig::partial_sign(&cache, &session, secnonce, &boltz_keys)
+                .unwrap()
+                .to_be_bytes();
+            if !honest {
+                partial_sig[31] ^= 1;
+            }
+            let response = serde_json::json!({
+                "pubNonce": hex::encode(pubnonce.serialize()),
+                "partialSignature": hex::encode(partial_sig),
+            });
+            (200, response.to_string().into_bytes())
+        })
+    }
+
+    #[test]
+    fn test_sign_cooperative() {
+        let secp = Secp256k1::new();
+        const RETURN_ADDRESS: &str = "tb1qw2c3lxufxqe2x9s4rdzh65tpf4d7fssjgh8nv6";
+        let utxo_value = 50_000;
+        let outpoint = OutPoint::from_str(
+            "10c31b81b93a69a635ab337bb48adeefe662d40d3e5d50b319a1fed17485104a:1",
+        )
+        .unwrap();
+        let preimage =
+            Preimage::from_str("898396fe53c58375cf8a5a8cfead2a285dc4b5b84cd149800914fc60c9f3a70b")
+                .unwrap();
+
+        for (swap_type, side, honest) in [
+            (SwapType::ReverseSubmarine, None, true),
+            (SwapType::Submarine, None, true),
+            (SwapType::ReverseSubmarine, None, false),
+            (SwapType::Chain, Some(ChainSwapSide::Lockup), true),
+            (SwapType::Chain, Some(ChainSwapSide::Claim), true),
+        ] {
+            let (mut swap_script, reciever, sender) = taproot_swap_script(swap_type.clone());
+            swap_script.side = side;
+            // boltz is the reciever of a submarine swap or a chain swap lockup, and the sender otherwise
+            let claim = swap_type != SwapType::Submarine && side != Some(ChainSwapSide::Lockup);
+            let (our_keys, boltz_keys) = if claim {
+                (reciever, sender)
+            } else {
+                (sender, reciever)
+            };
+            let prevout = TxOut {
+                script_pubkey: swap_script.to_address().unwrap().script_pubkey(),
+                value: utxo_value,
+            };
+            let output_key = swap_script.taproot_spend_info().unwrap().output_key();
+            let url = mock_cooperative_boltz(&swap_script, boltz_keys, prevout.clone(), honest);
+            let boltz = BoltzApiClientV2::new(&url);
+
+            let mut swap_tx = if claim {
+                BtcSwapTx::new_claim(swap_script, RETURN_ADDRESS.to_string(), 300)
+            } else {
+                BtcSwapTx::new_refund(swap_script, RETURN_ADDRESS.to_string(), 300)
+            }
+            .unwrap();
+            swap_tx.utxos = vec![lockup_utxo(outpoint, utxo_value)];
+
+            let signed_tx = swap_tx.sign_cooperative(our_keys, preimage.clone(), &boltz, "swap");
+            if !honest || side == Some(ChainSwapSide::Claim) {
+                // invalid partial signature or no cooperative chain claim
+                assert!(signed_tx.is_err());
+                continue;
+            }
+            let signed_tx = signed_tx.unwrap();
+            let witness: Vec<&[u8]> = signed_tx.input[0].witness.iter().collect();
+            assert_eq!(witness.len(), 1);
+            assert_eq!(signed_tx.lock_time, LockTime::ZERO);
+            let signature = bitcoin::secp256k1::schnorr::Signature::from_slice(witness[0]).unwrap();
+            let sighash = SighashCache::new(&signed_tx)
+                .taproot_key_spend_signature_hash(
+                    0,
+                    &Prevouts::All(&[prevout]),
+                    TapSighashType::Default,
+                )
+                .unwrap();
+            let message = Message::from_slice(&sighash[..]).unwrap();
+            assert!(secp
+                .verify_schnorr(&signature, &message, &output_key.to_inner())
+                .is_ok());
+        }
+
+        // boltz unreachable: the refund leaf is only used once the timelock is reached
+        let (swap_script, _, sender) = taproot_swap_script(SwapType::Submarine);
+        let timelock = swap_script.timelock;
+        let mut backend = MockChainBackend::new(timelock - 1, 1.0);
+        backend.add_funding(
+            &swap_script.to_address().unwrap().script_pubkey(),
+            outpoint.txid,
+            vec![],
+            &[(outpoint.vout, utxo_value)],
+            timelock - 10,
+        );
+        let boltz = BoltzApiClientV2::new("http://127.0.0.1:1");
+        let mut refund_tx =
+            BtcSwapTx::new_refund(swap_script, RETURN_ADDRESS.to_string(), 300).unwrap();
+        let refused = refund_tx
+            .drain_cooperative(
+                &backend,
+                sender,
+                preimage.clone(),
+                utxo_value,
+                &boltz,
+                "swap",
+            )
+            .unwrap_err();
+        assert_eq!(refused.kind, "BoltzApi");
+        assert!(refused.message.contains("locked until block"));
+
+        backend.set_tip_height(timelock);
+        let spend = refund_tx
+            .drain_cooperative(&backend, sender, preimage, utxo_value, &boltz, "swap")
+            .unwrap();
+        assert_eq!(spend.cooperative_error.unwrap().kind, "BoltzApi");
+        assert_eq!(spend.tx.input[0].witness.len(), 3);
+        assert!(spend.tx.input[0].sequence.enables_absolute_lock_time());
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn test_drain_async_with_mock_backend() {
+        let secp = Secp256k1::new();
+        let out_amount = 50_000;
+        let keypair = KeyPair::from_seckey_str(
+            &secp,
+            "5f9f8cb71d8193cb031b1a8b9b1ec08057a130dd8ac9f69cea2e3d8e6675f3a1",
+        )
+        .unwrap();
+        let preimage =
+            Preimage::from_str("898396fe53c58375cf8a5a8cfead2a285dc4b5b84cd149800914fc60c9f3a70b")
+                .unwrap();
+        let redeem_script = "8201208763a9143b2b7485171679c84f6540a8b907c2c830e9a60b88210223a99c57bfbc2a4bfc9353d49d6fd7312afaec8e8eefb82273d26c34c54589866775030bce26b1752103778dc69769e3cbdd9091d05a5e027ebc1919675d0725d2c1f2259f821a3e6a2668ac";
+        let swap_script =
+            BtcSwapScript::reverse_from_str(BitcoinNetwork::BitcoinTestnet, redeem_script).unwrap();
+        let funding_txid =
+            Txid::from_str("10c31b81b93a69a635ab337bb48adeefe662d40d3e5d50b319a1fed17485104a")
+                .unwrap();
+        let mut backend = MockChainBackend::new(swap_script.timelock - 10, 1.0);
+        backend.add_funding(
+            &swap_script.to_address().unwrap().script_pubkey(),
+            funding_txid,
+            vec![],
+            &[(1, out_amount)],
+            0,
+        );
+        assert_eq!(
+            swap_script.get_balance_async(&backend).await.unwrap(),
+            (0, out_amount as i64)
+        );
+
+        let mut claim_tx = BtcSwapTx::new_claim(
+            swap_script,
+            "tb1qw2c3lxufxqe2x9s4rdzh65tpf4d7fssjgh8nv6".to_string(),
+            300,
+        )
+        .unwrap();
+        let signed_tx = claim_tx
+            .drain_async(&backend, keypair, preimage, out_amount)
+            .await
+            .unwrap();
+        assert_eq!(
+            signed_tx.input[0].previous_output,
+            OutPoint::new(funding_txid, 1)
+        );
+        let txid = claim_tx
+            .broadcast_async(&backend, signed_tx.clone())
+            .await
+            .unwrap();
+        assert_eq!(txid, signed_tx.txid().to_string());
+    }
 }
@@ -8,10 +8,16 @@ use std::str::FromStr;
 // use std::time::Duration;
 // use ureq::{Agent, AgentBuilder, Error};
 
-use crate::util::error::{ErrorKind, S5Error};
-
-use crate::network::electrum::{BitcoinNetwork, DEFAULT_MAINNET_NODE};
+use crate::util::error::{BoltzApiError, ErrorKind, S5Error};
+
+#[cfg(feature = "async")]
+use crate::network::backend::AsyncChainBackend;
+use crate::network::{
+    backend::{ChainBackend, Utxo},
+    electrum::BitcoinNetwork,
+};
 use crate::swaps::bitcoin::BtcSwapScript;
+use crate::swaps::boltzv2::SwapStatusTransaction;
 
 pub const BOLTZ_TESTNET_URL: &str = "https://api.testnet.boltz.exchange";
 pub const BOLTZ_MAINNET_URL: &str = "https://api.boltz.exchange";
@@ -22,6 +28,149 @@ pub enum SwapTxKind {
     Refund,
 }
 
+/// Miner fee of a claim or refund transaction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fee {
+    /// In sats, whatever the size of the transaction.
+    Absolute(u64),
+    /// In sats per vbyte. Liquid vbytes are discounted for confidential outputs.
+    Relative(f64),
+}
+
+impl Fee {
+    /// The backend's estimate for confirming within `target_blocks`.
+    pub fn from_backend(backend: &dyn ChainBackend, target_blocks: usize) -> Result<Fee, S5Error> {
+        Ok(Fee::Relative(backend.get_fee_estimate(target_blocks)?))
+    }
+
+    #[cfg(feature = "async")]
+    pub async fn from_backend_async(
+        backend: &dyn AsyncChainBackend,
+        target_blocks: usize,
+    ) -> Result<Fee, S5Error> {
+        Ok(Fee::Relative(
+            backend.get_fee_estimate(target_blocks).await?,
+        ))
+    }
+
+    /// Sats to pay for a transaction of `weight` weight units.
+    pub fn absolute(&self, weight: u64) -> u64 {
+        match self {
+            Fee::Absolute(fee) => *fee,
+            Fee::Relative(rate) => ceil_sats(rate * weight.div_ceil(4) as f64),
+        }
+    }
+}
+
+/// Which of the outputs at a lockup script a claim or refund spends.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UtxoPolicy {
+    /// A single output of exactly the expected value.
+    Exact,
+    /// Every output, as long as together they are worth at least the expected value.
+    AtLeast,
+    /// Every output, whatever their value. Use it to refund over-, under- and double payments.
+    All,
+}
+
+/// The outputs found at a lockup script, compared to the value the swap expects.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UtxoReport {
+    /// None when the caller did not say what to expect.
+    pub expected_value: Option<u64>,
+    /// Liquid values are unblinded.
+    pub utxos: Vec<Utxo>,
+    /// Outputs at the lockup script that cannot be spent as part of the swap,
+    /// e.g. Liquid outputs of another asset or that the swap's blinding key does not unblind.
+    pub skipped: Vec<SkippedUtxo>,
+}
+
+/// An output found at a lockup script but left out of `UtxoReport::utxos`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkippedUtxo {
+    /// As listed by the backend; the value of a confidential output is 0.
+    pub utxo: Utxo,
+    pub reason: String,
+}
+
+impl UtxoReport {
+    pub fn total(&self) -> u64 {
+        self.utxos.iter().map(|utxo| utxo.value).sum()
+    }
+    /// Found minus expected value: positive when overpaid, negative when underpaid.
+    pub fn difference(&self) -> Option<i64> {
+        self.expected_value
+            .map(|expected| self.total() as i64 - expected as i64)
+    }
+    /// The outputs to spend under `policy`.
+    pub fn select(&self, policy: UtxoPolicy) -> Result<Vec<Utxo>, S5Error> {
+        if self.utxos.is_empty() {
+            return Err(S5Error::new(
+                ErrorKind::Transaction,
+                "0 utxos found for this script",
+            ));
+        }
+        let expected_value = match (policy, self.expected_value) {
+            (UtxoPolicy::All, _) => return Ok(self.utxos.clone()),
+            (_, Some(result)) => result,
+            (_, None) => {
+                return Err(S5Error::new(
+                    ErrorKind::Input,
+                    &format!("{:?} utxo policy requires an expected value.", policy),
+                ))
+            }
+        };
+        let selected = match policy {
+            UtxoPolicy::Exact => self
+                .utxos
+                .iter()
+                .find(|utxo| utxo.value == expected_value)
+                .map(|utxo| vec![utxo.clone()]),
+            _ if self.total() >= expected_value => Some(self.utxos.clone()),
+            _ => None,
+        };
+        match selected {
+            Some(result) => Ok(result),
+            None => Err(S5Error::new(ErrorKind::Input, &self.to_string())),
+        }
+    }
+}
+
+impl std::fmt::Display for UtxoReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let values: Vec<String> = self
+            .utxos
+            .iter()
+            .map(|utxo| utxo.value.to_string())
+            .collect();
+        write!(
+            f,
+            "Found {} utxos ({}) worth {}",
+            self.utxos.len(),
+            values.join(", "),
+            self.total()
+        )?;
+        match (self.expected_value, self.difference()) {
+            (Some(expected), Some(0)) => write!(f, ", Expected {}", expected),
+            (Some(expected), Some(difference)) if difference > 0 => {
+                write!(f, ", Expected {}, Overpaid by {}", expected, difference)
+            }
+            (Some(expected), Some(difference)) => {
+                write!(f, ", Expected {}, Underpaid by {}", expected, -difference)
+            }
+            _ => Ok(()),
+        }?;
+        for skipped in &self.skipped {
+            write!(
+                f,
+                ", Skipped {}:{} ({})",
+                skipped.utxo.txid, skipped.utxo.vout, skipped.reason
+            )?;
+        }
+        Ok(())
+    }
+}
+
 use reqwest::blocking::Client;
 
 pub struct BoltzApiClient {
@@ -35,60 +184,121 @@ impl BoltzApiClient {
         }
     }
 
-    pub fn get_pairs(&self) -> Result<GetPairsResponse, S5Error> {
-        let url = format!("{}/getpairs", self.base_url);
-
-        let res = Client::new().get(&url).send().unwrap();
+    fn handle_response<T: for<'de> Deserialize<'de>>(
+        res: Result<reqwest::blocking::Response, reqwest::Error>,
+    ) -> Result<T, BoltzApiError> {
+        let res = match res {
+            Ok(result) => result,
+            Err(e) => return Err(BoltzApiError::Transport(e.to_string())),
+        };
+        let status = res.status().as_u16();
+        let body = match res.text() {
+            Ok(result) => result,
+            Err(e) => return Err(BoltzApiError::Transport(e.to_string())),
+        };
+        parse_response(status, body)
+    }
 
-        if res.status().is_success() {
-            let body = res.text().unwrap();
-            let get_pairs_response: GetPairsResponse = serde_json::from_str(&body).unwrap();
-            Ok(get_pairs_response)
-        } else {
-            Err(S5Error::new(ErrorKind::BoltzApi, &res.text().unwrap()))
-        }
+    pub fn get_pairs(&self) -> Result<GetPairsResponse, BoltzApiError> {
+        let url = format!("{}/getpairs", self.base_url);
+        Self::handle_response(Client::new().get(&url).send())
     }
 
-    pub fn get_fee_estimation(&self) -> Result<GetFeeEstimationResponse, S5Error> {
+    pub fn get_fee_estimation(&self) -> Result<GetFeeEstimationResponse, BoltzApiError> {
         let url = format!("{}/getfeeestimation", self.base_url);
-        let res = Client::new().get(&url).send().unwrap();
-
-        if res.status().is_success() {
-            let body = res.text().unwrap();
-            let get_fee_estimation_response: GetFeeEstimationResponse =
-                serde_json::from_str(&body).unwrap();
-            Ok(get_fee_estimation_response)
-        } else {
-            Err(S5Error::new(ErrorKind::BoltzApi, &res.text().unwrap()))
-        }
+        Self::handle_response(Client::new().get(&url).send())
     }
 
-    pub fn create_swap(&self, request: CreateSwapRequest) -> Result<CreateSwapResponse, S5Error> {
+    pub fn create_swap(
+        &self,
+        request: CreateSwapRequest,
+    ) -> Result<CreateSwapResponse, BoltzApiError> {
         let url = format!("{}/createswap", self.base_url);
-        let res = Client::new().post(&url).json(&request).send().unwrap();
-
-        if res.status().is_success() {
-            let body = res.text().unwrap();
-            let create_swap_response: CreateSwapResponse = serde_json::from_str(&body).unwrap();
-            Ok(create_swap_response)
-        } else {
-            Err(S5Error::new(ErrorKind::BoltzApi, &res.text().unwrap()))
-        }
+        Self::handle_response(Client::new().post(&url).json(&request).send())
     }
 
-    pub fn swap_status(&self, request: SwapStatusRequest) -> Result<SwapStatusResponse, S5Error> {
+    pub fn swap_status(
+        &self,
+        request: SwapStatusRequest,
+    ) -> Result<SwapStatusResponse, BoltzApiError> {
         let url = format!("{}/swapstatus", self.base_url);
+        Self::handle_response(Client::new().post(&url).json(&request).send())
+    }
+}
+
+/// Non 2xx statuses become Http errors; 2xx bodies must deserialize into T.
+pub(crate) fn parse_response<T: for<'de> Deserialize<'de>>(
+    status: u16,
+    body: String,
+) -> Result<T, BoltzApiError> {
+    if !(200..300).contains(&status) {
+        return Err(BoltzApiError::from_response(status, &body));
+    }
+    match serde_json::from_str(&body) {
+        Ok(result) => Ok(result),
+        Err(e) => Err(BoltzApiError::Deserialize {
+            error: e.to_string(),
+            body,
+        }),
+    }
+}
 
-        let res = Client::new().post(&url).json(&request).send().unwrap();
+/// Non-blocking BoltzApiClient, for use inside async runtimes where the blocking client panics.
+#[cfg(feature = "async")]
+pub struct AsyncBoltzApiClient {
+    base_url: String,
+    client: reqwest::Client,
+}
 
-        if res.status().is_success() {
-            let body = res.text().unwrap();
-            let swap_status_response: SwapStatusResponse = serde_json::from_str(&body).unwrap();
-            Ok(swap_status_response)
-        } else {
-            Err(S5Error::new(ErrorKind::BoltzApi, &res.text().unwrap()))
+#[cfg(feature = "async")]
+impl AsyncBoltzApiClient {
+    pub fn new(base_url: &str) -> Self {
+        AsyncBoltzApiClient {
+            base_url: base_url.to_string(),
+            client: reqwest::Client::new(),
         }
     }
+
+    async fn handle_response<T: for<'de> Deserialize<'de>>(
+        res: Result<reqwest::Response, reqwest::Error>,
+    ) -> Result<T, BoltzApiError> {
+        let res = match res {
+            Ok(result) => result,
+            Err(e) => return Err(BoltzApiError::Transport(e.to_string())),
+        };
+        let status = res.status().as_u16();
+        let body = match res.text().await {
+            Ok(result) => result,
+            Err(e) => return Err(BoltzApiError::Transport(e.to_string())),
+        };
+        parse_response(status, body)
+    }
+
+    pub async fn get_pairs(&self) -> Result<GetPairsResponse, BoltzApiError> {
+        let url = format!("{}/getpairs", self.base_url);
+        Self::handle_response(self.client.get(&url).send().await).await
+    }
+
+    pub async fn get_fee_estimation(&self) -> Result<GetFeeEstimationResponse, BoltzApiError> {
+        let url = format!("{}/getfeeestimation", self.base_url);
+        Self::handle_response(self.client.get(&url).send().await).await
+    }
+
+    pub async fn create_swap(
+        &self,
+        request: CreateSwapRequest,
+    ) -> Result<CreateSwapResponse, BoltzApiError> {
+        let url = format!("{}/createswap", self.base_url);
+        Self::handle_response(self.client.post(&url).json(&request).send().await).await
+    }
+
+    pub async fn swap_status(
+        &self,
+        request: SwapStatusRequest,
+    ) -> Result<SwapStatusResponse, BoltzApiError> {
+        let url = format!("{}/swapstatus", self.base_url);
+        Self::handle_response(self.client.post(&url).json(&request).send().await).await
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -156,46 +366,237 @@ pub struct Pair {
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Limits {
-    maximal: i64,
-    minimal: i64,
-    maximal_zero_conf: MaximalZeroConf,
+    pub maximal: i64,
+    pub minimal: i64,
+    pub maximal_zero_conf: MaximalZeroConf,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct MaximalZeroConf {
-    base_asset: i64,
-    quote_asset: i64,
+    pub base_asset: i64,
+    pub quote_asset: i64,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Fees {
-    percentage: f64,
-    percentage_swap_in: f64,
-    miner_fees: MinerFees,
+    /// Reverse swaps
+    pub percentage: f64,
+    /// Submarine swaps
+    pub percentage_swap_in: f64,
+    pub miner_fees: MinerFees,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct MinerFees {
-    base_asset: MinerFee,
-    quote_asset: MinerFee,
+    pub base_asset: MinerFee,
+    pub quote_asset: MinerFee,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct MinerFee {
-    normal: i64,
-    reverse: ReverseMinerFee,
+    /// Boltz's claim of a submarine swap lockup
+    pub normal: i64,
+    pub reverse: ReverseMinerFee,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ReverseMinerFee {
-    claim: i64,
-    lockup: i64,
+    /// Estimate for our claim of Boltz's lockup; not charged by Boltz
+    pub claim: i64,
+    pub lockup: i64,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// The amount a quote starts from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QuoteAmount {
+    Invoice(u64),
+    Onchain(u64),
+}
+
+/// What a swap costs, from the fees and limits of a `Pair`.
+/// For submarine swaps `onchain_amount` is what we lock; for reverse swaps it is what Boltz locks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwapQuote {
+    pub invoice_amount: u64,
+    pub onchain_amount: u64,
+    pub service_fee: u64,
+    /// Boltz's miner fee to claim our lockup (submarine).
+    pub boltz_claim_fee: u64,
+    /// Boltz's miner fee to lock up (reverse).
+    pub boltz_lockup_fee: u64,
+    /// Estimated miner fee of our claim transaction (reverse); not charged by Boltz.
+    pub claim_fee_estimate: u64,
+    pub above_minimal: bool,
+    pub below_maximal: bool,
+    /// Whether a lockup of `onchain_amount` is accepted with zero confirmations.
+    pub within_zero_conf: bool,
+}
+
+impl SwapQuote {
+    pub fn within_limits(&self) -> bool {
+        self.above_minimal && self.below_maximal
+    }
+}
+
+impl Pair {
+    /// Quotes a submarine or reverse swap on this pair. Limits apply to the invoice amount.
+    /// The onchain side of the BTC/BTC and L-BTC/BTC pairs is the base asset.
+    pub fn quote(&self, swap_type: &SwapType, amount: QuoteAmount) -> Result<SwapQuote, S5Error> {
+        let miner_fees = &self.fees.miner_fees.base_asset;
+        let (boltz_claim_fee, boltz_lockup_fee, claim_fee_estimate) = match swap_type {
+            SwapType::Submarine => (miner_fee(miner_fees.normal)?, 0, 0),
+            _ => (
+                0,
+                miner_fee(miner_fees.reverse.lockup)?,
+                miner_fee(miner_fees.reverse.claim)?,
+            ),
+        };
+        let (invoice_amount, onchain_amount, service_fee) = match swap_type {
+            SwapType::Submarine => {
+                let percentage = fee_percentage(self.fees.percentage_swap_in)?;
+                match amount {
+                    QuoteAmount::Invoice(invoice_amount) => {
+                        let service_fee = percentage_fee(invoice_amount, percentage);
+                        let onchain_amount = match invoice_amount
+                            .checked_add(service_fee)
+                            .and_then(|total| total.checked_add(boltz_claim_fee))
+                        {
+                            Some(result) => result,
+                            None => return Err(amount_too_large(invoice_amount)),
+                        };
+                        (invoice_amount, onchain_amount, service_fee)
+                    }
+                    QuoteAmount::Onchain(onchain_amount) => {
+                        // largest invoice whose expected amount still fits in onchain_amount
+                        let budget = match onchain_amount.checked_sub(boltz_claim_fee) {
+                            Some(result) => result,
+                            None => return Err(amount_too_small(onchain_amount)),
+                        };
+                        let fits = |invoice_amount: u64| {
+                            invoice_amount
+                                .checked_add(percentage_fee(invoice_amount, percentage))
+                                .is_some_and(|total| total <= budget)
+                        };
+                        let mut invoice_amount =
+                            (budget as f64 / (1.0 + percentage / 100.0)).floor() as u64;
+                        while invoice_amount > 0 && !fits(invoice_amount) {
+                            invoice_amount -= 1;
+                        }
+                        while invoice_amount < budget && fits(invoice_amount + 1) {
+                            invoice_amount += 1;
+                        }
+                        let service_fee = percentage_fee(invoice_amount, percentage);
+                        (
+                            invoice_amount,
+                            invoice_amount + service_fee + boltz_claim_fee,
+                            service_fee,
+                        )
+                    }
+                }
+            }
+            SwapType::ReverseSubmarine => {
+                let percentage = fee_percentage(self.fees.percentage)?;
+                match amount {
+                    QuoteAmount::Invoice(invoice_amount) => {
+                        let service_fee = percentage_fee(invoice_amount, percentage);
+                        let onchain_amount = match service_fee
+                            .checked_add(boltz_lockup_fee)
+                            .and_then(|fees| invoice_amount.checked_sub(fees))
+                        {
+                            Some(result) => result,
+                            None => return Err(amount_too_small(invoice_amount)),
+                        };
+                        (invoice_amount, onchain_amount, service_fee)
+                    }
+                    QuoteAmount::Onchain(onchain_amount) => {
+                        // Boltz locks exactly onchain_amount and rounds the invoice up
+                        let locked = match onchain_amount.checked_add(boltz_lockup_fee) {
+                            Some(result) => result,
+                            None => return Err(amount_too_large(onchain_amount)),
+                        };
+                        let invoice_amount = ceil_sats(locked as f64 / (1.0 - percentage / 100.0));
+                        // the float division saturates for amounts close to u64::MAX
+                        let service_fee = match invoice_amount.checked_sub(locked) {
+                            Some(result) => result,
+                            None => return Err(amount_too_large(onchain_amount)),
+                        };
+                        (invoice_amount, onchain_amount, service_fee)
+                    }
+                }
+            }
+            SwapType::Chain => {
+                return Err(S5Error::new(
+                    ErrorKind::Input,
+                    "Chain swaps are quoted from the v2 chain pairs.",
+                ))
+            }
+        };
+        Ok(SwapQuote {
+            invoice_amount,
+            onchain_amount,
+            service_fee,
+            boltz_claim_fee,
+            boltz_lockup_fee,
+            claim_fee_estimate,
+            above_minimal: invoice_amount as i128 >= self.limits.minimal as i128,
+            below_maximal: invoice_amount as i128 <= self.limits.maximal as i128,
+            within_zero_conf: onchain_amount as i128
+                <= self.limits.maximal_zero_conf.base_asset as i128,
+        })
+    }
+}
+
+/// Boltz rounds percentage fees up.
+fn percentage_fee(amount: u64, percentage: f64) -> u64 {
+    ceil_sats(amount as f64 * percentage / 100.0)
+}
+
+/// Rounds up, without letting float noise (e.g. 100.00000000000001) add a satoshi.
+fn ceil_sats(value: f64) -> u64 {
+    (value - 1e-9).ceil().max(0.0) as u64
+}
+
+/// Miner fees come from the server, a negative one would wrap around.
+fn miner_fee(fee: i64) -> Result<u64, S5Error> {
+    match u64::try_from(fee) {
+        Ok(result) => Ok(result),
+        Err(_) => Err(S5Error::new(
+            ErrorKind::Input,
+            &format!("Invalid miner fee {} in pair.", fee),
+        )),
+    }
+}
+
+/// A percentage fee of 100 or more would take the whole amount.
+fn fee_percentage(percentage: f64) -> Result<f64, S5Error> {
+    if (0.0..100.0).contains(&percentage) {
+        Ok(percentage)
+    } else {
+        Err(S5Error::new(
+            ErrorKind::Input,
+            &format!("Invalid fee percentage {} in pair.", percentage),
+        ))
+    }
+}
+
+fn amount_too_large(amount: u64) -> S5Error {
+    S5Error::new(
+        ErrorKind::Input,
+        &format!("Amount {} is too large to quote.", amount),
+    )
+}
+
+fn amount_too_small(amount: u64) -> S5Error {
+    S5Error::new(
+        ErrorKind::Input,
+        &format!("Amount {} does not cover the swap fees.", amount),
+    )
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 #[serde(rename_all = "snake_case")]
 pub enum SubSwapStates {
     Created,
@@ -241,7 +642,7 @@ impl FromStr for SubSwapStates {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 #[serde(rename_all = "snake_case")]
 pub enum RevSwapStates {
     Created,
@@ -290,11 +691,52 @@ impl FromStr for RevSwapStates {
     }
 }
 
+/// A swap status parsed for its swap type.
+/// Statuses this client does not know, including every chain swap status, are kept as `Unknown`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SwapState {
+    Submarine(SubSwapStates),
+    Reverse(RevSwapStates),
+    Unknown(String),
+}
+
+impl SwapState {
+    pub fn from_status(swap_type: &SwapType, status: &str) -> SwapState {
+        let state = match swap_type {
+            SwapType::Submarine => SubSwapStates::from_str(status).map(SwapState::Submarine),
+            SwapType::ReverseSubmarine => RevSwapStates::from_str(status).map(SwapState::Reverse),
+            SwapType::Chain => Err(()),
+        };
+        state.unwrap_or_else(|()| SwapState::Unknown(status.to_string()))
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 #[serde(rename_all = "lowercase")]
 pub enum SwapType {
     Submarine,
     ReverseSubmarine,
+    Chain,
+}
+
+/// The two lockups of a chain swap.
+/// Lockup: we lock on the source chain and Boltz claims. Claim: Boltz locks on the destination chain
+/// and we claim.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ChainSwapSide {
+    Lockup,
+    Claim,
+}
+
+/// Lockup script template.
+/// Legacy swaps use a single HTLC script (p2shwsh/p2wsh); taproot swaps use a swap tree with
+/// separate claim and refund leaves under a MuSig2 internal key.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum SwapScriptVersion {
+    Legacy,
+    Taproot,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -488,18 +930,15 @@ impl CreateSwapResponse {
     pub fn validate_script_preimage160(&self, preimage_hash160: hash160::Hash) -> bool {
         match &self.redeem_script {
             Some(rs) => {
-                let script_elements = match BtcSwapScript::submarine_from_str(
-                    BitcoinNetwork::Bitcoin,
-                    DEFAULT_MAINNET_NODE.to_string(),
-                    &rs,
-                ) {
-                    // network doesnt matter here, we just want the hashlock extracted
-                    Ok(se) => se,
-                    Err(e) => {
-                        println!("Error parsing sub script elements:{:?}", e);
-                        return false;
-                    }
-                };
+                let script_elements =
+                    match BtcSwapScript::submarine_from_str(BitcoinNetwork::Bitcoin, &rs) {
+                        // network doesnt matter here, we just want the hashlock extracted
+                        Ok(se) => se,
+                        Err(e) => {
+                            println!("Error parsing sub script elements:{:?}", e);
+                            return false;
+                        }
+                    };
                 // println!("{}-m----m-{}", script_elements.hashlock, preimage_hash160);
                 if &script_elements.hashlock == &preimage_hash160.to_string() {
                     true
@@ -527,15 +966,37 @@ pub struct SwapStatusRequest {
 #[serde(rename_all = "camelCase")]
 pub struct SwapStatusResponse {
     pub status: String,
-    zero_conf_rejected: Option<bool>,
+    pub zero_conf_rejected: Option<bool>,
+    /// Lockup transaction, once Boltz has seen it.
+    pub transaction: Option<SwapStatusTransaction>,
+    pub failure_reason: Option<String>,
+}
+
+impl SwapStatusResponse {
+    /// `status` parsed for the swap type it was requested for.
+    pub fn state(&self, swap_type: &SwapType) -> SwapState {
+        SwapState::from_status(swap_type, &self.status)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct GetFeeEstimationResponse {
     #[serde(rename = "BTC")]
-    btc: f64,
+    pub btc: f64,
     #[serde(rename = "L-BTC")]
-    lbtc: f64,
+    pub lbtc: f64,
+}
+
+impl GetFeeEstimationResponse {
+    /// Boltz's fee rate for the chain of `network`, in sats per vbyte.
+    pub fn fee_rate(&self, network: &BitcoinNetwork) -> Fee {
+        match network {
+            BitcoinNetwork::Bitcoin | BitcoinNetwork::BitcoinTestnet => Fee::Relative(self.btc),
+            BitcoinNetwork::Liquid
+            | BitcoinNetwork::LiquidTestnet
+            | BitcoinNetwork::ElementsRegtest => Fee::Relative(self.lbtc),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -700,4 +1161,350 @@ mod tests {
         let response = client.swap_status(request);
         assert!(response.is_ok());
     }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn test_async_client() {
+        use crate::network::mock::mock_http_server;
+
+        let url = mock_http_server(|method, path, body| match (method, path) {
+            ("GET", "/getfeeestimation") => (200, br#"{"BTC":2,"L-BTC":0.11}"#.to_vec()),
+            ("POST", "/swapstatus") => {
+                let request: serde_json::Value = serde_json::from_slice(body).unwrap();
+                if request["id"] == "Nh7Y1J" {
+                    (200, br#"{"status":"invoice.set"}"#.to_vec())
+                } else {
+                    (
+                        404,
+                        br#"{"error":"could not find swap with id: x"}"#.to_vec(),
+                    )
+                }
+            }
+            _ => (404, b"".to_vec()),
+        });
+        let client = AsyncBoltzApiClient::new(&url);
+
+        let fees = client.get_fee_estimation().await.unwrap();
+        assert_eq!(fees.btc, 2.0);
+        assert_eq!(fees.lbtc, 0.11);
+
+        let request = SwapStatusRequest {
+            id: "Nh7Y1J".to_string(),
+        };
+        let response = client.swap_status(request).await.unwrap();
+        assert_eq!(response.status, "invoice.set");
+
+        let request = SwapStatusRequest {
+            id: "x".to_string(),
+        };
+        let error = client.swap_status(request).await.unwrap_err();
+        assert_eq!(
+            error,
+            BoltzApiError::Http {
+                status: 404,
+                message: "could not find swap with id: x".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_fee() {
+        // 561 wu round up to 141 vbytes
+        assert_eq!(Fee::Absolute(300).absolute(561), 300);
+        assert_eq!(Fee::Relative(2.0).absolute(561), 282);
+        assert_eq!(Fee::Relative(0.1).absolute(561), 15);
+
+        let estimation: GetFeeEstimationResponse =
+            serde_json::from_str(r#"{"BTC":12.5,"L-BTC":0.1}"#).unwrap();
+        assert_eq!(
+            estimation.fee_rate(&BitcoinNetwork::BitcoinTestnet),
+            Fee::Relative(12.5)
+        );
+        assert_eq!(
+            estimation.fee_rate(&BitcoinNetwork::Liquid),
+            Fee::Relative(0.1)
+        );
+
+        let backend = crate::network::mock::MockChainBackend::new(0, 3.0);
+        assert_eq!(Fee::from_backend(&backend, 2).unwrap(), Fee::Relative(3.0));
+    }
+
+    #[test]
+    fn test_utxo_report() {
+        let utxo = |vout, value| Utxo {
+            txid: bitcoin::Txid::from_str(
+                "10c31b81b93a69a635ab337bb48adeefe662d40d3e5d50b319a1fed17485104a",
+            )
+            .unwrap(),
+            vout,
+            value,
+            height: 0,
+        };
+        let report = UtxoReport {
+            expected_value: Some(50_000),
+            utxos: vec![utxo(0, 20_000), utxo(1, 50_000)],
+            skipped: vec![],
+        };
+        assert_eq!(report.total(), 70_000);
+        assert_eq!(report.difference(), Some(20_000));
+        assert_eq!(
+            report.to_string(),
+            "Found 2 utxos (20000, 50000) worth 70000, Expected 50000, Overpaid by 20000"
+        );
+        assert_eq!(
+            report.select(UtxoPolicy::Exact).unwrap(),
+            vec![utxo(1, 50_000)]
+        );
+        assert_eq!(report.select(UtxoPolicy::AtLeast).unwrap().len(), 2);
+        assert_eq!(report.select(UtxoPolicy::All).unwrap().len(), 2);
+
+        // paid twice, but short of the expected value
+        let report = UtxoReport {
+            expected_value: Some(50_000),
+            utxos: vec![utxo(0, 20_000), utxo(1, 25_000)],
+            skipped: vec![],
+        };
+        assert_eq!(report.difference(), Some(-5_000));
+        let error = report.select(UtxoPolicy::Exact).unwrap_err();
+        assert!(error.message.contains("Underpaid by 5000"));
+        assert!(report.select(UtxoPolicy::AtLeast).is_err());
+        assert_eq!(report.select(UtxoPolicy::All).unwrap().len(), 2);
+
+        let report = UtxoReport {
+            expected_value: None,
+            utxos: vec![utxo(0, 20_000)],
+            skipped: vec![SkippedUtxo {
+                utxo: utxo(1, 0),
+                reason: "foreign asset".to_string(),
+            }],
+        };
+        assert_eq!(
+            report.to_string(),
+            "Found 1 utxos (20000) worth 20000, Skipped 10c31b81b93a69a635ab337bb48adeefe662d40d3e5d50b319a1fed17485104a:1 (foreign asset)"
+        );
+        assert!(report.select(UtxoPolicy::Exact).is_err());
+        assert_eq!(report.select(UtxoPolicy::All).unwrap().len(), 1);
+
+        let report = UtxoReport {
+            expected_value: Some(50_000),
+            utxos: vec![],
+            skipped: vec![],
+        };
+        assert!(report.select(UtxoPolicy::All).is_err());
+    }
+
+    #[test]
+    fn test_swap_quote() {
+        let response: GetPairsResponse = serde_json::from_str(
+            r#"{"info":[],"warnings":[],"pairs":{"BTC/BTC":{"hash":"a1","rate":1,"limits":{"maximal":25000000,"minimal":50000,"maximalZeroConf":{"baseAsset":0,"quoteAsset":0}},"fees":{"percentage":0.5,"percentageSwapIn":0.1,"minerFees":{"baseAsset":{"normal":340,"reverse":{"claim":276,"lockup":306}},"quoteAsset":{"normal":340,"reverse":{"claim":276,"lockup":306}}}}},"L-BTC/BTC":{"hash":"b2","rate":1,"limits":{"maximal":25000000,"minimal":1000,"maximalZeroConf":{"baseAsset":100000,"quoteAsset":0}},"fees":{"percentage":0.25,"percentageSwapIn":0.1,"minerFees":{"baseAsset":{"normal":147,"reverse":{"claim":143,"lockup":276}},"quoteAsset":{"normal":340,"reverse":{"claim":276,"lockup":306}}}}}}}"#,
+        )
+        .unwrap();
+        let btc = response.pairs.pairs.get("BTC/BTC").unwrap();
+        let lbtc = response.pairs.pairs.get("L-BTC/BTC").unwrap();
+
+        let quote = btc
+            .quote(&SwapType::Submarine, QuoteAmount::Invoice(100_000))
+            .unwrap();
+        assert_eq!(
+            quote,
+            SwapQuote {
+                invoice_amount: 100_000,
+                onchain_amount: 100_440,
+                service_fee: 100,
+                boltz_claim_fee: 340,
+                boltz_lockup_fee: 0,
+                claim_fee_estimate: 0,
+                above_minimal: true,
+                below_maximal: true,
+                within_zero_conf: false,
+            }
+        );
+        let quote = btc
+            .quote(&SwapType::Submarine, QuoteAmount::Onchain(100_440))
+            .unwrap();
+        assert_eq!(quote.invoice_amount, 100_000);
+        let quote = btc
+            .quote(&SwapType::Submarine, QuoteAmount::Onchain(100_441))
+            .unwrap();
+        assert_eq!(quote.invoice_amount, 100_000);
+        assert_eq!(quote.onchain_amount, 100_440);
+
+        let quote = btc
+            .quote(&SwapType::ReverseSubmarine, QuoteAmount::Invoice(100_000))
+            .unwrap();
+        assert_eq!(quote.service_fee, 500);
+        assert_eq!(quote.boltz_lockup_fee, 306);
+        assert_eq!(quote.claim_fee_estimate, 276);
+        assert_eq!(quote.onchain_amount, 99_194);
+        let quote = btc
+            .quote(&SwapType::ReverseSubmarine, QuoteAmount::Onchain(99_194))
+            .unwrap();
+        assert_eq!(quote.invoice_amount, 100_000);
+        assert_eq!(quote.service_fee, 500);
+
+        let quote = lbtc
+            .quote(&SwapType::Submarine, QuoteAmount::Invoice(50_000))
+            .unwrap();
+        assert_eq!(quote.onchain_amount, 50_197);
+        assert!(quote.within_zero_conf);
+        assert!(quote.within_limits());
+
+        let quote = lbtc
+            .quote(&SwapType::ReverseSubmarine, QuoteAmount::Invoice(500))
+            .unwrap();
+        assert_eq!(quote.onchain_amount, 222);
+        assert!(!quote.above_minimal);
+        assert!(!quote.within_limits());
+        assert!(lbtc
+            .quote(&SwapType::ReverseSubmarine, QuoteAmount::Invoice(200))
+            .is_err());
+        assert!(lbtc
+            .quote(&SwapType::Submarine, QuoteAmount::Onchain(100))
+            .is_err());
+        assert!(btc
+            .quote(&SwapType::Chain, QuoteAmount::Invoice(100_000))
+            .is_err());
+
+        // amounts close to u64::MAX error instead of overflowing
+        assert!(btc
+            .quote(&SwapType::Submarine, QuoteAmount::Invoice(u64::MAX))
+            .is_err());
+        let quote = btc
+            .quote(&SwapType::Submarine, QuoteAmount::Onchain(u64::MAX))
+            .unwrap();
+        assert_eq!(quote.onchain_amount, u64::MAX);
+        assert_eq!(
+            btc.quote(&SwapType::ReverseSubmarine, QuoteAmount::Invoice(u64::MAX))
+                .unwrap()
+                .invoice_amount,
+            u64::MAX
+        );
+        assert_eq!(
+            btc.quote(&SwapType::ReverseSubmarine, QuoteAmount::Onchain(u64::MAX))
+                .unwrap_err()
+                .kind,
+            "Input"
+        );
+
+        // a dishonest server cannot make the fees wrap around
+        let copy = |pair: &Pair| -> Pair {
+            serde_json::from_str(&serde_json::to_string(pair).unwrap()).unwrap()
+        };
+        let mut greedy = copy(btc);
+        greedy.fees.percentage = 100.0;
+        greedy.fees.percentage_swap_in = 150.0;
+        for swap_type in [SwapType::Submarine, SwapType::ReverseSubmarine] {
+            let error = greedy
+                .quote(&swap_type, QuoteAmount::Onchain(100_000))
+                .unwrap_err();
+            assert!(error.message.contains("Invalid fee percentage"));
+        }
+        let mut negative = copy(btc);
+        negative.fees.miner_fees.base_asset.reverse.lockup = -1;
+        let error = negative
+            .quote(&SwapType::ReverseSubmarine, QuoteAmount::Invoice(100_000))
+            .unwrap_err();
+        assert!(error.message.contains("Invalid miner fee -1"));
+    }
+
+    #[test]
+    fn test_swap_status_response() {
+        let response: SwapStatusResponse = serde_json::from_str(
+            r#"{"status":"transaction.mempool","zeroConfRejected":true,"transaction":{"id":"a1b2","hex":"0200"}}"#,
+        )
+        .unwrap();
+        assert_eq!(response.zero_conf_rejected, Some(true));
+        assert_eq!(response.transaction.as_ref().unwrap().id, "a1b2");
+        assert_eq!(
+            response.state(&SwapType::Submarine),
+            SwapState::Submarine(SubSwapStates::TransactionMempool)
+        );
+        assert_eq!(
+            response.state(&SwapType::ReverseSubmarine),
+            SwapState::Reverse(RevSwapStates::TransactionMempool)
+        );
+
+        let response: SwapStatusResponse =
+            serde_json::from_str(r#"{"status":"invoice.failedToPay","failureReason":"no route"}"#)
+                .unwrap();
+        assert_eq!(response.failure_reason, Some("no route".to_string()));
+        assert!(response.transaction.is_none());
+        assert_eq!(
+            response.state(&SwapType::Submarine),
+            SwapState::Submarine(SubSwapStates::InvoiceFailedToPay)
+        );
+        // not a reverse swap status
+        assert_eq!(
+            response.state(&SwapType::ReverseSubmarine),
+            SwapState::Unknown("invoice.failedToPay".to_string())
+        );
+
+        let response: SwapStatusResponse =
+            serde_json::from_str(r#"{"status":"transaction.lockupFailed"}"#).unwrap();
+        assert_eq!(
+            response.state(&SwapType::Submarine),
+            SwapState::Unknown("transaction.lockupFailed".to_string())
+        );
+    }
+
+    #[test]
+    fn test_boltz_api_errors() {
+        use crate::network::mock::mock_http_server;
+
+        let url = mock_http_server(|_, path, _| match path {
+            "/getpairs" => (
+                200,
+                br#"{"info":[],"warnings":[],"pairs":"unexpected"}"#.to_vec(),
+            ),
+            "/getfeeestimation" => (503, b"<html>Service Unavailable</html>".to_vec()),
+            "/createswap" => (
+                409,
+                br#"{"error":"a swap with this invoice exists already"}"#.to_vec(),
+            ),
+            _ => (404, b"".to_vec()),
+        });
+        let client = BoltzApiClient::new(&url);
+
+        let error = client.get_pairs().unwrap_err();
+        match &error {
+            BoltzApiError::Deserialize { body, .. } => assert!(body.contains("unexpected")),
+            _ => panic!("expected a deserialize error, got {:?}", error),
+        }
+        assert_eq!(error.status(), None);
+
+        let error = client.get_fee_estimation().unwrap_err();
+        assert_eq!(
+            error,
+            BoltzApiError::Http {
+                status: 503,
+                message: "<html>Service Unavailable</html>".to_string()
+            }
+        );
+
+        let request = CreateSwapRequest::new_btc_submarine(
+            "pair_hash".to_string(),
+            "lntb1".to_string(),
+            "02".to_string(),
+        );
+        let error = client.create_swap(request).unwrap_err();
+        assert_eq!(error.status(), Some(409));
+        let s5error: S5Error = error.into();
+        assert_eq!(s5error.kind, ErrorKind::BoltzApi.to_string());
+        assert_eq!(
+            s5error.message,
+            "HTTP 409: a swap with this invoice exists already"
+        );
+
+        // nothing listening
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let client = BoltzApiClient::new(&format!("http://127.0.0.1:{}", port));
+        match client.get_pairs() {
+            Err(BoltzApiError::Transport(_)) => (),
+            other => panic!("expected a transport error, got {:?}", other),
+        }
+    }
 }
@@ -0,0 +1,565 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+
+use crate::swaps::boltz::{parse_response, SwapState, SwapType};
+use crate::util::error::BoltzApiError;
+
+pub const BOLTZ_TESTNET_URL_V2: &str = "https://api.testnet.boltz.exchange/v2";
+pub const BOLTZ_MAINNET_URL_V2: &str = "https://api.boltz.exchange/v2";
+
+/// Client for the Boltz v2 REST API.
+///
+/// Lives alongside the legacy BoltzApiClient so callers can migrate one swap type at a time.
+/// `base_url` includes the `/v2` prefix.
+pub struct BoltzApiClientV2 {
+    base_url: String,
+}
+
+impl BoltzApiClientV2 {
+    pub fn new(base_url: &str) -> Self {
+        BoltzApiClientV2 {
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    fn get<T: for<'de> Deserialize<'de>>(&self, path: &str) -> Result<T, BoltzApiError> {
+        let url = format!("{}/{}", self.base_url, path);
+        let res = match Client::new().get(&url).send() {
+            Ok(result) => result,
+            Err(e) => return Err(BoltzApiError::Transport(e.to_string())),
+        };
+        let status = res.status().as_u16();
+        match res.text() {
+            Ok(body) => parse_response(status, body),
+            Err(e) => Err(BoltzApiError::Transport(e.to_string())),
+        }
+    }
+
+    fn post<T: for<'de> Deserialize<'de>>(
+        &self,
+        path: &str,
+        request: &impl Serialize,
+    ) -> Result<T, BoltzApiError> {
+        let url = format!("{}/{}", self.base_url, path);
+        let res = match Client::new().post(&url).json(request).send() {
+            Ok(result) => result,
+            Err(e) => return Err(BoltzApiError::Transport(e.to_string())),
+        };
+        let status = res.status().as_u16();
+        match res.text() {
+            Ok(body) => parse_response(status, body),
+            Err(e) => Err(BoltzApiError::Transport(e.to_string())),
+        }
+    }
+
+    pub fn get_submarine_pairs(&self) -> Result<SubmarinePairs, BoltzApiError> {
+        self.get("swap/submarine")
+    }
+
+    pub fn create_submarine_swap(
+        &self,
+        request: &CreateSubmarineRequest,
+    ) -> Result<CreateSubmarineResponse, BoltzApiError> {
+        self.post("swap/submarine", request)
+    }
+
+    pub fn get_reverse_pairs(&self) -> Result<ReversePairs, BoltzApiError> {
+        self.get("swap/reverse")
+    }
+
+    pub fn create_reverse_swap(
+        &self,
+        request: &CreateReverseRequest,
+    ) -> Result<CreateReverseResponse, BoltzApiError> {
+        self.post("swap/reverse", request)
+    }
+
+    pub fn get_chain_pairs(&self) -> Result<ChainPairs, BoltzApiError> {
+        self.get("swap/chain")
+    }
+
+    pub fn create_chain_swap(
+        &self,
+        request: &CreateChainRequest,
+    ) -> Result<CreateChainResponse, BoltzApiError> {
+        self.post("swap/chain", request)
+    }
+
+    /// Asks Boltz to co-sign a cooperative key-path claim of a reverse swap lockup.
+    pub fn get_reverse_partial_sig(
+        &self,
+        id: &str,
+        request: &PartialSigRequest,
+    ) -> Result<PartialSigResponse, BoltzApiError> {
+        self.post(&format!("swap/reverse/{}/claim", id), request)
+    }
+
+    /// Asks Boltz to co-sign a cooperative key-path refund of a failed submarine swap.
+    pub fn get_submarine_partial_sig(
+        &self,
+        id: &str,
+        request: &PartialSigRequest,
+    ) -> Result<PartialSigResponse, BoltzApiError> {
+        self.post(&format!("swap/submarine/{}/refund", id), request)
+    }
+
+    /// Asks Boltz to co-sign a cooperative key-path refund of a chain swap lockup.
+    pub fn get_chain_partial_sig(
+        &self,
+        id: &str,
+        request: &PartialSigRequest,
+    ) -> Result<PartialSigResponse, BoltzApiError> {
+        self.post(&format!("swap/chain/{}/refund", id), request)
+    }
+
+    /// Status of any swap type.
+    pub fn get_swap_status(&self, id: &str) -> Result<SwapStatusResponseV2, BoltzApiError> {
+        self.get(&format!("swap/{}", id))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Currency {
+    #[serde(rename = "BTC")]
+    Btc,
+    #[serde(rename = "L-BTC")]
+    LBtc,
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Currency::Btc => write!(f, "BTC"),
+            Currency::LBtc => write!(f, "L-BTC"),
+        }
+    }
+}
+
+/// Pairs are keyed by the currency sent, then by the currency received.
+pub type PairMap<T> = HashMap<String, HashMap<String, T>>;
+
+fn find_pair<T>(pairs: &PairMap<T>, from: Currency, to: Currency) -> Option<&T> {
+    pairs.get(&from.to_string())?.get(&to.to_string())
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PairLimits {
+    pub minimal: u64,
+    pub maximal: u64,
+    pub maximal_zero_conf: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SubmarineFees {
+    pub percentage: f64,
+    pub miner_fees: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SubmarinePair {
+    pub hash: String,
+    pub rate: f64,
+    pub limits: PairLimits,
+    pub fees: SubmarineFees,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SubmarinePairs(pub PairMap<SubmarinePair>);
+
+impl SubmarinePairs {
+    pub fn get(&self, from: Currency, to: Currency) -> Option<&SubmarinePair> {
+        find_pair(&self.0, from, to)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReverseMinerFees {
+    pub lockup: u64,
+    pub claim: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ReverseFees {
+    pub percentage: f64,
+    pub miner_fees: ReverseMinerFees,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ReversePair {
+    pub hash: String,
+    pub rate: f64,
+    pub limits: PairLimits,
+    pub fees: ReverseFees,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReversePairs(pub PairMap<ReversePair>);
+
+impl ReversePairs {
+    pub fn get(&self, from: Currency, to: Currency) -> Option<&ReversePair> {
+        find_pair(&self.0, from, to)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChainUserMinerFees {
+    pub claim: u64,
+    pub lockup: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChainMinerFees {
+    pub server: u64,
+    pub user: ChainUserMinerFees,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainFees {
+    pub percentage: f64,
+    pub miner_fees: ChainMinerFees,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainPair {
+    pub hash: String,
+    pub rate: f64,
+    pub limits: PairLimits,
+    pub fees: ChainFees,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChainPairs(pub PairMap<ChainPair>);
+
+impl ChainPairs {
+    pub fn get(&self, from: Currency, to: Currency) -> Option<&ChainPair> {
+        find_pair(&self.0, from, to)
+    }
+}
+
+/// A tapscript leaf as returned by Boltz: leaf version and hex encoded script.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Leaf {
+    pub version: u8,
+    pub output: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SwapTree {
+    pub claim_leaf: Leaf,
+    pub refund_leaf: Leaf,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateSubmarineRequest {
+    pub from: Currency,
+    pub to: Currency,
+    pub invoice: String,
+    pub refund_public_key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pair_hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub referral_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateSubmarineResponse {
+    pub id: String,
+    pub address: String,
+    pub bip21: Option<String>,
+    pub swap_tree: SwapTree,
+    pub claim_public_key: String,
+    pub timeout_block_height: u32,
+    pub accept_zero_conf: bool,
+    pub expected_amount: u64,
+    /// Liquid only
+    pub blinding_key: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateReverseRequest {
+    pub from: Currency,
+    pub to: Currency,
+    pub preimage_hash: String,
+    pub claim_public_key: String,
+    /// Set either the invoice amount or the onchain amount.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub invoice_amount: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub onchain_amount: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pair_hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub referral_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address_signature: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateReverseResponse {
+    pub id: String,
+    pub invoice: String,
+    pub swap_tree: SwapTree,
+    pub lockup_address: String,
+    pub refund_public_key: String,
+    pub timeout_block_height: u32,
+    pub onchain_amount: u64,
+    /// Liquid only
+    pub blinding_key: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateChainRequest {
+    pub from: Currency,
+    pub to: Currency,
+    pub preimage_hash: String,
+    pub claim_public_key: String,
+    pub refund_public_key: String,
+    /// Set either the amount we lock or the amount Boltz locks.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_lock_amount: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server_lock_amount: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pair_hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub referral_id: Option<String>,
+}
+
+/// One side of a chain swap.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainSwapDetails {
+    pub swap_tree: SwapTree,
+    pub lockup_address: String,
+    pub server_public_key: String,
+    pub timeout_block_height: u32,
+    pub amount: u64,
+    /// Liquid only
+    pub blinding_key: Option<String>,
+    pub bip21: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateChainResponse {
+    pub id: String,
+    /// Boltz locks on the destination chain; we claim.
+    pub claim_details: ChainSwapDetails,
+    /// We lock on the source chain; Boltz claims.
+    pub lockup_details: ChainSwapDetails,
+}
+
+/// Our MuSig2 public nonce and the transaction to co-sign. The preimage is only sent for claims.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PartialSigRequest {
+    pub index: usize,
+    pub transaction: String,
+    pub pub_nonce: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preimage: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PartialSigResponse {
+    pub pub_nonce: String,
+    pub partial_signature: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SwapStatusTransaction {
+    pub id: String,
+    pub hex: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SwapStatusResponseV2 {
+    pub status: String,
+    pub zero_conf_rejected: Option<bool>,
+    pub transaction: Option<SwapStatusTransaction>,
+    pub failure_reason: Option<String>,
+}
+
+impl SwapStatusResponseV2 {
+    /// `status` parsed for the swap type it was requested for.
+    pub fn state(&self, swap_type: &SwapType) -> SwapState {
+        SwapState::from_status(swap_type, &self.status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::network::mock::mock_http_server;
+
+    const SUBMARINE_PAIRS: &str = r#"{"BTC":{"BTC":{"hash":"4f5ba4bbed0ba4cb1a4e1e4e1c0a1a5d","rate":1,"limits":{"maximal":25000000,"minimal":1000,"maximalZeroConf":100000},"fees":{"percentage":0.1,"minerFees":1500}}},"L-BTC":{"BTC":{"hash":"6e1a2c8c4e0f2a4d","rate":1,"limits":{"maximal":25000000,"minimal":1000,"maximalZeroConf":100000},"fees":{"percentage":0.1,"minerFees":150}}}}"#;
+    const REVERSE_PAIRS: &str = r#"{"BTC":{"BTC":{"hash":"a1b2","rate":1,"limits":{"maximal":25000000,"minimal":10000},"fees":{"percentage":0.5,"minerFees":{"lockup":462,"claim":333}}},"L-BTC":{"hash":"c3d4","rate":1,"limits":{"maximal":25000000,"minimal":1000},"fees":{"percentage":0.25,"minerFees":{"lockup":27,"claim":20}}}}}"#;
+    const CHAIN_PAIRS: &str = r#"{"BTC":{"L-BTC":{"hash":"e5f6","rate":1,"limits":{"maximal":10000000,"minimal":25000,"maximalZeroConf":0},"fees":{"percentage":0.1,"minerFees":{"server":489,"user":{"claim":20,"lockup":462}}}}}}"#;
+    const SUBMARINE_SWAP: &str = r#"{"id":"Fy2Yr7","bip21":"bitcoin:bcrt1p...","address":"bcrt1pqg2g","swapTree":{"claimLeaf":{"version":192,"output":"a914b8e0ba7e0b6bbf5e9c7cf1bd71dbd4c7cb2f2c2f8820f8a3e9b1a4e7de0a56fd1f9f0e46bb8c6f6f69c0c8bf82b9c1d1ee7f1f4e4d0aac"},"refundLeaf":{"version":192,"output":"20f30e2e5bbf0b8e4f5a10ab9b6d8e4ba1f8c2b3e6c9d7f6c6c3e9a8b1c5d4e3f2ad03e3f20ab1"}},"claimPublicKey":"0336d5b0c2e1b5a4f2f6e4d1c3b9a8f7e6d5c4b3a29181706f5e4d3c2b1a0f9e8d","timeoutBlockHeight":1061,"acceptZeroConf":true,"expectedAmount":10147}"#;
+    const REVERSE_SWAP: &str = r#"{"id":"Wx1Abc","invoice":"lnbcrt101470n1pj","swapTree":{"claimLeaf":{"version":192,"output":"82012088a914"},"refundLeaf":{"version":192,"output":"20f3ad03b1"}},"lockupAddress":"el1pqt","refundPublicKey":"02c4","timeoutBlockHeight":1120,"onchainAmount":9857,"blindingKey":"8e5bd3f0"}"#;
+    const CHAIN_SWAP: &str = r#"{"id":"Ch4in1","claimDetails":{"swapTree":{"claimLeaf":{"version":196,"output":"82012088a914"},"refundLeaf":{"version":196,"output":"20aaad03b1"}},"lockupAddress":"el1pq","serverPublicKey":"03aa","timeoutBlockHeight":2400,"amount":99000,"blindingKey":"0b"},"lockupDetails":{"swapTree":{"claimLeaf":{"version":192,"output":"82012088a914"},"refundLeaf":{"version":192,"output":"20bbad03b1"}},"lockupAddress":"bcrt1pq","serverPublicKey":"02bb","timeoutBlockHeight":1300,"amount":100000,"bip21":"bitcoin:bcrt1pq?amount=0.001"}}"#;
+
+    #[test]
+    fn test_v2_fixtures() {
+        let pairs: SubmarinePairs = serde_json::from_str(SUBMARINE_PAIRS).unwrap();
+        let pair = pairs.get(Currency::LBtc, Currency::Btc).unwrap();
+        assert_eq!(pair.fees.miner_fees, 150);
+        assert_eq!(pair.limits.maximal_zero_conf, Some(100000));
+        assert!(pairs.get(Currency::Btc, Currency::LBtc).is_none());
+
+        let pairs: ReversePairs = serde_json::from_str(REVERSE_PAIRS).unwrap();
+        let pair = pairs.get(Currency::Btc, Currency::LBtc).unwrap();
+        assert_eq!(pair.hash, "c3d4");
+        assert_eq!(pair.fees.miner_fees.claim, 20);
+
+        let pairs: ChainPairs = serde_json::from_str(CHAIN_PAIRS).unwrap();
+        let pair = pairs.get(Currency::Btc, Currency::LBtc).unwrap();
+        assert_eq!(pair.fees.miner_fees.server, 489);
+        assert_eq!(pair.fees.miner_fees.user.lockup, 462);
+
+        let swap: CreateSubmarineResponse = serde_json::from_str(SUBMARINE_SWAP).unwrap();
+        assert_eq!(swap.swap_tree.claim_leaf.version, 0xc0);
+        assert_eq!(swap.expected_amount, 10147);
+        assert!(swap.blinding_key.is_none());
+
+        let swap: CreateReverseResponse = serde_json::from_str(REVERSE_SWAP).unwrap();
+        assert_eq!(swap.onchain_amount, 9857);
+        assert_eq!(swap.blinding_key, Some("8e5bd3f0".to_string()));
+
+        let swap: CreateChainResponse = serde_json::from_str(CHAIN_SWAP).unwrap();
+        assert_eq!(swap.claim_details.swap_tree.claim_leaf.version, 0xc4);
+        assert_eq!(swap.lockup_details.amount, 100000);
+    }
+
+    #[test]
+    fn test_v2_client() {
+        let requests: Arc<Mutex<Vec<(String, String, serde_json::Value)>>> =
+            Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        let url = mock_http_server(move |method, path, body| {
+            let body = serde_json::from_slice(body).unwrap_or(serde_json::Value::Null);
+            recorded
+                .lock()
+                .unwrap()
+                .push((method.to_string(), path.to_string(), body));
+            match (method, path) {
+                ("GET", "/v2/swap/submarine") => (200, SUBMARINE_PAIRS.as_bytes().to_vec()),
+                ("POST", "/v2/swap/submarine") => (201, SUBMARINE_SWAP.as_bytes().to_vec()),
+                ("GET", "/v2/swap/reverse") => (200, REVERSE_PAIRS.as_bytes().to_vec()),
+                ("POST", "/v2/swap/reverse") => (201, REVERSE_SWAP.as_bytes().to_vec()),
+                ("GET", "/v2/swap/chain") => (200, CHAIN_PAIRS.as_bytes().to_vec()),
+                ("POST", "/v2/swap/chain") => (201, CHAIN_SWAP.as_bytes().to_vec()),
+                ("GET", "/v2/swap/Wx1Abc") => (
+                    200,
+                    br#"{"status":"transaction.mempool","transaction":{"id":"aa","hex":"02"}}"#
+                        .to_vec(),
+                ),
+                _ => (
+                    404,
+                    br#"{"error":"could not find swap with id: x"}"#.to_vec(),
+                ),
+            }
+        });
+        let client = BoltzApiClientV2::new(&format!("{}/v2/", url));
+
+        let pairs = client.get_submarine_pairs().unwrap();
+        let request = CreateSubmarineRequest {
+            from: Currency::Btc,
+            to: Currency::Btc,
+            invoice: "lnbcrt101470n1pj".to_string(),
+            refund_public_key: "02c4".to_string(),
+            pair_hash: Some(
+                pairs
+                    .get(Currency::Btc, Currency::Btc)
+                    .unwrap()
+                    .hash
+                    .clone(),
+            ),
+            referral_id: None,
+        };
+        assert_eq!(client.create_submarine_swap(&request).unwrap().id, "Fy2Yr7");
+
+        client.get_reverse_pairs().unwrap();
+        let request = CreateReverseRequest {
+            from: Currency::Btc,
+            to: Currency::LBtc,
+            preimage_hash: "00".repeat(32),
+            claim_public_key: "02c4".to_string(),
+            invoice_amount: Some(10000),
+            onchain_amount: None,
+            pair_hash: None,
+            referral_id: None,
+            address: None,
+            address_signature: None,
+        };
+        assert_eq!(client.create_reverse_swap(&request).unwrap().id, "Wx1Abc");
+
+        client.get_chain_pairs().unwrap();
+        let request = CreateChainRequest {
+            from: Currency::Btc,
+            to: Currency::LBtc,
+            preimage_hash: "00".repeat(32),
+            claim_public_key: "02c4".to_string(),
+            refund_public_key: "03d5".to_string(),
+            user_lock_amount: Some(100000),
+            server_lock_amount: None,
+            pair_hash: None,
+            referral_id: None,
+        };
+        assert_eq!(client.create_chain_swap(&request).unwrap().id, "Ch4in1");
+
+        let status = client.get_swap_status("Wx1Abc").unwrap();
+        assert_eq!(status.status, "transaction.mempool");
+        assert_eq!(
+            status.state(&SwapType::ReverseSubmarine),
+            SwapState::Reverse(crate::swaps::boltz::RevSwapStates::TransactionMempool)
+        );
+        assert_eq!(status.transaction.unwrap().hex, Some("02".to_string()));
+        assert_eq!(client.get_swap_status("x").unwrap_err().status(), Some(404));
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(
+            requests[1].2,
+            serde_json::json!({
+                "from": "BTC",
+                "to": "BTC",
+                "invoice": "lnbcrt101470n1pj",
+                "refundPublicKey": "02c4",
+                "pairHash": "4f5ba4bbed0ba4cb1a4e1e4e1c0a1a5d"
+            })
+        );
+        assert_eq!(
+            requests[3].2,
+            serde_json::json!({
+                "from": "BTC",
+                "to": "L-BTC",
+                "preimageHash": "00".repeat(32),
+                "claimPublicKey": "02c4",
+                "invoiceAmount": 10000
+            })
+        );
+        assert_eq!(requests[5].2["userLockAmount"], 100000);
+    }
+}
@@ -0,0 +1,415 @@
+use std::collections::{HashMap, VecDeque};
+use std::net::TcpStream;
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tungstenite::{stream::MaybeTlsStream, Message, WebSocket};
+
+use crate::swaps::boltz::{SwapState, SwapType};
+use crate::util::error::{ErrorKind, S5Error};
+
+pub const BOLTZ_TESTNET_WS_URL: &str = "wss://api.testnet.boltz.exchange/v2/ws";
+pub const BOLTZ_MAINNET_WS_URL: &str = "wss://api.boltz.exchange/v2/ws";
+
+const SWAP_UPDATE_CHANNEL: &str = "swap.update";
+const DEFAULT_MAX_RECONNECTS: u32 = 5;
+const DEFAULT_RECONNECT_DELAY: Duration = Duration::from_secs(2);
+const DEFAULT_PING_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwapUpdate {
+    pub id: String,
+    pub state: SwapState,
+}
+
+#[derive(Serialize)]
+struct WsRequest<'a> {
+    op: &'a str,
+    channel: &'a str,
+    args: Vec<&'a str>,
+}
+
+#[derive(Deserialize)]
+struct WsEvent {
+    event: String,
+    #[serde(default)]
+    args: serde_json::Value,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct WsSwapStatus {
+    id: String,
+    status: String,
+}
+
+/// Client for Boltz's WebSocket status stream.
+///
+/// One connection carries the status updates of every subscribed swap. If the connection drops,
+/// `next_update` reconnects and resubscribes; Boltz answers a subscription with the current status,
+/// so updates missed while disconnected are not lost.
+/// A connection that stays silent for a ping interval is pinged, and one that does not answer
+/// within another interval is treated as dropped, so a dead server cannot block forever.
+pub struct BoltzWsClient {
+    url: String,
+    socket: Option<WebSocket<MaybeTlsStream<TcpStream>>>,
+    swaps: HashMap<String, SwapType>,
+    pending: VecDeque<SwapUpdate>,
+    max_reconnects: u32,
+    reconnect_delay: Duration,
+    ping_interval: Duration,
+}
+
+impl BoltzWsClient {
+    /// The connection is opened on the first subscription.
+    pub fn new(url: &str) -> Self {
+        BoltzWsClient {
+            url: url.to_string(),
+            socket: None,
+            swaps: HashMap::new(),
+            pending: VecDeque::new(),
+            max_reconnects: DEFAULT_MAX_RECONNECTS,
+            reconnect_delay: DEFAULT_RECONNECT_DELAY,
+            ping_interval: DEFAULT_PING_INTERVAL,
+        }
+    }
+
+    /// How often to retry a dropped connection, and how long to wait before each attempt.
+    pub fn set_reconnect_policy(&mut self, max_reconnects: u32, reconnect_delay: Duration) {
+        self.max_reconnects = max_reconnects;
+        self.reconnect_delay = reconnect_delay;
+    }
+
+    /// How long the connection may stay silent before it is pinged. Applies from the next
+    /// connection on.
+    pub fn set_ping_interval(&mut self, ping_interval: Duration) {
+        self.ping_interval = ping_interval;
+    }
+
+    /// Subscribes to the status of submarine and reverse swaps, given as (id, swap type).
+    pub fn subscribe(&mut self, swaps: &[(String, SwapType)]) -> Result<(), S5Error> {
+        for (id, swap_type) in swaps {
+            if *swap_type == SwapType::Chain {
+                return Err(S5Error::new(
+                    ErrorKind::Input,
+                    &format!("Chain swap {} has no typed status updates.", id),
+                ));
+            }
+        }
+        for (id, swap_type) in swaps {
+            self.swaps.insert(id.clone(), swap_type.clone());
+        }
+        if self.socket.is_none() {
+            // subscribes to everything, including the new swaps
+            return self.connect();
+        }
+        let ids: Vec<&str> = swaps.iter().map(|(id, _)| id.as_str()).collect();
+        self.send("subscribe", ids)
+    }
+
+    pub fn unsubscribe(&mut self, ids: &[String]) -> Result<(), S5Error> {
+        for id in ids {
+            self.swaps.remove(id);
+        }
+        if self.socket.is_none() {
+            return Ok(());
+        }
+        self.send("unsubscribe", ids.iter().map(|id| id.as_str()).collect())
+    }
+
+    /// Blocks until the next status update of a subscribed swap.
+    pub fn next_update(&mut self) -> Result<SwapUpdate, S5Error> {
+        let mut reconnects = 0;
+        let mut awaiting_pong = false;
+        loop {
+            if let Some(update) = self.pending.pop_front() {
+                return Ok(update);
+            }
+            if self.swaps.is_empty() {
+                return Err(S5Error::new(ErrorKind::Input, "No swaps subscribed."));
+            }
+            let message = match self.socket.as_mut() {
+                Some(socket) => socket.read(),
+                None => Err(tungstenite::Error::ConnectionClosed),
+            };
+            match message {
+                Ok(Message::Text(text)) => {
+                    reconnects = 0;
+                    awaiting_pong = false;
+                    self.handle_message(&text)?;
+                }
+                // pongs and any other frame show that the server is still there
+                Ok(_) => awaiting_pong = false,
+                Err(tungstenite::Error::Io(e)) if is_timeout(&e) && !awaiting_pong => {
+                    awaiting_pong = true;
+                    if let Some(socket) = self.socket.as_mut() {
+                        if socket.send(Message::Ping(vec![])).is_err() {
+                            self.socket = None;
+                        }
+                    }
+                }
+                Err(e) => {
+                    awaiting_pong = false;
+                    self.socket = None;
+                    if reconnects >= self.max_reconnects {
+                        return Err(S5Error::new(ErrorKind::Network, &e.to_string()));
+                    }
+                    reconnects += 1;
+                    thread::sleep(self.reconnect_delay);
+                    // a failed attempt leaves the socket empty and is retried on the next pass
+                    let _ = self.connect();
+                }
+            }
+        }
+    }
+
+    fn connect(&mut self) -> Result<(), S5Error> {
+        let (socket, _) = match tungstenite::connect(&self.url) {
+            Ok(result) => result,
+            Err(e) => return Err(S5Error::new(ErrorKind::Network, &e.to_string())),
+        };
+        let timeout = Some(self.ping_interval);
+        let result = match socket.get_ref() {
+            MaybeTlsStream::Plain(stream) => stream.set_read_timeout(timeout),
+            MaybeTlsStream::NativeTls(stream) => stream.get_ref().set_read_timeout(timeout),
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            return Err(S5Error::new(ErrorKind::Network, &e.to_string()));
+        }
+        self.socket = Some(socket);
+        let ids: Vec<String> = self.swaps.keys().cloned().collect();
+        if ids.is_empty() {
+            return Ok(());
+        }
+        self.send("subscribe", ids.iter().map(|id| id.as_str()).collect())
+    }
+
+    fn send(&mut self, op: &str, args: Vec<&str>) -> Result<(), S5Error> {
+        let request = WsRequest {
+            op,
+            channel: SWAP_UPDATE_CHANNEL,
+            args,
+        };
+        let text = match serde_json::to_string(&request) {
+            Ok(result) => result,
+            Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+        };
+        let socket = match self.socket.as_mut() {
+            Some(result) => result,
+            None => return Err(S5Error::new(ErrorKind::Network, "Not connected.")),
+        };
+        match socket.send(Message::Text(text)) {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                self.socket = None;
+                Err(S5Error::new(ErrorKind::Network, &e.to_string()))
+            }
+        }
+    }
+
+    fn handle_message(&mut self, text: &str) -> Result<(), S5Error> {
+        let event: WsEvent = match serde_json::from_str(text) {
+            Ok(result) => result,
+            Err(e) => return Err(S5Error::new(ErrorKind::BoltzApi, &e.to_string())),
+        };
+        match event.event.as_str() {
+            "update" => {
+                let statuses: Vec<WsSwapStatus> = match serde_json::from_value(event.args) {
+                    Ok(result) => result,
+                    Err(e) => return Err(S5Error::new(ErrorKind::BoltzApi, &e.to_string())),
+                };
+                for status in statuses {
+                    // updates for swaps we already unsubscribed from can still be in flight
+                    if let Some(swap_type) = self.swaps.get(&status.id) {
+                        let state = SwapState::from_status(swap_type, &status.status);
+                        self.pending.push_back(SwapUpdate {
+                            id: status.id,
+                            state,
+                        });
+                    }
+                }
+                Ok(())
+            }
+            "error" => Err(S5Error::new(
+                ErrorKind::BoltzApi,
+                &event.error.unwrap_or_else(|| text.to_string()),
+            )),
+            // subscribe/unsubscribe acknowledgements and pongs
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A read that ran into the read timeout. Depending on the platform this is WouldBlock or TimedOut.
+fn is_timeout(error: &std::io::Error) -> bool {
+    matches!(
+        error.kind(),
+        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+
+    use super::*;
+    use crate::swaps::boltz::{RevSwapStates, SubSwapStates};
+
+    /// Minimal stand-in for Boltz's status stream. Each accepted connection is handed to the
+    /// next handler in `sessions`; every subscribe request is reported on the returned channel.
+    fn mock_ws_server(
+        sessions: Vec<fn(&mut WebSocket<TcpStream>)>,
+    ) -> (String, mpsc::Receiver<serde_json::Value>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for session in sessions {
+                let stream = listener.accept().unwrap().0;
+                let mut socket = tungstenite::accept(stream).unwrap();
+                let request = socket.read().unwrap().into_text().unwrap();
+                sender
+                    .send(serde_json::from_str(&request).unwrap())
+                    .unwrap();
+                session(&mut socket);
+            }
+        });
+        (url, receiver)
+    }
+
+    fn send(socket: &mut WebSocket<TcpStream>, message: &str) {
+        socket.send(Message::Text(message.to_string())).unwrap();
+    }
+
+    #[test]
+    fn test_ws_subscribe_and_reconnect() {
+        let (url, requests) = mock_ws_server(vec![
+            |socket| {
+                send(
+                    socket,
+                    r#"{"event":"subscribe","channel":"swap.update","args":["sub1","rev1"]}"#,
+                );
+                send(
+                    socket,
+                    r#"{"event":"update","channel":"swap.update","args":[{"id":"sub1","status":"invoice.set"},{"id":"rev1","status":"transaction.mempool"}]}"#,
+                );
+                send(
+                    socket,
+                    r#"{"event":"update","channel":"swap.update","args":[{"id":"other","status":"swap.created"},{"id":"rev1","status":"transaction.claimed"}]}"#,
+                );
+                // drop the connection without a close frame
+            },
+            |socket| {
+                send(socket, r#"{"event":"pong"}"#);
+                send(
+                    socket,
+                    r#"{"event":"update","channel":"swap.update","args":[{"id":"rev1","status":"invoice.settled"}]}"#,
+                );
+                send(socket, r#"{"event":"error","error":"rate limited"}"#);
+            },
+        ]);
+
+        let mut client = BoltzWsClient::new(&url);
+        client.set_reconnect_policy(3, Duration::from_millis(10));
+        client
+            .subscribe(&[
+                ("sub1".to_string(), SwapType::Submarine),
+                ("rev1".to_string(), SwapType::ReverseSubmarine),
+            ])
+            .unwrap();
+        let request = requests.recv().unwrap();
+        assert_eq!(request["op"], "subscribe");
+        assert_eq!(request["channel"], "swap.update");
+        let mut ids: Vec<String> = serde_json::from_value(request["args"].clone()).unwrap();
+        ids.sort();
+        assert_eq!(ids, vec!["rev1", "sub1"]);
+
+        assert_eq!(
+            client.next_update().unwrap(),
+            SwapUpdate {
+                id: "sub1".to_string(),
+                state: SwapState::Submarine(SubSwapStates::InvoiceSet)
+            }
+        );
+        assert_eq!(
+            client.next_update().unwrap(),
+            SwapUpdate {
+                id: "rev1".to_string(),
+                state: SwapState::Reverse(RevSwapStates::TransactionMempool)
+            }
+        );
+        // not a reverse swap status; "other" was never subscribed
+        assert_eq!(
+            client.next_update().unwrap(),
+            SwapUpdate {
+                id: "rev1".to_string(),
+                state: SwapState::Unknown("transaction.claimed".to_string())
+            }
+        );
+
+        // the server dropped the connection: reconnect and resubscribe to both swaps
+        assert_eq!(
+            client.next_update().unwrap(),
+            SwapUpdate {
+                id: "rev1".to_string(),
+                state: SwapState::Reverse(RevSwapStates::InvoiceSettled)
+            }
+        );
+        let request = requests.recv().unwrap();
+        assert_eq!(request["args"].as_array().unwrap().len(), 2);
+        assert_eq!(client.next_update().unwrap_err().message, "rate limited");
+
+        // the server is gone for good
+        assert_eq!(client.next_update().unwrap_err().kind, "Network");
+    }
+
+    #[test]
+    fn test_ws_ping() {
+        let (url, _requests) = mock_ws_server(vec![
+            |socket| {
+                // stay silent until pinged; the pong goes out with the next write
+                assert!(matches!(socket.read().unwrap(), Message::Ping(_)));
+                send(
+                    socket,
+                    r#"{"event":"update","channel":"swap.update","args":[{"id":"rev1","status":"swap.created"}]}"#,
+                );
+            },
+            // never answers the ping
+            |_| thread::sleep(Duration::from_millis(500)),
+        ]);
+
+        let mut client = BoltzWsClient::new(&url);
+        client.set_reconnect_policy(0, Duration::from_millis(10));
+        client.set_ping_interval(Duration::from_millis(50));
+        client
+            .subscribe(&[("rev1".to_string(), SwapType::ReverseSubmarine)])
+            .unwrap();
+        assert_eq!(
+            client.next_update().unwrap(),
+            SwapUpdate {
+                id: "rev1".to_string(),
+                state: SwapState::Reverse(RevSwapStates::Created)
+            }
+        );
+
+        let mut client = BoltzWsClient::new(&url);
+        client.set_reconnect_policy(0, Duration::from_millis(10));
+        client.set_ping_interval(Duration::from_millis(50));
+        client
+            .subscribe(&[("rev1".to_string(), SwapType::ReverseSubmarine)])
+            .unwrap();
+        assert_eq!(client.next_update().unwrap_err().kind, "Network");
+    }
+
+    #[test]
+    fn test_ws_rejects_chain_swaps() {
+        let mut client = BoltzWsClient::new("ws://127.0.0.1:1");
+        assert!(client
+            .subscribe(&[("chain1".to_string(), SwapType::Chain)])
+            .is_err());
+        assert!(client.next_update().is_err());
+    }
+}
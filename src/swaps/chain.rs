@@ -0,0 +1,341 @@
+use elements::secp256k1_zkp::KeyPair as ZKKeyPair;
+
+use crate::{
+    network::electrum::BitcoinNetwork,
+    swaps::{
+        bitcoin::{BtcSwapScript, BtcSwapTx},
+        boltz::ChainSwapSide,
+        boltzv2::{ChainSwapDetails, CreateChainRequest, CreateChainResponse, Currency},
+        liquid::{LBtcSwapScript, LBtcSwapTx},
+    },
+    util::{
+        error::{ErrorKind, S5Error},
+        preimage::Preimage,
+    },
+};
+
+/// A chain swap locks funds on two chains: we lock on the source chain and Boltz locks on the
+/// destination chain. Each side is a taproot swap tree on its own chain.
+#[derive(Debug, Clone)]
+pub enum ChainSwapScript {
+    Btc(BtcSwapScript),
+    LBtc(LBtcSwapScript),
+}
+
+/// Claim or refund transaction of one side of a chain swap.
+pub enum ChainSwapTx {
+    Btc(BtcSwapTx),
+    LBtc(LBtcSwapTx),
+}
+
+#[derive(Debug, Clone)]
+pub struct ChainSwap {
+    pub id: String,
+    pub from: Currency,
+    pub to: Currency,
+    /// Script we fund on the source chain.
+    pub lockup_script: ChainSwapScript,
+    /// Script Boltz funds on the destination chain.
+    pub claim_script: ChainSwapScript,
+    pub lockup_address: String,
+    /// Amount we have to lock on the source chain.
+    pub lockup_amount: u64,
+    /// Amount Boltz locks on the destination chain.
+    pub claim_amount: u64,
+}
+
+impl ChainSwap {
+    /// Rebuilds both lockup scripts from our request and Boltz's response and checks them
+    /// against the swap trees and addresses Boltz returned.
+    ///
+    /// `btc_network` and `lbtc_network` select the network of the BTC and L-BTC side.
+    pub fn from_response(
+        request: &CreateChainRequest,
+        response: &CreateChainResponse,
+        btc_network: BitcoinNetwork,
+        lbtc_network: BitcoinNetwork,
+    ) -> Result<ChainSwap, S5Error> {
+        if request.from == request.to {
+            return Err(S5Error::new(
+                ErrorKind::Input,
+                "Chain swaps must be between two different chains.",
+            ));
+        }
+        let preimage = Preimage::from_sha256_str(&request.preimage_hash)?;
+        let hashlock = preimage.hash160.to_string();
+
+        // Boltz claims what we lock with the preimage; we refund after the timeout.
+        let lockup_script = chain_swap_script(
+            request.from,
+            ChainSwapSide::Lockup,
+            &hashlock,
+            &response.lockup_details.server_public_key,
+            &request.refund_public_key,
+            &response.lockup_details,
+            btc_network,
+            lbtc_network,
+        )?;
+        // We claim what Boltz locks with the preimage; Boltz refunds after the timeout.
+        let claim_script = chain_swap_script(
+            request.to,
+            ChainSwapSide::Claim,
+            &hashlock,
+            &request.claim_public_key,
+            &response.claim_details.server_public_key,
+            &response.claim_details,
+            btc_network,
+            lbtc_network,
+        )?;
+
+        if let Some(amount) = request.user_lock_amount {
+            if response.lockup_details.amount != amount {
+                return Err(S5Error::new(
+                    ErrorKind::BoltzApi,
+                    &format!(
+                        "Lockup amount does not match request. Expected {}, Found {}",
+                        amount, response.lockup_details.amount
+                    ),
+                ));
+            }
+        }
+        if let Some(amount) = request.server_lock_amount {
+            if response.claim_details.amount != amount {
+                return Err(S5Error::new(
+                    ErrorKind::BoltzApi,
+                    &format!(
+                        "Claim amount does not match request. Expected {}, Found {}",
+                        amount, response.claim_details.amount
+                    ),
+                ));
+            }
+        }
+
+        Ok(ChainSwap {
+            id: response.id.clone(),
+            from: request.from,
+            to: request.to,
+            lockup_script,
+            claim_script,
+            lockup_address: response.lockup_details.lockup_address.clone(),
+            lockup_amount: response.lockup_details.amount,
+            claim_amount: response.claim_details.amount,
+        })
+    }
+
+    /// Claim of Boltz's lockup on the destination chain.
+    pub fn new_claim_tx(
+        &self,
+        output_address: String,
+        absolute_fees: u32,
+    ) -> Result<ChainSwapTx, S5Error> {
+        match &self.claim_script {
+            ChainSwapScript::Btc(script) => Ok(ChainSwapTx::Btc(BtcSwapTx::new_claim(
+                script.clone(),
+                output_address,
+                absolute_fees,
+            )?)),
+            ChainSwapScript::LBtc(script) => Ok(ChainSwapTx::LBtc(LBtcSwapTx::new_claim(
+                script.clone(),
+                output_address,
+                absolute_fees,
+            )?)),
+        }
+    }
+
+    /// Refund of our lockup on the source chain, after its timeout.
+    pub fn new_refund_tx(
+        &self,
+        output_address: String,
+        absolute_fees: u32,
+    ) -> Result<ChainSwapTx, S5Error> {
+        match &self.lockup_script {
+            ChainSwapScript::Btc(script) => Ok(ChainSwapTx::Btc(BtcSwapTx::new_refund(
+                script.clone(),
+                output_address,
+                absolute_fees,
+            )?)),
+            ChainSwapScript::LBtc(script) => Ok(ChainSwapTx::LBtc(LBtcSwapTx::new_refund(
+                script.clone(),
+                output_address,
+                absolute_fees,
+            )?)),
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn chain_swap_script(
+    currency: Currency,
+    side: ChainSwapSide,
+    hashlock: &str,
+    reciever_pubkey: &str,
+    sender_pubkey: &str,
+    details: &ChainSwapDetails,
+    btc_network: BitcoinNetwork,
+    lbtc_network: BitcoinNetwork,
+) -> Result<ChainSwapScript, S5Error> {
+    match currency {
+        Currency::Btc => {
+            let script = BtcSwapScript::new_chain(
+                btc_network,
+                side,
+                hashlock.to_string(),
+                reciever_pubkey.to_string(),
+                details.timeout_block_height,
+                sender_pubkey.to_string(),
+            );
+            script.validate_swap_tree(&details.swap_tree, &details.lockup_address)?;
+            Ok(ChainSwapScript::Btc(script))
+        }
+        Currency::LBtc => {
+            let blinding_str = match &details.blinding_key {
+                Some(result) => result,
+                None => {
+                    return Err(S5Error::new(
+                        ErrorKind::Input,
+                        "L-BTC side of a chain swap requires a blinding key.",
+                    ))
+                }
+            };
+            let secp = elements::secp256k1_zkp::Secp256k1::new();
+            let blinding_key = match ZKKeyPair::from_seckey_str(&secp, blinding_str) {
+                Ok(result) => result,
+                Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+            };
+            let script = LBtcSwapScript::new_chain(
+                lbtc_network,
+                side,
+                hashlock.to_string(),
+                reciever_pubkey.to_string(),
+                details.timeout_block_height,
+                sender_pubkey.to_string(),
+                blinding_key,
+            );
+            script.validate_swap_tree(&details.swap_tree, &details.lockup_address)?;
+            Ok(ChainSwapScript::LBtc(script))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use elements::address::Address as EAddress;
+    use elements::secp256k1_zkp::Secp256k1;
+    use std::str::FromStr;
+
+    const PREIMAGE_HASH: &str = "55315b9073c7a025e1d96a6db9c1e6a4bb8867bad5e3af6ddcff12e3e898abba";
+    const CLAIM_PUBKEY: &str = "0223a99c57bfbc2a4bfc9353d49d6fd7312afaec8e8eefb82273d26c34c5458986";
+    const REFUND_PUBKEY: &str =
+        "023946267e8f3eeeea651b0ea865b52d1f9d1c12e851b0f98a3303c15a26cf235d";
+    const BTC_LOCKUP_ADDRESS: &str =
+        "tb1pz5g7fxph4hhtjuh20scfrfsrrhlr30e5ws295qvhhlzndwtnklrqceg9t2";
+    const LBTC_LOCKUP_ADDRESS: &str =
+        "tex1pra0phme9r69ph6g9at65pre6d0qu0xa3jyww4kxzal9lwryttv2qlku9pj";
+    // BTC -> L-BTC
+    const CHAIN_SWAP: &str = r#"{"id":"Ch4in1","claimDetails":{"swapTree":{"claimLeaf":{"version":196,"output":"82012088a9143b2b7485171679c84f6540a8b907c2c830e9a60b882023a99c57bfbc2a4bfc9353d49d6fd7312afaec8e8eefb82273d26c34c5458986ac"},"refundLeaf":{"version":196,"output":"20466d7fcae563e5cb09a0d1870bb580344804617879a14949cf22285f1bae3f27ad03102016b1"}},"lockupAddress":"tex1pra0phme9r69ph6g9at65pre6d0qu0xa3jyww4kxzal9lwryttv2qlku9pj","serverPublicKey":"02466d7fcae563e5cb09a0d1870bb580344804617879a14949cf22285f1bae3f27","timeoutBlockHeight":1450000,"amount":99000,"blindingKey":"3333333333333333333333333333333333333333333333333333333333333333"},"lockupDetails":{"swapTree":{"claimLeaf":{"version":192,"output":"82012088a9143b2b7485171679c84f6540a8b907c2c830e9a60b88204f355bdcb7cc0af728ef3cceb9615d90684bb5b2ca5f859ab0f0b704075871aaac"},"refundLeaf":{"version":192,"output":"203946267e8f3eeeea651b0ea865b52d1f9d1c12e851b0f98a3303c15a26cf235dad03205e27b1"}},"lockupAddress":"tb1pz5g7fxph4hhtjuh20scfrfsrrhlr30e5ws295qvhhlzndwtnklrqceg9t2","serverPublicKey":"034f355bdcb7cc0af728ef3cceb9615d90684bb5b2ca5f859ab0f0b704075871aa","timeoutBlockHeight":2580000,"amount":100000,"bip21":"bitcoin:tb1pz5g7fxph4hhtjuh20scfrfsrrhlr30e5ws295qvhhlzndwtnklrqceg9t2?amount=0.001"}}"#;
+
+    fn chain_request() -> CreateChainRequest {
+        CreateChainRequest {
+            from: Currency::Btc,
+            to: Currency::LBtc,
+            preimage_hash: PREIMAGE_HASH.to_string(),
+            claim_public_key: CLAIM_PUBKEY.to_string(),
+            refund_public_key: REFUND_PUBKEY.to_string(),
+            user_lock_amount: Some(100000),
+            server_lock_amount: None,
+            pair_hash: None,
+            referral_id: None,
+        }
+    }
+
+    fn from_response(response: &CreateChainResponse) -> Result<ChainSwap, S5Error> {
+        ChainSwap::from_response(
+            &chain_request(),
+            response,
+            BitcoinNetwork::BitcoinTestnet,
+            BitcoinNetwork::LiquidTestnet,
+        )
+    }
+
+    #[test]
+    fn test_chain_swap_from_response() {
+        let response: CreateChainResponse = serde_json::from_str(CHAIN_SWAP).unwrap();
+        let swap = from_response(&response).unwrap();
+        assert_eq!(swap.lockup_address, BTC_LOCKUP_ADDRESS);
+        assert_eq!(swap.lockup_amount, 100000);
+        assert_eq!(swap.claim_amount, 99000);
+
+        // Boltz claims our BTC lockup, we claim Boltz's L-BTC lockup
+        let lockup_script = match &swap.lockup_script {
+            ChainSwapScript::Btc(script) => script,
+            _ => panic!("lockup side should be BTC"),
+        };
+        assert_eq!(lockup_script.side(), Some(ChainSwapSide::Lockup));
+        assert_eq!(
+            lockup_script.musig_pubkeys().unwrap()[0].to_string(),
+            response.lockup_details.server_public_key
+        );
+        let claim_script = match &swap.claim_script {
+            ChainSwapScript::LBtc(script) => script,
+            _ => panic!("claim side should be L-BTC"),
+        };
+        assert_eq!(claim_script.side(), Some(ChainSwapSide::Claim));
+        assert_eq!(
+            claim_script.musig_pubkeys().unwrap()[0].to_string(),
+            response.claim_details.server_public_key
+        );
+        let secp = Secp256k1::new();
+        let blinding_key = ZKKeyPair::from_seckey_str(
+            &secp,
+            "3333333333333333333333333333333333333333333333333333333333333333",
+        )
+        .unwrap();
+        assert_eq!(
+            claim_script.to_address().unwrap(),
+            EAddress::from_str(LBTC_LOCKUP_ADDRESS)
+                .unwrap()
+                .to_confidential(blinding_key.public_key())
+        );
+
+        match swap
+            .new_claim_tx(LBTC_LOCKUP_ADDRESS.to_string(), 300)
+            .unwrap()
+        {
+            ChainSwapTx::LBtc(_) => (),
+            _ => panic!("claim should be on the destination chain"),
+        }
+        match swap
+            .new_refund_tx(BTC_LOCKUP_ADDRESS.to_string(), 300)
+            .unwrap()
+        {
+            ChainSwapTx::Btc(_) => (),
+            _ => panic!("refund should be on the source chain"),
+        }
+    }
+
+    #[test]
+    fn test_chain_swap_rejects_bad_response() {
+        let response: CreateChainResponse = serde_json::from_str(CHAIN_SWAP).unwrap();
+
+        let mut bad = response.clone();
+        bad.lockup_details.server_public_key = REFUND_PUBKEY.to_string();
+        assert!(from_response(&bad).is_err());
+
+        let mut bad = response.clone();
+        bad.claim_details.lockup_address = BTC_LOCKUP_ADDRESS.to_string();
+        assert!(from_response(&bad).is_err());
+
+        let mut bad = response.clone();
+        bad.claim_details.timeout_block_height += 1;
+        assert!(from_response(&bad).is_err());
+
+        let mut bad = response.clone();
+        bad.claim_details.blinding_key = None;
+        assert!(from_response(&bad).is_err());
+
+        let mut bad = response;
+        bad.lockup_details.amount = 1000;
+        assert!(from_response(&bad).is_err());
+    }
+}
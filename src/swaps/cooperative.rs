@@ -0,0 +1,154 @@
+use bitcoin::secp256k1::{schnorr, KeyPair, PublicKey, Scalar};
+
+use crate::swaps::boltz::{SwapTxKind, SwapType};
+use crate::swaps::boltzv2::{BoltzApiClientV2, PartialSigRequest, PartialSigResponse};
+use crate::util::{
+    error::{ErrorKind, S5Error},
+    musig,
+    preimage::Preimage,
+};
+
+/// A spend signed by `drain_cooperative`.
+#[derive(Debug, Clone)]
+pub struct CooperativeSpend<T> {
+    pub tx: T,
+    /// Why the key-path spend failed, when the script path was signed instead.
+    pub cooperative_error: Option<S5Error>,
+}
+
+/// Co-signs key-path spends of one swap's taproot lockup with Boltz.
+pub struct CooperativeSigner<'a> {
+    pub boltz: &'a BoltzApiClientV2,
+    pub swap_id: &'a str,
+    pub swap_type: SwapType,
+    pub kind: SwapTxKind,
+}
+
+impl CooperativeSigner<'_> {
+    /// Signs `sighashes`, the key-path sighash of every input in input order.
+    /// Every input is signed in its own MuSig2 session, Boltz is asked once per input.
+    /// `musig_pubkeys` start with Boltz's key; `tweak` is the taproot tweak of the lockup.
+    /// `transaction` is the hex encoded unsigned transaction, which Boltz checks before signing.
+    pub fn sign_inputs(
+        &self,
+        keys: &KeyPair,
+        preimage: &Preimage,
+        musig_pubkeys: &[PublicKey],
+        tweak: Scalar,
+        transaction: &str,
+        sighashes: &[[u8; 32]],
+    ) -> Result<Vec<schnorr::Signature>, S5Error> {
+        if self.swap_type == SwapType::Chain && matches!(self.kind, SwapTxKind::Claim) {
+            return Err(S5Error::new(
+                ErrorKind::Transaction,
+                "Cooperative chain swap claims are not supported.",
+            ));
+        }
+        let preimage = match self.kind {
+            SwapTxKind::Claim => match preimage.bytes {
+                Some(result) => Some(hex::encode(result)),
+                None => return Err(S5Error::new(ErrorKind::Input, "Claim requires preimage.")),
+            },
+            SwapTxKind::Refund => None,
+        };
+        let mut key_agg_cache = musig::KeyAggCache::new(musig_pubkeys)?;
+        key_agg_cache.x_only_tweak_add(tweak)?;
+
+        let mut signatures = vec![];
+        for (index, sighash) in sighashes.iter().enumerate() {
+            let signature = musig::cooperative_sign(
+                &key_agg_cache,
+                keys,
+                &musig_pubkeys[0],
+                sighash,
+                |pub_nonce| {
+                    let response = self.partial_sig(&PartialSigRequest {
+                        index,
+                        transaction: transaction.to_string(),
+                        pub_nonce: hex::encode(pub_nonce.serialize()),
+                        preimage: preimage.clone(),
+                    })?;
+                    musig::parse_partial_sig(&response.pub_nonce, &response.partial_signature)
+                },
+            )?;
+            signatures.push(signature);
+        }
+        Ok(signatures)
+    }
+
+    fn partial_sig(&self, request: &PartialSigRequest) -> Result<PartialSigResponse, S5Error> {
+        let response = match (&self.kind, &self.swap_type) {
+            (SwapTxKind::Claim, _) => self.boltz.get_reverse_partial_sig(self.swap_id, request),
+            (SwapTxKind::Refund, SwapType::Chain) => {
+                self.boltz.get_chain_partial_sig(self.swap_id, request)
+            }
+            (SwapTxKind::Refund, _) => self.boltz.get_submarine_partial_sig(self.swap_id, request),
+        };
+        Ok(response?)
+    }
+}
+
+/// Signs the script path with `sign` after the key-path spend failed with `cooperative_error`.
+/// A refund's script path is locked until `timelock`, so before `tip_height` reaches it the
+/// cooperative error is returned instead.
+pub fn fall_back<T, F>(
+    kind: &SwapTxKind,
+    timelock: u32,
+    tip_height: u32,
+    cooperative_error: S5Error,
+    sign: F,
+) -> Result<CooperativeSpend<T>, S5Error>
+where
+    F: FnOnce() -> Result<T, S5Error>,
+{
+    if matches!(kind, SwapTxKind::Refund) && tip_height < timelock {
+        return Err(S5Error {
+            kind: cooperative_error.kind,
+            message: format!(
+                "{}; the refund script path is locked until block {}, the tip is at {}",
+                cooperative_error.message, timelock, tip_height
+            ),
+        });
+    }
+    match sign() {
+        Ok(tx) => Ok(CooperativeSpend {
+            tx,
+            cooperative_error: Some(cooperative_error),
+        }),
+        Err(e) => Err(S5Error {
+            kind: e.kind,
+            message: format!(
+                "Cooperative spend failed ({}), script path failed ({})",
+                cooperative_error.message, e.message
+            ),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fall_back() {
+        let error = S5Error::new(ErrorKind::BoltzApi, "boltz is down");
+
+        let spend = fall_back(&SwapTxKind::Claim, 100, 0, error.clone(), || Ok(1)).unwrap();
+        assert_eq!(spend.tx, 1);
+        assert_eq!(spend.cooperative_error.unwrap().message, "boltz is down");
+
+        let refused = fall_back(&SwapTxKind::Refund, 100, 99, error.clone(), || Ok(1)).unwrap_err();
+        assert_eq!(refused.kind, "BoltzApi");
+        assert!(refused.message.starts_with("boltz is down;"));
+
+        let spend = fall_back(&SwapTxKind::Refund, 100, 100, error.clone(), || Ok(1)).unwrap();
+        assert_eq!(spend.tx, 1);
+
+        let failed = fall_back::<u32, _>(&SwapTxKind::Claim, 100, 0, error, || {
+            Err(S5Error::new(ErrorKind::Transaction, "no utxos"))
+        })
+        .unwrap_err();
+        assert_eq!(failed.kind, "Transaction");
+        assert!(failed.message.contains("boltz is down") && failed.message.contains("no utxos"));
+    }
+}
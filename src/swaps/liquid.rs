@@ -1,5 +1,4 @@
-use electrum_client::ElectrumApi;
-use std::{fs::File, path::Path, str::FromStr};
+use std::str::FromStr;
 
 use bitcoin::{
     script::Script as BitcoinScript,
@@ -7,22 +6,40 @@ use bitcoin::{
 };
 use elements::{
     confidential::{self, AssetBlindingFactor, ValueBlindingFactor},
-    hashes::hash160,
+    hashes::{hash160, Hash},
     secp256k1_zkp::{self, Secp256k1},
-    sighash::SighashCache,
-    Address, AssetIssuance, OutPoint, Script, Sequence, Transaction, TxIn, TxInWitness, TxOut,
-    TxOutSecrets, TxOutWitness,
+    sighash::{Prevouts, SighashCache},
+    taproot::{ControlBlock, LeafVersion, TapLeafHash, TaprootBuilder, TaprootSpendInfo},
+    Address, AssetId, AssetIssuance, BlockHash, OutPoint, SchnorrSighashType, Script, Sequence,
+    Transaction, TxIn, TxInWitness, TxOut, TxOutSecrets, TxOutWitness,
 };
 
 use elements::encode::serialize;
+use elements::pset::{
+    Input as PsetInput, Output as PsetOutput, PartiallySignedTransaction as Pset,
+};
 use elements::secp256k1_zkp::Message;
+use std::collections::HashMap;
 
+#[cfg(feature = "async")]
+use crate::network::backend::AsyncChainBackend;
 use crate::{
-    network::electrum::{BitcoinNetwork, NetworkConfig},
-    swaps::boltz::SwapTxKind,
+    network::{
+        backend::{ChainBackend, Utxo},
+        electrum::{BitcoinNetwork, _LIQUID_POLICY_ASSET_STR, LIQUID_TESTNET_POLICY_ASSET_STR},
+    },
+    swaps::{
+        boltz::{
+            ChainSwapSide, Fee, SkippedUtxo, SwapScriptVersion, SwapTxKind, UtxoPolicy, UtxoReport,
+        },
+        boltzv2::{BoltzApiClientV2, SwapTree},
+        cooperative::{fall_back, CooperativeSigner, CooperativeSpend},
+    },
     util::{
         error::{ErrorKind, S5Error},
+        musig,
         preimage::Preimage,
+        script::{cltv_satisfied, legacy_template, match_template, ScriptElement},
     },
 };
 
@@ -31,6 +48,14 @@ pub const DUST_VALUE: u64 = 546;
 pub const DEFAULT_SURJECTIONPROOF_SIZE: u64 = 135;
 // 52-bit rangeproof
 pub const DEFAULT_RANGEPROOF_SIZE: u64 = 4174;
+// taproot sighashes commit to the chain's genesis block
+pub const LIQUID_GENESIS_HASH: &str =
+    "1466275836220db2944ca059a3a10ef6fd2ea684b0688d2c379296888a206003";
+pub const LIQUID_TESTNET_GENESIS_HASH: &str =
+    "a771da8e52ee6ad581ed1e9a99825e5b3b7992225534eaa2ae23244fe26ab1c1";
+// default elementsd -chain=elementsregtest
+pub const ELEMENTS_REGTEST_GENESIS_HASH: &str =
+    "00902a6b70c2ca83b5d9c815d96a0e2f4202179316970d14ea1847dae5b1ca21";
 use bitcoin::PublicKey;
 use elements::secp256k1_zkp::{KeyPair as ZKKeyPair, PublicKey as NoncePublicKey};
 use elements::{
@@ -45,8 +70,10 @@ use super::boltz::SwapType;
 #[derive(Debug, Clone, PartialEq)]
 pub struct LBtcSwapScript {
     network: BitcoinNetwork,
-    electrum_url: String,
     swap_type: SwapType,
+    version: SwapScriptVersion,
+    // only set for chain swaps
+    side: Option<ChainSwapSide>,
     pub hashlock: String,
     pub reciever_pubkey: String,
     pub timelock: u32,
@@ -57,7 +84,6 @@ pub struct LBtcSwapScript {
 impl LBtcSwapScript {
     pub fn new(
         network: BitcoinNetwork,
-        electrum_url: String,
         swap_type: SwapType,
         hashlock: String,
         reciever_pubkey: String,
@@ -67,8 +93,56 @@ impl LBtcSwapScript {
     ) -> Self {
         LBtcSwapScript {
             network,
-            electrum_url,
             swap_type,
+            version: SwapScriptVersion::Legacy,
+            side: None,
+            hashlock,
+            reciever_pubkey,
+            timelock,
+            sender_pubkey,
+            blinding_key,
+        }
+    }
+    /// An elements-taproot swap: a confidential P2TR lockup whose internal key is the MuSig2
+    /// aggregate of both pubkeys, committing to a claim leaf and a refund leaf.
+    pub fn new_taproot(
+        network: BitcoinNetwork,
+        swap_type: SwapType,
+        hashlock: String,
+        reciever_pubkey: String,
+        timelock: u32,
+        sender_pubkey: String,
+        blinding_key: ZKKeyPair,
+    ) -> Self {
+        LBtcSwapScript {
+            network,
+            swap_type,
+            version: SwapScriptVersion::Taproot,
+            side: None,
+            hashlock,
+            reciever_pubkey,
+            timelock,
+            sender_pubkey,
+            blinding_key,
+        }
+    }
+    /// One of the two lockup scripts of a chain swap. The `Lockup` side is the script we fund on
+    /// the source chain (Boltz claims it); the `Claim` side is the script Boltz funds on the
+    /// destination chain (we claim it).
+    pub fn new_chain(
+        network: BitcoinNetwork,
+        side: ChainSwapSide,
+        hashlock: String,
+        reciever_pubkey: String,
+        timelock: u32,
+        sender_pubkey: String,
+        blinding_key: ZKKeyPair,
+    ) -> Self {
+        LBtcSwapScript {
+            network,
+            swap_type: SwapType::Chain,
+            version: SwapScriptVersion::Taproot,
+            side: Some(side),
             hashlock,
             reciever_pubkey,
             timelock,
@@ -76,172 +150,108 @@ impl LBtcSwapScript {
             blinding_key,
         }
     }
+    pub fn version(&self) -> SwapScriptVersion {
+        self.version
+    }
+    pub fn side(&self) -> Option<ChainSwapSide> {
+        self.side
+    }
     pub fn submarine_from_str(
         network: BitcoinNetwork,
-        electrum_url: String,
         redeem_script_str: &str,
         blinding_str: String,
     ) -> Result<Self, S5Error> {
-        // let script_bytes = hex::decode(redeem_script_str).unwrap().to_owned();
-        let script = EScript::from_str(&redeem_script_str).unwrap();
-        // let address = Address::p2shwsh(&script, bitcoin::Network::Testnet);
-
-        let instructions = script.instructions();
-        let mut last_op = OP_0NOTEQUAL;
-        let mut hashlock = None;
-        let mut reciever_pubkey = None;
-        let mut timelock = None;
-        let mut sender_pubkey = None;
-
-        for instruction in instructions {
-            match instruction {
-                Ok(Instruction::Op(opcode)) => {
-                    last_op = opcode;
-                    // println!("{:?}", opcode)
-                }
-
-                Ok(Instruction::PushBytes(bytes)) => {
-                    if last_op == OP_HASH160 {
-                        hashlock = Some(hex::encode(bytes));
-                    }
-                    if last_op == OP_IF {
-                        reciever_pubkey = Some(hex::encode(bytes));
-                    }
-                    if last_op == OP_ELSE {
-                        timelock = Some(bytes_to_u32_little_endian(&bytes));
-                    }
-                    if last_op == OP_DROP {
-                        sender_pubkey = Some(hex::encode(bytes));
-                    }
-                    // println!("{:?}", bytes)
-                }
-                Err(e) => println!("Error: {:?}", e),
-            }
-        }
-
-        if hashlock.is_some()
-            && sender_pubkey.is_some()
-            && timelock.is_some()
-            && sender_pubkey.is_some()
-        {
-            let zksecp = Secp256k1::new();
-
-            Ok(LBtcSwapScript {
-                network,
-                electrum_url,
-                swap_type: SwapType::Submarine,
-                hashlock: hashlock.unwrap(),
-                reciever_pubkey: reciever_pubkey.unwrap(),
-                timelock: timelock.unwrap(),
-                sender_pubkey: sender_pubkey.unwrap(),
-                blinding_key: ZKKeyPair::from_seckey_str(&zksecp, &blinding_str).unwrap(),
-            })
-        } else {
-            Err(S5Error::new(
-                ErrorKind::Input,
-                &format!(
-                    "Could not extract all elements: {:?} {:?} {:?} {:?}",
-                    hashlock, reciever_pubkey, timelock, sender_pubkey
-                ),
-            ))
-        }
+        Self::from_template(
+            network,
+            SwapType::Submarine,
+            redeem_script_str,
+            &blinding_str,
+        )
     }
 
     pub fn reverse_from_str(
         network: BitcoinNetwork,
-        electrum_url: String,
         redeem_script_str: &str,
         blinding_str: String,
     ) -> Result<Self, S5Error> {
-        let script = EScript::from_str(&redeem_script_str).unwrap();
-        // let address = Address::p2shwsh(&script, bitcoin::Network::Testnet);
-        // println!("ADDRESS DECODED: {:?}",address);
-        // let script_hash = script.script_hash();
-        // let sh_str = hex::encode(script_hash.to_raw_hash().to_string());
-        // println!("DECODED SCRIPT HASH: {}",sh_str);
-        let instructions = script.instructions();
-        let mut last_op = OP_0NOTEQUAL;
-        let mut hashlock = None;
-        let mut reciever_pubkey = None;
-        let mut timelock = None;
-        let mut sender_pubkey = None;
-
-        for instruction in instructions {
-            match instruction {
-                Ok(Instruction::Op(opcode)) => {
-                    last_op = opcode;
-                    // println!("{:?}", opcode)
-                }
+        Self::from_template(
+            network,
+            SwapType::ReverseSubmarine,
+            redeem_script_str,
+            &blinding_str,
+        )
+    }
 
-                Ok(Instruction::PushBytes(bytes)) => {
-                    if last_op == OP_HASH160 {
-                        hashlock = Some(hex::encode(bytes));
-                    }
-                    if last_op == OP_EQUALVERIFY {
-                        reciever_pubkey = Some(hex::encode(bytes));
-                    }
-                    if last_op == OP_DROP {
-                        if bytes.len() == 3 as usize {
-                            timelock = Some(bytes_to_u32_little_endian(&bytes));
-                        } else {
-                            sender_pubkey = Some(hex::encode(bytes));
-                        }
-                    }
-                    // println!("{:?}: LENGTH: {}", bytes, bytes.len() )
-                }
-                Err(e) => println!("Error: {:?}", e),
-            }
+    /// Only accepts a redeem script byte-identical to the one we would build from its fields.
+    fn from_template(
+        network: BitcoinNetwork,
+        swap_type: SwapType,
+        redeem_script_str: &str,
+        blinding_str: &str,
+    ) -> Result<Self, S5Error> {
+        let script_bytes = match hex::decode(redeem_script_str) {
+            Ok(result) => result,
+            Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+        };
+        let blinding_key = match ZKKeyPair::from_seckey_str(&Secp256k1::new(), blinding_str) {
+            Ok(result) => result,
+            Err(e) => return Err(S5Error::new(ErrorKind::Key, &e.to_string())),
+        };
+        let elements: Vec<Result<ScriptElement, String>> = EScript::from(script_bytes.clone())
+            .instructions_minimal()
+            .map(|instruction| match instruction {
+                Ok(Instruction::Op(opcode)) => Ok(ScriptElement::Op(opcode.into_u8())),
+                Ok(Instruction::PushBytes(bytes)) => Ok(ScriptElement::Push(bytes.to_vec())),
+                Err(e) => Err(e.to_string()),
+            })
+            .collect();
+        let fields = match_template(legacy_template(&swap_type)?, &elements)?;
+        let swap_script = LBtcSwapScript {
+            network,
+            swap_type,
+            version: SwapScriptVersion::Legacy,
+            side: None,
+            hashlock: fields.hashlock,
+            reciever_pubkey: fields.reciever_pubkey,
+            timelock: fields.timelock,
+            sender_pubkey: fields.sender_pubkey,
+            blinding_key,
+        };
+        if swap_script.to_script()?.as_bytes() != script_bytes.as_slice() {
+            return Err(S5Error::new(
+                ErrorKind::Script,
+                "Redeem script is not byte-identical to the script rebuilt from its fields.",
+            ));
         }
+        Ok(swap_script)
+    }
 
-        if hashlock.is_some()
-            && sender_pubkey.is_some()
-            && timelock.is_some()
-            && sender_pubkey.is_some()
-        {
-            let zksecp = Secp256k1::new();
-
-            Ok(LBtcSwapScript {
-                network,
-                electrum_url,
-                swap_type: SwapType::ReverseSubmarine,
-                hashlock: hashlock.unwrap(),
-                reciever_pubkey: reciever_pubkey.unwrap(),
-                timelock: timelock.unwrap(),
-                sender_pubkey: sender_pubkey.unwrap(),
-                blinding_key: ZKKeyPair::from_seckey_str(&zksecp, &blinding_str).unwrap(),
-            })
-        } else {
-            Err(S5Error::new(
-                ErrorKind::Input,
-                &format!(
-                    "Could not extract all elements: {:?} {:?} {:?} {:?}",
-                    hashlock, reciever_pubkey, timelock, sender_pubkey
-                ),
-            ))
-        }
-    }
-    pub fn to_script(&self) -> EScript {
-        /*
-            HASH160 <hash of the preimage>
-            EQUAL
-            IF <reciever public key>
-            ELSE <timeout block height>
-            CHECKLOCKTIMEVERIFY
-            DROP <sender public key>
-            ENDIF
-            CHECKSIG
-        */
+    pub fn to_script(&self) -> Result<EScript, S5Error> {
+        if self.version == SwapScriptVersion::Taproot {
+            return Err(S5Error::new(
+                ErrorKind::Script,
+                "Taproot swaps have no single redeem script. Use claim_leaf/refund_leaf.",
+            ));
+        }
+        let locktime = LockTime::from_consensus(self.timelock);
         match self.swap_type {
+            SwapType::Chain => Err(S5Error::new(
+                ErrorKind::Script,
+                "Chain swaps only use taproot swap trees.",
+            )),
             SwapType::Submarine => {
-                let reciever_pubkey = PublicKey::from_str(&self.reciever_pubkey).unwrap();
-                let sender_pubkey = PublicKey::from_str(&self.sender_pubkey).unwrap();
-                let locktime = LockTime::from_consensus(self.timelock);
-                let hashvalue = hash160::Hash::from_str(&self.hashlock).unwrap();
-                let hashbytes_slice: &[u8] = hashvalue.as_ref();
-                let hashbytes: [u8; 20] =
-                    hashbytes_slice.try_into().expect("Hash must be 20 bytes");
-
-                let script = EBuilder::new()
+                /*
+                    HASH160 <hash of the preimage>
+                    EQUAL
+                    IF <reciever public key>
+                    ELSE <timeout block height>
+                    CHECKLOCKTIMEVERIFY
+                    DROP <sender public key>
+                    ENDIF
+                    CHECKSIG
+                */
+                let (reciever_pubkey, sender_pubkey, hashbytes) = self.script_fields()?;
+                Ok(EBuilder::new()
                     .push_opcode(OP_HASH160)
                     .push_slice(&hashbytes)
                     .push_opcode(OP_EQUAL)
@@ -254,9 +264,7 @@ impl LBtcSwapScript {
                     .push_key(&sender_pubkey)
                     .push_opcode(OP_ENDIF)
                     .push_opcode(OP_CHECKSIG)
-                    .into_script();
-
-                script
+                    .into_script())
             }
             SwapType::ReverseSubmarine => {
                 /*
@@ -273,15 +281,8 @@ impl LBtcSwapScript {
                     OP_ENDIF
                     OP_CHECKSIG
                 */
-                let reciever_pubkey = PublicKey::from_str(&self.reciever_pubkey).unwrap();
-                let sender_pubkey = PublicKey::from_str(&self.sender_pubkey).unwrap();
-                let locktime = LockTime::from_consensus(self.timelock);
-                let hashvalue = hash160::Hash::from_str(&self.hashlock).unwrap();
-                let hashbytes_slice: &[u8] = hashvalue.as_ref();
-                let hashbytes: [u8; 20] =
-                    hashbytes_slice.try_into().expect("Hash must be 20 bytes");
-
-                let script = EBuilder::new()
+                let (reciever_pubkey, sender_pubkey, hashbytes) = self.script_fields()?;
+                Ok(EBuilder::new()
                     .push_opcode(OP_SIZE)
                     .push_slice(&[32])
                     .push_opcode(OP_EQUAL)
@@ -298,44 +299,338 @@ impl LBtcSwapScript {
                     .push_key(&sender_pubkey)
                     .push_opcode(OP_ENDIF)
                     .push_opcode(OP_CHECKSIG)
-                    .into_script();
+                    .into_script())
+            }
+        }
+    }
+
+    /// (reciever pubkey, sender pubkey, hashlock) of a legacy script, parsed
+    fn script_fields(&self) -> Result<(PublicKey, PublicKey, [u8; 20]), S5Error> {
+        let reciever_pubkey = match PublicKey::from_str(&self.reciever_pubkey) {
+            Ok(result) => result,
+            Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+        };
+        let sender_pubkey = match PublicKey::from_str(&self.sender_pubkey) {
+            Ok(result) => result,
+            Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+        };
+        let hashvalue = match hash160::Hash::from_str(&self.hashlock) {
+            Ok(result) => result,
+            Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+        };
+        Ok((reciever_pubkey, sender_pubkey, hashvalue.to_byte_array()))
+    }
+
+    /// Claim leaf of the taproot swap tree. Same template as BtcSwapScript::claim_leaf.
+    pub fn claim_leaf(&self) -> Result<EScript, S5Error> {
+        let reciever_pubkey = x_only_pubkey(&self.reciever_pubkey)?;
+        let hashvalue = match hash160::Hash::from_str(&self.hashlock) {
+            Ok(result) => result,
+            Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+        };
+        let hashbytes: &[u8] = hashvalue.as_ref();
+
+        let builder = match self.swap_type {
+            SwapType::Submarine => EBuilder::new(),
+            SwapType::ReverseSubmarine | SwapType::Chain => EBuilder::new()
+                .push_opcode(OP_SIZE)
+                .push_slice(&[32])
+                .push_opcode(OP_EQUALVERIFY),
+        };
+        Ok(builder
+            .push_opcode(OP_HASH160)
+            .push_slice(hashbytes)
+            .push_opcode(OP_EQUALVERIFY)
+            .push_slice(&reciever_pubkey.serialize())
+            .push_opcode(OP_CHECKSIG)
+            .into_script())
+    }
+
+    /// Refund leaf of the taproot swap tree: <sender> CHECKSIGVERIFY <timeout block height> CLTV
+    pub fn refund_leaf(&self) -> Result<EScript, S5Error> {
+        let sender_pubkey = x_only_pubkey(&self.sender_pubkey)?;
+        Ok(EBuilder::new()
+            .push_slice(&sender_pubkey.serialize())
+            .push_opcode(OP_CHECKSIGVERIFY)
+            .push_int(self.timelock as i64)
+            .push_opcode(OP_CLTV)
+            .into_script())
+    }
+
+    /// MuSig2 signers in Boltz's order: Boltz's key first, then ours.
+    /// Boltz is the reciever of a submarine swap or a chain swap lockup, and the sender otherwise.
+    pub fn musig_pubkeys(&self) -> Result<[ZKPublicKey; 2], S5Error> {
+        let reciever_pubkey = match PublicKey::from_str(&self.reciever_pubkey) {
+            Ok(result) => result.inner,
+            Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+        };
+        let sender_pubkey = match PublicKey::from_str(&self.sender_pubkey) {
+            Ok(result) => result.inner,
+            Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+        };
+        let boltz_is_reciever = match self.swap_type {
+            SwapType::Submarine => true,
+            SwapType::ReverseSubmarine => false,
+            SwapType::Chain => self.side == Some(ChainSwapSide::Lockup),
+        };
+        if boltz_is_reciever {
+            Ok([reciever_pubkey, sender_pubkey])
+        } else {
+            Ok([sender_pubkey, reciever_pubkey])
+        }
+    }
+
+    /// Elements taproot uses its own tagged hashes and leaf version 0xc4.
+    pub fn taproot_spend_info(&self) -> Result<TaprootSpendInfo, S5Error> {
+        let internal_key = musig::key_agg(&self.musig_pubkeys()?)?;
+        let (claim_leaf, refund_leaf) = (self.claim_leaf()?, self.refund_leaf()?);
+        let builder = match TaprootBuilder::new()
+            .add_leaf(1, claim_leaf)
+            .and_then(|builder| builder.add_leaf(1, refund_leaf))
+        {
+            Ok(result) => result,
+            Err(e) => return Err(S5Error::new(ErrorKind::Script, &e.to_string())),
+        };
+        match builder.finalize(&Secp256k1::new(), internal_key.x_only_public_key().0) {
+            Ok(result) => Ok(result),
+            Err(_) => Err(S5Error::new(
+                ErrorKind::Script,
+                "Could not finalize taproot swap tree.",
+            )),
+        }
+    }
 
-                script
+    /// Checks that the swap tree returned by Boltz commits to exactly our claim and refund leaves,
+    /// and that the lockup address Boltz returned with it is the one derived from those leaves.
+    /// Boltz may return the unconfidential address; the blinding key is checked when present.
+    pub fn validate_swap_tree(
+        &self,
+        swap_tree: &SwapTree,
+        lockup_address: &str,
+    ) -> Result<(), S5Error> {
+        let leaf_version = LeafVersion::default().as_u8();
+        let leaves = [
+            ("claim", &swap_tree.claim_leaf, self.claim_leaf()?),
+            ("refund", &swap_tree.refund_leaf, self.refund_leaf()?),
+        ];
+        for (name, leaf, expected) in leaves {
+            if leaf.version != leaf_version {
+                return Err(S5Error::new(
+                    ErrorKind::Script,
+                    &format!(
+                        "Unexpected {} leaf version. Expected {}, Found {}",
+                        name, leaf_version, leaf.version
+                    ),
+                ));
             }
+            let expected = hex::encode(expected.as_bytes());
+            if leaf.output != expected {
+                return Err(S5Error::new(
+                    ErrorKind::Script,
+                    &format!(
+                        "Boltz {} leaf does not match. Expected {}, Found {}",
+                        name, expected, leaf.output
+                    ),
+                ));
+            }
+        }
+        let address = match EAddress::from_str(lockup_address) {
+            Ok(result) => result,
+            Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+        };
+        let expected = self.to_address()?;
+        if address.script_pubkey() != expected.script_pubkey()
+            || address
+                .blinding_pubkey
+                .map_or(false, |pubkey| Some(pubkey) != expected.blinding_pubkey)
+        {
+            return Err(S5Error::new(
+                ErrorKind::Script,
+                &format!(
+                    "Boltz lockup address does not match. Expected {}, Found {}",
+                    expected, lockup_address
+                ),
+            ));
         }
+        Ok(())
     }
 
-    pub fn to_address(&self) -> EAddress {
-        let script = self.to_script();
+    pub fn to_address(&self) -> Result<EAddress, S5Error> {
         let address_params = match self.network {
             BitcoinNetwork::Liquid => &AddressParams::LIQUID,
             _ => &AddressParams::LIQUID_TESTNET,
         };
+        if self.version == SwapScriptVersion::Taproot {
+            let spend_info = self.taproot_spend_info()?;
+            return Ok(EAddress::p2tr_tweaked(
+                spend_info.output_key(),
+                Some(self.blinding_key.public_key()),
+                address_params,
+            ));
+        }
+        let script = self.to_script()?;
 
         match self.swap_type {
-            SwapType::Submarine => EAddress::p2shwsh(
+            SwapType::Submarine => Ok(EAddress::p2shwsh(
                 &script,
                 Some(self.blinding_key.public_key()),
                 address_params,
             )
-            .to_confidential(self.blinding_key.public_key()),
-            SwapType::ReverseSubmarine => EAddress::p2wsh(
+            .to_confidential(self.blinding_key.public_key())),
+            SwapType::ReverseSubmarine | SwapType::Chain => Ok(EAddress::p2wsh(
                 &script,
                 Some(self.blinding_key.public_key()),
                 address_params,
             )
-            .to_confidential(self.blinding_key.public_key()),
+            .to_confidential(self.blinding_key.public_key())),
+        }
+    }
+
+    /// Every output at the lockup script, unblinded and compared to `expected_value`.
+    /// Outputs that are not spendable L-BTC are listed in `UtxoReport::skipped`.
+    pub fn utxo_report(
+        &self,
+        backend: &dyn ChainBackend,
+        expected_value: u64,
+    ) -> Result<UtxoReport, S5Error> {
+        Ok(self.lockup_outputs(backend)?.report(expected_value))
+    }
+
+    #[cfg(feature = "async")]
+    pub async fn utxo_report_async(
+        &self,
+        backend: &dyn AsyncChainBackend,
+        expected_value: u64,
+    ) -> Result<UtxoReport, S5Error> {
+        Ok(self
+            .lockup_outputs_async(backend)
+            .await?
+            .report(expected_value))
+    }
+
+    fn lockup_outputs(&self, backend: &dyn ChainBackend) -> Result<LockupOutputs, S5Error> {
+        let script_pubkey = self.to_address()?.script_pubkey();
+        let utxos = backend.list_unspent(BitcoinScript::from_bytes(script_pubkey.as_bytes()))?;
+        let mut outputs = LockupOutputs::default();
+        for utxo in utxos {
+            let raw_tx = backend.get_tx(&utxo.txid)?;
+            self.unblind_utxo(utxo, &raw_tx, &mut outputs)?;
+        }
+        Ok(outputs)
+    }
+
+    #[cfg(feature = "async")]
+    async fn lockup_outputs_async(
+        &self,
+        backend: &dyn AsyncChainBackend,
+    ) -> Result<LockupOutputs, S5Error> {
+        let script_pubkey = self.to_address()?.script_pubkey();
+        let utxos = backend
+            .list_unspent(BitcoinScript::from_bytes(script_pubkey.as_bytes()))
+            .await?;
+        let mut outputs = LockupOutputs::default();
+        for utxo in utxos {
+            let raw_tx = backend.get_tx(&utxo.txid).await?;
+            self.unblind_utxo(utxo, &raw_tx, &mut outputs)?;
         }
+        Ok(outputs)
     }
+
+    /// Finds `utxo` in its funding transaction and unblinds it into `outputs`. The spendable utxo
+    /// carries the unblinded value. Explicit outputs, e.g. from a sender that did not blind, are
+    /// taken as is. Outputs the blinding key does not unblind, or of another asset than the
+    /// network's policy asset, are skipped.
+    fn unblind_utxo(
+        &self,
+        utxo: Utxo,
+        raw_tx: &[u8],
+        outputs: &mut LockupOutputs,
+    ) -> Result<(), S5Error> {
+        let tx: Transaction = match elements::encode::deserialize(raw_tx) {
+            Ok(result) => result,
+            Err(e) => return Err(S5Error::new(ErrorKind::Transaction, &e.to_string())),
+        };
+        let script_pubkey = self.to_address()?.script_pubkey();
+        let txout = match tx.output.get(utxo.vout as usize) {
+            Some(result) if result.script_pubkey == script_pubkey => result.clone(),
+            _ => {
+                return Err(S5Error::new(
+                    ErrorKind::Transaction,
+                    &format!(
+                        "Output {}:{} does not pay the lockup script",
+                        utxo.txid, utxo.vout
+                    ),
+                ))
+            }
+        };
+        let secrets = match (txout.asset, txout.value) {
+            (confidential::Asset::Explicit(asset), confidential::Value::Explicit(value)) => {
+                TxOutSecrets::new(
+                    asset,
+                    AssetBlindingFactor::zero(),
+                    value,
+                    ValueBlindingFactor::zero(),
+                )
+            }
+            _ => match txout.unblind(&Secp256k1::new(), self.blinding_key.secret_key()) {
+                Ok(result) => result,
+                Err(e) => {
+                    outputs.skip(utxo, &format!("cannot unblind output: {}", e));
+                    return Ok(());
+                }
+            },
+        };
+        if secrets.asset != policy_asset(&self.network) {
+            let reason = format!("output is of asset {}, not L-BTC", secrets.asset);
+            outputs.skip(utxo, &reason);
+            return Ok(());
+        }
+        let value = secrets.value;
+        let output = LockupOutput {
+            outpoint: OutPoint::new(tx.txid(), utxo.vout),
+            txout,
+            secrets,
+        };
+        outputs.spendable.push((Utxo { value, ..utxo }, output));
+        Ok(())
+    }
+}
+
+/// The L-BTC asset of `network`.
+fn policy_asset(network: &BitcoinNetwork) -> AssetId {
+    let asset_id = match network {
+        BitcoinNetwork::Liquid => _LIQUID_POLICY_ASSET_STR,
+        _ => LIQUID_TESTNET_POLICY_ASSET_STR,
+    };
+    AssetId::from_str(asset_id).expect("valid policy asset constant")
 }
 
-fn bytes_to_u32_little_endian(bytes: &[u8]) -> u32 {
-    let mut result = 0u32;
-    for (i, &byte) in bytes.iter().enumerate() {
-        result |= (byte as u32) << (8 * i);
+fn x_only_pubkey(pubkey: &str) -> Result<secp256k1_zkp::XOnlyPublicKey, S5Error> {
+    match PublicKey::from_str(pubkey) {
+        Ok(result) => Ok(result.inner.x_only_public_key().0),
+        Err(e) => Err(S5Error::new(ErrorKind::Input, &e.to_string())),
     }
-    result
 }
+
+/// Genesis block committed to by elements taproot sighashes.
+fn genesis_hash(network: &BitcoinNetwork) -> BlockHash {
+    let genesis_hash = match network {
+        BitcoinNetwork::Liquid => LIQUID_GENESIS_HASH,
+        BitcoinNetwork::ElementsRegtest => ELEMENTS_REGTEST_GENESIS_HASH,
+        _ => LIQUID_TESTNET_GENESIS_HASH,
+    };
+    BlockHash::from_str(genesis_hash).expect("valid genesis hash constant")
+}
+
+type ElementsSig = (secp256k1_zkp::ecdsa::Signature, elements::EcdsaSighashType);
+
+fn elementssig_to_rawsig(sig: &ElementsSig) -> Vec<u8> {
+    let ser_sig = sig.0.serialize_der();
+    let mut raw_sig = Vec::from(&ser_sig[..]);
+    raw_sig.push(sig.1 as u8);
+    raw_sig
+}
+
 fn u32_to_bytes_little_endian(value: u32) -> [u8; 4] {
     let b1: u8 = (value & 0xff) as u8;
     let b2: u8 = ((value >> 8) & 0xff) as u8;
@@ -344,22 +639,107 @@ fn u32_to_bytes_little_endian(value: u32) -> [u8; 4] {
     [b1, b2, b3, b4]
 }
 
+/// Serialized size of a CompactSize length prefix.
+fn varint_len(n: u64) -> u64 {
+    match n {
+        0..=0xfc => 1,
+        0xfd..=0xffff => 3,
+        0x10000..=0xffff_ffff => 5,
+        _ => 9,
+    }
+}
+
+/// Elements serializes the witness flag even without witnesses, so the base size is that of the
+/// transaction with all input and output witnesses cleared.
+fn tx_weight(tx: &Transaction) -> u64 {
+    let mut stripped = tx.clone();
+    for input in stripped.input.iter_mut() {
+        input.witness = TxInWitness::default();
+    }
+    for output in stripped.output.iter_mut() {
+        output.witness = TxOutWitness::default();
+    }
+    (serialize(&stripped).len() * 3 + serialize(tx).len()) as u64
+}
+
+// low-R DER signature of at most 71 bytes, plus the sighash type
+const MAX_ECDSA_SIG_SIZE: usize = 72;
+const SCHNORR_SIG_SIZE: usize = 64;
+const PREIMAGE_SIZE: usize = 32;
+
+/// A lockup output and the secrets that unblind it.
+#[derive(Debug, Clone)]
+struct LockupOutput {
+    outpoint: OutPoint,
+    // the output itself, sighashes commit to its value commitment (and taproot ones to all of it)
+    txout: TxOut,
+    secrets: TxOutSecrets,
+}
+
+/// The outputs at a lockup script, split into spendable L-BTC and the rest.
+#[derive(Debug, Default)]
+struct LockupOutputs {
+    spendable: Vec<(Utxo, LockupOutput)>,
+    skipped: Vec<SkippedUtxo>,
+}
+
+impl LockupOutputs {
+    fn skip(&mut self, utxo: Utxo, reason: &str) {
+        self.skipped.push(SkippedUtxo {
+            utxo,
+            reason: reason.to_string(),
+        });
+    }
+
+    fn report(&self, expected_value: u64) -> UtxoReport {
+        UtxoReport {
+            expected_value: Some(expected_value),
+            utxos: self
+                .spendable
+                .iter()
+                .map(|(utxo, _)| utxo.clone())
+                .collect(),
+            skipped: self.skipped.clone(),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct LBtcSwapTx {
     kind: SwapTxKind,
     swap_script: LBtcSwapScript,
     output_address: Address,
-    absolute_fees: u32,
-    utxo: Option<OutPoint>,
-    utxo_value: Option<u64>, // there should only ever be one outpoint in a swap
-    txout_secrets: Option<TxOutSecrets>,
+    fee: Fee,
+    utxos: Vec<LockupOutput>,
+    utxo_policy: UtxoPolicy,
 }
 
 impl LBtcSwapTx {
-    pub fn manual_utxo_update(&mut self, utxo: OutPoint, value: u64) -> LBtcSwapTx {
-        self.utxo = Some(utxo);
-        self.utxo_value = Some(value);
-        self.clone()
+    /// Sets an explicit (unblinded) lockup output of the network's policy asset,
+    /// instead of discovering the outputs in `drain`.
+    pub fn manual_utxo_update(
+        &mut self,
+        utxo: OutPoint,
+        value: u64,
+    ) -> Result<LBtcSwapTx, S5Error> {
+        let asset_id = policy_asset(&self.swap_script.network);
+        self.utxos = vec![LockupOutput {
+            outpoint: utxo,
+            txout: TxOut {
+                asset: confidential::Asset::Explicit(asset_id),
+                value: confidential::Value::Explicit(value),
+                nonce: confidential::Nonce::Null,
+                script_pubkey: self.lockup_script_pubkey()?,
+                witness: TxOutWitness::default(),
+            },
+            secrets: TxOutSecrets::new(
+                asset_id,
+                AssetBlindingFactor::zero(),
+                value,
+                ValueBlindingFactor::zero(),
+            ),
+        }];
+        Ok(self.clone())
     }
     pub fn new_claim(
         swap_script: LBtcSwapScript,
@@ -374,10 +754,9 @@ impl LBtcSwapTx {
             kind: SwapTxKind::Claim,
             swap_script: swap_script,
             output_address: address,
-            absolute_fees,
-            utxo: None,
-            utxo_value: None,
-            txout_secrets: None,
+            fee: Fee::Absolute(absolute_fees as u64),
+            utxos: vec![],
+            utxo_policy: UtxoPolicy::Exact,
         })
     }
     pub fn new_refund(
@@ -394,130 +773,903 @@ impl LBtcSwapTx {
             kind: SwapTxKind::Refund,
             swap_script: swap_script,
             output_address: address,
-            absolute_fees,
-            utxo: None,
-            utxo_value: None,
-            txout_secrets: None,
+            fee: Fee::Absolute(absolute_fees as u64),
+            utxos: vec![],
+            utxo_policy: UtxoPolicy::Exact,
         })
     }
 
-    pub fn drain(&mut self, keys: ZKKeyPair, preimage: Preimage) -> Result<Transaction, S5Error> {
-        self.fetch_utxo();
-        if !self.has_utxo() {
-            return Err(S5Error::new(
-                ErrorKind::Transaction,
-                "No utxos available yet",
-            ));
-        }
-        match self.kind {
-            SwapTxKind::Claim => Ok(self.sign_claim_tx(keys, preimage)),
-            SwapTxKind::Refund => {
-                self.sign_refund_tx(keys);
-                Err(S5Error::new(
-                    ErrorKind::Transaction,
-                    "Refund transaction signing not supported yet",
-                ))
-            }
-        }
-        // let sweep_psbt = Psbt::from_unsigned_tx(sweep_tx);
+    /// Replaces the fee given on construction, e.g. with a fee rate from `Fee::from_backend`.
+    pub fn set_fee(&mut self, fee: Fee) {
+        self.fee = fee;
     }
 
-    fn fetch_utxo(&mut self) -> () {
-        let electrum_client = NetworkConfig::default_liquid()
-            .electrum_url
-            .build_client()
-            .unwrap();
-        let address = self.swap_script.to_address();
-        let history = electrum_client
-            .script_get_history(BitcoinScript::from_bytes(
-                self.swap_script.to_script().to_v0_p2wsh().as_bytes(),
-            ))
-            .unwrap();
-        let bitcoin_txid = history.first().unwrap().tx_hash;
-        let raw_tx = electrum_client.transaction_get_raw(&bitcoin_txid).unwrap();
-        let tx: Transaction = elements::encode::deserialize(&raw_tx).unwrap();
-        // println!("TXID: {}", tx.txid());
-        // WRITE TX TO FILE
-        let manifest_dir = env!("CARGO_MANIFEST_DIR");
-        let file_path = Path::new(manifest_dir).join("tx.previous");
-        let mut file = File::create(file_path).unwrap();
-        use std::io::Write;
-        writeln!(file, "{:#?}", tx.clone()).unwrap();
-        // WRITE TX TO FILE
-        let mut vout = 0;
-        for output in tx.clone().output {
-            if output.script_pubkey == address.script_pubkey() {
-                let zksecp = Secp256k1::new();
-                println!("FOUND SPENDABLE OUTPUT!\nvout: {:?}", vout);
-
-                let unblinded = output
-                    .unblind(&zksecp, self.swap_script.blinding_key.secret_key())
-                    .unwrap();
-                // println!("{:?}", unblinded);
-                let el_txid = tx.clone().txid();
-                let outpoint_0 = OutPoint::new(el_txid, vout);
-                let utxo_value = unblinded.value;
-                println!("VALUE:{:?}", utxo_value);
-
-                self.utxo = Some(outpoint_0);
-                self.utxo_value = Some(utxo_value);
-                self.txout_secrets = Some(unblinded);
-                break;
-            }
-            vout += 1;
-        }
-        ()
-    }
-    fn has_utxo(&self) -> bool {
-        self.utxo.is_some() && self.utxo_value.is_some()
+    /// Which lockup outputs `drain` spends. Defaults to `UtxoPolicy::Exact`.
+    pub fn set_utxo_policy(&mut self, utxo_policy: UtxoPolicy) {
+        self.utxo_policy = utxo_policy;
     }
 
-    pub fn _check_utxo_value(&self, expected_value: u64) -> bool {
-        self.has_utxo() && self.utxo_value.unwrap() == expected_value
+    /// The lockup outputs spent by the last `drain`.
+    pub fn outpoints(&self) -> Vec<OutPoint> {
+        self.utxos.iter().map(|utxo| utxo.outpoint).collect()
     }
 
-    fn sign_claim_tx(&self, keys: KeyPair, preimage: Preimage) -> Transaction {
-        let sequence = Sequence::from_consensus(0xFFFFFFFF);
-        let unsigned_input: TxIn = TxIn {
-            sequence: sequence,
-            previous_output: self.utxo.unwrap(),
-            script_sig: Script::new(),
-            witness: TxInWitness::default(),
+    /// Discounted weight (ELIP 200) of the signed transaction, which fee rates apply to: the blinded
+    /// output counts as if it were explicit, without its rangeproof and surjection proof.
+    /// Signatures are counted at their largest size. `cooperative` selects the key-path spend of
+    /// a taproot lockup.
+    pub fn discount_weight(&self, cooperative: bool) -> Result<u64, S5Error> {
+        let (script_sig, script_witness) = self.placeholder_witness(cooperative)?;
+        // any asset id has the same size
+        let asset_id = AssetId::from_str(LIQUID_TESTNET_POLICY_ASSET_STR)
+            .expect("valid policy asset constant");
+        let input = TxIn {
+            previous_output: OutPoint::default(),
+            script_sig,
+            sequence: Sequence::MAX,
+            witness: TxInWitness {
+                amount_rangeproof: None,
+                inflation_keys_rangeproof: None,
+                script_witness,
+                pegin_witness: vec![],
+            },
             is_pegin: false,
             asset_issuance: AssetIssuance::default(),
         };
+        let tx = Transaction {
+            version: 2,
+            lock_time: LockTime::ZERO,
+            input: vec![input; self.utxos.len().max(1)],
+            output: vec![
+                TxOut {
+                    asset: confidential::Asset::Explicit(asset_id),
+                    value: confidential::Value::Explicit(0),
+                    nonce: confidential::Nonce::Null,
+                    script_pubkey: self.output_address.script_pubkey(),
+                    witness: TxOutWitness::default(),
+                },
+                TxOut::new_fee(0, asset_id),
+            ],
+        };
+        Ok(tx_weight(&tx))
+    }
 
-        use bitcoin::secp256k1::rand::rngs::OsRng;
-        let mut rng = OsRng::default();
-        let secp = Secp256k1::new();
+    /// Full weight of the signed transaction, including the blinded output's commitments,
+    /// rangeproof (`DEFAULT_RANGEPROOF_SIZE`) and surjection proof (`DEFAULT_SURJECTIONPROOF_SIZE`).
+    pub fn weight(&self, cooperative: bool) -> Result<u64, S5Error> {
+        // value commitment of 33 instead of 9 bytes, nonce of 33 instead of 1
+        let commitments = (24 + 32) * 4;
+        // the proofs replace two empty ones
+        let proofs = varint_len(DEFAULT_RANGEPROOF_SIZE)
+            + DEFAULT_RANGEPROOF_SIZE
+            + varint_len(DEFAULT_SURJECTIONPROOF_SIZE)
+            + DEFAULT_SURJECTIONPROOF_SIZE
+            - 2;
+        Ok(self.discount_weight(cooperative)? + commitments + proofs)
+    }
 
-        let asset_id = self.txout_secrets.unwrap().asset;
-        let out_abf = AssetBlindingFactor::new(&mut rng);
-        let exp_asset = confidential::Asset::Explicit(asset_id);
-        let inp_txout_secrets = self.txout_secrets.unwrap();
+    /// The absolute fee this transaction pays, given its discounted weight.
+    pub fn fee(&self, cooperative: bool) -> Result<u64, S5Error> {
+        Ok(self.fee.absolute(self.discount_weight(cooperative)?))
+    }
 
-        let (blinded_asset, asset_surjection_proof) = exp_asset
-            .blind(&mut rng, &secp, out_abf, &[inp_txout_secrets])
-            .unwrap();
+    /// scriptSig and witness stack of a signed input, with placeholders of the same size.
+    fn placeholder_witness(&self, cooperative: bool) -> Result<(Script, Vec<Vec<u8>>), S5Error> {
+        match self.swap_script.version {
+            SwapScriptVersion::Taproot if cooperative => {
+                Ok((Script::new(), vec![vec![0; SCHNORR_SIG_SIZE]]))
+            }
+            SwapScriptVersion::Taproot => {
+                let (leaf, preimage) = match self.kind {
+                    SwapTxKind::Claim => (self.swap_script.claim_leaf()?, Some(PREIMAGE_SIZE)),
+                    SwapTxKind::Refund => (self.swap_script.refund_leaf()?, None),
+                };
+                let mut witness = vec![vec![0; SCHNORR_SIG_SIZE]];
+                if let Some(size) = preimage {
+                    witness.push(vec![0; size]);
+                }
+                witness.push(leaf.as_bytes().to_vec());
+                witness.push(self.control_block(&leaf)?.serialize());
+                Ok((Script::new(), witness))
+            }
+            SwapScriptVersion::Legacy => {
+                let redeem_script = self.swap_script.to_script()?;
+                match self.kind {
+                    SwapTxKind::Claim => Ok((
+                        Script::new(),
+                        vec![
+                            vec![0; MAX_ECDSA_SIG_SIZE],
+                            vec![0; PREIMAGE_SIZE],
+                            redeem_script.as_bytes().to_vec(),
+                        ],
+                    )),
+                    SwapTxKind::Refund => Ok((
+                        self.refund_script_sig(&redeem_script),
+                        vec![
+                            vec![0; MAX_ECDSA_SIG_SIZE],
+                            vec![],
+                            redeem_script.as_bytes().to_vec(),
+                        ],
+                    )),
+                }
+            }
+        }
+    }
 
-        let output_value = self.utxo_value.unwrap() - self.absolute_fees as u64;
-        println!(
-            "OUTPUT_VALUE: {}\nOUTPUT_FEE: {}",
-            output_value, self.absolute_fees as u64
-        );
-        // let out_vbf = ValueBlindingFactor::new(&mut rng);
+    /// Spends the lockup outputs `utxo_policy` selects, compared to the unblinded
+    /// `expected_utxo_value`.
+    pub fn drain(
+        &mut self,
+        backend: &dyn ChainBackend,
+        keys: ZKKeyPair,
+        preimage: Preimage,
+        expected_utxo_value: u64,
+    ) -> Result<Transaction, S5Error> {
+        self.fetch_utxo(backend, expected_utxo_value)?;
+        self.sign(keys, preimage)
+    }
 
-        let final_vbf = ValueBlindingFactor::last(
-            &secp,
-            output_value,
+    #[cfg(feature = "async")]
+    pub async fn drain_async(
+        &mut self,
+        backend: &dyn AsyncChainBackend,
+        keys: ZKKeyPair,
+        preimage: Preimage,
+        expected_utxo_value: u64,
+    ) -> Result<Transaction, S5Error> {
+        let outputs = self.swap_script.lockup_outputs_async(backend).await?;
+        self.set_utxo(outputs, expected_utxo_value)?;
+        self.sign(keys, preimage)
+    }
+
+    fn sign(&self, keys: ZKKeyPair, preimage: Preimage) -> Result<Transaction, S5Error> {
+        if !self.has_utxo() {
+            return Err(S5Error::new(
+                ErrorKind::Transaction,
+                "No utxos available yet",
+            ));
+        }
+        match (&self.kind, self.swap_script.version) {
+            (SwapTxKind::Claim, SwapScriptVersion::Legacy) => self.sign_claim_tx(keys, preimage),
+            (SwapTxKind::Refund, SwapScriptVersion::Legacy) => self.sign_refund_tx(keys),
+            (SwapTxKind::Claim, SwapScriptVersion::Taproot) => {
+                let preimage = match preimage.bytes {
+                    Some(result) => result,
+                    None => return Err(S5Error::new(ErrorKind::Input, "Claim requires preimage.")),
+                };
+                self.sign_taproot_script_path(keys, self.leaf()?, Some(preimage.to_vec()))
+            }
+            (SwapTxKind::Refund, SwapScriptVersion::Taproot) => {
+                self.sign_taproot_script_path(keys, self.leaf()?, None)
+            }
+        }
+    }
+
+    /// Like drain, but returns the unsigned, blinded script-path spend as a PSET instead of
+    /// signing it, for keys held by an external signer. Pass the signed PSET to `finalize_pset`.
+    pub fn pset(
+        &mut self,
+        backend: &dyn ChainBackend,
+        expected_utxo_value: u64,
+    ) -> Result<Pset, S5Error> {
+        self.fetch_utxo(backend, expected_utxo_value)?;
+        self.to_pset()
+    }
+
+    #[cfg(feature = "async")]
+    pub async fn pset_async(
+        &mut self,
+        backend: &dyn AsyncChainBackend,
+        expected_utxo_value: u64,
+    ) -> Result<Pset, S5Error> {
+        let outputs = self.swap_script.lockup_outputs_async(backend).await?;
+        self.set_utxo(outputs, expected_utxo_value)?;
+        self.to_pset()
+    }
+
+    /// The script-path spend of the current lockup outputs, with the payment output blinded
+    /// with the unblinded secrets of the inputs. Every input carries `witness_utxo`, its
+    /// unblinded `amount` and `asset`, and `in_utxo_rangeproof` when the utxo is confidential.
+    /// Legacy inputs add `witness_script`, and `redeem_script` for nested segwit lockups.
+    /// Taproot inputs add the leaf to sign in `tap_scripts`.
+    /// Blinding fixes the outputs, so sign the PSET as returned, see `pset_sighashes`.
+    pub fn to_pset(&self) -> Result<Pset, S5Error> {
+        if !self.has_utxo() {
+            return Err(S5Error::new(
+                ErrorKind::Transaction,
+                "No utxos available yet",
+            ));
+        }
+        let fee = self.fee(false)?;
+        let utxo_value = self.utxo_total();
+        if utxo_value <= fee {
+            return Err(S5Error::new(
+                ErrorKind::Transaction,
+                &format!("Fees exceed utxo value. Fees {}, Utxo {}", fee, utxo_value),
+            ));
+        }
+        let asset_id = self.lockup_asset()?;
+        let blinding_pubkey = self.blinding_pubkey()?;
+
+        let (_, lock_time, sequence) = self.script_path_params()?;
+        let mut pset = Pset::new_v2();
+        pset.global.tx_data.fallback_locktime = Some(lock_time);
+        for utxo in self.utxos.iter() {
+            let mut input = PsetInput::from_prevout(utxo.outpoint);
+            input.sequence = Some(sequence);
+            input.witness_utxo = Some(utxo.txout.clone());
+            // the unblinded amount and asset let the signer check the fee, the rangeproof lets it
+            // unblind the utxo itself
+            input.amount = Some(utxo.secrets.value);
+            input.asset = Some(utxo.secrets.asset);
+            input.in_utxo_rangeproof = utxo.txout.witness.rangeproof.clone();
+            match self.swap_script.version {
+                SwapScriptVersion::Legacy => {
+                    let redeem_script = self.swap_script.to_script()?;
+                    if self.swap_script.swap_type == SwapType::Submarine {
+                        input.redeem_script = Some(redeem_script.to_v0_p2wsh());
+                    }
+                    input.witness_script = Some(redeem_script);
+                }
+                SwapScriptVersion::Taproot => {
+                    let spend_info = self.swap_script.taproot_spend_info()?;
+                    let leaf = self.leaf()?;
+                    input
+                        .tap_scripts
+                        .insert(self.control_block(&leaf)?, (leaf, LeafVersion::default()));
+                    input.tap_internal_key = Some(spend_info.internal_key());
+                    input.tap_merkle_root = spend_info.merkle_root();
+                }
+            }
+            pset.add_input(input);
+        }
+
+        // the first input's owner blinds, which is us for every input
+        let mut payment_output = PsetOutput::new_explicit(
+            self.output_address.script_pubkey(),
+            utxo_value - fee,
+            asset_id,
+            Some(PublicKey::new(blinding_pubkey)),
+        );
+        payment_output.blinder_index = Some(0);
+        pset.add_output(payment_output);
+        pset.add_output(PsetOutput::new_explicit(Script::new(), fee, asset_id, None));
+
+        let inp_txout_secrets: HashMap<usize, TxOutSecrets> = self
+            .utxos
+            .iter()
+            .enumerate()
+            .map(|(index, utxo)| (index, utxo.secrets))
+            .collect();
+        let secp = Secp256k1::new();
+        use bitcoin::secp256k1::rand::rngs::OsRng;
+        let mut rng = OsRng::default();
+        if let Err(e) = pset.blind_last(&mut rng, &secp, &inp_txout_secrets) {
+            return Err(S5Error::new(ErrorKind::Transaction, &e.to_string()));
+        }
+        Ok(pset)
+    }
+
+    /// The message each input of a PSET from `to_pset` signs, for signers that cannot compute
+    /// Elements sighashes themselves: segwit v0 with SIGHASH_ALL for legacy lockups, or the
+    /// default taproot sighash of the leaf.
+    pub fn pset_sighashes(&self, pset: &Pset) -> Result<Vec<Message>, S5Error> {
+        let tx = self.extract_unsigned(pset)?;
+        let prevouts: Vec<TxOut> = self.utxos.iter().map(|utxo| utxo.txout.clone()).collect();
+        let mut sighash_cache = SighashCache::new(&tx);
+        let mut sighashes = vec![];
+        for (index, utxo) in self.utxos.iter().enumerate() {
+            let sighash = match self.swap_script.version {
+                SwapScriptVersion::Legacy => sighash_cache.segwitv0_sighash(
+                    index,
+                    &self.swap_script.to_script()?,
+                    utxo.txout.value,
+                    elements::EcdsaSighashType::All,
+                )[..]
+                    .to_vec(),
+                SwapScriptVersion::Taproot => {
+                    match sighash_cache.taproot_script_spend_signature_hash(
+                        index,
+                        &Prevouts::All(&prevouts),
+                        TapLeafHash::from_script(&self.leaf()?, LeafVersion::default()),
+                        SchnorrSighashType::Default,
+                        genesis_hash(&self.swap_script.network),
+                    ) {
+                        Ok(result) => result[..].to_vec(),
+                        Err(e) => return Err(S5Error::new(ErrorKind::Transaction, &e.to_string())),
+                    }
+                }
+            };
+            match Message::from_slice(&sighash) {
+                Ok(result) => sighashes.push(result),
+                Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+            }
+        }
+        Ok(sighashes)
+    }
+
+    /// Builds the witnesses of a PSET from `to_pset` once it is signed: every input needs a
+    /// signature of the receiver key for claims, or of the sender key for refunds, in
+    /// `partial_sigs` (legacy) or `tap_script_sigs` (taproot).
+    /// Signatures are checked first. `preimage` is required for claims and rejected for refunds.
+    pub fn finalize_pset(
+        &self,
+        mut pset: Pset,
+        preimage: Option<&Preimage>,
+    ) -> Result<Transaction, S5Error> {
+        let sighashes = self.pset_sighashes(&pset)?;
+        let (pubkey, preimage) = match (&self.kind, preimage) {
+            (SwapTxKind::Claim, Some(preimage)) => match preimage.bytes {
+                Some(_) if preimage.hash160.to_string() != self.swap_script.hashlock => {
+                    return Err(S5Error::new(
+                        ErrorKind::Input,
+                        "Preimage does not match the hashlock.",
+                    ))
+                }
+                Some(result) => (&self.swap_script.reciever_pubkey, Some(result.to_vec())),
+                None => return Err(S5Error::new(ErrorKind::Input, "Claim requires preimage.")),
+            },
+            (SwapTxKind::Claim, None) => {
+                return Err(S5Error::new(ErrorKind::Input, "Claim requires preimage."))
+            }
+            (SwapTxKind::Refund, None) => (&self.swap_script.sender_pubkey, None),
+            (SwapTxKind::Refund, Some(_)) => {
+                return Err(S5Error::new(
+                    ErrorKind::Input,
+                    "Refunds spend the timeout branch and take no preimage.",
+                ))
+            }
+        };
+        let pubkey = match PublicKey::from_str(pubkey) {
+            Ok(result) => result,
+            Err(e) => return Err(S5Error::new(ErrorKind::Key, &e.to_string())),
+        };
+
+        let secp = Secp256k1::verification_only();
+        let (script_sig, _, _) = self.script_path_params()?;
+        let mut witnesses = vec![];
+        for (index, sighash) in sighashes.iter().enumerate() {
+            let input = &pset.inputs()[index];
+            let mut witness = vec![];
+            match self.swap_script.version {
+                SwapScriptVersion::Legacy => {
+                    let signature = match input.partial_sigs.get(&pubkey) {
+                        Some(result) => result.clone(),
+                        None => {
+                            return Err(S5Error::new(
+                                ErrorKind::Transaction,
+                                &format!("Input {} is not signed by {}", index, pubkey),
+                            ))
+                        }
+                    };
+                    let valid = match signature.split_last() {
+                        Some((&sighash_type, der)) if sighash_type == 0x01 => {
+                            match secp256k1_zkp::ecdsa::Signature::from_der(der) {
+                                Ok(sig) => secp.verify_ecdsa(sighash, &sig, &pubkey.inner).is_ok(),
+                                Err(_) => false,
+                            }
+                        }
+                        _ => false,
+                    };
+                    if !valid {
+                        return Err(S5Error::new(
+                            ErrorKind::Transaction,
+                            &format!("Invalid signature for input {}", index),
+                        ));
+                    }
+                    // an empty preimage fails the HASH160 check and selects the ELSE branch
+                    witness.push(signature);
+                    witness.push(preimage.clone().unwrap_or_default());
+                    witness.push(self.swap_script.to_script()?.as_bytes().to_vec());
+                }
+                SwapScriptVersion::Taproot => {
+                    let leaf = self.leaf()?;
+                    let leaf_hash = TapLeafHash::from_script(&leaf, LeafVersion::default());
+                    let x_only = pubkey.inner.x_only_public_key().0;
+                    let signature = match input.tap_script_sigs.get(&(x_only, leaf_hash)) {
+                        Some(result) => *result,
+                        None => {
+                            return Err(S5Error::new(
+                                ErrorKind::Transaction,
+                                &format!("Input {} is not signed by {}", index, x_only),
+                            ))
+                        }
+                    };
+                    if signature.hash_ty != SchnorrSighashType::Default
+                        || secp
+                            .verify_schnorr(&signature.sig, sighash, &x_only)
+                            .is_err()
+                    {
+                        return Err(S5Error::new(
+                            ErrorKind::Transaction,
+                            &format!("Invalid signature for input {}", index),
+                        ));
+                    }
+                    witness.push(signature.sig.as_ref().to_vec());
+                    if let Some(preimage) = &preimage {
+                        witness.push(preimage.clone());
+                    }
+                    witness.push(leaf.as_bytes().to_vec());
+                    witness.push(self.control_block(&leaf)?.serialize());
+                }
+            }
+            witnesses.push(witness);
+        }
+
+        // BIP174 finalizer: only the final fields remain
+        for (input, witness) in pset.inputs_mut().iter_mut().zip(witnesses) {
+            let mut finalized = PsetInput::from_prevout(OutPoint::new(
+                input.previous_txid,
+                input.previous_output_index,
+            ));
+            finalized.sequence = input.sequence;
+            finalized.witness_utxo = input.witness_utxo.take();
+            if !script_sig.is_empty() {
+                finalized.final_script_sig = Some(script_sig.clone());
+            }
+            finalized.final_script_witness = Some(witness);
+            *input = finalized;
+        }
+        self.extract_unsigned(&pset)
+    }
+
+    /// The transaction of a PSET, after checking it spends the current lockup outputs.
+    fn extract_unsigned(&self, pset: &Pset) -> Result<Transaction, S5Error> {
+        let tx = match pset.extract_tx() {
+            Ok(result) => result,
+            Err(e) => return Err(S5Error::new(ErrorKind::Transaction, &e.to_string())),
+        };
+        let spent: Vec<OutPoint> = tx.input.iter().map(|input| input.previous_output).collect();
+        if !self.has_utxo() || spent != self.outpoints() {
+            return Err(S5Error::new(
+                ErrorKind::Input,
+                "PSET does not spend the swap utxos.",
+            ));
+        }
+        Ok(tx)
+    }
+
+    /// The leaf a script-path spend of a taproot lockup uses.
+    fn leaf(&self) -> Result<EScript, S5Error> {
+        match self.kind {
+            SwapTxKind::Claim => self.swap_script.claim_leaf(),
+            SwapTxKind::Refund => self.swap_script.refund_leaf(),
+        }
+    }
+
+    /// scriptSig, locktime and sequence of a script-path spend.
+    /// Refunds use a non-final sequence, so that nLockTime is enforced.
+    fn script_path_params(&self) -> Result<(Script, LockTime, Sequence), S5Error> {
+        let timelock = LockTime::from_consensus(self.swap_script.timelock);
+        match (&self.kind, self.swap_script.version) {
+            (SwapTxKind::Claim, SwapScriptVersion::Legacy) => {
+                Ok((Script::new(), timelock, Sequence::MAX))
+            }
+            (SwapTxKind::Claim, SwapScriptVersion::Taproot) => {
+                Ok((Script::new(), LockTime::ZERO, Sequence::MAX))
+            }
+            (SwapTxKind::Refund, SwapScriptVersion::Legacy) => Ok((
+                self.refund_script_sig(&self.swap_script.to_script()?),
+                timelock,
+                Sequence::from_consensus(0xFFFFFFFE),
+            )),
+            (SwapTxKind::Refund, SwapScriptVersion::Taproot) => Ok((
+                Script::new(),
+                timelock,
+                Sequence::from_consensus(0xFFFFFFFE),
+            )),
+        }
+    }
+
+    /// submarine lockups are p2shwsh, reverse lockups are p2wsh, taproot lockups are p2tr
+    fn lockup_script_pubkey(&self) -> Result<Script, S5Error> {
+        Ok(self.swap_script.to_address()?.script_pubkey())
+    }
+
+    /// Like drain, but first tries a cooperative key-path spend with Boltz.
+    /// If Boltz does not cooperate the script path is signed instead and the reason is returned
+    /// with it. A refund only falls back once the chain tip has reached the timelock.
+    pub fn drain_cooperative(
+        &mut self,
+        backend: &dyn ChainBackend,
+        keys: ZKKeyPair,
+        preimage: Preimage,
+        expected_utxo_value: u64,
+        boltz: &BoltzApiClientV2,
+        swap_id: &str,
+    ) -> Result<CooperativeSpend<Transaction>, S5Error> {
+        self.fetch_utxo(backend, expected_utxo_value)?;
+        match self.sign_cooperative(keys, preimage.clone(), boltz, swap_id) {
+            Ok(tx) => Ok(CooperativeSpend {
+                tx,
+                cooperative_error: None,
+            }),
+            Err(e) => fall_back(
+                &self.kind,
+                self.swap_script.timelock,
+                backend.tip_height()?,
+                e,
+                || self.sign(keys, preimage),
+            ),
+        }
+    }
+
+    /// Signs a key-path spend of a taproot lockup together with Boltz (MuSig2).
+    /// Legacy lockups have no key path and are signed on the script path.
+    /// Errors if Boltz does not cooperate or sends an invalid partial signature; `drain_cooperative`
+    /// falls back to the script path instead.
+    pub fn sign_cooperative(
+        &self,
+        keys: ZKKeyPair,
+        preimage: Preimage,
+        boltz: &BoltzApiClientV2,
+        swap_id: &str,
+    ) -> Result<Transaction, S5Error> {
+        if !self.has_utxo() {
+            return Err(S5Error::new(
+                ErrorKind::Transaction,
+                "No utxos available yet",
+            ));
+        }
+        if self.swap_script.version != SwapScriptVersion::Taproot {
+            return self.sign(keys, preimage);
+        }
+        self.sign_taproot_key_path(keys, &preimage, boltz, swap_id)
+    }
+
+    /// Witness: <aggregate schnorr signature>
+    fn sign_taproot_key_path(
+        &self,
+        keys: ZKKeyPair,
+        preimage: &Preimage,
+        boltz: &BoltzApiClientV2,
+        swap_id: &str,
+    ) -> Result<Transaction, S5Error> {
+        let (mut tx, prevouts) =
+            self.taproot_unsigned_tx(LockTime::ZERO, Sequence::MAX, self.fee(true)?)?;
+        let spend_info = self.swap_script.taproot_spend_info()?;
+        let tweak = match <[u8; 32]>::try_from(&spend_info.tap_tweak()[..]) {
+            Ok(bytes) => musig::scalar_mod_n(bytes),
+            Err(e) => return Err(S5Error::new(ErrorKind::Script, &e.to_string())),
+        };
+
+        let mut sighash_cache = SighashCache::new(&tx);
+        let mut sighashes = vec![];
+        for index in 0..prevouts.len() {
+            let sighash = match sighash_cache.taproot_key_spend_signature_hash(
+                index,
+                &Prevouts::All(&prevouts),
+                SchnorrSighashType::Default,
+                genesis_hash(&self.swap_script.network),
+            ) {
+                Ok(result) => result,
+                Err(e) => return Err(S5Error::new(ErrorKind::Transaction, &e.to_string())),
+            };
+            match <[u8; 32]>::try_from(&sighash[..]) {
+                Ok(result) => sighashes.push(result),
+                Err(e) => return Err(S5Error::new(ErrorKind::Transaction, &e.to_string())),
+            };
+        }
+        let signer = CooperativeSigner {
+            boltz,
+            swap_id,
+            swap_type: self.swap_script.swap_type.clone(),
+            kind: self.kind.clone(),
+        };
+        let signatures = signer.sign_inputs(
+            &keys,
+            preimage,
+            &self.swap_script.musig_pubkeys()?,
+            tweak,
+            &hex::encode(serialize(&tx)),
+            &sighashes,
+        )?;
+
+        for (input, signature) in tx.input.iter_mut().zip(signatures) {
+            input.witness = TxInWitness {
+                amount_rangeproof: None,
+                inflation_keys_rangeproof: None,
+                script_witness: vec![signature.as_ref().to_vec()],
+                pegin_witness: vec![],
+            };
+        }
+        Ok(tx)
+    }
+    fn fetch_utxo(
+        &mut self,
+        backend: &dyn ChainBackend,
+        expected_value: u64,
+    ) -> Result<(), S5Error> {
+        let outputs = self.swap_script.lockup_outputs(backend)?;
+        self.set_utxo(outputs, expected_value)
+    }
+
+    /// Keeps the outputs `utxo_policy` selects.
+    fn set_utxo(&mut self, outputs: LockupOutputs, expected_value: u64) -> Result<(), S5Error> {
+        let report = outputs.report(expected_value);
+        let selected = report.select(self.utxo_policy)?;
+        self.utxos = outputs
+            .spendable
+            .into_iter()
+            .filter(|(utxo, _)| selected.contains(utxo))
+            .map(|(_, output)| output)
+            .collect();
+        Ok(())
+    }
+    fn has_utxo(&self) -> bool {
+        !self.utxos.is_empty()
+    }
+
+    pub fn _check_utxo_value(&self, expected_value: u64) -> bool {
+        self.has_utxo() && self.utxo_total() == expected_value
+    }
+
+    fn utxo_total(&self) -> u64 {
+        self.utxos.iter().map(|utxo| utxo.secrets.value).sum()
+    }
+
+    /// One input per lockup output, in the order of `utxos`.
+    fn inputs(&self, script_sig: Script, sequence: Sequence) -> Vec<TxIn> {
+        self.utxos
+            .iter()
+            .map(|utxo| TxIn {
+                previous_output: utxo.outpoint,
+                script_sig: script_sig.clone(),
+                sequence,
+                witness: TxInWitness::default(),
+                is_pegin: false,
+                asset_issuance: AssetIssuance::default(),
+            })
+            .collect()
+    }
+    fn sign_claim_tx(&self, keys: KeyPair, preimage: Preimage) -> Result<Transaction, S5Error> {
+        let preimage = match preimage.bytes {
+            Some(result) => result,
+            None => return Err(S5Error::new(ErrorKind::Input, "Claim requires preimage.")),
+        };
+        let secp = Secp256k1::new();
+        let (payment_output, fee_output) = self.blinded_outputs(&secp, self.fee(false)?)?;
+
+        let (script_sig, lock_time, sequence) = self.script_path_params()?;
+        let unsigned_tx = Transaction {
+            version: 2,
+            lock_time,
+            input: self.inputs(script_sig, sequence),
+            output: vec![payment_output, fee_output],
+        };
+        // witness: <signature> <preimage> <redeem script>
+        // segwit scripts must leave exactly one element, so there is no leading dummy element
+        let redeem_script = self.swap_script.to_script()?;
+        self.sign_segwit_v0(keys, unsigned_tx, &redeem_script, |sig| {
+            vec![sig, preimage.to_vec(), redeem_script.as_bytes().to_vec()]
+        })
+    }
+    /// Spends the timeout branch of a submarine swap script.
+    /// Only valid once the chain has reached `LBtcSwapScript::timelock`.
+    fn sign_refund_tx(&self, keys: KeyPair) -> Result<Transaction, S5Error> {
+        let redeem_script = self.swap_script.to_script()?;
+        let secp = Secp256k1::new();
+        let (payment_output, fee_output) = self.blinded_outputs(&secp, self.fee(false)?)?;
+
+        let (script_sig, lock_time, sequence) = self.script_path_params()?;
+        let unsigned_tx = Transaction {
+            version: 2,
+            lock_time,
+            input: self.inputs(script_sig, sequence),
+            output: vec![payment_output, fee_output],
+        };
+        // an empty preimage fails the HASH160 check and selects the ELSE branch
+        self.sign_segwit_v0(keys, unsigned_tx, &redeem_script, |sig| {
+            vec![sig, vec![], redeem_script.as_bytes().to_vec()]
+        })
+    }
+    /// Signs every input against `redeem_script` and the value commitment of its lockup output.
+    /// `witness` builds the witness stack around the signature.
+    fn sign_segwit_v0<F>(
+        &self,
+        keys: KeyPair,
+        mut tx: Transaction,
+        redeem_script: &EScript,
+        witness: F,
+    ) -> Result<Transaction, S5Error>
+    where
+        F: Fn(Vec<u8>) -> Vec<Vec<u8>>,
+    {
+        let secp = Secp256k1::new();
+        let mut sighash_cache = SighashCache::new(&tx);
+        let mut witnesses = vec![];
+        for (index, utxo) in self.utxos.iter().enumerate() {
+            let sighash = match Message::from_slice(
+                &sighash_cache.segwitv0_sighash(
+                    index,
+                    redeem_script,
+                    utxo.txout.value,
+                    elements::EcdsaSighashType::All,
+                )[..],
+            ) {
+                Ok(result) => result,
+                Err(e) => return Err(S5Error::new(ErrorKind::Transaction, &e.to_string())),
+            };
+            let sig: secp256k1_zkp::ecdsa::Signature =
+                secp.sign_ecdsa_low_r(&sighash, &keys.secret_key());
+            let sig = elementssig_to_rawsig(&(sig, elements::EcdsaSighashType::All));
+            witnesses.push(TxInWitness {
+                amount_rangeproof: None,
+                inflation_keys_rangeproof: None,
+                script_witness: witness(sig),
+                pegin_witness: vec![],
+            });
+        }
+
+        for (input, witness) in tx.input.iter_mut().zip(witnesses) {
+            input.witness = witness;
+        }
+        Ok(tx)
+    }
+    /// nested segwit: scriptSig pushes the p2wsh witness program
+    fn refund_script_sig(&self, redeem_script: &EScript) -> Script {
+        match self.swap_script.swap_type {
+            SwapType::Submarine => EBuilder::new()
+                .push_slice(redeem_script.to_v0_p2wsh().as_bytes())
+                .into_script(),
+            SwapType::ReverseSubmarine | SwapType::Chain => Script::new(),
+        }
+    }
+    /// The taproot spend without witness, and the lockup outputs it spends.
+    /// Script-path and key-path spends share it.
+    fn taproot_unsigned_tx(
+        &self,
+        lock_time: LockTime,
+        sequence: Sequence,
+        fee: u64,
+    ) -> Result<(Transaction, Vec<TxOut>), S5Error> {
+        let secp = Secp256k1::new();
+        let (payment_output, fee_output) = self.blinded_outputs(&secp, fee)?;
+        let tx = Transaction {
+            version: 2,
+            lock_time,
+            input: self.inputs(Script::new(), sequence),
+            output: vec![payment_output, fee_output],
+        };
+        let prevouts = self.utxos.iter().map(|utxo| utxo.txout.clone()).collect();
+        Ok((tx, prevouts))
+    }
+    /// Spends `leaf` of the taproot swap tree with every input, with the same blinded outputs as
+    /// the segwit v0 spends.
+    /// Witness: <schnorr signature> [<preimage>] <leaf script> <control block>
+    fn sign_taproot_script_path(
+        &self,
+        keys: KeyPair,
+        leaf: EScript,
+        preimage: Option<Vec<u8>>,
+    ) -> Result<Transaction, S5Error> {
+        let (_, lock_time, sequence) = self.script_path_params()?;
+        let (mut tx, prevouts) = self.taproot_unsigned_tx(lock_time, sequence, self.fee(false)?)?;
+        let secp = Secp256k1::new();
+        let control_block = self.control_block(&leaf)?;
+        let mut sighash_cache = SighashCache::new(&tx);
+        let mut witnesses = vec![];
+        for index in 0..prevouts.len() {
+            let sighash = match sighash_cache.taproot_script_spend_signature_hash(
+                index,
+                &Prevouts::All(&prevouts),
+                TapLeafHash::from_script(&leaf, LeafVersion::default()),
+                SchnorrSighashType::Default,
+                genesis_hash(&self.swap_script.network),
+            ) {
+                Ok(result) => result,
+                Err(e) => return Err(S5Error::new(ErrorKind::Transaction, &e.to_string())),
+            };
+            let sighash_message = match Message::from_slice(&sighash[..]) {
+                Ok(result) => result,
+                Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+            };
+            let signature = secp.sign_schnorr(&sighash_message, &keys);
+
+            let mut script_witness: Vec<Vec<u8>> = vec![signature.as_ref().to_vec()];
+            if let Some(preimage) = &preimage {
+                script_witness.push(preimage.clone());
+            }
+            script_witness.push(leaf.as_bytes().to_vec());
+            script_witness.push(control_block.serialize());
+            witnesses.push(TxInWitness {
+                amount_rangeproof: None,
+                inflation_keys_rangeproof: None,
+                script_witness,
+                pegin_witness: vec![],
+            });
+        }
+
+        for (input, witness) in tx.input.iter_mut().zip(witnesses) {
+            input.witness = witness;
+        }
+        Ok(tx)
+    }
+    fn control_block(&self, leaf: &EScript) -> Result<ControlBlock, S5Error> {
+        let spend_info = self.swap_script.taproot_spend_info()?;
+        match spend_info.control_block(&(leaf.clone(), LeafVersion::default())) {
+            Some(result) => Ok(result),
+            None => Err(S5Error::new(
+                ErrorKind::Script,
+                "Leaf is not part of the swap tree.",
+            )),
+        }
+    }
+    /// The unblinded asset of the lockup outputs, which all have to be the network's policy asset.
+    fn lockup_asset(&self) -> Result<AssetId, S5Error> {
+        if self.utxos.is_empty() {
+            return Err(S5Error::new(
+                ErrorKind::Transaction,
+                "No utxos available yet",
+            ));
+        }
+        let asset_id = policy_asset(&self.swap_script.network);
+        if let Some(utxo) = self
+            .utxos
+            .iter()
+            .find(|utxo| utxo.secrets.asset != asset_id)
+        {
+            return Err(S5Error::new(
+                ErrorKind::Transaction,
+                &format!(
+                    "Lockup output {} is of asset {}, not L-BTC",
+                    utxo.outpoint, utxo.secrets.asset
+                ),
+            ));
+        }
+        Ok(asset_id)
+    }
+    /// The key the payment output is blinded to; only confidential addresses have one.
+    fn blinding_pubkey(&self) -> Result<NoncePublicKey, S5Error> {
+        match self.output_address.blinding_pubkey {
+            Some(result) => Ok(result),
+            None => Err(S5Error::new(
+                ErrorKind::Input,
+                "Output address is not confidential.",
+            )),
+        }
+    }
+    /// Blinds the swept value to `output_address` and builds the explicit fee output.
+    /// Claim and refund share these outputs; they only differ in how the inputs are signed.
+    fn blinded_outputs(
+        &self,
+        secp: &Secp256k1<secp256k1_zkp::All>,
+        fee: u64,
+    ) -> Result<(TxOut, TxOut), S5Error> {
+        let utxo_value = self.utxo_total();
+        if utxo_value <= fee {
+            return Err(S5Error::new(
+                ErrorKind::Transaction,
+                &format!("Fees exceed utxo value. Fees {}, Utxo {}", fee, utxo_value),
+            ));
+        }
+        let asset_id = self.lockup_asset()?;
+        let inp_txout_secrets: Vec<TxOutSecrets> =
+            self.utxos.iter().map(|utxo| utxo.secrets).collect();
+        use bitcoin::secp256k1::rand::rngs::OsRng;
+        let mut rng = OsRng::default();
+
+        let out_abf = AssetBlindingFactor::new(&mut rng);
+        let exp_asset = confidential::Asset::Explicit(asset_id);
+
+        let (blinded_asset, asset_surjection_proof) =
+            match exp_asset.blind(&mut rng, secp, out_abf, &inp_txout_secrets) {
+                Ok(result) => result,
+                Err(e) => return Err(S5Error::new(ErrorKind::Transaction, &e.to_string())),
+            };
+
+        let output_value = utxo_value - fee;
+
+        let final_vbf = ValueBlindingFactor::last(
+            secp,
+            output_value,
             out_abf,
+            &inp_txout_secrets
+                .iter()
+                .map(|secrets| (secrets.value, secrets.asset_bf, secrets.value_bf))
+                .collect::<Vec<_>>(),
             &[(
-                self.txout_secrets.unwrap().value,
-                self.txout_secrets.unwrap().asset_bf,
-                self.txout_secrets.unwrap().value_bf,
-            )],
-            &[(
-                self.absolute_fees as u64,
+                fee,
                 AssetBlindingFactor::zero(),
                 ValueBlindingFactor::zero(),
             )],
@@ -530,192 +1682,1208 @@ impl LBtcSwapTx {
         };
         let ephemeral_sk = SecretKey::new(&mut rng);
 
-        let (blinded_value, nonce, rangeproof) = explicit_value
-            .blind(
-                &secp,
-                final_vbf,
-                self.output_address.blinding_pubkey.unwrap(),
-                ephemeral_sk,
-                &self.output_address.script_pubkey(),
-                &msg,
-            )
+        let (blinded_value, nonce, rangeproof) = match explicit_value.blind(
+            secp,
+            final_vbf,
+            self.blinding_pubkey()?,
+            ephemeral_sk,
+            &self.output_address.script_pubkey(),
+            &msg,
+        ) {
+            Ok(result) => result,
+            Err(e) => return Err(S5Error::new(ErrorKind::Transaction, &e.to_string())),
+        };
+
+        let tx_out_witness = TxOutWitness {
+            surjection_proof: Some(Box::new(asset_surjection_proof)), // from asset blinding
+            rangeproof: Some(Box::new(rangeproof)),                   // from value blinding
+        };
+        let payment_output: TxOut = TxOut {
+            script_pubkey: self.output_address.script_pubkey(),
+            value: blinded_value,
+            asset: blinded_asset,
+            nonce: nonce,
+            witness: tx_out_witness,
+        };
+        let fee_output: TxOut = TxOut::new_fee(fee, asset_id);
+
+        Ok((payment_output, fee_output))
+    }
+    /// Checks that `signed_tx` spends swap lockup outputs the way this crate builds such spends,
+    /// so that a broken spend fails with the reason instead of being rejected by the node.
+    /// For every input it checks that the spent output is one of the swap's lockup outputs, that
+    /// the witness reveals the swap's redeem script or leaf (with a valid control block), that a
+    /// claim's preimage matches the hashlock, and that the signature is valid for the input's
+    /// sighash under the key of the branch taken. A refund branch only passes when the input is
+    /// not final and the transaction's nLockTime is of the timelock's kind and at least the
+    /// timelock; the chain height is not looked at, so a refund that passes is still rejected
+    /// until the chain reaches its nLockTime.
+    /// This is a structural comparison to the swap's spending conditions, not an execution of the
+    /// scripts: rust-elements ships no script interpreter. Blinding, balance and fees are not
+    /// checked either. `broadcast` runs only this check before handing the transaction to the
+    /// backend, so on Liquid no consensus check happens before the node sees the spend.
+    /// Only SIGHASH_ALL (segwit v0) and SIGHASH_DEFAULT (taproot) signatures are accepted, which is
+    /// what this crate and Boltz sign with.
+    pub fn check_swap_spend(&self, signed_tx: &Transaction) -> Result<(), S5Error> {
+        let mut lockups = vec![];
+        for (index, input) in signed_tx.input.iter().enumerate() {
+            match self
+                .utxos
+                .iter()
+                .find(|utxo| utxo.outpoint == input.previous_output)
+            {
+                Some(utxo) => lockups.push(utxo),
+                None => {
+                    return Err(S5Error::new(
+                        ErrorKind::Transaction,
+                        &format!("Input {} does not spend a swap utxo", index),
+                    ))
+                }
+            }
+        }
+        let prevouts: Vec<TxOut> = lockups.iter().map(|utxo| utxo.txout.clone()).collect();
+        for (index, utxo) in lockups.iter().enumerate() {
+            let result = match self.swap_script.version {
+                SwapScriptVersion::Legacy => self.verify_segwit_v0_input(signed_tx, index, utxo),
+                SwapScriptVersion::Taproot => {
+                    self.verify_taproot_input(signed_tx, index, &prevouts)
+                }
+            };
+            if let Err(e) = result {
+                return Err(S5Error::new(
+                    ErrorKind::Transaction,
+                    &format!(
+                        "Input {} does not satisfy the lockup script: {}",
+                        index, e.message
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Witness: <signature> <preimage> <redeem script>. The HASH160 (submarine) or SIZE (reverse
+    /// and chain) check of the preimage selects the branch: the receiver's key, or the sender's
+    /// key once the locktime reaches the timelock.
+    fn verify_segwit_v0_input(
+        &self,
+        tx: &Transaction,
+        index: usize,
+        utxo: &LockupOutput,
+    ) -> Result<(), S5Error> {
+        let input = &tx.input[index];
+        let redeem_script = self.swap_script.to_script()?;
+        if utxo.txout.script_pubkey != self.lockup_script_pubkey()? {
+            return Err(S5Error::new(
+                ErrorKind::Script,
+                "Spent output is not the lockup script.",
+            ));
+        }
+        if input.script_sig != self.refund_script_sig(&redeem_script) {
+            return Err(S5Error::new(
+                ErrorKind::Script,
+                "scriptSig does not push the witness program of the lockup.",
+            ));
+        }
+        let (signature, preimage) = match input.witness.script_witness.as_slice() {
+            [signature, preimage, script] if *script == redeem_script.as_bytes() => {
+                (signature, preimage)
+            }
+            [.., script] if *script != redeem_script.as_bytes() => {
+                return Err(S5Error::new(
+                    ErrorKind::Script,
+                    "Witness script is not the redeem script.",
+                ))
+            }
+            witness => {
+                return Err(S5Error::new(
+                    ErrorKind::Script,
+                    &format!("Unexpected number of witness elements: {}", witness.len()),
+                ))
+            }
+        };
+        let hash_matches = hash160::Hash::hash(preimage).to_string() == self.swap_script.hashlock;
+        let is_claim = match self.swap_script.swap_type {
+            SwapType::Submarine => hash_matches,
+            SwapType::ReverseSubmarine | SwapType::Chain => preimage.len() == PREIMAGE_SIZE,
+        };
+        let pubkey = if is_claim {
+            if !hash_matches {
+                return Err(S5Error::new(
+                    ErrorKind::Script,
+                    "Preimage does not match the hashlock.",
+                ));
+            }
+            &self.swap_script.reciever_pubkey
+        } else {
+            if !cltv_satisfied(
+                tx.lock_time.to_consensus_u32(),
+                input.sequence.to_consensus_u32(),
+                self.swap_script.timelock,
+            ) {
+                return Err(S5Error::new(
+                    ErrorKind::Script,
+                    &format!(
+                        "Locktime {} does not reach the timelock {}, or the input is final.",
+                        tx.lock_time.to_consensus_u32(),
+                        self.swap_script.timelock
+                    ),
+                ));
+            }
+            &self.swap_script.sender_pubkey
+        };
+        let pubkey = match PublicKey::from_str(pubkey) {
+            Ok(result) => result,
+            Err(e) => return Err(S5Error::new(ErrorKind::Key, &e.to_string())),
+        };
+        let signature = match signature.split_last() {
+            Some((&sighash_type, der)) if sighash_type == elements::EcdsaSighashType::All as u8 => {
+                match secp256k1_zkp::ecdsa::Signature::from_der(der) {
+                    Ok(result) => result,
+                    Err(e) => return Err(S5Error::new(ErrorKind::Transaction, &e.to_string())),
+                }
+            }
+            _ => {
+                return Err(S5Error::new(
+                    ErrorKind::Transaction,
+                    "Unsupported sighash type.",
+                ))
+            }
+        };
+        let sighash = match Message::from_slice(
+            &SighashCache::new(tx).segwitv0_sighash(
+                index,
+                &redeem_script,
+                utxo.txout.value,
+                elements::EcdsaSighashType::All,
+            )[..],
+        ) {
+            Ok(result) => result,
+            Err(e) => return Err(S5Error::new(ErrorKind::Transaction, &e.to_string())),
+        };
+        let secp = Secp256k1::verification_only();
+        match secp.verify_ecdsa(&sighash, &signature, &pubkey.inner) {
+            Ok(()) => Ok(()),
+            Err(_) => Err(S5Error::new(ErrorKind::Transaction, "Invalid signature.")),
+        }
+    }
+
+    /// A key-path spend needs a signature of the tweaked output key. A script-path spend has to
+    /// reveal one of the two leaves with its control block, and satisfy it: the receiver's
+    /// signature and the preimage for the claim leaf, the sender's signature and a locktime of
+    /// at least the timelock for the refund leaf.
+    fn verify_taproot_input(
+        &self,
+        tx: &Transaction,
+        index: usize,
+        prevouts: &[TxOut],
+    ) -> Result<(), S5Error> {
+        let input = &tx.input[index];
+        let genesis_hash = genesis_hash(&self.swap_script.network);
+        let mut sighash_cache = SighashCache::new(tx);
+        let (signature, pubkey, sighash) = match input.witness.script_witness.as_slice() {
+            [] => return Err(S5Error::new(ErrorKind::Transaction, "Empty witness.")),
+            [signature] => {
+                let output_key = match secp256k1_zkp::XOnlyPublicKey::from_slice(
+                    &prevouts[index].script_pubkey.as_bytes()[2..],
+                ) {
+                    Ok(result) => result,
+                    Err(e) => return Err(S5Error::new(ErrorKind::Script, &e.to_string())),
+                };
+                let sighash = match sighash_cache.taproot_key_spend_signature_hash(
+                    index,
+                    &Prevouts::All(prevouts),
+                    SchnorrSighashType::Default,
+                    genesis_hash,
+                ) {
+                    Ok(result) => result,
+                    Err(e) => return Err(S5Error::new(ErrorKind::Transaction, &e.to_string())),
+                };
+                (signature, output_key, sighash)
+            }
+            [args @ .., leaf, control_block] => {
+                let leaf = EScript::from(leaf.clone());
+                let is_claim = leaf == self.swap_script.claim_leaf()?;
+                if !is_claim && leaf != self.swap_script.refund_leaf()? {
+                    return Err(S5Error::new(
+                        ErrorKind::Script,
+                        "Leaf is not part of the swap tree.",
+                    ));
+                }
+                if *control_block != self.control_block(&leaf)?.serialize() {
+                    return Err(S5Error::new(
+                        ErrorKind::Script,
+                        "Control block does not commit to the leaf.",
+                    ));
+                }
+                let pubkey = match (is_claim, args) {
+                    (true, [_, preimage]) => {
+                        if hash160::Hash::hash(preimage).to_string() != self.swap_script.hashlock {
+                            return Err(S5Error::new(
+                                ErrorKind::Script,
+                                "Preimage does not match the hashlock.",
+                            ));
+                        }
+                        if self.swap_script.swap_type != SwapType::Submarine
+                            && preimage.len() != PREIMAGE_SIZE
+                        {
+                            return Err(S5Error::new(
+                                ErrorKind::Script,
+                                "Preimage is not 32 bytes.",
+                            ));
+                        }
+                        x_only_pubkey(&self.swap_script.reciever_pubkey)?
+                    }
+                    (false, [_]) => {
+                        if !cltv_satisfied(
+                            tx.lock_time.to_consensus_u32(),
+                            input.sequence.to_consensus_u32(),
+                            self.swap_script.timelock,
+                        ) {
+                            return Err(S5Error::new(
+                                ErrorKind::Script,
+                                &format!(
+                                    "Locktime {} does not reach the timelock {}, \
+                                     or the input is final.",
+                                    tx.lock_time.to_consensus_u32(),
+                                    self.swap_script.timelock
+                                ),
+                            ));
+                        }
+                        x_only_pubkey(&self.swap_script.sender_pubkey)?
+                    }
+                    _ => {
+                        return Err(S5Error::new(
+                            ErrorKind::Script,
+                            &format!("Unexpected number of witness elements: {}", args.len()),
+                        ))
+                    }
+                };
+                let sighash = match sighash_cache.taproot_script_spend_signature_hash(
+                    index,
+                    &Prevouts::All(prevouts),
+                    TapLeafHash::from_script(&leaf, LeafVersion::default()),
+                    SchnorrSighashType::Default,
+                    genesis_hash,
+                ) {
+                    Ok(result) => result,
+                    Err(e) => return Err(S5Error::new(ErrorKind::Transaction, &e.to_string())),
+                };
+                (&args[0], pubkey, sighash)
+            }
+        };
+        // SIGHASH_DEFAULT signatures have no sighash type byte
+        let signature = match secp256k1_zkp::schnorr::Signature::from_slice(signature) {
+            Ok(result) => result,
+            Err(_) => {
+                return Err(S5Error::new(
+                    ErrorKind::Transaction,
+                    "Unsupported sighash type.",
+                ))
+            }
+        };
+        let message = match Message::from_slice(&sighash[..]) {
+            Ok(result) => result,
+            Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+        };
+        let secp = Secp256k1::verification_only();
+        match secp.verify_schnorr(&signature, &message, &pubkey) {
+            Ok(()) => Ok(()),
+            Err(_) => Err(S5Error::new(ErrorKind::Transaction, "Invalid signature.")),
+        }
+    }
+
+    /// Checks `signed_tx` with `check_swap_spend` before handing it to the backend.
+    /// No consensus check is run, see `check_swap_spend`.
+    pub fn broadcast(
+        &self,
+        backend: &dyn ChainBackend,
+        signed_tx: Transaction,
+    ) -> Result<String, S5Error> {
+        self.check_swap_spend(&signed_tx)?;
+        let serialized = serialize(&signed_tx);
+        let txid = backend.broadcast(&serialized)?;
+        Ok(txid.to_string())
+    }
+
+    #[cfg(feature = "async")]
+    pub async fn broadcast_async(
+        &self,
+        backend: &dyn AsyncChainBackend,
+        signed_tx: Transaction,
+    ) -> Result<String, S5Error> {
+        self.check_swap_spend(&signed_tx)?;
+        let serialized = serialize(&signed_tx);
+        let txid = backend.broadcast(&serialized).await?;
+        Ok(txid.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::electrum::{NetworkConfig, LIQUID_TESTNET_POLICY_ASSET_STR};
+    use std::{fs::File, path::Path};
+
+    /// https://liquidtestnet.com/utils
+    /// https://blockstream.info/liquidtestnet
+    ///
+
+    // use std::io::Write;
+    // use std::path::Path;
+    // use std::{fs::File, str::FromStr};
+    #[test]
+    fn test_liquid_swap_elements() {
+        // let secp = Secp256k1::new();
+        let secp = Secp256k1::new();
+        const RETURN_ADDRESS: &str =
+        "tlq1qqtc07z9kljll7dk2jyhz0qj86df9gnrc70t0wuexutzkxjavdpht0d4vwhgs2pq2f09zsvfr5nkglc394766w3hdaqrmay4tw";
+        let redeem_script_str = "8201208763a9142bdd03d431251598f46a625f1d3abfcd7f491535882102ccbab5f97c89afb97d814831c5355ef5ba96a18c9dcd1b5c8cfd42c697bfe53c677503715912b1752103fced00385bd14b174a571d88b4b6aced2cb1d532237c29c4ec61338fbb7eff4068ac".to_string();
+        let expected_address = "tlq1qq0gnj2my5tp8r77srvvdmwfrtr8va9mgz9e8ja0rzk75jvsanjvgz5sfvl093l5a7xztrtzhyhfmfyr2exdxtpw7cehfgtzgn62zdzcsgrz8c4pjfvtj";
+        let expected_timeout = 1202545;
+        let boltz_blinding_str = "02702ae71ec11a895f6255e26395983585a0d791ea1eb83d1aa54a66056469da";
+        let boltz_blinding_key = ZKKeyPair::from_seckey_str(&secp, boltz_blinding_str).unwrap();
+        let preimage_str = "6ef7d91c721ea06b3b65d824ae1d69777cd3892d41090234aef13a572ff0e64f";
+        let preimage = Preimage::from_str(preimage_str).unwrap();
+        // println!("{}", blinding_key.public_key().to_string());
+        let _id = "axtHXB";
+        let my_key_pair = KeyPair::from_seckey_str(
+            &secp,
+            "aecbc2bddfcd3fa6953d257a9f369dc20cdc66f2605c73efb4c91b90703506b6",
+        )
+        .unwrap();
+
+        let decoded = LBtcSwapScript::reverse_from_str(
+            BitcoinNetwork::LiquidTestnet,
+            &redeem_script_str.clone(),
+            boltz_blinding_str.to_string(),
+        )
+        .unwrap();
+        // println!("{:?}", decoded);
+        assert_eq!(
+            decoded.reciever_pubkey,
+            my_key_pair.public_key().to_string()
+        );
+        assert_eq!(decoded.timelock, expected_timeout);
+        let error = LBtcSwapScript::submarine_from_str(
+            BitcoinNetwork::LiquidTestnet,
+            &redeem_script_str,
+            boltz_blinding_str.to_string(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.message,
+            "Redeem script element 0 should be OP_HASH160: found OP_SIZE"
+        );
+
+        let el_script = LBtcSwapScript {
+            hashlock: decoded.hashlock,
+            reciever_pubkey: decoded.reciever_pubkey,
+            sender_pubkey: decoded.sender_pubkey,
+            timelock: decoded.timelock,
+            network: BitcoinNetwork::LiquidTestnet,
+            swap_type: SwapType::ReverseSubmarine,
+            version: SwapScriptVersion::Legacy,
+            side: None,
+            blinding_key: boltz_blinding_key,
+        };
+
+        let address = el_script.to_address().unwrap();
+        println!("ADDRESS FROM ENCODED: {:?}", address.to_string());
+        println!("Blinding Pub: {:?}", address.blinding_pubkey);
+
+        assert_eq!(address.to_string(), expected_address);
+
+        let mut liquid_swap_tx =
+            LBtcSwapTx::new_claim(el_script, RETURN_ADDRESS.to_string(), 5_000).unwrap();
+        let backend = NetworkConfig::default_liquid()
+            .electrum_url
+            .build_client()
             .unwrap();
+        // the amount Boltz locked is not part of this vector
+        liquid_swap_tx.set_utxo_policy(UtxoPolicy::All);
+        let final_tx = liquid_swap_tx
+            .drain(&backend, my_key_pair, preimage, 0)
+            .unwrap();
+        println!("FINALIZED TX SIZE: {:?}", final_tx.size());
+        let manifest_dir = env!("CARGO_MANIFEST_DIR");
+
+        let file_path = Path::new(manifest_dir).join("tx.constructed");
+        let mut file = File::create(file_path).unwrap();
+        use std::io::Write;
+        writeln!(file, "{:#?}", final_tx).unwrap();
+        // println!("CHECK FILE tx.hex!");
+
+        let txid = liquid_swap_tx.broadcast(&backend, final_tx).unwrap();
+        println!("TXID: {}", txid);
+    }
+
+    #[test]
+    fn test_sign_refund_tx() {
+        use bitcoin::secp256k1::rand::rngs::OsRng;
+        let secp = Secp256k1::new();
+        let mut rng = OsRng::default();
+        const RETURN_ADDRESS: &str =
+        "tlq1qqtc07z9kljll7dk2jyhz0qj86df9gnrc70t0wuexutzkxjavdpht0d4vwhgs2pq2f09zsvfr5nkglc394766w3hdaqrmay4tw";
+        let utxo_value = 50_000;
+        let absolute_fees = 300;
+        let timelock = 1202545;
+        let sender_key_pair = KeyPair::from_seckey_str(
+            &secp,
+            "aecbc2bddfcd3fa6953d257a9f369dc20cdc66f2605c73efb4c91b90703506b6",
+        )
+        .unwrap();
+        let reciever_key_pair = KeyPair::from_seckey_str(
+            &secp,
+            "d5f984d2ab332345dbf7ddff9f47852125721b2025329e6981c4130671e237d0",
+        )
+        .unwrap();
+        let blinding_key = ZKKeyPair::from_seckey_str(
+            &secp,
+            "02702ae71ec11a895f6255e26395983585a0d791ea1eb83d1aa54a66056469da",
+        )
+        .unwrap();
+        let preimage = Preimage::new();
+
+        let swap_script = LBtcSwapScript::new(
+            BitcoinNetwork::LiquidTestnet,
+            SwapType::Submarine,
+            preimage.hash160.to_string(),
+            reciever_key_pair.public_key().to_string(),
+            timelock,
+            sender_key_pair.public_key().to_string(),
+            blinding_key,
+        );
+        let redeem_script = swap_script.to_script().unwrap();
+        let lockup_address = swap_script.to_address().unwrap();
+        assert_eq!(
+            redeem_script.to_v0_p2wsh().to_p2sh(),
+            lockup_address.script_pubkey()
+        );
+
+        let mut refund_tx =
+            LBtcSwapTx::new_refund(swap_script, RETURN_ADDRESS.to_string(), absolute_fees).unwrap();
+        let asset_id = elements::AssetId::from_str(LIQUID_TESTNET_POLICY_ASSET_STR).unwrap();
+        let value_commitment = confidential::Value::Explicit(utxo_value);
+        refund_tx.utxos = vec![LockupOutput {
+            outpoint: OutPoint::new(
+                elements::Txid::from_str(
+                    "10c31b81b93a69a635ab337bb48adeefe662d40d3e5d50b319a1fed17485104a",
+                )
+                .unwrap(),
+                1,
+            ),
+            txout: TxOut {
+                asset: confidential::Asset::Explicit(asset_id),
+                value: value_commitment,
+                nonce: confidential::Nonce::Null,
+                script_pubkey: lockup_address.script_pubkey(),
+                witness: TxOutWitness::default(),
+            },
+            secrets: TxOutSecrets::new(
+                asset_id,
+                AssetBlindingFactor::new(&mut rng),
+                utxo_value,
+                ValueBlindingFactor::new(&mut rng),
+            ),
+        }];
+
+        let signed_tx = refund_tx.sign_refund_tx(sender_key_pair).unwrap();
+
+        assert_eq!(signed_tx.lock_time, LockTime::from_consensus(timelock));
+        assert_eq!(signed_tx.output.len(), 2);
+        assert!(signed_tx.output[1].is_fee());
+        assert_eq!(
+            signed_tx.output[1].value,
+            confidential::Value::Explicit(absolute_fees as u64)
+        );
 
-        let tx_out_witness = TxOutWitness {
-            surjection_proof: Some(Box::new(asset_surjection_proof)), // from asset blinding
-            rangeproof: Some(Box::new(rangeproof)),                   // from value blinding
-        };
-        let payment_output: TxOut = TxOut {
-            script_pubkey: self.output_address.script_pubkey(),
-            value: blinded_value,
-            asset: blinded_asset,
-            nonce: nonce,
-            witness: tx_out_witness,
-        };
-        let fee_output: TxOut = TxOut::new_fee(self.absolute_fees as u64, asset_id);
+        let input = &signed_tx.input[0];
+        assert_eq!(input.sequence, Sequence::from_consensus(0xFFFFFFFE));
+        assert_eq!(
+            input.script_sig,
+            EBuilder::new()
+                .push_slice(redeem_script.to_v0_p2wsh().as_bytes())
+                .into_script()
+        );
 
-        let unsigned_tx = Transaction {
-            version: 2,
-            lock_time: LockTime::from_consensus(self.swap_script.timelock),
-            input: vec![unsigned_input],
-            output: vec![payment_output.clone(), fee_output.clone()],
-        };
+        // witness: <signature> <empty preimage> <redeem script>
+        let witness = &input.witness.script_witness;
+        assert_eq!(witness.len(), 3);
+        assert!(witness[1].is_empty());
+        assert_eq!(witness[2], redeem_script.as_bytes().to_vec());
 
-        // SIGN TRANSACTION
+        let raw_sig = &witness[0];
+        assert_eq!(
+            raw_sig[raw_sig.len() - 1],
+            elements::EcdsaSighashType::All as u8
+        );
+        let sig = secp256k1_zkp::ecdsa::Signature::from_der(&raw_sig[..raw_sig.len() - 1]).unwrap();
         let sighash = Message::from_slice(
-            &SighashCache::new(&unsigned_tx).segwitv0_sighash(
+            &SighashCache::new(&signed_tx).segwitv0_sighash(
                 0,
-                &&self.swap_script.to_script(),
-                blinded_value,
+                &redeem_script,
+                value_commitment,
                 elements::EcdsaSighashType::All,
             )[..],
         )
         .unwrap();
-        pub type ElementsSig = (secp256k1_zkp::ecdsa::Signature, elements::EcdsaSighashType);
-
-        pub fn elementssig_to_rawsig(sig: &ElementsSig) -> Vec<u8> {
-            let ser_sig = sig.0.serialize_der();
-            let mut raw_sig = Vec::from(&ser_sig[..]);
-            raw_sig.push(sig.1 as u8);
-            raw_sig
-        }
-        let sig: secp256k1_zkp::ecdsa::Signature =
-            secp.sign_ecdsa_low_r(&sighash, &keys.secret_key());
-        let sig = elementssig_to_rawsig(&(sig, elements::EcdsaSighashType::All));
-        // let mut sig = [0; 73];
-        // sig[..signature.len()].copy_from_slice(&signature);
-        // sig[signature.len()] = elements::EcdsaSighashType::All as u8;
-        // let final_sig_pushed = sig[..signature.len() + 1].to_vec();
-        let mut script_witness: Vec<Vec<u8>> = vec![vec![]];
-        script_witness.push(sig);
-        script_witness.push(preimage.bytes.unwrap().to_vec());
-        script_witness.push(self.swap_script.to_script().as_bytes().to_vec());
-
-        let witness = TxInWitness {
-            amount_rangeproof: None,
-            inflation_keys_rangeproof: None,
-            script_witness: script_witness,
-            pegin_witness: vec![],
-        };
-
-        let signed_txin = TxIn {
-            previous_output: self.utxo.unwrap(),
-            script_sig: Script::default(),
-            sequence: sequence,
-            witness: witness,
-            is_pegin: false,
-            asset_issuance: AssetIssuance::default(),
-        };
+        assert!(secp
+            .verify_ecdsa(&sighash, &sig, &sender_key_pair.public_key())
+            .is_ok());
 
-        let signed_tx = Transaction {
-            version: 2,
-            lock_time: LockTime::from_consensus(self.swap_script.timelock),
-            input: vec![signed_txin],
-            output: vec![payment_output, fee_output],
-        };
-        signed_tx
-    }
-    fn sign_refund_tx(&self, _keys: KeyPair) -> () {
-        ()
+        // fee rates apply to the discounted weight; the full weight counts the blinding proofs
+        refund_tx.set_fee(Fee::Relative(0.1));
+        let discount_weight = refund_tx.discount_weight(false).unwrap();
+        let fee = refund_tx.fee(false).unwrap();
+        assert_eq!(fee, Fee::Relative(0.1).absolute(discount_weight));
+        let signed_tx = refund_tx.sign_refund_tx(sender_key_pair).unwrap();
+        assert_eq!(
+            signed_tx.output[1].value,
+            confidential::Value::Explicit(fee)
+        );
+        assert!(tx_weight(&signed_tx) <= refund_tx.weight(false).unwrap());
+        assert!(tx_weight(&signed_tx) > discount_weight);
     }
-    pub fn broadcast(&mut self, signed_tx: Transaction) -> Result<String, S5Error> {
-        let electrum_client = NetworkConfig::new(
+
+    #[test]
+    fn test_multiple_utxos() {
+        use crate::network::mock::MockChainBackend;
+        let secp = Secp256k1::new();
+        const RETURN_ADDRESS: &str =
+        "tlq1qqtc07z9kljll7dk2jyhz0qj86df9gnrc70t0wuexutzkxjavdpht0d4vwhgs2pq2f09zsvfr5nkglc394766w3hdaqrmay4tw";
+        let expected_value = 50_000;
+        let absolute_fees = 300;
+        let timelock = 1202545;
+        let sender_key_pair = KeyPair::from_seckey_str(
+            &secp,
+            "aecbc2bddfcd3fa6953d257a9f369dc20cdc66f2605c73efb4c91b90703506b6",
+        )
+        .unwrap();
+        let reciever_key_pair = KeyPair::from_seckey_str(
+            &secp,
+            "d5f984d2ab332345dbf7ddff9f47852125721b2025329e6981c4130671e237d0",
+        )
+        .unwrap();
+        let blinding_key = ZKKeyPair::from_seckey_str(
+            &secp,
+            "02702ae71ec11a895f6255e26395983585a0d791ea1eb83d1aa54a66056469da",
+        )
+        .unwrap();
+        let swap_script = LBtcSwapScript::new(
             BitcoinNetwork::LiquidTestnet,
-            &self.swap_script.electrum_url,
-            true,
-            true,
-            false,
-            None,
+            SwapType::Submarine,
+            Preimage::new().hash160.to_string(),
+            reciever_key_pair.public_key().to_string(),
+            timelock,
+            sender_key_pair.public_key().to_string(),
+            blinding_key,
+        );
+        let lockup_script_pubkey = swap_script.to_address().unwrap().script_pubkey();
+        let asset_id = elements::AssetId::from_str(LIQUID_TESTNET_POLICY_ASSET_STR).unwrap();
+
+        // the sender paid twice, without blinding, and short of the expected value
+        let mut backend = MockChainBackend::new(timelock, 0.1);
+        let mut funding_txs = vec![];
+        for (lock_time, value) in [(1, 20_000), (2, 25_000)] {
+            let funding_tx = Transaction {
+                version: 2,
+                lock_time: LockTime::from_consensus(lock_time),
+                input: vec![TxIn {
+                    previous_output: OutPoint::default(),
+                    script_sig: Script::new(),
+                    sequence: Sequence::MAX,
+                    witness: TxInWitness::default(),
+                    is_pegin: false,
+                    asset_issuance: AssetIssuance::default(),
+                }],
+                output: vec![
+                    TxOut::new_fee(100, asset_id),
+                    TxOut {
+                        asset: confidential::Asset::Explicit(asset_id),
+                        value: confidential::Value::Explicit(value),
+                        nonce: confidential::Nonce::Null,
+                        script_pubkey: lockup_script_pubkey.clone(),
+                        witness: TxOutWitness::default(),
+                    },
+                ],
+            };
+            let txid = bitcoin::Txid::from_str(&funding_tx.txid().to_string()).unwrap();
+            backend.add_funding(
+                BitcoinScript::from_bytes(lockup_script_pubkey.as_bytes()),
+                txid,
+                serialize(&funding_tx),
+                &[(1, 0)],
+                0,
+            );
+            funding_txs.push(funding_tx);
+        }
+
+        let report = swap_script.utxo_report(&backend, expected_value).unwrap();
+        assert_eq!(report.total(), 45_000);
+        assert_eq!(report.difference(), Some(-5_000));
+        // the fee output does not pay the lockup script
+        let utxo = report.utxos[0].clone();
+        let raw_tx = serialize(&funding_txs[0]);
+        assert!(swap_script
+            .unblind_utxo(
+                Utxo { vout: 0, ..utxo },
+                &raw_tx,
+                &mut LockupOutputs::default()
+            )
+            .is_err());
+
+        let mut refund_tx = LBtcSwapTx::new_refund(
+            swap_script.clone(),
+            RETURN_ADDRESS.to_string(),
+            absolute_fees,
         )
-        .electrum_url
-        .build_client()?;
-        let serialized = serialize(&signed_tx);
-        match electrum_client.transaction_broadcast_raw(&serialized) {
-            Ok(txid) => Ok(txid.to_string()),
-            Err(e) => Err(S5Error::new(ErrorKind::Network, &e.to_string())),
+        .unwrap();
+        let error = refund_tx
+            .drain(&backend, sender_key_pair, Preimage::new(), expected_value)
+            .unwrap_err();
+        assert!(error.message.contains("Underpaid by 5000"));
+        refund_tx.set_utxo_policy(UtxoPolicy::AtLeast);
+        let error = refund_tx
+            .drain(&backend, sender_key_pair, Preimage::new(), expected_value)
+            .unwrap_err();
+        assert!(error.message.contains("Underpaid by 5000"));
+
+        refund_tx.set_utxo_policy(UtxoPolicy::All);
+        refund_tx.fetch_utxo(&backend, expected_value).unwrap();
+        assert_eq!(
+            refund_tx.outpoints(),
+            funding_txs
+                .iter()
+                .map(|tx| OutPoint::new(tx.txid(), 1))
+                .collect::<Vec<_>>()
+        );
+        let signed_tx = refund_tx.sign_refund_tx(sender_key_pair).unwrap();
+        assert_eq!(signed_tx.input.len(), 2);
+        assert_eq!(
+            signed_tx.output[1].value,
+            confidential::Value::Explicit(absolute_fees as u64)
+        );
+        assert!(tx_weight(&signed_tx) <= refund_tx.weight(false).unwrap());
+        assert!(refund_tx.check_swap_spend(&signed_tx).is_ok());
+        let mut early = signed_tx.clone();
+        early.lock_time = LockTime::from_consensus(timelock - 1);
+        let error = refund_tx.check_swap_spend(&early).unwrap_err();
+        assert!(error.message.contains("does not reach the timelock"));
+
+        let redeem_script = swap_script.to_script().unwrap();
+        for (index, input) in signed_tx.input.iter().enumerate() {
+            let raw_sig = &input.witness.script_witness[0];
+            let sig =
+                secp256k1_zkp::ecdsa::Signature::from_der(&raw_sig[..raw_sig.len() - 1]).unwrap();
+            let sighash = Message::from_slice(
+                &SighashCache::new(&signed_tx).segwitv0_sighash(
+                    index,
+                    &redeem_script,
+                    funding_txs[index].output[1].value,
+                    elements::EcdsaSighashType::All,
+                )[..],
+            )
+            .unwrap();
+            assert!(secp
+                .verify_ecdsa(&sighash, &sig, &sender_key_pair.public_key())
+                .is_ok());
+        }
+
+        // an explicit output of another asset, and one blinded to another key (the refund's
+        // payment output, blinded to RETURN_ADDRESS), are skipped and never spent
+        let foreign_asset = elements::AssetId::from_str(
+            "0000000000000000000000000000000000000000000000000000000000000001",
+        )
+        .unwrap();
+        let mut foreign_output = funding_txs[0].output[1].clone();
+        foreign_output.asset = confidential::Asset::Explicit(foreign_asset);
+        foreign_output.value = confidential::Value::Explicit(expected_value);
+        let mut blinded_output = signed_tx.output[0].clone();
+        blinded_output.script_pubkey = lockup_script_pubkey.clone();
+        let mut skipped_outpoints = vec![];
+        for (lock_time, output) in [(3, foreign_output), (4, blinded_output)] {
+            let mut funding_tx = funding_txs[0].clone();
+            funding_tx.lock_time = LockTime::from_consensus(lock_time);
+            funding_tx.output[1] = output;
+            let txid = bitcoin::Txid::from_str(&funding_tx.txid().to_string()).unwrap();
+            backend.add_funding(
+                BitcoinScript::from_bytes(lockup_script_pubkey.as_bytes()),
+                txid,
+                serialize(&funding_tx),
+                &[(1, 0)],
+                0,
+            );
+            skipped_outpoints.push((txid, 1));
         }
+
+        let report = swap_script.utxo_report(&backend, expected_value).unwrap();
+        assert_eq!(report.utxos.len(), 2);
+        assert_eq!(report.total(), 45_000);
+        assert_eq!(
+            report
+                .skipped
+                .iter()
+                .map(|skipped| (skipped.utxo.txid, skipped.utxo.vout))
+                .collect::<Vec<_>>(),
+            skipped_outpoints
+        );
+        assert_eq!(
+            report.skipped[0].reason,
+            format!("output is of asset {}, not L-BTC", foreign_asset)
+        );
+        assert!(report.skipped[1]
+            .reason
+            .starts_with("cannot unblind output"));
+        assert!(report.to_string().contains("Skipped"));
+
+        refund_tx.fetch_utxo(&backend, expected_value).unwrap();
+        assert_eq!(
+            refund_tx.outpoints(),
+            funding_txs
+                .iter()
+                .map(|tx| OutPoint::new(tx.txid(), 1))
+                .collect::<Vec<_>>()
+        );
+        // even when the foreign output alone is of the expected value
+        refund_tx.set_utxo_policy(UtxoPolicy::Exact);
+        let error = refund_tx.fetch_utxo(&backend, expected_value).unwrap_err();
+        assert!(error.message.contains("Underpaid by 5000"));
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::network::electrum::DEFAULT_LIQUID_TESTNET_NODE;
-    use std::{fs::File, path::Path};
+    #[test]
+    fn test_sign_legacy_reverse_claim() {
+        let secp = Secp256k1::new();
+        const RETURN_ADDRESS: &str =
+        "tlq1qqtc07z9kljll7dk2jyhz0qj86df9gnrc70t0wuexutzkxjavdpht0d4vwhgs2pq2f09zsvfr5nkglc394766w3hdaqrmay4tw";
+        let sender_key_pair = KeyPair::from_seckey_str(
+            &secp,
+            "aecbc2bddfcd3fa6953d257a9f369dc20cdc66f2605c73efb4c91b90703506b6",
+        )
+        .unwrap();
+        let reciever_key_pair = KeyPair::from_seckey_str(
+            &secp,
+            "d5f984d2ab332345dbf7ddff9f47852125721b2025329e6981c4130671e237d0",
+        )
+        .unwrap();
+        let blinding_key = ZKKeyPair::from_seckey_str(
+            &secp,
+            "02702ae71ec11a895f6255e26395983585a0d791ea1eb83d1aa54a66056469da",
+        )
+        .unwrap();
+        let preimage = Preimage::new();
+        let swap_script = LBtcSwapScript::new(
+            BitcoinNetwork::LiquidTestnet,
+            SwapType::ReverseSubmarine,
+            preimage.hash160.to_string(),
+            reciever_key_pair.public_key().to_string(),
+            1202545,
+            sender_key_pair.public_key().to_string(),
+            blinding_key,
+        );
+        let mut claim_tx =
+            LBtcSwapTx::new_claim(swap_script.clone(), RETURN_ADDRESS.to_string(), 300).unwrap();
+        let outpoint = OutPoint::from_str(
+            "c2a9a2d1e1ee3a6e4e0e1d86d5ea4f5f8e2ba1e30d6b8c48e7a46f2b5c3a1d01:1",
+        )
+        .unwrap();
+        claim_tx.manual_utxo_update(outpoint, 50_000).unwrap();
+        let signed_tx = claim_tx.sign(reciever_key_pair, preimage.clone()).unwrap();
 
-    /// https://liquidtestnet.com/utils
-    /// https://blockstream.info/liquidtestnet
-    ///
+        // the script consumes exactly <signature> <preimage>, anything below them would be left
+        // on the stack and fail the segwit clean stack rule
+        let redeem_script = swap_script.to_script().unwrap();
+        let witness = &signed_tx.input[0].witness.script_witness;
+        assert_eq!(witness.len(), 3);
+        assert_eq!(witness[1], preimage.bytes.unwrap().to_vec());
+        assert_eq!(witness[2], redeem_script.as_bytes().to_vec());
+        let sig =
+            secp256k1_zkp::ecdsa::Signature::from_der(&witness[0][..witness[0].len() - 1]).unwrap();
+        let sighash = Message::from_slice(
+            &SighashCache::new(&signed_tx).segwitv0_sighash(
+                0,
+                &redeem_script,
+                confidential::Value::Explicit(50_000),
+                elements::EcdsaSighashType::All,
+            )[..],
+        )
+        .unwrap();
+        assert!(secp
+            .verify_ecdsa(&sighash, &sig, &reciever_key_pair.public_key())
+            .is_ok());
+        assert!(claim_tx.check_swap_spend(&signed_tx).is_ok());
+        assert!(tx_weight(&signed_tx) <= claim_tx.weight(false).unwrap());
+    }
 
-    // use std::io::Write;
-    // use std::path::Path;
-    // use std::{fs::File, str::FromStr};
     #[test]
-    fn test_liquid_swap_elements() {
-        // let secp = Secp256k1::new();
+    fn test_unconfidential_output_address() {
+        let secp = Secp256k1::new();
+        let sender_key_pair = KeyPair::from_seckey_str(
+            &secp,
+            "aecbc2bddfcd3fa6953d257a9f369dc20cdc66f2605c73efb4c91b90703506b6",
+        )
+        .unwrap();
+        let blinding_key = ZKKeyPair::from_seckey_str(
+            &secp,
+            "02702ae71ec11a895f6255e26395983585a0d791ea1eb83d1aa54a66056469da",
+        )
+        .unwrap();
+        let swap_script = LBtcSwapScript::new(
+            BitcoinNetwork::LiquidTestnet,
+            SwapType::Submarine,
+            Preimage::new().hash160.to_string(),
+            sender_key_pair.public_key().to_string(),
+            1202545,
+            sender_key_pair.public_key().to_string(),
+            blinding_key,
+        );
+        let output_address = EAddress::p2wpkh(
+            &PublicKey::new(sender_key_pair.public_key()),
+            None,
+            &AddressParams::LIQUID_TESTNET,
+        );
+        let mut refund_tx =
+            LBtcSwapTx::new_refund(swap_script, output_address.to_string(), 300).unwrap();
+        refund_tx
+            .manual_utxo_update(OutPoint::default(), 50_000)
+            .unwrap();
+        // an explicit output would reveal the amount, so there is nothing to blind it to
+        let error = refund_tx.sign_refund_tx(sender_key_pair).unwrap_err();
+        assert_eq!(error.message, "Output address is not confidential.");
+    }
+
+    #[test]
+    fn test_pset() {
+        use elements::SchnorrSig;
         let secp = Secp256k1::new();
         const RETURN_ADDRESS: &str =
         "tlq1qqtc07z9kljll7dk2jyhz0qj86df9gnrc70t0wuexutzkxjavdpht0d4vwhgs2pq2f09zsvfr5nkglc394766w3hdaqrmay4tw";
-        let redeem_script_str = "8201208763a9142bdd03d431251598f46a625f1d3abfcd7f491535882102ccbab5f97c89afb97d814831c5355ef5ba96a18c9dcd1b5c8cfd42c697bfe53c677503715912b1752103fced00385bd14b174a571d88b4b6aced2cb1d532237c29c4ec61338fbb7eff4068ac".to_string();
-        let expected_address = "tlq1qq0gnj2my5tp8r77srvvdmwfrtr8va9mgz9e8ja0rzk75jvsanjvgz5sfvl093l5a7xztrtzhyhfmfyr2exdxtpw7cehfgtzgn62zdzcsgrz8c4pjfvtj";
-        let expected_timeout = 1202545;
-        let boltz_blinding_str = "02702ae71ec11a895f6255e26395983585a0d791ea1eb83d1aa54a66056469da";
-        let boltz_blinding_key = ZKKeyPair::from_seckey_str(&secp, boltz_blinding_str).unwrap();
-        let preimage_str = "6ef7d91c721ea06b3b65d824ae1d69777cd3892d41090234aef13a572ff0e64f";
-        let preimage = Preimage::from_str(preimage_str).unwrap();
-        // println!("{}", blinding_key.public_key().to_string());
-        let _id = "axtHXB";
-        let my_key_pair = KeyPair::from_seckey_str(
+        let absolute_fees = 300;
+        let timelock = 1202545;
+        let sender_key_pair = KeyPair::from_seckey_str(
             &secp,
             "aecbc2bddfcd3fa6953d257a9f369dc20cdc66f2605c73efb4c91b90703506b6",
         )
         .unwrap();
+        let reciever_key_pair = KeyPair::from_seckey_str(
+            &secp,
+            "d5f984d2ab332345dbf7ddff9f47852125721b2025329e6981c4130671e237d0",
+        )
+        .unwrap();
+        let blinding_key = ZKKeyPair::from_seckey_str(
+            &secp,
+            "02702ae71ec11a895f6255e26395983585a0d791ea1eb83d1aa54a66056469da",
+        )
+        .unwrap();
+        let preimage = Preimage::new();
+        let outpoint = OutPoint::from_str(
+            "c2a9a2d1e1ee3a6e4e0e1d86d5ea4f5f8e2ba1e30d6b8c48e7a46f2b5c3a1d01:1",
+        )
+        .unwrap();
 
-        let decoded = LBtcSwapScript::reverse_from_str(
+        // legacy submarine refund
+        let swap_script = LBtcSwapScript::new(
             BitcoinNetwork::LiquidTestnet,
-            DEFAULT_LIQUID_TESTNET_NODE.to_string(),
-            &redeem_script_str.clone(),
-            boltz_blinding_str.to_string(),
+            SwapType::Submarine,
+            preimage.hash160.to_string(),
+            reciever_key_pair.public_key().to_string(),
+            timelock,
+            sender_key_pair.public_key().to_string(),
+            blinding_key,
+        );
+        let mut refund_tx = LBtcSwapTx::new_refund(
+            swap_script.clone(),
+            RETURN_ADDRESS.to_string(),
+            absolute_fees,
         )
         .unwrap();
-        // println!("{:?}", decoded);
+        refund_tx.manual_utxo_update(outpoint, 50_000).unwrap();
+        let mut pset = refund_tx.to_pset().unwrap();
+        let redeem_script = swap_script.to_script().unwrap();
+        let input = &pset.inputs()[0];
         assert_eq!(
-            decoded.reciever_pubkey,
-            my_key_pair.public_key().to_string()
+            input.witness_utxo.as_ref().unwrap().script_pubkey,
+            swap_script.to_address().unwrap().script_pubkey()
         );
-        assert_eq!(decoded.timelock, expected_timeout);
+        assert_eq!(input.witness_script, Some(redeem_script.clone()));
+        assert_eq!(input.redeem_script, Some(redeem_script.to_v0_p2wsh()));
+        assert_eq!(input.amount, Some(50_000));
+        assert_eq!(
+            input.asset,
+            Some(elements::AssetId::from_str(LIQUID_TESTNET_POLICY_ASSET_STR).unwrap())
+        );
+        // an explicit utxo has no rangeproof
+        assert_eq!(input.in_utxo_rangeproof, None);
+        // the payment output is blinded, the fee output stays explicit
+        let unsigned_tx = pset.extract_tx().unwrap();
+        assert!(unsigned_tx.output[0].value.is_confidential());
+        assert_eq!(
+            unsigned_tx.output[1].value,
+            confidential::Value::Explicit(absolute_fees as u64)
+        );
+        assert_eq!(unsigned_tx.lock_time, LockTime::from_consensus(timelock));
+        assert!(refund_tx.finalize_pset(pset.clone(), None).is_err());
 
-        let el_script = LBtcSwapScript {
-            hashlock: decoded.hashlock,
-            reciever_pubkey: decoded.reciever_pubkey,
-            sender_pubkey: decoded.sender_pubkey,
-            timelock: decoded.timelock,
-            network: BitcoinNetwork::LiquidTestnet,
-            electrum_url: DEFAULT_LIQUID_TESTNET_NODE.to_string(),
-            swap_type: SwapType::ReverseSubmarine,
-            blinding_key: boltz_blinding_key,
-        };
+        // an external signer signs the exposed sighash
+        let sighashes = refund_tx.pset_sighashes(&pset).unwrap();
+        let sig = secp.sign_ecdsa_low_r(&sighashes[0], &sender_key_pair.secret_key());
+        let raw_sig = elementssig_to_rawsig(&(sig, elements::EcdsaSighashType::All));
+        let wrong_sig = secp.sign_ecdsa_low_r(&sighashes[0], &reciever_key_pair.secret_key());
+        let mut wrong_pset = pset.clone();
+        wrong_pset.inputs_mut()[0].partial_sigs.insert(
+            PublicKey::new(sender_key_pair.public_key()),
+            elementssig_to_rawsig(&(wrong_sig, elements::EcdsaSighashType::All)),
+        );
+        assert!(refund_tx.finalize_pset(wrong_pset, None).is_err());
+        pset.inputs_mut()[0].partial_sigs.insert(
+            PublicKey::new(sender_key_pair.public_key()),
+            raw_sig.clone(),
+        );
+        assert_eq!(
+            refund_tx
+                .finalize_pset(pset.clone(), Some(&Preimage::new()))
+                .unwrap_err()
+                .kind,
+            "Input"
+        );
+        let signed_tx = refund_tx.finalize_pset(pset, None).unwrap();
+        assert_eq!(
+            signed_tx.input[0].script_sig,
+            refund_tx.refund_script_sig(&redeem_script)
+        );
+        assert_eq!(
+            signed_tx.input[0].witness.script_witness,
+            vec![raw_sig, vec![], redeem_script.as_bytes().to_vec()]
+        );
+        assert!(tx_weight(&signed_tx) <= refund_tx.weight(false).unwrap());
+        assert!(refund_tx.check_swap_spend(&signed_tx).is_ok());
 
-        let address = el_script.to_address();
-        println!("ADDRESS FROM ENCODED: {:?}", address.to_string());
-        println!("Blinding Pub: {:?}", address.blinding_pubkey);
+        // taproot reverse claim
+        let swap_script = LBtcSwapScript::new_taproot(
+            BitcoinNetwork::LiquidTestnet,
+            SwapType::ReverseSubmarine,
+            preimage.hash160.to_string(),
+            reciever_key_pair.public_key().to_string(),
+            timelock,
+            sender_key_pair.public_key().to_string(),
+            blinding_key,
+        );
+        let mut claim_tx = LBtcSwapTx::new_claim(
+            swap_script.clone(),
+            RETURN_ADDRESS.to_string(),
+            absolute_fees,
+        )
+        .unwrap();
+        claim_tx.manual_utxo_update(outpoint, 50_000).unwrap();
+        let mut pset = claim_tx.to_pset().unwrap();
+        let leaf = swap_script.claim_leaf().unwrap();
+        let spend_info = swap_script.taproot_spend_info().unwrap();
+        assert_eq!(
+            pset.inputs()[0].tap_internal_key,
+            Some(spend_info.internal_key())
+        );
+        assert!(pset.inputs()[0]
+            .tap_scripts
+            .values()
+            .any(|(script, _)| *script == leaf));
 
-        assert_eq!(address.to_string(), expected_address);
+        let sighashes = claim_tx.pset_sighashes(&pset).unwrap();
+        let sig = secp.sign_schnorr(&sighashes[0], &reciever_key_pair);
+        let leaf_hash = TapLeafHash::from_script(&leaf, LeafVersion::default());
+        pset.inputs_mut()[0].tap_script_sigs.insert(
+            (reciever_key_pair.x_only_public_key().0, leaf_hash),
+            SchnorrSig {
+                sig,
+                hash_ty: SchnorrSighashType::Default,
+            },
+        );
+        assert!(claim_tx.finalize_pset(pset.clone(), None).is_err());
+        assert!(claim_tx
+            .finalize_pset(pset.clone(), Some(&Preimage::new()))
+            .is_err());
+        let signed_tx = claim_tx.finalize_pset(pset, Some(&preimage)).unwrap();
+        assert_eq!(
+            signed_tx.input[0].witness.script_witness,
+            vec![
+                sig.as_ref().to_vec(),
+                preimage.bytes.unwrap().to_vec(),
+                leaf.as_bytes().to_vec(),
+                claim_tx.control_block(&leaf).unwrap().serialize(),
+            ]
+        );
+        assert_eq!(signed_tx.lock_time, LockTime::ZERO);
+        assert!(tx_weight(&signed_tx) <= claim_tx.weight(false).unwrap());
+        assert!(claim_tx.check_swap_spend(&signed_tx).is_ok());
 
-        let mut liquid_swap_tx =
-            LBtcSwapTx::new_claim(el_script, RETURN_ADDRESS.to_string(), 5_000).unwrap();
-        let final_tx = liquid_swap_tx.drain(my_key_pair, preimage).unwrap();
-        println!("FINALIZED TX SIZE: {:?}", final_tx.size());
-        let manifest_dir = env!("CARGO_MANIFEST_DIR");
+        // a claim with another preimage, or signed by the sender, does not reach the node
+        let mut tampered = signed_tx.clone();
+        tampered.input[0].witness.script_witness[1] = vec![0; 32];
+        let error = claim_tx.check_swap_spend(&tampered).unwrap_err();
+        assert!(error
+            .message
+            .contains("Preimage does not match the hashlock"));
+        let wrong_signer = claim_tx
+            .sign_taproot_script_path(sender_key_pair, leaf, preimage.bytes.map(|b| b.to_vec()))
+            .unwrap();
+        let backend = crate::network::mock::MockChainBackend::new(timelock, 0.1);
+        let error = claim_tx.broadcast(&backend, wrong_signer).unwrap_err();
+        assert!(error.message.contains("Invalid signature"));
+        assert!(backend.broadcasted().is_empty());
+    }
 
-        let file_path = Path::new(manifest_dir).join("tx.constructed");
-        let mut file = File::create(file_path).unwrap();
-        use std::io::Write;
-        writeln!(file, "{:#?}", final_tx).unwrap();
-        // println!("CHECK FILE tx.hex!");
+    #[test]
+    fn test_taproot_swap_script() {
+        use crate::swaps::bitcoin::BtcSwapScript;
+        use bitcoin::secp256k1::rand::rngs::OsRng;
+        let secp = Secp256k1::new();
+        let mut rng = OsRng::default();
+        const RETURN_ADDRESS: &str =
+        "tlq1qqtc07z9kljll7dk2jyhz0qj86df9gnrc70t0wuexutzkxjavdpht0d4vwhgs2pq2f09zsvfr5nkglc394766w3hdaqrmay4tw";
+        let utxo_value = 50_000;
+        let absolute_fees = 300;
+        let timelock = 1202545;
+        let reciever_key_pair = KeyPair::from_seckey_str(
+            &secp,
+            "aecbc2bddfcd3fa6953d257a9f369dc20cdc66f2605c73efb4c91b90703506b6",
+        )
+        .unwrap();
+        let sender_key_pair = KeyPair::from_seckey_str(
+            &secp,
+            "d5f984d2ab332345dbf7ddff9f47852125721b2025329e6981c4130671e237d0",
+        )
+        .unwrap();
+        let blinding_key = ZKKeyPair::from_seckey_str(
+            &secp,
+            "02702ae71ec11a895f6255e26395983585a0d791ea1eb83d1aa54a66056469da",
+        )
+        .unwrap();
+        let preimage = Preimage::new();
 
-        let txid = liquid_swap_tx.broadcast(final_tx).unwrap();
-        println!("TXID: {}", txid);
+        let swap_script = LBtcSwapScript::new_taproot(
+            BitcoinNetwork::LiquidTestnet,
+            SwapType::ReverseSubmarine,
+            preimage.hash160.to_string(),
+            reciever_key_pair.public_key().to_string(),
+            timelock,
+            sender_key_pair.public_key().to_string(),
+            blinding_key,
+        );
+        // leaves are the same scripts as on bitcoin, only the leaf version differs
+        let btc_swap_script = BtcSwapScript::new_taproot(
+            BitcoinNetwork::BitcoinTestnet,
+            SwapType::ReverseSubmarine,
+            preimage.hash160.to_string(),
+            reciever_key_pair.public_key().to_string(),
+            timelock,
+            sender_key_pair.public_key().to_string(),
+        );
+        let claim_leaf = swap_script.claim_leaf().unwrap();
+        let refund_leaf = swap_script.refund_leaf().unwrap();
+        assert_eq!(
+            claim_leaf.as_bytes(),
+            btc_swap_script.claim_leaf().unwrap().as_bytes()
+        );
+        assert_eq!(
+            refund_leaf.as_bytes(),
+            btc_swap_script.refund_leaf().unwrap().as_bytes()
+        );
+
+        let address = swap_script.to_address().unwrap();
+        assert!(address.is_blinded());
+        // taproot and chain swaps have no single redeem script
+        assert!(swap_script.to_script().is_err());
+        let chain_script = LBtcSwapScript::new(
+            BitcoinNetwork::LiquidTestnet,
+            SwapType::Chain,
+            preimage.hash160.to_string(),
+            reciever_key_pair.public_key().to_string(),
+            timelock,
+            sender_key_pair.public_key().to_string(),
+            blinding_key,
+        );
+        assert!(chain_script.to_script().is_err());
+        assert!(chain_script.to_address().is_err());
+        assert_eq!(address.blinding_pubkey, Some(blinding_key.public_key()));
+        let spend_info = swap_script.taproot_spend_info().unwrap();
+        assert_eq!(
+            address.script_pubkey(),
+            EScript::new_v1_p2tr_tweaked(spend_info.output_key())
+        );
+
+        let mut swap_tree: SwapTree = serde_json::from_value(serde_json::json!({
+            "claimLeaf": {"version": 196, "output": hex::encode(claim_leaf.as_bytes())},
+            "refundLeaf": {"version": 196, "output": hex::encode(refund_leaf.as_bytes())},
+        }))
+        .unwrap();
+        let lockup_address = address.to_string();
+        assert!(swap_script
+            .validate_swap_tree(&swap_tree, &lockup_address)
+            .is_ok());
+        // Boltz may leave out the blinding key, but not pay somewhere else
+        let unconfidential = address.to_unconfidential().to_string();
+        assert!(swap_script
+            .validate_swap_tree(&swap_tree, &unconfidential)
+            .is_ok());
+        let other_address = btc_swap_script.to_address().unwrap().to_string();
+        assert!(swap_script
+            .validate_swap_tree(&swap_tree, &other_address)
+            .is_err());
+        swap_tree.claim_leaf.version = 0xc0;
+        assert!(swap_script
+            .validate_swap_tree(&swap_tree, &lockup_address)
+            .is_err());
+
+        let asset_id = elements::AssetId::from_str(LIQUID_TESTNET_POLICY_ASSET_STR).unwrap();
+        let prevout = TxOut {
+            asset: confidential::Asset::Explicit(asset_id),
+            value: confidential::Value::Explicit(utxo_value),
+            nonce: confidential::Nonce::Null,
+            script_pubkey: address.script_pubkey(),
+            witness: TxOutWitness::default(),
+        };
+        let mut refund_tx =
+            LBtcSwapTx::new_refund(swap_script, RETURN_ADDRESS.to_string(), absolute_fees).unwrap();
+        refund_tx.utxos = vec![LockupOutput {
+            outpoint: OutPoint::new(
+                elements::Txid::from_str(
+                    "10c31b81b93a69a635ab337bb48adeefe662d40d3e5d50b319a1fed17485104a",
+                )
+                .unwrap(),
+                1,
+            ),
+            txout: prevout.clone(),
+            secrets: TxOutSecrets::new(
+                asset_id,
+                AssetBlindingFactor::new(&mut rng),
+                utxo_value,
+                ValueBlindingFactor::new(&mut rng),
+            ),
+        }];
+
+        let signed_tx = refund_tx.sign(sender_key_pair, preimage).unwrap();
+        assert_eq!(signed_tx.lock_time, LockTime::from_consensus(timelock));
+        assert!(signed_tx.output[1].is_fee());
+        let input = &signed_tx.input[0];
+        assert!(input.script_sig.is_empty());
+
+        // witness: <signature> <refund leaf> <control block>
+        let witness = &input.witness.script_witness;
+        assert_eq!(witness.len(), 3);
+        assert_eq!(witness[1], refund_leaf.as_bytes().to_vec());
+        let sig = secp256k1_zkp::schnorr::Signature::from_slice(&witness[0]).unwrap();
+        let sighash = SighashCache::new(&signed_tx)
+            .taproot_script_spend_signature_hash(
+                0,
+                &Prevouts::All(&[prevout]),
+                TapLeafHash::from_script(&refund_leaf, LeafVersion::default()),
+                SchnorrSighashType::Default,
+                genesis_hash(&BitcoinNetwork::LiquidTestnet),
+            )
+            .unwrap();
+        let message = Message::from_slice(&sighash[..]).unwrap();
+        assert!(secp
+            .verify_schnorr(&sig, &message, &sender_key_pair.x_only_public_key().0)
+            .is_ok());
     }
 }
 
@@ -1,3 +1,9 @@
 pub mod bitcoin;
 pub mod boltz;
+pub mod boltzv2;
+#[cfg(feature = "ws")]
+pub mod boltzws;
+pub mod chain;
+pub mod cooperative;
 pub mod liquid;
+pub mod validation;
@@ -0,0 +1,908 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use bitcoin::hashes::{hash160, ripemd160, sha256, Hash};
+use elements::secp256k1_zkp::{KeyPair as ZKKeyPair, Secp256k1 as ZKSecp256k1};
+use lightning_invoice::Bolt11Invoice;
+
+use crate::{
+    network::electrum::BitcoinNetwork,
+    swaps::{
+        bitcoin::BtcSwapScript,
+        boltz::{CreateSwapResponse, SwapQuote},
+        liquid::LBtcSwapScript,
+    },
+    util::error::{ErrorKind, S5Error},
+};
+
+/// One check of a Boltz response that failed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mismatch {
+    MissingField(&'static str),
+    InvalidScript(String),
+    Hashlock {
+        expected: String,
+        found: String,
+    },
+    RefundPubkey {
+        expected: String,
+        found: String,
+    },
+    ClaimPubkey {
+        expected: String,
+        found: String,
+    },
+    InvalidInvoice(String),
+    PaymentHash {
+        expected: String,
+        found: String,
+    },
+    InvoiceAmount {
+        expected: u64,
+        found: u64,
+    },
+    InvalidBlindingKey(String),
+    /// `timeoutBlockHeight` differs from the timelock in the redeem script.
+    TimeoutBlockHeight {
+        script: u32,
+        response: u64,
+    },
+    /// The timelock is not between `min` and `max`, both absolute heights.
+    Timeout {
+        timeout: u32,
+        min: u32,
+        max: u32,
+    },
+    /// The timelock leaves us less than `min`, an absolute height.
+    TimeoutTooSoon {
+        timeout: u32,
+        min: u32,
+    },
+    Address {
+        expected: String,
+        found: String,
+    },
+    ExpectedAmount {
+        expected: u64,
+        found: u64,
+    },
+    OnchainAmount {
+        expected: u64,
+        found: u64,
+    },
+}
+
+impl Display for Mismatch {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Mismatch::MissingField(field) => write!(f, "Missing {}", field),
+            Mismatch::InvalidScript(error) => write!(f, "Invalid redeem script: {}", error),
+            Mismatch::Hashlock { expected, found } => {
+                write!(f, "Hashlock: expected {}, found {}", expected, found)
+            }
+            Mismatch::RefundPubkey { expected, found } => {
+                write!(f, "Refund pubkey: expected {}, found {}", expected, found)
+            }
+            Mismatch::ClaimPubkey { expected, found } => {
+                write!(f, "Claim pubkey: expected {}, found {}", expected, found)
+            }
+            Mismatch::InvalidInvoice(error) => write!(f, "Invalid invoice: {}", error),
+            Mismatch::PaymentHash { expected, found } => {
+                write!(f, "Payment hash: expected {}, found {}", expected, found)
+            }
+            Mismatch::InvoiceAmount { expected, found } => {
+                write!(f, "Invoice amount: expected {}, found {}", expected, found)
+            }
+            Mismatch::InvalidBlindingKey(error) => write!(f, "Invalid blinding key: {}", error),
+            Mismatch::TimeoutBlockHeight { script, response } => write!(
+                f,
+                "Timeout: script has {}, response has {}",
+                script, response
+            ),
+            Mismatch::Timeout { timeout, min, max } => write!(
+                f,
+                "Timeout {} is outside the accepted window {}..={}",
+                timeout, min, max
+            ),
+            Mismatch::TimeoutTooSoon { timeout, min } => {
+                write!(f, "Timeout {} is before {}", timeout, min)
+            }
+            Mismatch::Address { expected, found } => {
+                write!(f, "Address: expected {}, found {}", expected, found)
+            }
+            Mismatch::ExpectedAmount { expected, found } => {
+                write!(f, "Expected amount: expected {}, found {}", expected, found)
+            }
+            Mismatch::OnchainAmount { expected, found } => {
+                write!(f, "Onchain amount: expected {}, found {}", expected, found)
+            }
+        }
+    }
+}
+
+/// Every check a Boltz response failed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        let mismatches: Vec<String> = self.mismatches.iter().map(|m| m.to_string()).collect();
+        write!(f, "{}", mismatches.join("; "))
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl From<Mismatch> for ValidationError {
+    fn from(mismatch: Mismatch) -> Self {
+        ValidationError {
+            mismatches: vec![mismatch],
+        }
+    }
+}
+
+impl From<ValidationError> for S5Error {
+    fn from(error: ValidationError) -> Self {
+        S5Error::new(ErrorKind::BoltzApi, &error.to_string())
+    }
+}
+
+/// What we asked for in a submarine swap, to check Boltz's response against before funding the lockup.
+#[derive(Debug, Clone)]
+pub struct SubmarineSwapExpectation {
+    pub preimage_hash160: hash160::Hash,
+    pub refund_pubkey: String,
+    /// Quote for the invoice we sent; its `onchain_amount` is what Boltz should ask us to lock.
+    pub quote: SwapQuote,
+    pub tip_height: u32,
+    /// The timeout must be at least this many blocks after `tip_height`, so we don't fund an
+    /// almost expired swap...
+    pub min_timeout_blocks: u32,
+    /// ...and at most this many, so our funds aren't locked for longer than we agreed to.
+    pub max_timeout_blocks: u32,
+}
+
+/// What we asked for in a reverse swap, to check Boltz's response against before paying the invoice.
+#[derive(Debug, Clone)]
+pub struct ReverseSwapExpectation {
+    pub preimage_sha256: sha256::Hash,
+    pub claim_pubkey: String,
+    /// Quote the swap was requested from: Boltz should lock `onchain_amount` for an invoice of
+    /// `invoice_amount`.
+    pub quote: SwapQuote,
+    pub tip_height: u32,
+    /// Blocks we need, after `tip_height`, to claim before Boltz can refund.
+    pub min_timeout_blocks: u32,
+}
+
+impl CreateSwapResponse {
+    /// Checks a BTC submarine swap and returns its redeem script if everything matches.
+    pub fn validate_btc_submarine(
+        &self,
+        network: BitcoinNetwork,
+        expected: &SubmarineSwapExpectation,
+    ) -> Result<BtcSwapScript, ValidationError> {
+        let redeem_script = required(&self.redeem_script, "redeemScript")?;
+        let script = match BtcSwapScript::submarine_from_str(network, redeem_script) {
+            Ok(result) => result,
+            Err(e) => return Err(Mismatch::InvalidScript(e.message).into()),
+        };
+        let address = match script.to_address() {
+            Ok(result) => result.to_string(),
+            Err(e) => return Err(Mismatch::InvalidScript(e.message).into()),
+        };
+        let mismatches = self.check_submarine(
+            expected,
+            &script.hashlock,
+            &script.sender_pubkey,
+            script.timelock,
+            &address,
+        );
+        if mismatches.is_empty() {
+            Ok(script)
+        } else {
+            Err(ValidationError { mismatches })
+        }
+    }
+
+    /// Checks an L-BTC submarine swap and returns its redeem script if everything matches.
+    /// The lockup address is confidential, blinded with the `blindingKey` Boltz returned.
+    pub fn validate_lbtc_submarine(
+        &self,
+        network: BitcoinNetwork,
+        expected: &SubmarineSwapExpectation,
+    ) -> Result<LBtcSwapScript, ValidationError> {
+        let redeem_script = required(&self.redeem_script, "redeemScript")?;
+        let blinding_key = blinding_key(&self.blinding_key)?;
+        let script = match LBtcSwapScript::submarine_from_str(network, redeem_script, blinding_key)
+        {
+            Ok(result) => result,
+            Err(e) => return Err(Mismatch::InvalidScript(e.message).into()),
+        };
+        let address = match script.to_address() {
+            Ok(result) => result.to_string(),
+            Err(e) => return Err(Mismatch::InvalidScript(e.message).into()),
+        };
+        let mismatches = self.check_submarine(
+            expected,
+            &script.hashlock,
+            &script.sender_pubkey,
+            script.timelock,
+            &address,
+        );
+        if mismatches.is_empty() {
+            Ok(script)
+        } else {
+            Err(ValidationError { mismatches })
+        }
+    }
+
+    fn check_submarine(
+        &self,
+        expected: &SubmarineSwapExpectation,
+        hashlock: &str,
+        refund_pubkey: &str,
+        timelock: u32,
+        address: &str,
+    ) -> Vec<Mismatch> {
+        let mut mismatches = Vec::new();
+        if hashlock != expected.preimage_hash160.to_string() {
+            mismatches.push(Mismatch::Hashlock {
+                expected: expected.preimage_hash160.to_string(),
+                found: hashlock.to_string(),
+            });
+        }
+        if refund_pubkey != expected.refund_pubkey {
+            mismatches.push(Mismatch::RefundPubkey {
+                expected: expected.refund_pubkey.clone(),
+                found: refund_pubkey.to_string(),
+            });
+        }
+        check_timeout(
+            &mut mismatches,
+            self.timeout_block_height,
+            timelock,
+            expected.tip_height,
+            expected.min_timeout_blocks,
+            Some(expected.max_timeout_blocks),
+        );
+        match &self.address {
+            Some(found) if found == address => (),
+            Some(found) => mismatches.push(Mismatch::Address {
+                expected: address.to_string(),
+                found: found.clone(),
+            }),
+            None => mismatches.push(Mismatch::MissingField("address")),
+        }
+        match self.expected_amount {
+            Some(found) if found == expected.quote.onchain_amount => (),
+            Some(found) => mismatches.push(Mismatch::ExpectedAmount {
+                expected: expected.quote.onchain_amount,
+                found,
+            }),
+            None => mismatches.push(Mismatch::MissingField("expectedAmount")),
+        }
+        mismatches
+    }
+
+    /// Checks a BTC reverse swap and returns its redeem script if everything matches.
+    pub fn validate_btc_reverse(
+        &self,
+        network: BitcoinNetwork,
+        expected: &ReverseSwapExpectation,
+    ) -> Result<BtcSwapScript, ValidationError> {
+        let redeem_script = required(&self.redeem_script, "redeemScript")?;
+        let script = match BtcSwapScript::reverse_from_str(network, redeem_script) {
+            Ok(result) => result,
+            Err(e) => return Err(Mismatch::InvalidScript(e.message).into()),
+        };
+        let address = match script.to_address() {
+            Ok(result) => result.to_string(),
+            Err(e) => return Err(Mismatch::InvalidScript(e.message).into()),
+        };
+        let mismatches = self.check_reverse(
+            expected,
+            &script.hashlock,
+            &script.reciever_pubkey,
+            script.timelock,
+            &address,
+        );
+        if mismatches.is_empty() {
+            Ok(script)
+        } else {
+            Err(ValidationError { mismatches })
+        }
+    }
+
+    /// Checks an L-BTC reverse swap and returns its redeem script if everything matches.
+    /// `blindingKey` must be a valid key and blind the lockup address, so we can unblind the lockup.
+    pub fn validate_lbtc_reverse(
+        &self,
+        network: BitcoinNetwork,
+        expected: &ReverseSwapExpectation,
+    ) -> Result<LBtcSwapScript, ValidationError> {
+        let redeem_script = required(&self.redeem_script, "redeemScript")?;
+        let blinding_key = blinding_key(&self.blinding_key)?;
+        let script = match LBtcSwapScript::reverse_from_str(network, redeem_script, blinding_key) {
+            Ok(result) => result,
+            Err(e) => return Err(Mismatch::InvalidScript(e.message).into()),
+        };
+        let address = match script.to_address() {
+            Ok(result) => result.to_string(),
+            Err(e) => return Err(Mismatch::InvalidScript(e.message).into()),
+        };
+        let mismatches = self.check_reverse(
+            expected,
+            &script.hashlock,
+            &script.reciever_pubkey,
+            script.timelock,
+            &address,
+        );
+        if mismatches.is_empty() {
+            Ok(script)
+        } else {
+            Err(ValidationError { mismatches })
+        }
+    }
+
+    fn check_reverse(
+        &self,
+        expected: &ReverseSwapExpectation,
+        hashlock: &str,
+        claim_pubkey: &str,
+        timelock: u32,
+        address: &str,
+    ) -> Vec<Mismatch> {
+        let mut mismatches = Vec::new();
+        let preimage_hash160 = ripemd160::Hash::hash(&expected.preimage_sha256[..]).to_string();
+        if hashlock != preimage_hash160 {
+            mismatches.push(Mismatch::Hashlock {
+                expected: preimage_hash160,
+                found: hashlock.to_string(),
+            });
+        }
+        if claim_pubkey != expected.claim_pubkey {
+            mismatches.push(Mismatch::ClaimPubkey {
+                expected: expected.claim_pubkey.clone(),
+                found: claim_pubkey.to_string(),
+            });
+        }
+        check_timeout(
+            &mut mismatches,
+            self.timeout_block_height,
+            timelock,
+            expected.tip_height,
+            expected.min_timeout_blocks,
+            None,
+        );
+        match &self.lockup_address {
+            Some(found) if found == address => (),
+            Some(found) => mismatches.push(Mismatch::Address {
+                expected: address.to_string(),
+                found: found.clone(),
+            }),
+            None => mismatches.push(Mismatch::MissingField("lockupAddress")),
+        }
+        match self.onchain_amount {
+            Some(found) if found == expected.quote.onchain_amount => (),
+            Some(found) => mismatches.push(Mismatch::OnchainAmount {
+                expected: expected.quote.onchain_amount,
+                found,
+            }),
+            None => mismatches.push(Mismatch::MissingField("onchainAmount")),
+        }
+        match &self.invoice {
+            Some(invoice) => check_invoice(&mut mismatches, invoice, expected),
+            None => mismatches.push(Mismatch::MissingField("invoice")),
+        }
+        mismatches
+    }
+}
+
+fn check_invoice(mismatches: &mut Vec<Mismatch>, invoice: &str, expected: &ReverseSwapExpectation) {
+    let invoice = match Bolt11Invoice::from_str(invoice) {
+        Ok(result) => result,
+        Err(e) => {
+            mismatches.push(Mismatch::InvalidInvoice(e.to_string()));
+            return;
+        }
+    };
+    if *invoice.payment_hash() != expected.preimage_sha256 {
+        mismatches.push(Mismatch::PaymentHash {
+            expected: expected.preimage_sha256.to_string(),
+            found: invoice.payment_hash().to_string(),
+        });
+    }
+    match invoice.amount_milli_satoshis() {
+        Some(msat) if msat == expected.quote.invoice_amount * 1000 => (),
+        Some(msat) => mismatches.push(Mismatch::InvoiceAmount {
+            expected: expected.quote.invoice_amount,
+            found: msat / 1000,
+        }),
+        None => mismatches.push(Mismatch::InvalidInvoice(
+            "Invoice has no amount".to_string(),
+        )),
+    }
+}
+
+/// The redeem script parsers expect a valid blinding key.
+fn blinding_key(field: &Option<String>) -> Result<String, Mismatch> {
+    let blinding_str = required(field, "blindingKey")?;
+    match ZKKeyPair::from_seckey_str(&ZKSecp256k1::new(), blinding_str) {
+        Ok(_) => Ok(blinding_str.clone()),
+        Err(e) => Err(Mismatch::InvalidBlindingKey(e.to_string())),
+    }
+}
+
+fn required<'a>(field: &'a Option<String>, name: &'static str) -> Result<&'a String, Mismatch> {
+    match field {
+        Some(result) => Ok(result),
+        None => Err(Mismatch::MissingField(name)),
+    }
+}
+
+fn check_timeout(
+    mismatches: &mut Vec<Mismatch>,
+    response_timeout: Option<u64>,
+    timelock: u32,
+    tip_height: u32,
+    min_blocks: u32,
+    max_blocks: Option<u32>,
+) {
+    match response_timeout {
+        Some(response) if response == timelock as u64 => (),
+        Some(response) => mismatches.push(Mismatch::TimeoutBlockHeight {
+            script: timelock,
+            response,
+        }),
+        None => mismatches.push(Mismatch::MissingField("timeoutBlockHeight")),
+    }
+    // saturating: bounds past the largest height leave no valid timeout, rather than wrapping
+    let min = tip_height.saturating_add(min_blocks);
+    match max_blocks {
+        Some(max_blocks) => {
+            let max = tip_height.saturating_add(max_blocks);
+            if timelock < min || timelock > max {
+                mismatches.push(Mismatch::Timeout {
+                    timeout: timelock,
+                    min,
+                    max,
+                });
+            }
+        }
+        None => {
+            if timelock < min {
+                mismatches.push(Mismatch::TimeoutTooSoon {
+                    timeout: timelock,
+                    min,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::secp256k1::{KeyPair, Secp256k1};
+
+    use super::*;
+    use crate::{swaps::boltz::SwapType, util::preimage::Preimage};
+
+    const TIP_HEIGHT: u32 = 2_541_900;
+    const TIMEOUT: u32 = 2_542_048;
+
+    fn keys() -> (KeyPair, KeyPair) {
+        let secp = Secp256k1::new();
+        let ours = KeyPair::from_seckey_str(
+            &secp,
+            "d5f984d2ab332345dbf7ddff9f47852125721b2025329e6981c4130671e237d0",
+        )
+        .unwrap();
+        let boltz = KeyPair::from_seckey_str(
+            &secp,
+            "5f9f8cb71d8193cb031b1a8b9b1ec08057a130dd8ac9f69cea2e3d8e6675f3a1",
+        )
+        .unwrap();
+        (ours, boltz)
+    }
+
+    fn expectation(preimage: &Preimage, refund_pubkey: String) -> SubmarineSwapExpectation {
+        SubmarineSwapExpectation {
+            preimage_hash160: preimage.hash160,
+            refund_pubkey,
+            quote: SwapQuote {
+                invoice_amount: 50_000,
+                onchain_amount: 50_390,
+                service_fee: 50,
+                boltz_claim_fee: 340,
+                boltz_lockup_fee: 0,
+                claim_fee_estimate: 0,
+                above_minimal: true,
+                below_maximal: true,
+                within_zero_conf: false,
+            },
+            tip_height: TIP_HEIGHT,
+            min_timeout_blocks: 6,
+            max_timeout_blocks: 288,
+        }
+    }
+
+    fn submarine_response(redeem_script: String, address: String) -> CreateSwapResponse {
+        serde_json::from_value(serde_json::json!({
+            "id": "FwS1I8",
+            "redeemScript": redeem_script,
+            "address": address,
+            "timeoutBlockHeight": TIMEOUT,
+            "expectedAmount": 50_390,
+            "acceptZeroConf": false,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_check_timeout_overflow() {
+        // bounds past u32::MAX must not wrap around to small heights that any timeout passes
+        let mut mismatches = vec![];
+        check_timeout(
+            &mut mismatches,
+            Some(TIMEOUT as u64),
+            TIMEOUT,
+            u32::MAX - 10,
+            144,
+            Some(u32::MAX),
+        );
+        assert_eq!(
+            mismatches,
+            vec![Mismatch::Timeout {
+                timeout: TIMEOUT,
+                min: u32::MAX,
+                max: u32::MAX,
+            }]
+        );
+
+        let mut mismatches = vec![];
+        check_timeout(
+            &mut mismatches,
+            Some(TIMEOUT as u64),
+            TIMEOUT,
+            TIP_HEIGHT,
+            u32::MAX,
+            None,
+        );
+        assert_eq!(
+            mismatches,
+            vec![Mismatch::TimeoutTooSoon {
+                timeout: TIMEOUT,
+                min: u32::MAX,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_validate_btc_submarine() {
+        let (ours, boltz) = keys();
+        let preimage =
+            Preimage::from_str("898396fe53c58375cf8a5a8cfead2a285dc4b5b84cd149800914fc60c9f3a70b")
+                .unwrap();
+        let script = BtcSwapScript::new(
+            BitcoinNetwork::BitcoinTestnet,
+            SwapType::Submarine,
+            preimage.hash160.to_string(),
+            boltz.public_key().to_string(),
+            TIMEOUT,
+            ours.public_key().to_string(),
+        );
+        let redeem_script = script.to_script().unwrap().to_hex_string();
+        let address = script.to_address().unwrap().to_string();
+        let expected = expectation(&preimage, ours.public_key().to_string());
+
+        let response = submarine_response(redeem_script.clone(), address.clone());
+        assert_eq!(
+            response
+                .validate_btc_submarine(BitcoinNetwork::BitcoinTestnet, &expected)
+                .unwrap(),
+            script
+        );
+
+        // boltz swapped the keys, so it could refund itself; everything else is off too
+        let evil = BtcSwapScript::new(
+            BitcoinNetwork::BitcoinTestnet,
+            SwapType::Submarine,
+            preimage.hash160.to_string(),
+            ours.public_key().to_string(),
+            TIMEOUT + 1000,
+            boltz.public_key().to_string(),
+        );
+        let mut response = submarine_response(evil.to_script().unwrap().to_hex_string(), address);
+        response.expected_amount = Some(60_000);
+        let error = response
+            .validate_btc_submarine(BitcoinNetwork::BitcoinTestnet, &expected)
+            .unwrap_err();
+        assert_eq!(
+            error.mismatches,
+            vec![
+                Mismatch::RefundPubkey {
+                    expected: ours.public_key().to_string(),
+                    found: boltz.public_key().to_string(),
+                },
+                Mismatch::TimeoutBlockHeight {
+                    script: TIMEOUT + 1000,
+                    response: TIMEOUT as u64,
+                },
+                Mismatch::Timeout {
+                    timeout: TIMEOUT + 1000,
+                    min: TIP_HEIGHT + 6,
+                    max: TIP_HEIGHT + 288,
+                },
+                Mismatch::Address {
+                    expected: evil.to_address().unwrap().to_string(),
+                    found: script.to_address().unwrap().to_string(),
+                },
+                Mismatch::ExpectedAmount {
+                    expected: 50_390,
+                    found: 60_000,
+                },
+            ]
+        );
+        let s5error: S5Error = error.into();
+        assert!(s5error.message.starts_with("Refund pubkey"));
+
+        // a different hashlock
+        let other = Preimage::new();
+        let error = submarine_response(
+            redeem_script.clone(),
+            script.to_address().unwrap().to_string(),
+        )
+        .validate_btc_submarine(
+            BitcoinNetwork::BitcoinTestnet,
+            &expectation(&other, ours.public_key().to_string()),
+        )
+        .unwrap_err();
+        assert_eq!(error.mismatches.len(), 1);
+
+        let mut response = submarine_response(redeem_script, String::new());
+        response.redeem_script = None;
+        assert_eq!(
+            response
+                .validate_btc_submarine(BitcoinNetwork::BitcoinTestnet, &expected)
+                .unwrap_err()
+                .mismatches,
+            vec![Mismatch::MissingField("redeemScript")]
+        );
+    }
+
+    #[test]
+    fn test_validate_lbtc_submarine() {
+        let (ours, boltz) = keys();
+        let preimage =
+            Preimage::from_str("898396fe53c58375cf8a5a8cfead2a285dc4b5b84cd149800914fc60c9f3a70b")
+                .unwrap();
+        let blinding_str = "02702ae71ec11a895f6255e26395983585a0d791ea1eb83d1aa54a66056469da";
+        let blinding_key = ZKKeyPair::from_seckey_str(&ZKSecp256k1::new(), blinding_str).unwrap();
+        let script = LBtcSwapScript::new(
+            BitcoinNetwork::LiquidTestnet,
+            SwapType::Submarine,
+            preimage.hash160.to_string(),
+            boltz.public_key().to_string(),
+            TIMEOUT,
+            ours.public_key().to_string(),
+            blinding_key,
+        );
+        let redeem_script = hex::encode(script.to_script().unwrap().as_bytes());
+        let mut response =
+            submarine_response(redeem_script, script.to_address().unwrap().to_string());
+        let expected = expectation(&preimage, ours.public_key().to_string());
+        assert_eq!(
+            response
+                .validate_lbtc_submarine(BitcoinNetwork::LiquidTestnet, &expected)
+                .unwrap_err()
+                .mismatches,
+            vec![Mismatch::MissingField("blindingKey")]
+        );
+
+        response.blinding_key = Some(blinding_str.to_string());
+        assert_eq!(
+            response
+                .validate_lbtc_submarine(BitcoinNetwork::LiquidTestnet, &expected)
+                .unwrap(),
+            script
+        );
+
+        // the unconfidential address pays the same script but is not what we blind to
+        response.address = Some(script.to_address().unwrap().to_unconfidential().to_string());
+        assert!(matches!(
+            response
+                .validate_lbtc_submarine(BitcoinNetwork::LiquidTestnet, &expected)
+                .unwrap_err()
+                .mismatches[..],
+            [Mismatch::Address { .. }]
+        ));
+    }
+
+    // testnet invoice for 50_100 sats
+    const INVOICE: &str = "lntb501u1pjh67z3pp539hhfy9vk70yde3m0lkp838l2y0xqskmf5cwm8ng25rqp8asncmsdq8w3jhxaqxqyjw5qcqp2sp59dsnqt4ecde2frjn5zrnw6cunryadzv3p386glz8l7uj37pnwnvsrzjq2gyp9za7vc7vd8m59fvu63pu00u4pak35n4upuv4mhyw5l586dvkfkdwyqqq4sqqyqqqqqpqqqqqzsqqc9qyyssq4esj2vvneu5y4e8qtheyxmepjgg5turmxccgmuks78l08m9wguvhvw2yvrftfjh6tzaxy57mty3zsvg3jveazfxs60e6acn989pzdlspafd52g";
+    const PAYMENT_HASH: &str = "896f7490acb79e46e63b7fec13c4ff511e6042db4d30ed9e685506009fb09e37";
+
+    fn reverse_expectation(claim_pubkey: String) -> ReverseSwapExpectation {
+        ReverseSwapExpectation {
+            preimage_sha256: sha256::Hash::from_str(PAYMENT_HASH).unwrap(),
+            claim_pubkey,
+            quote: SwapQuote {
+                invoice_amount: 50_100,
+                onchain_amount: 49_633,
+                service_fee: 126,
+                boltz_claim_fee: 0,
+                boltz_lockup_fee: 341,
+                claim_fee_estimate: 276,
+                above_minimal: true,
+                below_maximal: true,
+                within_zero_conf: false,
+            },
+            tip_height: TIP_HEIGHT,
+            min_timeout_blocks: 24,
+        }
+    }
+
+    fn reverse_response(redeem_script: String, lockup_address: String) -> CreateSwapResponse {
+        serde_json::from_value(serde_json::json!({
+            "id": "RvS1I8",
+            "invoice": INVOICE,
+            "redeemScript": redeem_script,
+            "lockupAddress": lockup_address,
+            "timeoutBlockHeight": TIMEOUT,
+            "onchainAmount": 49_633,
+        }))
+        .unwrap()
+    }
+
+    fn reverse_hashlock() -> String {
+        let payment_hash = sha256::Hash::from_str(PAYMENT_HASH).unwrap();
+        ripemd160::Hash::hash(&payment_hash[..]).to_string()
+    }
+
+    #[test]
+    fn test_validate_btc_reverse() {
+        let (ours, boltz) = keys();
+        let script = BtcSwapScript::new(
+            BitcoinNetwork::BitcoinTestnet,
+            SwapType::ReverseSubmarine,
+            reverse_hashlock(),
+            ours.public_key().to_string(),
+            TIMEOUT,
+            boltz.public_key().to_string(),
+        );
+        let redeem_script = script.to_script().unwrap().to_hex_string();
+        let address = script.to_address().unwrap().to_string();
+        let expected = reverse_expectation(ours.public_key().to_string());
+
+        let response = reverse_response(redeem_script.clone(), address.clone());
+        assert_eq!(
+            response
+                .validate_btc_reverse(BitcoinNetwork::BitcoinTestnet, &expected)
+                .unwrap(),
+            script
+        );
+
+        // boltz can claim its own lockup, locks less and times out too early
+        let evil = BtcSwapScript::new(
+            BitcoinNetwork::BitcoinTestnet,
+            SwapType::ReverseSubmarine,
+            reverse_hashlock(),
+            boltz.public_key().to_string(),
+            TIP_HEIGHT + 2,
+            ours.public_key().to_string(),
+        );
+        let mut response = reverse_response(
+            evil.to_script().unwrap().to_hex_string(),
+            evil.to_address().unwrap().to_string(),
+        );
+        response.timeout_block_height = Some((TIP_HEIGHT + 2) as u64);
+        response.onchain_amount = Some(40_000);
+        let error = response
+            .validate_btc_reverse(BitcoinNetwork::BitcoinTestnet, &expected)
+            .unwrap_err();
+        assert_eq!(
+            error.mismatches,
+            vec![
+                Mismatch::ClaimPubkey {
+                    expected: ours.public_key().to_string(),
+                    found: boltz.public_key().to_string(),
+                },
+                Mismatch::TimeoutTooSoon {
+                    timeout: TIP_HEIGHT + 2,
+                    min: TIP_HEIGHT + 24,
+                },
+                Mismatch::OnchainAmount {
+                    expected: 49_633,
+                    found: 40_000,
+                },
+            ]
+        );
+
+        // the invoice pays for another preimage and a different amount
+        let mut expected_other = reverse_expectation(ours.public_key().to_string());
+        expected_other.preimage_sha256 = sha256::Hash::hash(&[0; 32]);
+        expected_other.quote.invoice_amount = 60_000;
+        let error = reverse_response(redeem_script.clone(), address.clone())
+            .validate_btc_reverse(BitcoinNetwork::BitcoinTestnet, &expected_other)
+            .unwrap_err();
+        assert_eq!(
+            error.mismatches,
+            vec![
+                Mismatch::Hashlock {
+                    expected: ripemd160::Hash::hash(&expected_other.preimage_sha256[..])
+                        .to_string(),
+                    found: reverse_hashlock(),
+                },
+                Mismatch::PaymentHash {
+                    expected: expected_other.preimage_sha256.to_string(),
+                    found: PAYMENT_HASH.to_string(),
+                },
+                Mismatch::InvoiceAmount {
+                    expected: 60_000,
+                    found: 50_100,
+                },
+            ]
+        );
+
+        let mut response = reverse_response(redeem_script, address);
+        response.invoice = Some("lntb1garbage".to_string());
+        response.lockup_address = None;
+        let mismatches = response
+            .validate_btc_reverse(BitcoinNetwork::BitcoinTestnet, &expected)
+            .unwrap_err()
+            .mismatches;
+        assert_eq!(mismatches[0], Mismatch::MissingField("lockupAddress"));
+        assert!(matches!(mismatches[1], Mismatch::InvalidInvoice(_)));
+    }
+
+    #[test]
+    fn test_validate_lbtc_reverse() {
+        let (ours, boltz) = keys();
+        let blinding_str = "02702ae71ec11a895f6255e26395983585a0d791ea1eb83d1aa54a66056469da";
+        let blinding_key = ZKKeyPair::from_seckey_str(&ZKSecp256k1::new(), blinding_str).unwrap();
+        let script = LBtcSwapScript::new(
+            BitcoinNetwork::LiquidTestnet,
+            SwapType::ReverseSubmarine,
+            reverse_hashlock(),
+            ours.public_key().to_string(),
+            TIMEOUT,
+            boltz.public_key().to_string(),
+            blinding_key,
+        );
+        let redeem_script = hex::encode(script.to_script().unwrap().as_bytes());
+        let mut response =
+            reverse_response(redeem_script, script.to_address().unwrap().to_string());
+        let expected = reverse_expectation(ours.public_key().to_string());
+
+        response.blinding_key = Some("not a key".to_string());
+        assert!(matches!(
+            response
+                .validate_lbtc_reverse(BitcoinNetwork::LiquidTestnet, &expected)
+                .unwrap_err()
+                .mismatches[..],
+            [Mismatch::InvalidBlindingKey(_)]
+        ));
+
+        response.blinding_key = Some(blinding_str.to_string());
+        assert_eq!(
+            response
+                .validate_lbtc_reverse(BitcoinNetwork::LiquidTestnet, &expected)
+                .unwrap(),
+            script
+        );
+
+        // a lockup blinded to another key could not be unblinded with the one Boltz gave us
+        response.blinding_key =
+            Some("5f9f8cb71d8193cb031b1a8b9b1ec08057a130dd8ac9f69cea2e3d8e6675f3a1".to_string());
+        assert!(matches!(
+            response
+                .validate_lbtc_reverse(BitcoinNetwork::LiquidTestnet, &expected)
+                .unwrap_err()
+                .mismatches[..],
+            [Mismatch::Address { .. }]
+        ));
+    }
+}
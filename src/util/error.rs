@@ -55,3 +55,62 @@ impl S5Error {
         CString::new(stringified).unwrap().into_raw()
     }
 }
+
+/// Errors returned by the Boltz API clients.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BoltzApiError {
+    /// The request did not complete: dns, connection, tls or timeout.
+    Transport(String),
+    /// Boltz responded with a non 2xx status.
+    /// `message` is the `error` field of the body when present, otherwise the raw body.
+    Http { status: u16, message: String },
+    /// A 2xx response whose body did not match the expected type.
+    Deserialize { error: String, body: String },
+}
+
+impl BoltzApiError {
+    /// Builds an Http error from a non 2xx response, extracting Boltz's `{"error": ...}` message.
+    pub fn from_response(status: u16, body: &str) -> Self {
+        let message = match serde_json::from_str::<serde_json::Value>(body) {
+            Ok(json) => match json.get("error") {
+                Some(serde_json::Value::String(error)) => error.clone(),
+                Some(error) => error.to_string(),
+                None => body.to_string(),
+            },
+            Err(_) => body.to_string(),
+        };
+        BoltzApiError::Http { status, message }
+    }
+
+    /// HTTP status code, if Boltz responded at all.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            BoltzApiError::Http { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+}
+
+impl Display for BoltzApiError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            BoltzApiError::Transport(error) => write!(f, "Transport: {}", error),
+            BoltzApiError::Http { status, message } => write!(f, "HTTP {}: {}", status, message),
+            BoltzApiError::Deserialize { error, body } => {
+                write!(
+                    f,
+                    "Could not deserialize response: {}. Body: {}",
+                    error, body
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for BoltzApiError {}
+
+impl From<BoltzApiError> for S5Error {
+    fn from(error: BoltzApiError) -> Self {
+        S5Error::new(ErrorKind::BoltzApi, &error.to_string())
+    }
+}
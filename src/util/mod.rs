@@ -1,4 +1,6 @@
 pub mod derivation;
 pub mod ec;
 pub mod error;
+pub mod musig;
 pub mod preimage;
+pub mod script;
@@ -0,0 +1,759 @@
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::secp256k1::rand::rngs::OsRng;
+use bitcoin::secp256k1::{schnorr, KeyPair, Message, PublicKey, Scalar, Secp256k1, SecretKey};
+
+use crate::util::error::{ErrorKind, S5Error};
+
+/// secp256k1 group order
+const CURVE_ORDER: [u8; 32] = [
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe,
+    0xba, 0xae, 0xdc, 0xe6, 0xaf, 0x48, 0xa0, 0x3b, 0xbf, 0xd2, 0x5e, 0x8c, 0xd0, 0x36, 0x41, 0x41,
+];
+
+/// BIP340 style tagged hash: sha256(sha256(tag) || sha256(tag) || msg)
+pub fn tagged_hash(tag: &str, msg: &[u8]) -> [u8; 32] {
+    let tag_hash = sha256::Hash::hash(tag.as_bytes());
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag_hash.as_ref());
+    engine.input(tag_hash.as_ref());
+    engine.input(msg);
+    sha256::Hash::from_engine(engine).to_byte_array()
+}
+
+/// Interprets 32 big endian bytes as an integer mod n.
+/// A hash is at least n with negligible probability, so one subtraction is enough.
+pub fn scalar_mod_n(bytes: [u8; 32]) -> Scalar {
+    match Scalar::from_be_bytes(bytes) {
+        Ok(result) => result,
+        Err(_) => {
+            let mut reduced = [0u8; 32];
+            let mut borrow = 0i16;
+            for i in (0..32).rev() {
+                let mut diff = bytes[i] as i16 - CURVE_ORDER[i] as i16 - borrow;
+                borrow = if diff < 0 { 1 } else { 0 };
+                if diff < 0 {
+                    diff += 256;
+                }
+                reduced[i] = diff as u8;
+            }
+            Scalar::from_be_bytes(reduced).expect("value below n after one reduction")
+        }
+    }
+}
+
+/// BIP327 KeyAgg. Key order matters: Boltz does not sort keys, it always puts its own key first.
+pub fn key_agg(pubkeys: &[PublicKey]) -> Result<PublicKey, S5Error> {
+    if pubkeys.is_empty() {
+        return Err(S5Error::new(ErrorKind::Key, "Cannot aggregate 0 keys"));
+    }
+    let secp = Secp256k1::new();
+    let mut tweaked = Vec::with_capacity(pubkeys.len());
+    for pubkey in pubkeys {
+        let coefficient = key_agg_coefficient(pubkeys, pubkey);
+        if coefficient == Scalar::ONE {
+            tweaked.push(*pubkey);
+            continue;
+        }
+        match pubkey.mul_tweak(&secp, &coefficient) {
+            Ok(result) => tweaked.push(result),
+            Err(e) => return Err(S5Error::new(ErrorKind::Key, &e.to_string())),
+        }
+    }
+    let tweaked: Vec<&PublicKey> = tweaked.iter().collect();
+    match PublicKey::combine_keys(&tweaked) {
+        Ok(result) => Ok(result),
+        Err(e) => Err(S5Error::new(ErrorKind::Key, &e.to_string())),
+    }
+}
+
+/// KeyAggCoeff(pk1..u, pk'): 1 for the second distinct key, otherwise H(L || pk') mod n
+pub fn key_agg_coefficient(pubkeys: &[PublicKey], pubkey: &PublicKey) -> Scalar {
+    let second_key = pubkeys.iter().find(|key| *key != &pubkeys[0]);
+    if second_key == Some(pubkey) {
+        return Scalar::ONE;
+    }
+    let serialized: Vec<u8> = pubkeys.iter().flat_map(|key| key.serialize()).collect();
+    let list_hash = tagged_hash("KeyAgg list", &serialized);
+    let mut msg = list_hash.to_vec();
+    msg.extend_from_slice(&pubkey.serialize());
+    scalar_mod_n(tagged_hash("KeyAgg coefficient", &msg))
+}
+
+/// BIP327 key aggregation context: the aggregate key plus the accumulated tweak and sign flip.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyAggCache {
+    pubkeys: Vec<PublicKey>,
+    agg_pk: PublicKey,
+    // gacc is -1 when true
+    negated: bool,
+    tacc: Scalar,
+}
+
+impl KeyAggCache {
+    pub fn new(pubkeys: &[PublicKey]) -> Result<Self, S5Error> {
+        Ok(KeyAggCache {
+            pubkeys: pubkeys.to_vec(),
+            agg_pk: key_agg(pubkeys)?,
+            negated: false,
+            tacc: Scalar::ZERO,
+        })
+    }
+
+    /// The (possibly tweaked) aggregate key that the final signature verifies against.
+    pub fn agg_pk(&self) -> PublicKey {
+        self.agg_pk
+    }
+
+    /// BIP327 ApplyTweak for x-only tweaks, i.e. the BIP341 taproot tweak of the internal key.
+    pub fn x_only_tweak_add(&mut self, tweak: Scalar) -> Result<(), S5Error> {
+        let secp = Secp256k1::new();
+        let q = if has_even_y(&self.agg_pk) {
+            self.agg_pk
+        } else {
+            self.negated = !self.negated;
+            self.tacc = scalar_negate(&self.tacc);
+            self.agg_pk.negate(&secp)
+        };
+        self.agg_pk = match q.add_exp_tweak(&secp, &tweak) {
+            Ok(result) => result,
+            Err(e) => return Err(S5Error::new(ErrorKind::Key, &e.to_string())),
+        };
+        self.tacc = scalar_add(&self.tacc, &tweak);
+        Ok(())
+    }
+}
+
+/// Secret nonce pair. Deliberately not Clone: reusing a nonce across two signatures leaks the key.
+pub struct SecNonce([SecretKey; 2]);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PubNonce([PublicKey; 2]);
+
+impl PubNonce {
+    pub fn serialize(&self) -> [u8; 66] {
+        let mut bytes = [0u8; 66];
+        bytes[..33].copy_from_slice(&self.0[0].serialize());
+        bytes[33..].copy_from_slice(&self.0[1].serialize());
+        bytes
+    }
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, S5Error> {
+        if bytes.len() != 66 {
+            return Err(S5Error::new(
+                ErrorKind::Input,
+                &format!("Public nonce must be 66 bytes, found {}", bytes.len()),
+            ));
+        }
+        let r1 = match PublicKey::from_slice(&bytes[..33]) {
+            Ok(result) => result,
+            Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+        };
+        let r2 = match PublicKey::from_slice(&bytes[33..]) {
+            Ok(result) => result,
+            Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+        };
+        Ok(PubNonce([r1, r2]))
+    }
+}
+
+/// Sum of all signers' nonces. Either half may be the point at infinity.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AggNonce([Option<PublicKey>; 2]);
+
+impl AggNonce {
+    pub fn serialize(&self) -> [u8; 66] {
+        let mut bytes = [0u8; 66];
+        for (i, point) in self.0.iter().enumerate() {
+            if let Some(point) = point {
+                bytes[i * 33..(i + 1) * 33].copy_from_slice(&point.serialize());
+            }
+        }
+        bytes
+    }
+}
+
+/// Fresh random nonces for one signing session.
+pub fn nonce_gen() -> (SecNonce, PubNonce) {
+    let secp = Secp256k1::new();
+    let k1 = SecretKey::new(&mut OsRng);
+    let k2 = SecretKey::new(&mut OsRng);
+    let pubnonce = PubNonce([
+        PublicKey::from_secret_key(&secp, &k1),
+        PublicKey::from_secret_key(&secp, &k2),
+    ]);
+    (SecNonce([k1, k2]), pubnonce)
+}
+
+pub fn nonce_agg(pubnonces: &[PubNonce]) -> AggNonce {
+    let mut agg = [None, None];
+    for (i, point) in agg.iter_mut().enumerate() {
+        let points: Vec<&PublicKey> = pubnonces.iter().map(|nonce| &nonce.0[i]).collect();
+        *point = PublicKey::combine_keys(&points).ok();
+    }
+    AggNonce(agg)
+}
+
+/// Values shared by every signer of one message: nonce coefficient b, final nonce R and challenge e.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Session {
+    b: Scalar,
+    r: PublicKey,
+    e: Scalar,
+}
+
+impl Session {
+    pub fn new(cache: &KeyAggCache, aggnonce: AggNonce, msg: &[u8; 32]) -> Result<Self, S5Error> {
+        let secp = Secp256k1::new();
+        let q = cache.agg_pk.x_only_public_key().0.serialize();
+        let mut preimage = aggnonce.serialize().to_vec();
+        preimage.extend_from_slice(&q);
+        preimage.extend_from_slice(msg);
+        let b = scalar_mod_n(tagged_hash("MuSig/noncecoef", &preimage));
+
+        let r2 = match aggnonce.0[1] {
+            Some(r2) => r2.mul_tweak(&secp, &b).ok(),
+            None => None,
+        };
+        let points: Vec<&PublicKey> = aggnonce.0[0].iter().chain(r2.iter()).collect();
+        // an infinite final nonce is replaced by G
+        let r = match PublicKey::combine_keys(&points) {
+            Ok(result) => result,
+            Err(_) => PublicKey::from_secret_key(&secp, &SecretKey::from_slice(&ONE).unwrap()),
+        };
+
+        let mut challenge = r.x_only_public_key().0.serialize().to_vec();
+        challenge.extend_from_slice(&q);
+        challenge.extend_from_slice(msg);
+        let e = scalar_mod_n(tagged_hash("BIP0340/challenge", &challenge));
+        Ok(Session { b, r, e })
+    }
+}
+
+/// s = k1 + b*k2 + e*a*d, with k negated for an odd R and d adjusted for the parity of Q.
+pub fn partial_sign(
+    cache: &KeyAggCache,
+    session: &Session,
+    secnonce: SecNonce,
+    keys: &KeyPair,
+) -> Result<Scalar, S5Error> {
+    let pubkey = keys.public_key();
+    if !cache.pubkeys.contains(&pubkey) {
+        return Err(S5Error::new(
+            ErrorKind::Key,
+            "Signing key is not part of the aggregate key",
+        ));
+    }
+    let [k1, k2] = secnonce.0;
+    let (mut k1, mut k2) = (to_scalar(&k1), to_scalar(&k2));
+    if !has_even_y(&session.r) {
+        k1 = scalar_negate(&k1);
+        k2 = scalar_negate(&k2);
+    }
+    let a = key_agg_coefficient(&cache.pubkeys, &pubkey);
+    let mut d = to_scalar(&keys.secret_key());
+    if has_even_y(&cache.agg_pk) == cache.negated {
+        d = scalar_negate(&d);
+    }
+    let ead = scalar_mul(&scalar_mul(&session.e, &a), &d);
+    Ok(scalar_add(
+        &scalar_add(&k1, &scalar_mul(&session.b, &k2)),
+        &ead,
+    ))
+}
+
+/// s*G == R1 + b*R2 (negated for an odd R) + e*a*g*P
+pub fn partial_verify(
+    cache: &KeyAggCache,
+    session: &Session,
+    pubnonce: &PubNonce,
+    pubkey: &PublicKey,
+    partial_sig: &Scalar,
+) -> bool {
+    let secp = Secp256k1::new();
+    let s = match SecretKey::from_slice(&partial_sig.to_be_bytes()) {
+        Ok(result) => result,
+        Err(_) => return false,
+    };
+    let r2 = match pubnonce.0[1].mul_tweak(&secp, &session.b) {
+        Ok(result) => result,
+        Err(_) => return false,
+    };
+    let mut re = match pubnonce.0[0].combine(&r2) {
+        Ok(result) => result,
+        Err(_) => return false,
+    };
+    if !has_even_y(&session.r) {
+        re = re.negate(&secp);
+    }
+    let mut ea = scalar_mul(&session.e, &key_agg_coefficient(&cache.pubkeys, pubkey));
+    if has_even_y(&cache.agg_pk) == cache.negated {
+        ea = scalar_negate(&ea);
+    }
+    let expected = match pubkey.mul_tweak(&secp, &ea) {
+        Ok(point) => re.combine(&point),
+        Err(_) => return false,
+    };
+    expected == Ok(PublicKey::from_secret_key(&secp, &s))
+}
+
+/// Final BIP340 signature: R || sum(s_i) + e*g*tacc
+pub fn partial_sig_agg(
+    cache: &KeyAggCache,
+    session: &Session,
+    partial_sigs: &[Scalar],
+) -> Result<schnorr::Signature, S5Error> {
+    let mut s = partial_sigs
+        .iter()
+        .fold(Scalar::ZERO, |acc, sig| scalar_add(&acc, sig));
+    let mut et = scalar_mul(&session.e, &cache.tacc);
+    if !has_even_y(&cache.agg_pk) {
+        et = scalar_negate(&et);
+    }
+    s = scalar_add(&s, &et);
+
+    let mut sig = [0u8; 64];
+    sig[..32].copy_from_slice(&session.r.x_only_public_key().0.serialize());
+    sig[32..].copy_from_slice(&s.to_be_bytes());
+    match schnorr::Signature::from_slice(&sig) {
+        Ok(result) => Ok(result),
+        Err(e) => Err(S5Error::new(ErrorKind::Key, &e.to_string())),
+    }
+}
+
+/// Our side of a 2-of-2 session with Boltz over `msg`.
+/// `exchange` sends our public nonce to Boltz and returns Boltz's public nonce and partial signature.
+/// Boltz's partial signature and the aggregate signature are both verified before returning.
+pub fn cooperative_sign<F>(
+    cache: &KeyAggCache,
+    keys: &KeyPair,
+    boltz_pubkey: &PublicKey,
+    msg: &[u8; 32],
+    exchange: F,
+) -> Result<schnorr::Signature, S5Error>
+where
+    F: FnOnce(&PubNonce) -> Result<(PubNonce, Scalar), S5Error>,
+{
+    let (secnonce, pubnonce) = nonce_gen();
+    let (boltz_pubnonce, boltz_partial_sig) = exchange(&pubnonce)?;
+    let session = Session::new(cache, nonce_agg(&[boltz_pubnonce, pubnonce]), msg)?;
+    if !partial_verify(
+        cache,
+        &session,
+        &boltz_pubnonce,
+        boltz_pubkey,
+        &boltz_partial_sig,
+    ) {
+        return Err(S5Error::new(
+            ErrorKind::Key,
+            "Invalid partial signature from Boltz",
+        ));
+    }
+    let partial_sig = partial_sign(cache, &session, secnonce, keys)?;
+    let signature = partial_sig_agg(cache, &session, &[boltz_partial_sig, partial_sig])?;
+
+    let secp = Secp256k1::new();
+    let message = match Message::from_slice(msg) {
+        Ok(result) => result,
+        Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+    };
+    match secp.verify_schnorr(&signature, &message, &cache.agg_pk.x_only_public_key().0) {
+        Ok(()) => Ok(signature),
+        Err(e) => Err(S5Error::new(ErrorKind::Key, &e.to_string())),
+    }
+}
+
+/// Parses Boltz's hex encoded public nonce and partial signature.
+pub fn parse_partial_sig(
+    pub_nonce: &str,
+    partial_sig: &str,
+) -> Result<(PubNonce, Scalar), S5Error> {
+    let pub_nonce = match hex::decode(pub_nonce) {
+        Ok(result) => PubNonce::from_slice(&result)?,
+        Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+    };
+    let partial_sig = match hex::decode(partial_sig) {
+        Ok(result) => partial_sig_from_slice(&result)?,
+        Err(e) => return Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+    };
+    Ok((pub_nonce, partial_sig))
+}
+
+pub fn partial_sig_from_slice(bytes: &[u8]) -> Result<Scalar, S5Error> {
+    let bytes: [u8; 32] = match bytes.try_into() {
+        Ok(result) => result,
+        Err(_) => {
+            return Err(S5Error::new(
+                ErrorKind::Input,
+                "Partial signature must be 32 bytes",
+            ))
+        }
+    };
+    match Scalar::from_be_bytes(bytes) {
+        Ok(result) => Ok(result),
+        Err(e) => Err(S5Error::new(ErrorKind::Input, &e.to_string())),
+    }
+}
+
+const ONE: [u8; 32] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1,
+];
+
+fn has_even_y(point: &PublicKey) -> bool {
+    point.serialize()[0] == 0x02
+}
+
+fn to_scalar(secret_key: &SecretKey) -> Scalar {
+    Scalar::from_be_bytes(secret_key.secret_bytes()).expect("secret keys are below n")
+}
+
+// scalar arithmetic mod n on top of SecretKey, which cannot represent 0
+
+fn scalar_add(a: &Scalar, b: &Scalar) -> Scalar {
+    match SecretKey::from_slice(&a.to_be_bytes()) {
+        Ok(a) => match a.add_tweak(b) {
+            Ok(result) => to_scalar(&result),
+            Err(_) => Scalar::ZERO,
+        },
+        Err(_) => *b,
+    }
+}
+
+fn scalar_mul(a: &Scalar, b: &Scalar) -> Scalar {
+    match SecretKey::from_slice(&a.to_be_bytes()) {
+        Ok(a) => match a.mul_tweak(b) {
+            Ok(result) => to_scalar(&result),
+            Err(_) => Scalar::ZERO,
+        },
+        Err(_) => Scalar::ZERO,
+    }
+}
+
+fn scalar_negate(a: &Scalar) -> Scalar {
+    match SecretKey::from_slice(&a.to_be_bytes()) {
+        Ok(a) => to_scalar(&a.negate()),
+        Err(_) => Scalar::ZERO,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    // https://github.com/bitcoin/bips/blob/master/bip-0327/vectors/key_agg_vectors.json
+    #[test]
+    fn test_key_agg_vectors() {
+        let keys: Vec<PublicKey> = [
+            "02F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9",
+            "03DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659",
+            "023590A94E768F8E1815C2F24B4D80A8E3149316C3518CE7B7AD338368D038CA66",
+        ]
+        .iter()
+        .map(|key| PublicKey::from_str(key).unwrap())
+        .collect();
+        let cases: [(&[usize], &str); 4] = [
+            (
+                &[0, 1, 2],
+                "90539EEDE565F5D054F32CC0C220126889ED1E5D193BAF15AEF344FE59D4610C",
+            ),
+            (
+                &[2, 1, 0],
+                "6204DE8B083426DC6EAF9502D27024D53FC826BF7D2012148A0575435DF54B2B",
+            ),
+            (
+                &[0, 0, 0],
+                "B436E3BAD62B8CD409969A224731C193D051162D8C5AE8B109306127DA3AA935",
+            ),
+            (
+                &[0, 0, 1, 1],
+                "69BC22BFA5D106306E48A20679DE1D7389386124D07571D0D872686028C26A3E",
+            ),
+        ];
+        for (indices, expected) in cases {
+            let pubkeys: Vec<PublicKey> = indices.iter().map(|i| keys[*i]).collect();
+            let aggregate = key_agg(&pubkeys).unwrap();
+            assert_eq!(
+                hex::encode(aggregate.x_only_public_key().0.serialize()),
+                expected.to_lowercase()
+            );
+        }
+        assert!(key_agg(&[]).is_err());
+    }
+
+    // https://github.com/bitcoin/bips/blob/master/bip-0327/vectors/nonce_agg_vectors.json
+    #[test]
+    fn test_nonce_agg_vectors() {
+        let pnonces: Vec<PubNonce> = [
+            "020151C80F435648DF67A22B749CD798CE54E0321D034B92B709B567D60A42E66603BA47FBC1834437B3212E89A84D8425E7BF12E0245D98262268EBDCB385D50641",
+            "03FF406FFD8ADB9CD29877E4985014F66A59F6CD01C0E88CAA8E5F3166B1F676A60248C264CDD57D3C24D79990B0F865674EB62A0F9018277A95011B41BFC193B833",
+            "020151C80F435648DF67A22B749CD798CE54E0321D034B92B709B567D60A42E6660279BE667EF9DCBBAC55A06295CE870B07029BFCDB2DCE28D959F2815B16F81798",
+            "03FF406FFD8ADB9CD29877E4985014F66A59F6CD01C0E88CAA8E5F3166B1F676A60379BE667EF9DCBBAC55A06295CE870B07029BFCDB2DCE28D959F2815B16F81798",
+        ]
+        .iter()
+        .map(|nonce| PubNonce::from_slice(&hex::decode(nonce).unwrap()).unwrap())
+        .collect();
+        assert_eq!(
+            hex::encode(nonce_agg(&pnonces[..2]).serialize()),
+            "035fe1873b4f2967f52fea4a06ad5a8eccbe9d0fd73068012c894e2e87ccb5804b024725377345bde0e9c33af3c43c0a29a9249f2f2956fa8cfeb55c8573d0262dc8"
+        );
+        // the second points cancel out and the infinite sum is encoded as 33 zero bytes
+        assert_eq!(
+            hex::encode(nonce_agg(&pnonces[2..]).serialize()),
+            "035fe1873b4f2967f52fea4a06ad5a8eccbe9d0fd73068012c894e2e87ccb5804b000000000000000000000000000000000000000000000000000000000000000000"
+        );
+    }
+
+    /// Signer key, secret nonce, public nonces and message shared by the BIP327 sign and tweak
+    /// vectors.
+    fn vector_signer() -> (KeyPair, [SecretKey; 2], Vec<PubNonce>, [u8; 32]) {
+        let secp = Secp256k1::new();
+        let sk = "7FB9E0E687ADA1EEBF7ECFE2F21E73EBDB51A7D450948DFE8D76D7F2D1007671";
+        let keys = KeyPair::from_seckey_str(&secp, sk).unwrap();
+        let secnonce = hex::decode("508B81A611F100A6B2B6B29656590898AF488BCF2E1F55CF22E5CFB84421FE61FA27FD49B1D50085B481285E1CA205D55C82CC1B31FF5CD54A489829355901F7").unwrap();
+        let secnonce = [
+            SecretKey::from_slice(&secnonce[..32]).unwrap(),
+            SecretKey::from_slice(&secnonce[32..]).unwrap(),
+        ];
+        let pnonces = [
+            "0337C87821AFD50A8644D820A8F3E02E499C931865C2360FB43D0A0D20DAFE07EA0287BF891D2A6DEAEBADC909352AA9405D1428C15F4B75F04DAE642A95C2548480",
+            "0279BE667EF9DCBBAC55A06295CE870B07029BFCDB2DCE28D959F2815B16F817980279BE667EF9DCBBAC55A06295CE870B07029BFCDB2DCE28D959F2815B16F81798",
+            "032DE2662628C90B03F5E720284EB52FF7D71F4284F627B68A853D78C78E1FFE9303E4C5524E83FFE1493B9077CF1CA6BEB2090C93D930321071AD40B2F44E599046",
+            "0237C87821AFD50A8644D820A8F3E02E499C931865C2360FB43D0A0D20DAFE07EA0387BF891D2A6DEAEBADC909352AA9405D1428C15F4B75F04DAE642A95C2548480",
+        ]
+        .iter()
+        .map(|nonce| PubNonce::from_slice(&hex::decode(nonce).unwrap()).unwrap())
+        .collect();
+        let msg = hex::decode("F95466D086770E689964664219266FE5ED215C92AE20BAB5C9D79ADDDDF3C0CF")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        (keys, secnonce, pnonces, msg)
+    }
+
+    // https://github.com/bitcoin/bips/blob/master/bip-0327/vectors/sign_verify_vectors.json
+    #[test]
+    fn test_sign_verify_vectors() {
+        let (keys, secnonce, pnonces, msg) = vector_signer();
+        let pubkeys: Vec<PublicKey> = [
+            "03935F972DA013F80AE011890FA89B67A27B7BE6CCB24D3274D18B2D4067F261A9",
+            "02F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9",
+            "02DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA661",
+        ]
+        .iter()
+        .map(|key| PublicKey::from_str(key).unwrap())
+        .collect();
+        assert_eq!(pubkeys[0], keys.public_key());
+        let cases: [(&[usize], &[usize], &str); 4] = [
+            (
+                &[0, 1, 2],
+                &[0, 1, 2],
+                "012ABBCB52B3016AC03AD82395A1A415C48B93DEF78718E62A7A90052FE224FB",
+            ),
+            (
+                &[1, 0, 2],
+                &[1, 0, 2],
+                "9FF2F7AAA856150CC8819254218D3ADEEB0535269051897724F9DB3789513A52",
+            ),
+            (
+                &[1, 2, 0],
+                &[1, 2, 0],
+                "FA23C359F6FAC4E7796BB93BC9F0532A95468C539BA20FF86D7C76ED92227900",
+            ),
+            // both halves of the aggregate nonce are the point at infinity
+            (
+                &[0, 1],
+                &[0, 3],
+                "AE386064B26105404798F75DE2EB9AF5EDA5387B064B83D049CB7C5E08879531",
+            ),
+        ];
+        for (key_indices, nonce_indices, expected) in cases {
+            let cache = KeyAggCache::new(
+                &key_indices
+                    .iter()
+                    .map(|i| pubkeys[*i])
+                    .collect::<Vec<PublicKey>>(),
+            )
+            .unwrap();
+            let aggnonce = nonce_agg(
+                &nonce_indices
+                    .iter()
+                    .map(|i| pnonces[*i])
+                    .collect::<Vec<PubNonce>>(),
+            );
+            let session = Session::new(&cache, aggnonce, &msg).unwrap();
+            let partial_sig = partial_sign(&cache, &session, SecNonce(secnonce), &keys).unwrap();
+            assert_eq!(
+                hex::encode(partial_sig.to_be_bytes()),
+                expected.to_lowercase()
+            );
+            assert!(partial_verify(
+                &cache,
+                &session,
+                &pnonces[0],
+                &keys.public_key(),
+                &partial_sig
+            ));
+            // the negated signature and a signature attributed to the wrong signer fail
+            assert!(!partial_verify(
+                &cache,
+                &session,
+                &pnonces[0],
+                &keys.public_key(),
+                &scalar_negate(&partial_sig)
+            ));
+            assert!(!partial_verify(
+                &cache,
+                &session,
+                &pnonces[0],
+                &pubkeys[1],
+                &partial_sig
+            ));
+        }
+    }
+
+    // https://github.com/bitcoin/bips/blob/master/bip-0327/vectors/tweak_vectors.json
+    // Only the x-only case: plain tweaks are never used for taproot outputs.
+    #[test]
+    fn test_tweak_vectors() {
+        let (keys, secnonce, pnonces, msg) = vector_signer();
+        let pubkeys: Vec<PublicKey> = [
+            "02F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9",
+            "02DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659",
+            "03935F972DA013F80AE011890FA89B67A27B7BE6CCB24D3274D18B2D4067F261A9",
+        ]
+        .iter()
+        .map(|key| PublicKey::from_str(key).unwrap())
+        .collect();
+        let tweak = hex::decode("E8F791FF9225A2AF0102AFFF4A9A723D9612A682A25EBE79802B263CDFCD83BB")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let mut cache = KeyAggCache::new(&pubkeys).unwrap();
+        cache
+            .x_only_tweak_add(Scalar::from_be_bytes(tweak).unwrap())
+            .unwrap();
+        let aggnonce = nonce_agg(&[pnonces[1], pnonces[2], pnonces[0]]);
+        assert_eq!(
+            hex::encode(aggnonce.serialize()),
+            "028465fcf0bbdbcf443aabcce533d42b4b5a10966ac09a49655e8c42daab8fcd61037496a3cc86926d452cafcfd55d25972ca1675d549310de296bff42f72eeea8c9"
+        );
+        let session = Session::new(&cache, aggnonce, &msg).unwrap();
+        let partial_sig = partial_sign(&cache, &session, SecNonce(secnonce), &keys).unwrap();
+        assert_eq!(
+            hex::encode(partial_sig.to_be_bytes()),
+            "e28a5c66e61e178c2ba19db77b6cf9f7e2f0f56c17918cd13135e60cc848fe91"
+        );
+        assert!(partial_verify(
+            &cache,
+            &session,
+            &pnonces[0],
+            &keys.public_key(),
+            &partial_sig
+        ));
+    }
+
+    /// Runs a full 2-of-2 session, optionally with a taproot tweak, and returns the aggregate key
+    /// and signature.
+    fn sign_2_of_2(tweak: Option<Scalar>, msg: &[u8; 32]) -> (PublicKey, schnorr::Signature) {
+        let secp = Secp256k1::new();
+        let boltz = KeyPair::new(&secp, &mut OsRng);
+        let ours = KeyPair::new(&secp, &mut OsRng);
+        let mut cache = KeyAggCache::new(&[boltz.public_key(), ours.public_key()]).unwrap();
+        if let Some(tweak) = tweak {
+            cache.x_only_tweak_add(tweak).unwrap();
+        }
+
+        let (boltz_secnonce, boltz_pubnonce) = nonce_gen();
+        let (our_secnonce, our_pubnonce) = nonce_gen();
+        let aggnonce = nonce_agg(&[boltz_pubnonce, our_pubnonce]);
+        let session = Session::new(&cache, aggnonce, msg).unwrap();
+
+        let boltz_sig = partial_sign(&cache, &session, boltz_secnonce, &boltz).unwrap();
+        let our_sig = partial_sign(&cache, &session, our_secnonce, &ours).unwrap();
+        assert!(partial_verify(
+            &cache,
+            &session,
+            &boltz_pubnonce,
+            &boltz.public_key(),
+            &boltz_sig
+        ));
+        assert!(partial_verify(
+            &cache,
+            &session,
+            &our_pubnonce,
+            &ours.public_key(),
+            &our_sig
+        ));
+        // a partial signature does not verify for the other signer
+        assert!(!partial_verify(
+            &cache,
+            &session,
+            &our_pubnonce,
+            &ours.public_key(),
+            &boltz_sig
+        ));
+        let stranger = KeyPair::new(&secp, &mut OsRng);
+        let (stranger_secnonce, _) = nonce_gen();
+        assert!(partial_sign(&cache, &session, stranger_secnonce, &stranger).is_err());
+
+        let sig = partial_sig_agg(&cache, &session, &[boltz_sig, our_sig]).unwrap();
+        (cache.agg_pk(), sig)
+    }
+
+    #[test]
+    fn test_musig_sign_and_aggregate() {
+        let secp = Secp256k1::new();
+        let msg = tagged_hash("test", b"musig");
+        let message = bitcoin::secp256k1::Message::from_slice(&msg).unwrap();
+        // run several sessions so that both parities of Q and R are exercised
+        for _ in 0..8 {
+            let (agg_pk, sig) = sign_2_of_2(None, &msg);
+            assert!(secp
+                .verify_schnorr(&sig, &message, &agg_pk.x_only_public_key().0)
+                .is_ok());
+
+            let tweak = scalar_mod_n(tagged_hash("tweak", &msg));
+            let (agg_pk, sig) = sign_2_of_2(Some(tweak), &msg);
+            assert!(secp
+                .verify_schnorr(&sig, &message, &agg_pk.x_only_public_key().0)
+                .is_ok());
+        }
+    }
+
+    #[test]
+    fn test_taproot_tweak_matches_output_key() {
+        use bitcoin::taproot::TapTweakHash;
+        let secp = Secp256k1::new();
+        for _ in 0..8 {
+            let pubkeys = [
+                KeyPair::new(&secp, &mut OsRng).public_key(),
+                KeyPair::new(&secp, &mut OsRng).public_key(),
+            ];
+            let mut cache = KeyAggCache::new(&pubkeys).unwrap();
+            let internal_key = cache.agg_pk().x_only_public_key().0;
+            let tweak = TapTweakHash::from_key_and_tweak(internal_key, None).to_scalar();
+            cache.x_only_tweak_add(tweak).unwrap();
+            let (output_key, _) = internal_key.add_tweak(&secp, &tweak).unwrap();
+            assert_eq!(cache.agg_pk().x_only_public_key().0, output_key);
+        }
+    }
+
+    #[test]
+    fn test_pubnonce_serialization() {
+        let (_, pubnonce) = nonce_gen();
+        let bytes = pubnonce.serialize();
+        assert_eq!(PubNonce::from_slice(&bytes).unwrap(), pubnonce);
+        assert!(PubNonce::from_slice(&bytes[..65]).is_err());
+        let agg = nonce_agg(&[pubnonce]);
+        assert_eq!(agg.serialize(), bytes);
+    }
+
+    #[test]
+    fn test_scalar_mod_n() {
+        assert_eq!(scalar_mod_n([0u8; 32]), Scalar::ZERO);
+        assert_eq!(scalar_mod_n(CURVE_ORDER), Scalar::ZERO);
+        let mut above = CURVE_ORDER;
+        above[31] += 1;
+        assert_eq!(scalar_mod_n(above), Scalar::ONE);
+    }
+}
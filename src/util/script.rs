@@ -0,0 +1,348 @@
+use std::fmt::{Display, Formatter};
+
+use bitcoin::opcodes::{all::*, All as Opcode};
+use bitcoin::PublicKey;
+
+use crate::{
+    swaps::boltz::SwapType,
+    util::error::{ErrorKind, S5Error},
+};
+
+/// One instruction of a legacy redeem script, read from either a bitcoin or an elements script.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScriptElement {
+    Op(u8),
+    Push(Vec<u8>),
+}
+
+impl Display for ScriptElement {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            ScriptElement::Op(op) => write!(f, "{:?}", Opcode::from(*op)),
+            ScriptElement::Push(bytes) => write!(f, "push of {}", hex::encode(bytes)),
+        }
+    }
+}
+
+/// One slot of a legacy redeem script template.
+/// `Op` and `Bytes` must match exactly; the other slots hold the swap's fields.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TemplateSlot {
+    Op(Opcode),
+    Bytes(&'static [u8]),
+    Hashlock,
+    RecieverPubkey,
+    Timelock,
+    SenderPubkey,
+}
+
+impl Display for TemplateSlot {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            TemplateSlot::Op(op) => write!(f, "{:?}", op),
+            TemplateSlot::Bytes(bytes) => write!(f, "push of {}", hex::encode(bytes)),
+            TemplateSlot::Hashlock => write!(f, "20 byte hashlock"),
+            TemplateSlot::RecieverPubkey => write!(f, "reciever pubkey"),
+            TemplateSlot::Timelock => write!(f, "timelock"),
+            TemplateSlot::SenderPubkey => write!(f, "sender pubkey"),
+        }
+    }
+}
+
+/// HASH160 <hashlock> EQUAL IF <reciever> ELSE <timelock> CLTV DROP <sender> ENDIF CHECKSIG
+pub const SUBMARINE_TEMPLATE: [TemplateSlot; 12] = [
+    TemplateSlot::Op(OP_HASH160),
+    TemplateSlot::Hashlock,
+    TemplateSlot::Op(OP_EQUAL),
+    TemplateSlot::Op(OP_IF),
+    TemplateSlot::RecieverPubkey,
+    TemplateSlot::Op(OP_ELSE),
+    TemplateSlot::Timelock,
+    TemplateSlot::Op(OP_CLTV),
+    TemplateSlot::Op(OP_DROP),
+    TemplateSlot::SenderPubkey,
+    TemplateSlot::Op(OP_ENDIF),
+    TemplateSlot::Op(OP_CHECKSIG),
+];
+
+/// SIZE 32 EQUAL IF HASH160 <hashlock> EQUALVERIFY <reciever>
+/// ELSE DROP <timelock> CLTV DROP <sender> ENDIF CHECKSIG
+pub const REVERSE_TEMPLATE: [TemplateSlot; 16] = [
+    TemplateSlot::Op(OP_SIZE),
+    TemplateSlot::Bytes(&[32]),
+    TemplateSlot::Op(OP_EQUAL),
+    TemplateSlot::Op(OP_IF),
+    TemplateSlot::Op(OP_HASH160),
+    TemplateSlot::Hashlock,
+    TemplateSlot::Op(OP_EQUALVERIFY),
+    TemplateSlot::RecieverPubkey,
+    TemplateSlot::Op(OP_ELSE),
+    TemplateSlot::Op(OP_DROP),
+    TemplateSlot::Timelock,
+    TemplateSlot::Op(OP_CLTV),
+    TemplateSlot::Op(OP_DROP),
+    TemplateSlot::SenderPubkey,
+    TemplateSlot::Op(OP_ENDIF),
+    TemplateSlot::Op(OP_CHECKSIG),
+];
+
+pub fn legacy_template(swap_type: &SwapType) -> Result<&'static [TemplateSlot], S5Error> {
+    match swap_type {
+        SwapType::Submarine => Ok(&SUBMARINE_TEMPLATE),
+        SwapType::ReverseSubmarine => Ok(&REVERSE_TEMPLATE),
+        SwapType::Chain => Err(S5Error::new(
+            ErrorKind::Script,
+            "Chain swaps have no legacy redeem script.",
+        )),
+    }
+}
+
+/// Swap fields read from a redeem script that matched its template.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemplateFields {
+    pub hashlock: String,
+    pub reciever_pubkey: String,
+    pub timelock: u32,
+    pub sender_pubkey: String,
+}
+
+/// Matches a redeem script against `template`, element by element, and fails on the first element
+/// that does not fit, naming its position and slot. Instructions that could not be read are passed
+/// as errors so they are reported at their position too.
+pub fn match_template(
+    template: &[TemplateSlot],
+    elements: &[Result<ScriptElement, String>],
+) -> Result<TemplateFields, S5Error> {
+    let mut hashlock = None;
+    let mut reciever_pubkey = None;
+    let mut timelock = None;
+    let mut sender_pubkey = None;
+
+    for (index, slot) in template.iter().enumerate() {
+        let element = match elements.get(index) {
+            Some(Ok(result)) => result,
+            Some(Err(e)) => return Err(template_error(index, slot, e)),
+            None => return Err(template_error(index, slot, "script ends here")),
+        };
+        let matched = match slot {
+            TemplateSlot::Op(op) => *element == ScriptElement::Op(op.to_u8()),
+            TemplateSlot::Bytes(bytes) => *element == ScriptElement::Push(bytes.to_vec()),
+            TemplateSlot::Hashlock => match element {
+                ScriptElement::Push(bytes) if bytes.len() == 20 => {
+                    hashlock = Some(hex::encode(bytes));
+                    true
+                }
+                _ => false,
+            },
+            TemplateSlot::RecieverPubkey => {
+                reciever_pubkey = read_pubkey(element);
+                reciever_pubkey.is_some()
+            }
+            TemplateSlot::Timelock => {
+                timelock = read_timelock(element);
+                timelock.is_some()
+            }
+            TemplateSlot::SenderPubkey => {
+                sender_pubkey = read_pubkey(element);
+                sender_pubkey.is_some()
+            }
+        };
+        if !matched {
+            return Err(template_error(index, slot, &format!("found {}", element)));
+        }
+    }
+    if elements.len() > template.len() {
+        return Err(S5Error::new(
+            ErrorKind::Script,
+            &format!(
+                "Redeem script has {} elements after the end of the template.",
+                elements.len() - template.len()
+            ),
+        ));
+    }
+
+    match (hashlock, reciever_pubkey, timelock, sender_pubkey) {
+        (Some(hashlock), Some(reciever_pubkey), Some(timelock), Some(sender_pubkey)) => {
+            Ok(TemplateFields {
+                hashlock,
+                reciever_pubkey,
+                timelock,
+                sender_pubkey,
+            })
+        }
+        _ => Err(S5Error::new(
+            ErrorKind::Script,
+            "Template does not hold every swap field.",
+        )),
+    }
+}
+
+fn template_error(index: usize, slot: &TemplateSlot, reason: &str) -> S5Error {
+    S5Error::new(
+        ErrorKind::Script,
+        &format!(
+            "Redeem script element {} should be {}: {}",
+            index, slot, reason
+        ),
+    )
+}
+
+/// Only compressed keys, which is what to_script pushes.
+fn read_pubkey(element: &ScriptElement) -> Option<String> {
+    match element {
+        ScriptElement::Push(bytes) if bytes.len() == 33 => match PublicKey::from_slice(bytes) {
+            Ok(result) => Some(result.to_string()),
+            Err(_) => None,
+        },
+        _ => None,
+    }
+}
+
+/// A positive, minimally encoded script number, as pushed for CHECKLOCKTIMEVERIFY.
+fn read_timelock(element: &ScriptElement) -> Option<u32> {
+    let bytes = match element {
+        ScriptElement::Op(op) if (OP_PUSHNUM_1.to_u8()..=OP_PUSHNUM_16.to_u8()).contains(op) => {
+            return Some((op - OP_PUSHNUM_1.to_u8() + 1) as u32)
+        }
+        ScriptElement::Push(bytes) if !bytes.is_empty() && bytes.len() <= 5 => bytes,
+        _ => return None,
+    };
+    let last = bytes[bytes.len() - 1];
+    // a sign bit makes it negative; a zero last byte is only needed to clear the sign bit
+    if last & 0x80 != 0 {
+        return None;
+    }
+    if last == 0 && (bytes.len() == 1 || bytes[bytes.len() - 2] & 0x80 == 0) {
+        return None;
+    }
+    let mut result = 0u64;
+    for (i, &byte) in bytes.iter().enumerate() {
+        result |= (byte as u64) << (8 * i);
+    }
+    if result > u32::MAX as u64 {
+        return None;
+    }
+    Some(result as u32)
+}
+
+/// Whether a spend passes CHECKLOCKTIMEVERIFY for `timelock`: its nLockTime has to count in the
+/// same unit (block height or time) and be at least `timelock`, and the input must not be final.
+pub fn cltv_satisfied(lock_time: u32, sequence: u32, timelock: u32) -> bool {
+    const LOCKTIME_THRESHOLD: u32 = 500_000_000;
+    (lock_time < LOCKTIME_THRESHOLD) == (timelock < LOCKTIME_THRESHOLD)
+        && lock_time >= timelock
+        && sequence != u32::MAX
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn submarine_elements() -> Vec<Result<ScriptElement, String>> {
+        vec![
+            Ok(ScriptElement::Op(OP_HASH160.to_u8())),
+            Ok(ScriptElement::Push(
+                hex::decode("61be1fecdb989e10275a19f893836066230ab208").unwrap(),
+            )),
+            Ok(ScriptElement::Op(OP_EQUAL.to_u8())),
+            Ok(ScriptElement::Op(OP_IF.to_u8())),
+            Ok(ScriptElement::Push(
+                hex::decode("039f3dece2229c2e957e43df168bd078bcdad7e66d1690a27c8b0277d7832ced21")
+                    .unwrap(),
+            )),
+            Ok(ScriptElement::Op(OP_ELSE.to_u8())),
+            Ok(ScriptElement::Push(vec![0xe0, 0xc9, 0x26])),
+            Ok(ScriptElement::Op(OP_CLTV.to_u8())),
+            Ok(ScriptElement::Op(OP_DROP.to_u8())),
+            Ok(ScriptElement::Push(
+                hex::decode("023946267e8f3eeeea651b0ea865b52d1f9d1c12e851b0f98a3303c15a26cf235d")
+                    .unwrap(),
+            )),
+            Ok(ScriptElement::Op(OP_ENDIF.to_u8())),
+            Ok(ScriptElement::Op(OP_CHECKSIG.to_u8())),
+        ]
+    }
+
+    #[test]
+    fn test_match_template() {
+        let fields = match_template(&SUBMARINE_TEMPLATE, &submarine_elements()).unwrap();
+        assert_eq!(fields.hashlock, "61be1fecdb989e10275a19f893836066230ab208");
+        assert_eq!(fields.timelock, 2_542_048);
+        assert_eq!(
+            fields.sender_pubkey,
+            "023946267e8f3eeeea651b0ea865b52d1f9d1c12e851b0f98a3303c15a26cf235d"
+        );
+
+        // a submarine script is not a reverse script
+        let error = match_template(&REVERSE_TEMPLATE, &submarine_elements()).unwrap_err();
+        assert_eq!(
+            error.message,
+            "Redeem script element 0 should be OP_SIZE: found OP_HASH160"
+        );
+
+        // the refund path no longer checks the timelock
+        let mut elements = submarine_elements();
+        elements[7] = Ok(ScriptElement::Op(OP_NOP.to_u8()));
+        let error = match_template(&SUBMARINE_TEMPLATE, &elements).unwrap_err();
+        assert_eq!(
+            error.message,
+            "Redeem script element 7 should be OP_CLTV: found OP_NOP"
+        );
+
+        let mut elements = submarine_elements();
+        elements[4] = Ok(ScriptElement::Push(vec![4; 33]));
+        let error = match_template(&SUBMARINE_TEMPLATE, &elements).unwrap_err();
+        assert!(error
+            .message
+            .starts_with("Redeem script element 4 should be reciever pubkey"));
+
+        let mut elements = submarine_elements();
+        elements.push(Ok(ScriptElement::Op(OP_DROP.to_u8())));
+        assert!(match_template(&SUBMARINE_TEMPLATE, &elements).is_err());
+
+        let mut elements = submarine_elements();
+        elements.truncate(9);
+        elements.push(Err("unexpected end of script".to_string()));
+        let error = match_template(&SUBMARINE_TEMPLATE, &elements).unwrap_err();
+        assert_eq!(
+            error.message,
+            "Redeem script element 9 should be sender pubkey: unexpected end of script"
+        );
+    }
+
+    #[test]
+    fn test_read_timelock() {
+        assert_eq!(
+            read_timelock(&ScriptElement::Op(OP_PUSHNUM_16.to_u8())),
+            Some(16)
+        );
+        assert_eq!(read_timelock(&ScriptElement::Push(vec![0x11])), Some(17));
+        assert_eq!(
+            read_timelock(&ScriptElement::Push(vec![0x80, 0x00])),
+            Some(128)
+        );
+        // negative, padded and too large
+        assert_eq!(read_timelock(&ScriptElement::Push(vec![0x81])), None);
+        assert_eq!(read_timelock(&ScriptElement::Push(vec![0x11, 0x00])), None);
+        assert_eq!(read_timelock(&ScriptElement::Push(vec![0; 0])), None);
+        assert_eq!(
+            read_timelock(&ScriptElement::Push(vec![0xff, 0xff, 0xff, 0xff, 0x00])),
+            Some(u32::MAX)
+        );
+        assert_eq!(
+            read_timelock(&ScriptElement::Push(vec![0, 0, 0, 0, 0x01])),
+            None
+        );
+    }
+
+    #[test]
+    fn test_cltv_satisfied() {
+        assert!(cltv_satisfied(2_580_000, 0xFFFFFFFE, 2_580_000));
+        assert!(cltv_satisfied(2_580_001, 0xFFFFFFFD, 2_580_000));
+        assert!(!cltv_satisfied(2_579_999, 0xFFFFFFFE, 2_580_000));
+        // a final input disables nLockTime
+        assert!(!cltv_satisfied(2_580_000, 0xFFFFFFFF, 2_580_000));
+        // a timestamp does not satisfy a block height
+        assert!(!cltv_satisfied(1_700_000_000, 0xFFFFFFFE, 2_580_000));
+    }
+}
@@ -7,7 +7,7 @@ use bitcoin::{
     secp256k1::{KeyPair, Secp256k1},
 };
 use boltzclient::{
-    network::electrum::{BitcoinNetwork, NetworkConfig, DEFAULT_TESTNET_NODE},
+    network::electrum::{BitcoinNetwork, NetworkConfig},
     swaps::{
         bitcoin::{BtcSwapScript, BtcSwapTx},
         boltz::{
@@ -105,16 +105,12 @@ fn test_bitcoin_ssi() {
         .unwrap()
         .clone();
 
-    let boltz_script = BtcSwapScript::submarine_from_str(
-        BitcoinNetwork::BitcoinTestnet,
-        DEFAULT_TESTNET_NODE.to_owned(),
-        &redeem_script_string,
-    )
-    .unwrap();
+    let boltz_script =
+        BtcSwapScript::submarine_from_str(BitcoinNetwork::BitcoinTestnet, &redeem_script_string)
+            .unwrap();
 
     let constructed_script = BtcSwapScript::new(
         BitcoinNetwork::BitcoinTestnet,
-        DEFAULT_TESTNET_NODE.to_owned(),
         SwapType::Submarine,
         preimage_states.hash160.to_string(),
         boltz_script.reciever_pubkey.clone(),
@@ -208,16 +204,12 @@ fn test_bitcoin_rsi() {
         .unwrap()
         .clone();
 
-    let boltz_rev_script = BtcSwapScript::reverse_from_str(
-        BitcoinNetwork::BitcoinTestnet,
-        DEFAULT_TESTNET_NODE.to_owned(),
-        &redeem_script_string,
-    )
-    .unwrap();
+    let boltz_rev_script =
+        BtcSwapScript::reverse_from_str(BitcoinNetwork::BitcoinTestnet, &redeem_script_string)
+            .unwrap();
 
     let constructed_rev_script = BtcSwapScript::new(
         BitcoinNetwork::BitcoinTestnet,
-        DEFAULT_TESTNET_NODE.to_owned(),
         SwapType::ReverseSubmarine,
         preimage.hash160.to_string(),
         keypair.public_key().to_string().clone(),
@@ -231,7 +223,9 @@ fn test_bitcoin_rsi() {
     println!("{}", constructed_address.to_string());
     assert_eq!(constructed_address.to_string(), lockup_address);
 
-    let script_balance = constructed_rev_script.get_balance().unwrap();
+    let script_balance = constructed_rev_script
+        .get_balance(&electrum_client)
+        .unwrap();
     assert_eq!(script_balance.0, 0);
     assert_eq!(script_balance.1, 0);
     println!("*******PAY********************");
@@ -257,7 +251,9 @@ fn test_bitcoin_rsi() {
             println!("*******BOLTZ******************");
             println!("*******ONCHAIN-TX*************");
             println!("*******DETECTED***************");
-            let script_balance = constructed_rev_script.get_balance().unwrap();
+            let script_balance = constructed_rev_script
+                .get_balance(&electrum_client)
+                .unwrap();
             println!(
                 "confirmed: {}, unconfirmed: {}",
                 script_balance.0, script_balance.1
@@ -277,7 +273,9 @@ fn test_bitcoin_rsi() {
     )
     .unwrap();
 
-    let signed_tx = rv_claim_tx.drain(keypair, preimage, out_amount).unwrap();
+    let signed_tx = rv_claim_tx
+        .drain(&electrum_client, keypair, preimage, out_amount)
+        .unwrap();
     let txid = electrum_client.transaction_broadcast(&signed_tx).unwrap();
     println!("{}", txid);
 }
@@ -314,19 +312,20 @@ fn test_recover_bitcoin_rsi() {
     let absolute_fees = 300;
 
     let mut rev_swap_tx = BtcSwapTx::new_claim(
-        BtcSwapScript::reverse_from_str(
-            BitcoinNetwork::BitcoinTestnet,
-            DEFAULT_TESTNET_NODE.to_owned(),
-            &redeem_script,
-        )
-        .unwrap(),
+        BtcSwapScript::reverse_from_str(BitcoinNetwork::BitcoinTestnet, &redeem_script).unwrap(),
         RETURN_ADDRESS.to_string(),
         absolute_fees,
     )
     .unwrap();
 
-    let signed_tx = rev_swap_tx.drain(keypair, preimage, out_amount).unwrap();
-    let txid = rev_swap_tx.broadcast(signed_tx).unwrap();
+    let electrum_client = NetworkConfig::default_bitcoin()
+        .electrum_url
+        .build_client()
+        .unwrap();
+    let signed_tx = rev_swap_tx
+        .drain(&electrum_client, keypair, preimage, out_amount)
+        .unwrap();
+    let txid = rev_swap_tx.broadcast(&electrum_client, signed_tx).unwrap();
     println!("{}", txid);
 }
 /*
@@ -1,5 +1,5 @@
 use boltzclient::{
-    network::electrum::{BitcoinNetwork, NetworkConfig, DEFAULT_LIQUID_TESTNET_NODE},
+    network::electrum::{BitcoinNetwork, NetworkConfig},
     swaps::{
         boltz::{BoltzApiClient, CreateSwapRequest, BOLTZ_TESTNET_URL},
         liquid::LBtcSwapScript,
@@ -67,7 +67,6 @@ fn test_liquid_ssi() {
 
     let boltz_script_elements = LBtcSwapScript::submarine_from_str(
         BitcoinNetwork::LiquidTestnet,
-        DEFAULT_LIQUID_TESTNET_NODE.to_string(),
         &redeem_script_string,
         blinding_string,
     )
@@ -152,7 +151,6 @@ fn test_liquid_rsi() {
 
     let boltz_script_elements = LBtcSwapScript::reverse_from_str(
         BitcoinNetwork::LiquidTestnet,
-        DEFAULT_LIQUID_TESTNET_NODE.to_string(),
         &redeem_script_string,
         blinding_string.clone(),
     )
@@ -160,7 +158,6 @@ fn test_liquid_rsi() {
     let secp = Secp256k1::new();
     let constructed_script_elements = LBtcSwapScript::new(
         BitcoinNetwork::LiquidTestnet,
-        DEFAULT_LIQUID_TESTNET_NODE.to_string(),
         boltzclient::swaps::boltz::SwapType::ReverseSubmarine,
         preimage.hash160.to_string(),
         keypair.public_key().to_string().clone(),